@@ -55,9 +55,14 @@ mod unix {
             create_alt_stack: true,
             use_alt_stack: true,
             resolve_frames: crashtracker::StacktraceCollection::WithoutSymbols,
+            signals: CrashtrackerConfiguration::default_signals(),
             endpoint,
+            minidump: false,
+            spool_dir: None,
+            symbol_stores: vec![],
             timeout_ms: TEST_COLLECTOR_TIMEOUT_MS,
             unix_socket_path: Some("".to_string()),
+            upload_rate_limit: None,
         };
 
         let metadata = CrashtrackerMetadata {
@@ -9,3 +9,4 @@ pub mod test_005_sigpipe_sigstack;
 pub mod test_006_sigchld_sigstack;
 pub mod test_007_chaining;
 pub mod test_008_fork;
+pub mod test_009_corrupted_stack;
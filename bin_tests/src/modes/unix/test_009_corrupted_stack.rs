@@ -0,0 +1,61 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+//
+// This test checks that a crash whose stack can't be unwound is still reported.  Instead of the
+// usual null dereference, `post()` points the stack and frame pointers at unmapped memory before
+// crashing, so that unwinding from the signal handler reads garbage, and usually crashes the
+// crash handler itself.  Either way, the report must still make it to the receiver, with the
+// signal info and whatever frames could be unwound.
+use crate::modes::behavior::Behavior;
+use datadog_crashtracker::CrashtrackerConfiguration;
+use std::path::Path;
+
+pub struct Test;
+
+impl Behavior for Test {
+    fn setup(
+        &self,
+        _output_dir: &Path,
+        _config: &mut CrashtrackerConfiguration,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn pre(&self, _output_dir: &Path) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn post(&self, _output_dir: &Path) -> anyhow::Result<()> {
+        unsafe { crash_with_corrupted_stack() }
+    }
+}
+
+/// Writes to address 0x10 with the stack pointer, and the frame pointer, at
+/// that same unmapped address.  The handler runs on the altstack.
+#[cfg(target_arch = "x86_64")]
+unsafe fn crash_with_corrupted_stack() -> ! {
+    std::arch::asm!(
+        "mov rsp, {garbage}",
+        "mov rbp, {garbage}",
+        "mov qword ptr [{garbage}], 0",
+        garbage = in(reg) 0x10usize,
+        options(noreturn)
+    )
+}
+
+#[cfg(target_arch = "aarch64")]
+unsafe fn crash_with_corrupted_stack() -> ! {
+    std::arch::asm!(
+        "mov sp, {garbage}",
+        "mov x29, {garbage}",
+        "mov x30, {garbage}",
+        "str xzr, [{garbage}]",
+        garbage = in(reg) 0x10usize,
+        options(noreturn)
+    )
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+unsafe fn crash_with_corrupted_stack() -> ! {
+    unimplemented!("Corrupting the stack is only implemented for x86_64 and aarch64")
+}
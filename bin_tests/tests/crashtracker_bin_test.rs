@@ -66,6 +66,54 @@ fn test_crash_tracking_bin_fork() {
     test_crash_tracking_bin(BuildProfile::Release, "fork");
 }
 
+#[test]
+#[cfg_attr(miri, ignore)]
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn test_crash_tracking_bin_corrupted_stack() {
+    let (crashtracker_bin, crashtracker_receiver) = setup_crashtracking_crates(BuildProfile::Debug);
+    let fixtures = setup_test_fixtures(&[&crashtracker_receiver, &crashtracker_bin]);
+
+    let mut p = process::Command::new(&fixtures.artifacts[&crashtracker_bin])
+        .arg(format!("file://{}", fixtures.crash_profile_path.display()))
+        .arg(fixtures.artifacts[&crashtracker_receiver].as_os_str())
+        .arg(&fixtures.output_dir)
+        .arg("corrupted_stack")
+        .spawn()
+        .unwrap();
+    let exit_status = bin_tests::timeit!("exit after signal", {
+        eprintln!("Waiting for exit");
+        p.wait().unwrap()
+    });
+    assert!(!exit_status.success());
+
+    // Unwinding the corrupted stack may or may not crash the crash handler, but either way the
+    // report gets through, with the signal and the frames unwound before the corruption.
+    let crash_profile = fs::read(fixtures.crash_profile_path)
+        .context("reading crashtracker profiling payload")
+        .unwrap();
+    let crash_payload = serde_json::from_slice::<serde_json::Value>(&crash_profile)
+        .context("deserializing crashtracker profiling payload to json")
+        .unwrap();
+    assert_matches_schema(&crash_payload);
+    assert_eq!(crash_payload["sig_info"]["si_signo"], 11);
+    assert_eq!(crash_payload["sig_info"]["si_addr"], "0x0000000000000010");
+    assert!(crash_payload["error"]["stack"]["frames"]
+        .as_array()
+        .is_some_and(|frames| !frames.is_empty()));
+    if crash_payload["incomplete"] == true {
+        // The receiver says why.
+        assert!(
+            crash_payload["log_messages"]
+                .as_array()
+                .is_some_and(|messages| !messages.is_empty()),
+            "{crash_payload:#}"
+        );
+    }
+}
+
 fn test_crash_tracking_bin(crash_tracking_receiver_profile: BuildProfile, mode: &str) {
     let (crashtracker_bin, crashtracker_receiver) =
         setup_crashtracking_crates(crash_tracking_receiver_profile);
@@ -121,12 +169,22 @@ fn test_crash_tracking_bin(crash_tracking_receiver_profile: BuildProfile, mode:
     );
     assert_eq!(
         serde_json::json!({
-          "signum": 11,
-          "signame": "SIGSEGV",
-          "faulting_address": 0,
+          "si_addr": "0x0000000000000000",
+          "si_code": 1,
+          "si_code_human_readable": "SEGV_MAPERR",
+          "si_signo": 11,
+          "si_signo_human_readable": "SIGSEGV",
         }),
-        crash_payload["siginfo"]
+        crash_payload["sig_info"]
     );
+    assert_matches_schema(&crash_payload);
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    assert!(crash_payload["fault_context"]["registers"]
+        .as_object()
+        .is_some_and(|registers| !registers.is_empty()));
 
     let crash_telemetry = fs::read(fixtures.crash_telemetry_path)
         .context("reading crashtracker telemetry payload")
@@ -169,21 +227,53 @@ fn assert_telemetry_message(crash_telemetry: &[u8]) {
         .as_str()
         .unwrap()
         .split(',')
-        .filter(|t| !t.starts_with("uuid:"))
+        // Partial reports are accepted, see above.
+        .filter(|t| !t.starts_with("uuid:") && !t.starts_with("incomplete:"))
         .collect::<std::collections::HashSet<_>>();
     assert_eq!(
         std::collections::HashSet::from_iter([
-            "signum:11",
+            "data_schema_version:1.0",
+            "is_crash:true",
             "profiler_unwinding:0",
             "profiler_collecting_sample:1",
             "profiler_inactive:0",
             "profiler_serializing:0",
-            "signame:SIGSEGV",
-            "faulting_address:0x0000000000000000",
+            "si_addr:0x0000000000000000",
+            "si_code:1",
+            "si_code_human_readable:SEGV_MAPERR",
+            "si_signo:11",
+            "si_signo_human_readable:SIGSEGV",
         ]),
         tags
     );
     assert_eq!(telemetry_payload["payload"][0]["is_sensitive"], true);
+
+    // The crash report itself is the message.
+    let crash_report: serde_json::Value =
+        serde_json::from_str(telemetry_payload["payload"][0]["message"].as_str().unwrap())
+            .context("deserializing crash report from the telemetry payload")
+            .unwrap();
+    assert_matches_schema(&crash_report);
+}
+
+/// Checks `crash_report` against the JSON schema of the crash reports, which
+/// is checked in for their consumers.
+fn assert_matches_schema(crash_report: &serde_json::Value) {
+    let schema: serde_json::Value = serde_json::from_str(include_str!(
+        "../../crashtracker/src/rfc5_crash_info/crash_info.schema.json"
+    ))
+    .unwrap();
+    let schema = jsonschema::JSONSchema::compile(&schema).unwrap();
+    let errors: Vec<_> = match schema.validate(crash_report) {
+        Ok(()) => vec![],
+        Err(errors) => errors
+            .map(|e| format!("{e} at {}", e.instance_path))
+            .collect(),
+    };
+    assert!(
+        errors.is_empty(),
+        "The crash report doesn't match its schema: {errors:#?}"
+    );
 }
 
 #[test]
@@ -2,6 +2,8 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::option_from_char_slice;
+use anyhow::Context;
+use datadog_crashtracker::SymbolStore;
 pub use datadog_crashtracker::{OpTypes, StacktraceCollection};
 use ddcommon::Endpoint;
 use ddcommon_ffi::slice::{AsBytes, CharSlice};
@@ -65,13 +67,37 @@ pub struct Config<'a> {
     /// The endpoint to send the crash report to (can be a file://).
     /// If None, the crashtracker will infer the agent host from env variables.
     pub endpoint: Option<&'a Endpoint>,
+    /// Whether to also write a Breakpad-compatible minidump of the crashing process.
+    /// Only supported on Linux x86_64 and aarch64. It is written next to the report, as
+    /// `<path>.dmp`, so it requires a file:// endpoint: configuring it with another endpoint is
+    /// an error.
+    pub minidump: bool,
     pub resolve_frames: StacktraceCollection,
+    /// The signals to handle. If empty, the default signals (SIGABRT, SIGBUS, SIGILL and
+    /// SIGSEGV) are handled. SIGFPE, SIGSYS and SIGTRAP may also be requested.
+    pub signals: Slice<'a, i32>,
+    /// Optional directory in which reports are kept until they are uploaded. Reports whose
+    /// upload failed are retried by `ddog_crasht_upload_pending_reports`.
+    pub optional_spool_dir: CharSlice<'a>,
+    /// Directories of debug files named by build id, like `/usr/lib/debug/.build-id`, in which
+    /// the receiver looks for the debug files of stripped binaries when it resolves the frames.
+    pub symbol_directories: Slice<'a, CharSlice<'a>>,
+    /// Optional url of a debuginfod server, from which the receiver downloads the debug files
+    /// which aren't in `symbol_directories`, into `optional_debuginfod_cache_dir`.
+    pub optional_debuginfod_url: CharSlice<'a>,
+    pub optional_debuginfod_cache_dir: CharSlice<'a>,
     /// Timeout in milliseconds before the signal handler starts tearing things down to return.
     /// This is given as a uint32_t, but the actual timeout needs to fit inside of an i32 (max
     /// 2^31-1). This is a limitation of the various interfaces used to guarantee the timeout.
     pub timeout_ms: u32,
     /// Optional filename for a unix domain socket if the receiver is used asynchonously
     pub optional_unix_socket_filename: CharSlice<'a>,
+    /// Optional directory in which the uploads of reports are counted by fingerprint. If given,
+    /// at most `upload_rate_limit_max_uploads` reports with the same fingerprint are uploaded
+    /// per `upload_rate_limit_period_secs`, and the others are dropped.
+    pub optional_upload_rate_limit_dir: CharSlice<'a>,
+    pub upload_rate_limit_max_uploads: u32,
+    pub upload_rate_limit_period_secs: u64,
 }
 
 impl<'a> TryFrom<Config<'a>> for datadog_crashtracker::CrashtrackerConfiguration {
@@ -87,18 +113,47 @@ impl<'a> TryFrom<Config<'a>> for datadog_crashtracker::CrashtrackerConfiguration
         let create_alt_stack = value.create_alt_stack;
         let use_alt_stack = value.use_alt_stack;
         let endpoint = value.endpoint.cloned();
+        let minidump = value.minidump;
         let resolve_frames = value.resolve_frames;
+        let signals = value.signals.iter().copied().collect();
+        let spool_dir = option_from_char_slice(value.optional_spool_dir)?;
+        let symbol_stores = {
+            let mut vec = Vec::with_capacity(value.symbol_directories.len() + 1);
+            for x in value.symbol_directories.iter() {
+                vec.push(SymbolStore::Directory(x.try_to_utf8()?.to_string()));
+            }
+            if let Some(url) = option_from_char_slice(value.optional_debuginfod_url)? {
+                let cache_dir = option_from_char_slice(value.optional_debuginfod_cache_dir)?
+                    .context("A debuginfod url requires a cache directory")?;
+                vec.push(SymbolStore::Debuginfod { url, cache_dir });
+            }
+            vec
+        };
         let timeout_ms = value.timeout_ms;
         let unix_socket_path = option_from_char_slice(value.optional_unix_socket_filename)?;
-        Self::new(
-            additional_files,
-            create_alt_stack,
-            use_alt_stack,
-            endpoint,
-            resolve_frames,
-            timeout_ms,
-            unix_socket_path,
-        )
+        let upload_rate_limit = option_from_char_slice(value.optional_upload_rate_limit_dir)?
+            .map(|state_dir| {
+                datadog_crashtracker::UploadRateLimit::new(
+                    value.upload_rate_limit_max_uploads,
+                    std::time::Duration::from_secs(value.upload_rate_limit_period_secs),
+                    state_dir,
+                )
+            })
+            .transpose()?;
+        Self::builder()
+            .set_additional_files(additional_files)
+            .set_create_alt_stack(create_alt_stack)
+            .set_use_alt_stack(use_alt_stack)
+            .set_endpoint(endpoint)
+            .set_minidump(minidump)
+            .set_resolve_frames(resolve_frames)
+            .set_signals(signals)
+            .set_spool_dir(spool_dir)
+            .set_symbol_stores(symbol_stores)
+            .set_timeout_ms(timeout_ms)
+            .set_unix_socket_path(unix_socket_path)
+            .set_upload_rate_limit(upload_rate_limit)
+            .build()
     }
 }
 
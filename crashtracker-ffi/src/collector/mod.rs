@@ -4,12 +4,13 @@ mod counters;
 mod datatypes;
 mod spans;
 
-use super::crash_info::Metadata;
-use crate::Result;
+use super::crash_info::{Metadata, StackFrame};
+use crate::{option_from_char_slice, Result};
 use anyhow::Context;
 pub use counters::*;
 use datadog_crashtracker::CrashtrackerReceiverConfig;
 pub use datatypes::*;
+use ddcommon_ffi::{CharSlice, Slice};
 pub use spans::*;
 
 #[no_mangle]
@@ -139,3 +140,38 @@ pub unsafe extern "C" fn ddog_crasht_init_without_receiver(
     .context("ddog_crasht_init failed")
     .into()
 }
+
+#[no_mangle]
+#[must_use]
+/// Reports an exception which the language runtime didn't handle, with the
+/// stack captured by the runtime, as a crash.  This is also the way to report
+/// a call to `std::terminate`, by calling it from a handler installed with
+/// `std::set_terminate`.
+///
+/// Only the first exception of the process is reported.  The process is
+/// expected to terminate after this, so the `SIGABRT` which usually follows
+/// isn't reported again.
+///
+/// # Preconditions
+///   This function assumes that the crash-tracker has previously been
+///   initialized.
+/// # Safety
+///   `message` and `stacktrace` must be valid slices.
+///   Crash-tracking functions are not reentrant.
+///   No other crash-handler functions should be called concurrently.
+pub unsafe extern "C" fn ddog_crasht_report_unhandled_exception(
+    message: CharSlice,
+    stacktrace: Slice<StackFrame>,
+) -> Result {
+    (|| {
+        let message = option_from_char_slice(message)?;
+        let mut stacktrace_vec: Vec<datadog_crashtracker::StackFrame> =
+            Vec::with_capacity(stacktrace.len());
+        for s in stacktrace.iter() {
+            stacktrace_vec.push(s.try_into()?)
+        }
+        datadog_crashtracker::report_unhandled_exception(message, stacktrace_vec.into())
+    })()
+    .context("ddog_crasht_report_unhandled_exception failed")
+    .into()
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::option_from_char_slice;
+use datadog_crashtracker::rfc5_crash_info::CrashInfoBuilder;
 use ddcommon::tag::Tag;
 use ddcommon_ffi::{
     slice::{AsBytes, ByteSlice},
@@ -12,18 +13,18 @@ use ddcommon_ffi::{
 /// the C API functions on this struct.
 #[repr(C)]
 pub struct CrashInfo {
-    // This may be null, but if not it will point to a valid CrashInfo.
-    inner: *mut datadog_crashtracker::CrashInfo,
+    // This may be null, but if not it will point to a valid CrashInfoBuilder.
+    inner: *mut CrashInfoBuilder,
 }
 
 impl CrashInfo {
-    pub(super) fn new(crash_info: datadog_crashtracker::CrashInfo) -> Self {
+    pub(super) fn new(crash_info: CrashInfoBuilder) -> Self {
         CrashInfo {
             inner: Box::into_raw(Box::new(crash_info)),
         }
     }
 
-    pub(super) fn take(&mut self) -> Option<Box<datadog_crashtracker::CrashInfo>> {
+    pub(super) fn take(&mut self) -> Option<Box<CrashInfoBuilder>> {
         // Leaving a null will help with double-free issues that can
         // arise in C. Of course, it's best to never get there in the
         // first place!
@@ -45,7 +46,7 @@ impl Drop for CrashInfo {
 
 pub(crate) unsafe fn crashinfo_ptr_to_inner<'a>(
     crashinfo_ptr: *mut CrashInfo,
-) -> anyhow::Result<&'a mut datadog_crashtracker::CrashInfo> {
+) -> anyhow::Result<&'a mut CrashInfoBuilder> {
     match crashinfo_ptr.as_mut() {
         None => anyhow::bail!("crashinfo pointer was null"),
         Some(inner_ptr) => match inner_ptr.inner.as_mut() {
@@ -229,6 +230,8 @@ impl<'a> TryFrom<&StackFrame<'a>> for datadog_crashtracker::StackFrame {
 pub struct SigInfo<'a> {
     pub signum: u64,
     pub signame: CharSlice<'a>,
+    /// The `si_code` of the `siginfo_t`, which gives the reason for the signal.
+    pub si_code: ddcommon_ffi::Option<i32>,
 }
 
 impl<'a> TryFrom<SigInfo<'a>> for datadog_crashtracker::SigInfo {
@@ -237,10 +240,12 @@ impl<'a> TryFrom<SigInfo<'a>> for datadog_crashtracker::SigInfo {
     fn try_from(value: SigInfo<'a>) -> Result<Self, Self::Error> {
         let signum = value.signum;
         let signame = option_from_char_slice(value.signame)?;
+        let si_code = value.si_code.into();
         let faulting_address = None; // TODO: Expose this to FFI
         Ok(Self {
             signum,
             signame,
+            si_code,
             faulting_address,
         })
     }
@@ -249,14 +254,18 @@ impl<'a> TryFrom<SigInfo<'a>> for datadog_crashtracker::SigInfo {
 #[repr(C)]
 pub struct ProcInfo {
     pub pid: u32,
+    /// The id of the thread which crashed, which is told apart from the other threads of the
+    /// process when they are unwound.
+    pub tid: ddcommon_ffi::Option<u32>,
 }
 
-impl TryFrom<ProcInfo> for datadog_crashtracker::ProcessInfo {
+impl TryFrom<ProcInfo> for datadog_crashtracker::rfc5_crash_info::ProcInfo {
     type Error = anyhow::Error;
 
     fn try_from(value: ProcInfo) -> anyhow::Result<Self> {
         let pid = value.pid;
-        Ok(Self { pid })
+        let tid = value.tid.into();
+        Ok(Self { pid, tid })
     }
 }
 
@@ -6,6 +6,8 @@ pub use datatypes::*;
 
 use crate::{option_from_char_slice, Result};
 use anyhow::Context;
+use datadog_crashtracker::rfc5_crash_info::{CrashInfoBuilder, ThreadData};
+use datadog_crashtracker::CrashtrackerMetadata;
 use ddcommon::Endpoint;
 use ddcommon_ffi::{slice::AsBytes, CharSlice, Slice, Timespec};
 
@@ -15,7 +17,7 @@ use ddcommon_ffi::{slice::AsBytes, CharSlice, Slice, Timespec};
 #[no_mangle]
 #[must_use]
 pub unsafe extern "C" fn ddog_crasht_CrashInfo_new() -> CrashInfoNewResult {
-    CrashInfoNewResult::Ok(CrashInfo::new(datadog_crashtracker::CrashInfo::new()))
+    CrashInfoNewResult::Ok(CrashInfo::new(CrashInfoBuilder::new()))
 }
 
 /// # Safety
@@ -135,8 +137,8 @@ pub unsafe extern "C" fn ddog_crasht_CrashInfo_set_metadata(
 ) -> Result {
     (|| {
         let crashinfo = crashinfo_ptr_to_inner(crashinfo)?;
-        let metadata = metadata.try_into()?;
-        crashinfo.set_metadata(metadata)
+        let metadata: CrashtrackerMetadata = metadata.try_into()?;
+        crashinfo.set_metadata(metadata.into())
     })()
     .context("ddog_crasht_CrashInfo_set_metadata failed")
     .into()
@@ -156,8 +158,8 @@ pub unsafe extern "C" fn ddog_crasht_CrashInfo_set_siginfo(
 ) -> Result {
     (|| {
         let crashinfo = crashinfo_ptr_to_inner(crashinfo)?;
-        let siginfo = siginfo.try_into()?;
-        crashinfo.set_siginfo(siginfo)
+        let siginfo: datadog_crashtracker::SigInfo = siginfo.try_into()?;
+        crashinfo.set_sig_info(siginfo.into())
     })()
     .context("ddog_crasht_CrashInfo_set_siginfo failed")
     .into()
@@ -184,7 +186,10 @@ pub unsafe extern "C" fn ddog_crasht_CrashInfo_set_stacktrace(
         for s in stacktrace.iter() {
             stacktrace_vec.push(s.try_into()?)
         }
-        crashinfo.set_stacktrace(thread_id, stacktrace_vec)
+        match thread_id {
+            Some(thread_id) => crashinfo.add_thread(ThreadData::from((thread_id, stacktrace_vec))),
+            None => crashinfo.set_stack(stacktrace_vec.into()),
+        }
     })()
     .context("ddog_crasht_CrashInfo_set_stacktrace failed")
     .into()
@@ -238,7 +243,7 @@ pub unsafe extern "C" fn ddog_crasht_CrashInfo_set_procinfo(
     (|| {
         let crashinfo = crashinfo_ptr_to_inner(crashinfo)?;
         let procinfo = procinfo.try_into()?;
-        crashinfo.set_procinfo(procinfo)
+        crashinfo.set_proc_info(procinfo)
     })()
     .context("ddog_crasht_CrashInfo_set_procinfo failed")
     .into()
@@ -246,6 +251,9 @@ pub unsafe extern "C" fn ddog_crasht_CrashInfo_set_procinfo(
 
 /// Exports `crashinfo` to the backend at `endpoint`
 /// Note that we support the "file://" endpoint for local file output.
+/// The report is built from what was set so far.  Once it's uploaded,
+/// `crashinfo` is left empty, so it can't be uploaded twice; should building or
+/// uploading the report fail, `crashinfo` is left as it was.
 /// # Safety
 /// `crashinfo` must be a valid pointer to a `CrashInfo` object.
 #[no_mangle]
@@ -257,8 +265,43 @@ pub unsafe extern "C" fn ddog_crasht_CrashInfo_upload_to_endpoint(
     (|| {
         let crashinfo = crashinfo_ptr_to_inner(crashinfo)?;
         let endpoint = endpoint.cloned();
-        crashinfo.upload_to_endpoint(&endpoint)
+        crashinfo.clone().build()?.upload_to_endpoint(&endpoint)?;
+        *crashinfo = Default::default();
+        anyhow::Ok(())
     })()
     .context("ddog_crasht_CrashInfo_upload_to_endpoint failed")
     .into()
 }
+
+/// Uploads the crash reports left in `spool_dir` by earlier crashes whose upload failed.
+/// Reports older than `max_age_secs` are deleted instead; 0 means the default of a week.
+/// API keys aren't spooled: `api_key` is used for the reports whose endpoint had one, and may be
+/// empty if there is none.
+///
+/// # Safety
+/// `spool_dir` and `api_key` should be valid references to utf8 encoded Strings.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn ddog_crasht_upload_pending_reports(
+    spool_dir: CharSlice,
+    max_age_secs: u64,
+    api_key: CharSlice,
+) -> Result {
+    (|| {
+        let spool_dir = spool_dir.try_to_utf8()?;
+        let api_key = Some(api_key.try_to_utf8()?).filter(|key| !key.is_empty());
+        let max_age = if max_age_secs == 0 {
+            datadog_crashtracker::DEFAULT_MAX_PENDING_REPORT_AGE
+        } else {
+            std::time::Duration::from_secs(max_age_secs)
+        };
+        datadog_crashtracker::upload_pending_reports(
+            std::path::Path::new(spool_dir),
+            max_age,
+            api_key,
+        )?;
+        anyhow::Ok(())
+    })()
+    .context("ddog_crasht_upload_pending_reports failed")
+    .into()
+}
@@ -122,15 +122,13 @@ fn test_crash() -> anyhow::Result<()> {
         stderr_filename,
         stdout_filename,
     )?;
-    let config = CrashtrackerConfiguration::new(
-        vec![],
-        create_alt_stack,
-        use_alt_stack,
-        endpoint,
-        resolve_frames,
-        timeout_ms,
-        None,
-    )?;
+    let config = CrashtrackerConfiguration::builder()
+        .set_create_alt_stack(create_alt_stack)
+        .set_use_alt_stack(use_alt_stack)
+        .set_endpoint(endpoint)
+        .set_resolve_frames(resolve_frames)
+        .set_timeout_ms(timeout_ms)
+        .build()?;
     let metadata = CrashtrackerMetadata::new(
         "libname".to_string(),
         "version".to_string(),
@@ -179,15 +177,13 @@ fn test_altstack_paradox() -> anyhow::Result<()> {
     let timeout_ms = 10_000;
 
     // This should return an error, because we're creating an altstack without using it
-    let config = CrashtrackerConfiguration::new(
-        vec![],
-        create_alt_stack,
-        use_alt_stack,
-        endpoint,
-        resolve_frames,
-        timeout_ms,
-        None,
-    );
+    let config = CrashtrackerConfiguration::builder()
+        .set_create_alt_stack(create_alt_stack)
+        .set_use_alt_stack(use_alt_stack)
+        .set_endpoint(endpoint)
+        .set_resolve_frames(resolve_frames)
+        .set_timeout_ms(timeout_ms)
+        .build();
 
     // This is slightly over-tuned to the language of the error message, but it'd require some
     // novel engineering just for this test in order to tighten this up.
@@ -247,15 +243,13 @@ fn test_altstack_use_create() -> anyhow::Result<()> {
         stderr_filename,
         stdout_filename,
     )?;
-    let config = CrashtrackerConfiguration::new(
-        vec![],
-        create_alt_stack,
-        use_alt_stack,
-        endpoint,
-        resolve_frames,
-        timeout_ms,
-        None,
-    )?;
+    let config = CrashtrackerConfiguration::builder()
+        .set_create_alt_stack(create_alt_stack)
+        .set_use_alt_stack(use_alt_stack)
+        .set_endpoint(endpoint)
+        .set_resolve_frames(resolve_frames)
+        .set_timeout_ms(timeout_ms)
+        .build()?;
     let metadata = CrashtrackerMetadata::new(
         "libname".to_string(),
         "version".to_string(),
@@ -374,15 +368,13 @@ fn test_altstack_use_nocreate() -> anyhow::Result<()> {
         stderr_filename,
         stdout_filename,
     )?;
-    let config = CrashtrackerConfiguration::new(
-        vec![],
-        create_alt_stack,
-        use_alt_stack,
-        endpoint,
-        resolve_frames,
-        timeout_ms,
-        None,
-    )?;
+    let config = CrashtrackerConfiguration::builder()
+        .set_create_alt_stack(create_alt_stack)
+        .set_use_alt_stack(use_alt_stack)
+        .set_endpoint(endpoint)
+        .set_resolve_frames(resolve_frames)
+        .set_timeout_ms(timeout_ms)
+        .build()?;
     let metadata = CrashtrackerMetadata::new(
         "libname".to_string(),
         "version".to_string(),
@@ -501,15 +493,13 @@ fn test_altstack_nouse() -> anyhow::Result<()> {
         stderr_filename,
         stdout_filename,
     )?;
-    let config = CrashtrackerConfiguration::new(
-        vec![],
-        create_alt_stack,
-        use_alt_stack,
-        endpoint,
-        resolve_frames,
-        timeout_ms,
-        None,
-    )?;
+    let config = CrashtrackerConfiguration::builder()
+        .set_create_alt_stack(create_alt_stack)
+        .set_use_alt_stack(use_alt_stack)
+        .set_endpoint(endpoint)
+        .set_resolve_frames(resolve_frames)
+        .set_timeout_ms(timeout_ms)
+        .build()?;
     let metadata = CrashtrackerMetadata::new(
         "libname".to_string(),
         "version".to_string(),
@@ -663,15 +653,13 @@ fn test_waitall_nohang() -> anyhow::Result<()> {
         stderr_filename,
         stdout_filename,
     )?;
-    let config = CrashtrackerConfiguration::new(
-        vec![],
-        create_alt_stack,
-        use_alt_stack,
-        endpoint,
-        resolve_frames,
-        timeout_ms,
-        None,
-    )?;
+    let config = CrashtrackerConfiguration::builder()
+        .set_create_alt_stack(create_alt_stack)
+        .set_use_alt_stack(use_alt_stack)
+        .set_endpoint(endpoint)
+        .set_resolve_frames(resolve_frames)
+        .set_timeout_ms(timeout_ms)
+        .build()?;
 
     let metadata = CrashtrackerMetadata::new(
         "libname".to_string(),
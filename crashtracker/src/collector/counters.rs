@@ -1,6 +1,7 @@
 // Copyright 2023-Present Datadog, Inc. https://www.datadoghq.com/
 // SPDX-License-Identifier: Apache-2.0
 
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicI64, Ordering::SeqCst};
 
 #[cfg(unix)]
@@ -104,6 +105,18 @@ pub fn emit_counters(w: &mut impl Write) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// The current value of each counter, by name.
+/// ATOMICITY:
+///     As for `emit_counters`, each counter is read atomically, but not the
+///     array as a whole.
+pub fn snapshot_counters() -> anyhow::Result<HashMap<String, i64>> {
+    OP_COUNTERS
+        .iter()
+        .enumerate()
+        .map(|(i, c)| Ok((OpTypes::name(i)?.to_string(), c.load(SeqCst))))
+        .collect()
+}
+
 /// Resets all counters to 0.
 /// Expected to be used after a fork, to reset the counters on the child
 /// ATOMICITY:
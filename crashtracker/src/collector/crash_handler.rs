@@ -4,9 +4,12 @@
 #![cfg(unix)]
 #![allow(deprecated)]
 
-use super::emitters::emit_crashreport;
+use super::counters::snapshot_counters;
+use super::emitters::{emit_crash_in_crash_handler, emit_crashreport, emit_error_report};
 use super::saguard::SaGuard;
+use super::spans::{snapshot_spans, snapshot_traces};
 use crate::crash_info::CrashtrackerMetadata;
+use crate::rfc5_crash_info::{CrashInfo, ErrorKind, Span, StackTrace};
 use crate::shared::configuration::{CrashtrackerConfiguration, CrashtrackerReceiverConfig};
 use crate::shared::constants::*;
 use anyhow::Context;
@@ -22,13 +25,14 @@ use nix::unistd::{close, Pid};
 use std::ffi::CString;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
+use std::mem::ManuallyDrop;
 use std::os::unix::{
     io::{FromRawFd, IntoRawFd, RawFd},
     net::UnixStream,
 };
 use std::ptr;
 use std::sync::atomic::Ordering::SeqCst;
-use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicPtr, AtomicU32, AtomicU64};
 use std::time::{Duration, Instant};
 
 // Note that this file makes use the following async-signal safe functions in a signal handler.
@@ -54,10 +58,19 @@ use libc::fork as vfork;
 #[cfg(target_os = "linux")]
 use libc::vfork;
 
+/// The handlers which were registered before ours, one per signal we
+/// registered for, so that we can chain to them.
 #[derive(Debug)]
 struct OldHandlers {
-    pub sigbus: SigAction,
-    pub sigsegv: SigAction,
+    pub handlers: Vec<(signal::Signal, SigAction)>,
+}
+
+impl OldHandlers {
+    fn get(&self, signum: i32) -> Option<&(signal::Signal, SigAction)> {
+        self.handlers
+            .iter()
+            .find(|(signal, _)| *signal as i32 == signum)
+    }
 }
 
 struct Receiver {
@@ -255,12 +268,30 @@ fn wait_for_pollhup(target_fd: RawFd, timeout_ms: i32) -> anyhow::Result<bool> {
 // This means that we can always clean up the memory inside one of these using
 // `Box::from_raw` to recreate the box, then dropping it.
 static ALTSTACK_INIT: AtomicBool = AtomicBool::new(false);
+/// Guards against sending more than one crash report per process for a signal.
+static NUM_TIMES_CALLED: AtomicU64 = AtomicU64::new(0);
+/// Guards against sending more than one report per process for an error reported by
+/// [report_error].  Kept apart from [NUM_TIMES_CALLED] so that a reported error doesn't prevent
+/// a later crash from being reported.
+static ERROR_REPORTED: AtomicBool = AtomicBool::new(false);
+/// Set once a fatal error was reported, so that the `SIGABRT` which usually follows it isn't
+/// reported as a second crash.
+static FATAL_ERROR_REPORTED: AtomicBool = AtomicBool::new(false);
 static OLD_HANDLERS: AtomicPtr<OldHandlers> = AtomicPtr::new(ptr::null_mut());
 static METADATA: AtomicPtr<(CrashtrackerMetadata, String)> = AtomicPtr::new(ptr::null_mut());
 static CONFIG: AtomicPtr<(CrashtrackerConfiguration, String)> = AtomicPtr::new(ptr::null_mut());
 static RECEIVER_CONFIG: AtomicPtr<CrashtrackerReceiverConfig> = AtomicPtr::new(ptr::null_mut());
 static RECEIVER_ARGS: AtomicPtr<PreparedExecve> = AtomicPtr::new(ptr::null_mut());
 
+// The receiver of the report being sent, if any, so that the report can still be finished if
+// the crash handler itself crashes.  The socket is -1 when no report is being sent, and the pid
+// is only set for oneshot receivers.  The thread is the one sending the report: only a signal on
+// that thread is the crash handler crashing.
+static IN_FLIGHT_RECEIVER_UDS: AtomicI32 = AtomicI32::new(-1);
+static IN_FLIGHT_THREAD: AtomicU64 = AtomicU64::new(0);
+static IN_FLIGHT_RECEIVER_PID: AtomicI32 = AtomicI32::new(0);
+static IN_FLIGHT_TIMEOUT_MS: AtomicU32 = AtomicU32::new(0);
+
 fn make_receiver(config: &CrashtrackerReceiverConfig) -> anyhow::Result<Receiver> {
     let stderr = open_file_or_quiet(config.stderr_filename.as_deref())?;
     let stdout = open_file_or_quiet(config.stdout_filename.as_deref())?;
@@ -390,20 +421,25 @@ pub fn configure_receiver(config: CrashtrackerReceiverConfig) {
 extern "C" fn handle_posix_sigaction(signum: i32, sig_info: *mut siginfo_t, ucontext: *mut c_void) {
     // Handle the signal.  Note this has a guard to ensure that we only generate
     // one crash report per process.
-    let _ = handle_posix_signal_impl(signum, sig_info);
+    let _ = handle_posix_signal_impl(signum, sig_info, ucontext);
 
     // Once we've handled the signal, chain to any previous handlers.
     // SAFETY: This was created by [register_crash_handlers].  There is a tiny
     // instant of time between when the handlers are registered, and the
     // `OLD_HANDLERS` are set.  This should be very short, but is hard to fully
     // eliminate given the existing POSIX APIs.
-    let old_handlers = unsafe { &*OLD_HANDLERS.load(SeqCst) };
-    let old_sigaction = if signum == libc::SIGSEGV {
-        old_handlers.sigsegv
-    } else if signum == libc::SIGBUS {
-        old_handlers.sigbus
-    } else {
-        unreachable!("The only signals we're registered for are SEGV and BUS")
+    let old_handlers = unsafe { OLD_HANDLERS.load(SeqCst).as_ref() };
+    let Some(&(signal, old_sigaction)) = old_handlers.and_then(|handlers| handlers.get(signum))
+    else {
+        // We only register for signals we store the old handlers of, so this shouldn't happen.
+        // Returning would re-execute the crashing instruction, so fall back to the default
+        // disposition instead.
+        let default = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+        if let Ok(signal) = signal::Signal::try_from(signum) {
+            let _ = unsafe { signal::sigaction(signal, &default) };
+        }
+        unsafe { libc::raise(signum) };
+        return;
     };
 
     // How we chain depends on what kind of handler we're chaining to.
@@ -416,13 +452,6 @@ extern "C" fn handle_posix_sigaction(signum: i32, sig_info: *mut siginfo_t, ucon
             // In the case of a default handler, we want to invoke it so that
             // the core-dump can be generated.  Restoring the handler then
             // re-raising the signal accomplishes that.
-            let signal = if signum == libc::SIGSEGV {
-                signal::SIGSEGV
-            } else if signum == libc::SIGBUS {
-                signal::SIGBUS
-            } else {
-                unreachable!("The only signals we're registered for are SEGV and BUS")
-            };
             unsafe { signal::sigaction(signal, &old_sigaction) }
                 .unwrap_or_else(|_| std::process::abort());
             // Signals are only delivered once.
@@ -496,7 +525,132 @@ fn receiver_finish(receiver: Receiver, start_time: Instant, timeout_ms: u32) {
     }
 }
 
-fn handle_posix_signal_impl(signum: i32, sig_info: *mut siginfo_t) -> anyhow::Result<()> {
+/// Allows the receiver to read this process's memory with `process_vm_readv`.
+/// Yama's default ptrace scope only lets ancestors do that.  Only a receiver we
+/// forked ourselves is granted access: the pid of a process on the other end of
+/// a socket may belong to another pid namespace, and then designate some other
+/// process.  `prctl` is signal safe.  This is best effort: without it the
+/// memory dumps are missing.
+#[cfg(target_os = "linux")]
+fn allow_receiver_to_read_memory(receiver: &Receiver) {
+    if receiver.receiver_pid > 0 {
+        unsafe {
+            libc::prctl(
+                libc::PR_SET_PTRACER,
+                receiver.receiver_pid as libc::c_ulong,
+                0,
+                0,
+                0,
+            )
+        };
+    }
+}
+
+/// Creates the receiver.  This all hinges on whether or not the configuration has a non-null unix
+/// domain socket specified.  If it doesn't, then we need to check the receiver configuration.  If
+/// it does, then we just connect to the socket.
+fn connect_receiver(config: &CrashtrackerConfiguration) -> anyhow::Result<Receiver> {
+    let unix_socket_path = config.unix_socket_path.clone().unwrap_or_default();
+
+    if !unix_socket_path.is_empty() {
+        receiver_from_socket(&unix_socket_path)
+    } else {
+        let receiver_config = RECEIVER_CONFIG.load(SeqCst);
+        if receiver_config.is_null() {
+            return Err(anyhow::anyhow!("No receiver config"));
+        }
+        let receiver_config = unsafe { receiver_config.as_ref().context("receiver config")? };
+        make_receiver(receiver_config)
+    }
+}
+
+/// Clones the value a global points to.  A reference to it can't be held
+/// instead: [update_config] and [update_metadata] free the value they replace.
+/// So the value is taken out of the global while it is cloned, the way the
+/// signal handler takes it, and put back unless a new value was set meanwhile.
+/// A crash during that short window finds no value, and isn't reported.
+fn clone_global<T: Clone>(global: &AtomicPtr<T>) -> Option<T> {
+    let ptr = global.swap(ptr::null_mut(), SeqCst);
+    if ptr.is_null() {
+        return None;
+    }
+    // Safety: the pointer comes from a box leaked by an update function, which no one else can
+    // free while it is out of the global.
+    let value = unsafe { (*ptr).clone() };
+    if global
+        .compare_exchange(ptr::null_mut(), ptr, SeqCst, SeqCst)
+        .is_err()
+    {
+        // Safety: the box was replaced meanwhile, so it isn't referenced anymore.
+        unsafe { drop(Box::from_raw(ptr)) };
+    }
+    Some(value)
+}
+
+/// Sends a report of an error which isn't a signal, e.g. a panic, to the
+/// receiver.  `stack` is the stack of the current thread.  Only the first error
+/// of the process is reported.  `fatal` tells whether the process is about to
+/// terminate because of the error, in which case the signal terminating it
+/// isn't reported again.
+///
+/// This doesn't wait for the receiver to be done with the report: the report
+/// is fully written before returning, and the receiver is waited for, and
+/// reaped if oneshot, from a background thread.
+///
+/// This isn't called from a signal handler, so unlike [handle_posix_signal_impl]
+/// it is free to allocate, and works on copies of the configuration and
+/// metadata, see [clone_global].
+pub(crate) fn report_error(
+    kind: ErrorKind,
+    message: Option<String>,
+    stack: StackTrace,
+    fatal: bool,
+) -> anyhow::Result<()> {
+    // Check this before the guard, so that an error before initialization doesn't prevent later
+    // errors from being reported.
+    anyhow::ensure!(
+        !CONFIG.load(SeqCst).is_null(),
+        "The crashtracker is not initialized"
+    );
+    if ERROR_REPORTED.swap(true, SeqCst) {
+        return Ok(());
+    }
+    let (config, config_str) = clone_global(&CONFIG).context("No crashtracking config")?;
+    let (metadata, _) = clone_global(&METADATA).context("No crashtracking metadata")?;
+
+    let start_time = Instant::now();
+    let mut crash_info = CrashInfo::new_error(kind, message, stack, metadata);
+    crash_info.counters = snapshot_counters()?;
+    let to_spans = |ids: Vec<u128>| ids.into_iter().map(Span::from_id).collect();
+    crash_info.span_ids = to_spans(snapshot_spans()?);
+    crash_info.trace_ids = to_spans(snapshot_traces()?);
+
+    let receiver = connect_receiver(&config)?;
+    let mut unix_stream = unsafe { UnixStream::from_raw_fd(receiver.receiver_uds) };
+    let res = emit_error_report(&mut unix_stream, &config_str, &crash_info);
+    let _ = unix_stream.flush();
+    unix_stream
+        .shutdown(std::net::Shutdown::Write)
+        .context("Could not shutdown writing on the stream")?;
+    if fatal && res.is_ok() {
+        FATAL_ERROR_REPORTED.store(true, SeqCst);
+    }
+    let timeout_ms = config.timeout_ms;
+    std::thread::Builder::new()
+        .name("dd-crasht-report".to_string())
+        .spawn(move || {
+            receiver_finish(receiver, start_time, timeout_ms);
+            drop(unix_stream);
+        })
+        .context("Could not spawn a thread to wait for the receiver")?;
+    res
+}
+
+fn handle_posix_signal_impl(
+    signum: i32,
+    sig_info: *mut siginfo_t,
+    ucontext: *mut c_void,
+) -> anyhow::Result<()> {
     // If this is a SIGSEGV signal, it could be called due to a stack overflow. In that case, since
     // this signal allocates to the stack and cannot guarantee it is running without SA_NODEFER, it
     // is possible that we will re-emit the signal. Contemporary unices handle this just fine (no
@@ -504,11 +658,18 @@ fn handle_posix_signal_impl(signum: i32, sig_info: *mut siginfo_t) -> anyhow::Re
     // In general, handlers do not know their own stack usage requirements in advance and are
     // incapable of guaranteeing that they will not overflow the stack.
 
+    // The process aborting after a fatal error was already reported isn't a new crash.
+    if signum == libc::SIGABRT && FATAL_ERROR_REPORTED.load(SeqCst) {
+        return Ok(());
+    }
+
     // One-time guard to guarantee at most one crash per process
-    static NUM_TIMES_CALLED: AtomicU64 = AtomicU64::new(0);
     if NUM_TIMES_CALLED.fetch_add(1, SeqCst) > 0 {
         // In the case where some lower-level signal handler recovered the error
         // we don't want to spam the system with calls.  Make this one shot.
+        // A signal on the thread sending the report is the crash handler itself crashing, though,
+        // in which case the receiver is told so and the report finished.
+        handle_crash_in_crash_handler(signum);
         return Ok(());
     }
 
@@ -538,13 +699,22 @@ fn handle_posix_signal_impl(signum: i32, sig_info: *mut siginfo_t) -> anyhow::Re
     let timeout_ms = config.timeout_ms;
     let start_time = Instant::now(); // This is the time at which the signal was received
 
-    // Derive the faulting address from `sig_info`
-    let faulting_address: Option<usize> =
-        if !sig_info.is_null() && (signum == libc::SIGSEGV || signum == libc::SIGBUS) {
-            unsafe { Some((*sig_info).si_addr() as usize) }
-        } else {
-            None
-        };
+    // Derive the faulting address and the reason for the signal from `sig_info`.  The address is
+    // only meaningful for signals raised by a faulting instruction.
+    let faulting_address: Option<usize> = if !sig_info.is_null()
+        && matches!(
+            signum,
+            libc::SIGSEGV | libc::SIGBUS | libc::SIGILL | libc::SIGFPE | libc::SIGTRAP
+        ) {
+        unsafe { Some((*sig_info).si_addr() as usize) }
+    } else {
+        None
+    };
+    let si_code = if sig_info.is_null() {
+        None
+    } else {
+        unsafe { Some((*sig_info).si_code) }
+    };
 
     // During the execution of this signal handler, block ALL other signals, especially because we
     // cannot control whether or not we run with SA_NODEFER (crashtracker might have been chained).
@@ -554,24 +724,24 @@ fn handle_posix_signal_impl(signum: i32, sig_info: *mut siginfo_t) -> anyhow::Re
     // disrupted.
     let _guard = SaGuard::<2>::new(&[signal::SIGCHLD, signal::SIGPIPE])?;
 
-    // Optionally, create the receiver.  This all hinges on whether or not the configuration has a
-    // non-null unix domain socket specified.  If it doesn't, then we need to check the receiver
-    // configuration.  If it does, then we just connect to the socket.
-    let unix_socket_path = config.unix_socket_path.clone().unwrap_or_default();
+    let receiver = connect_receiver(config)?;
 
-    let receiver = if !unix_socket_path.is_empty() {
-        receiver_from_socket(&unix_socket_path)?
-    } else {
-        let receiver_config = RECEIVER_CONFIG.load(SeqCst);
-        if receiver_config.is_null() {
-            return Err(anyhow::anyhow!("No receiver config"));
-        }
-        let receiver_config = unsafe { receiver_config.as_ref().context("receiver config")? };
-        make_receiver(receiver_config)?
-    };
+    #[cfg(target_os = "linux")]
+    allow_receiver_to_read_memory(&receiver);
 
     // No matter how the receiver was created, attach to its stream
     let mut unix_stream = unsafe { UnixStream::from_raw_fd(receiver.receiver_uds) };
+    IN_FLIGHT_RECEIVER_PID.store(
+        if receiver.oneshot {
+            receiver.receiver_pid
+        } else {
+            0
+        },
+        SeqCst,
+    );
+    IN_FLIGHT_TIMEOUT_MS.store(timeout_ms, SeqCst);
+    IN_FLIGHT_THREAD.store(current_thread_id(), SeqCst);
+    IN_FLIGHT_RECEIVER_UDS.store(receiver.receiver_uds, SeqCst);
 
     // Currently the emission of the crash report doesn't have a firm time guarantee
     // In a future patch, the timeout parameter should be passed into the IPC loop here and
@@ -582,9 +752,13 @@ fn handle_posix_signal_impl(signum: i32, sig_info: *mut siginfo_t) -> anyhow::Re
         config_str,
         metadata_string,
         signum,
+        si_code,
         faulting_address,
+        ucontext,
     );
 
+    // From here on, a crash is no longer one of the collection.
+    IN_FLIGHT_RECEIVER_UDS.store(-1, SeqCst);
     let _ = unix_stream.flush();
     unix_stream
         .shutdown(std::net::Shutdown::Write)
@@ -596,6 +770,56 @@ fn handle_posix_signal_impl(signum: i32, sig_info: *mut siginfo_t) -> anyhow::Re
     res
 }
 
+/// The id of the calling thread, which is async signal safe to get.
+fn current_thread_id() -> u64 {
+    #[cfg(target_os = "linux")]
+    return nix::unistd::gettid().as_raw() as u64;
+    #[cfg(not(target_os = "linux"))]
+    return unsafe { libc::pthread_self() } as usize as u64;
+}
+
+/// Finishes the report being sent, if any, after the crash handler crashed
+/// with `signum` while collecting it, e.g. while unwinding a corrupted stack.
+/// The original handler doesn't resume after this: the signal is chained to
+/// the previous handler, which usually terminates the process.  So this waits
+/// for the receiver, as the original handler would have.
+///
+/// This avoids anything which may create an `anyhow` error: with backtraces
+/// enabled, creating one captures a backtrace, and the crash may have
+/// interrupted a capture while it held the lock.
+fn handle_crash_in_crash_handler(signum: i32) {
+    // A signal on another thread, e.g. a crash racing with this one, isn't the crash handler
+    // crashing.  It returns as any signal after the first does, leaving the report to its thread.
+    if IN_FLIGHT_RECEIVER_UDS.load(SeqCst) < 0
+        || IN_FLIGHT_THREAD.load(SeqCst) != current_thread_id()
+    {
+        return;
+    }
+    // Only once: should this crash too, the next signal finds no report in flight.
+    let receiver_uds = IN_FLIGHT_RECEIVER_UDS.swap(-1, SeqCst);
+    if receiver_uds < 0 {
+        return;
+    }
+    // The interrupted handler still owns the stream.
+    let mut unix_stream = ManuallyDrop::new(unsafe { UnixStream::from_raw_fd(receiver_uds) });
+    let _ = emit_crash_in_crash_handler(&mut *unix_stream, signum);
+    let _ = unix_stream.shutdown(std::net::Shutdown::Write);
+
+    let mut poll_fds = [pollfd {
+        fd: receiver_uds,
+        events: POLLHUP,
+        revents: 0,
+    }];
+    let timeout_ms = IN_FLIGHT_TIMEOUT_MS.load(SeqCst).min(i32::MAX as u32) as i32;
+    unsafe { poll(poll_fds.as_mut_ptr(), poll_fds.len() as nfds_t, timeout_ms) };
+    // As in `receiver_finish`, a oneshot receiver is killed in case it is stuck.  It is left to
+    // be reaped along with this process.
+    let receiver_pid = IN_FLIGHT_RECEIVER_PID.load(SeqCst);
+    if receiver_pid > 1 {
+        unsafe { libc::kill(receiver_pid, libc::SIGKILL) };
+    }
+}
+
 /// Registers UNIX signal handlers to detect program crashes.
 /// This function can be called multiple times and will be idempotent: it will
 /// only create and set the handlers once.
@@ -629,9 +853,23 @@ pub fn register_crash_handlers() -> anyhow::Result<()> {
         if config.create_alt_stack {
             create_alt_stack()?;
         }
-        let sigbus = register_signal_handler(signal::SIGBUS, config)?;
-        let sigsegv = register_signal_handler(signal::SIGSEGV, config)?;
-        let boxed_ptr = Box::into_raw(Box::new(OldHandlers { sigbus, sigsegv }));
+        let mut handlers = Vec::with_capacity(config.signals.len());
+        for &signum in config.signals.iter() {
+            let registered = signal::Signal::try_from(signum)
+                .map_err(anyhow::Error::from)
+                .and_then(|signal| Ok((signal, register_signal_handler(signal, config)?)));
+            match registered {
+                Ok(handler) => handlers.push(handler),
+                Err(err) => {
+                    // Don't leave a partial registration behind.
+                    for (signal, old_handler) in handlers.iter() {
+                        let _ = signal::sigaction(*signal, old_handler);
+                    }
+                    return Err(err.context(format!("Failed to register handler for {signum}")));
+                }
+            }
+        }
+        let boxed_ptr = Box::into_raw(Box::new(OldHandlers { handlers }));
 
         let res = OLD_HANDLERS.compare_exchange(ptr::null_mut(), boxed_ptr, SeqCst, SeqCst);
         anyhow::ensure!(
@@ -679,9 +917,10 @@ pub fn restore_old_handlers(inside_signal_handler: bool) -> anyhow::Result<()> {
     anyhow::ensure!(!prev.is_null(), "No crashtracking previous signal handlers");
     // Safety: The only nonnull pointer stored here comes from Box::into_raw()
     let prev = unsafe { Box::from_raw(prev) };
-    // Safety: The value restored here was returned from a previous sigaction call
-    unsafe { signal::sigaction(signal::SIGBUS, &prev.sigbus)? };
-    unsafe { signal::sigaction(signal::SIGSEGV, &prev.sigsegv)? };
+    for (signal, old_handler) in prev.handlers.iter() {
+        // Safety: The value restored here was returned from a previous sigaction call
+        unsafe { signal::sigaction(*signal, old_handler)? };
+    }
     // We want to avoid freeing memory inside the handler, so just leak it
     // This is fine since we're crashing anyway at this point
     if inside_signal_handler {
@@ -4,6 +4,7 @@
 use crate::collector::counters::emit_counters;
 use crate::collector::spans::emit_spans;
 use crate::collector::spans::emit_traces;
+use crate::rfc5_crash_info::{SiCodes, SignalNames};
 use crate::shared::constants::*;
 use crate::CrashtrackerConfiguration;
 use crate::StacktraceCollection;
@@ -29,85 +30,88 @@ unsafe fn emit_backtrace_by_frames(
     w: &mut impl Write,
     resolve_frames: StacktraceCollection,
 ) -> anyhow::Result<()> {
+    // The values we can get without resolving, since these seem to be crash
+    // safe in my experiments.
+    fn write_addresses(w: &mut impl Write, frame: &backtrace::Frame) -> std::io::Result<()> {
+        write!(w, "{{\"ip\": \"{:?}\"", frame.ip())?;
+        if let Some(module_base_address) = frame.module_base_address() {
+            write!(w, ", \"module_base_address\": \"{module_base_address:?}\"")?;
+        }
+        write!(w, ", \"sp\": \"{:?}\"", frame.sp())?;
+        write!(w, ", \"symbol_address\": \"{:?}\"", frame.symbol_address())
+    }
+
     // https://docs.rs/backtrace/latest/backtrace/index.html
     writeln!(w, "{DD_CRASHTRACK_BEGIN_STACKTRACE}")?;
     backtrace::trace_unsynchronized(|frame| {
-        // Write the values we can get without resolving, since these seem to
-        // be crash safe in my experiments.
-        write!(w, "{{").unwrap();
-        write!(w, "\"ip\": \"{:?}\", ", frame.ip()).unwrap();
-        if let Some(module_base_address) = frame.module_base_address() {
-            write!(w, "\"module_base_address\": \"{module_base_address:?}\", ",).unwrap();
-        }
-        write!(w, "\"sp\": \"{:?}\", ", frame.sp()).unwrap();
-        write!(w, "\"symbol_address\": \"{:?}\"", frame.symbol_address()).unwrap();
         if resolve_frames == StacktraceCollection::EnabledWithInprocessSymbols {
-            write!(w, ", \"names\": [").unwrap();
-
-            let mut first = true;
+            let mut resolved = false;
             // This can give multiple answers in the case of inlined functions
             // https://docs.rs/backtrace/latest/backtrace/fn.resolve.html
-            // Store them all into an array of names
+            // Each of them is sent as a frame of its own, innermost first.
             unsafe {
                 backtrace::resolve_frame_unsynchronized(frame, |symbol| {
-                    if !first {
-                        write!(w, ", ").unwrap();
-                    }
-                    write!(w, "{{").unwrap();
-                    let mut comma_needed = false;
+                    write_addresses(w, frame).unwrap();
                     if let Some(name) = symbol.name() {
-                        write!(w, "\"name\": \"{}\"", name).unwrap();
-                        comma_needed = true;
+                        write!(w, ", \"function\": \"{}\"", name).unwrap();
                     }
                     if let Some(filename) = symbol.filename() {
-                        if comma_needed {
-                            write!(w, ", ").unwrap();
-                        }
-                        write!(w, "\"filename\": {:?}", filename).unwrap();
-                        comma_needed = true;
+                        write!(w, ", \"file\": {:?}", filename).unwrap();
                     }
                     if let Some(colno) = symbol.colno() {
-                        if comma_needed {
-                            write!(w, ", ").unwrap();
-                        }
-                        write!(w, "\"colno\": {}", colno).unwrap();
-                        comma_needed = true;
+                        write!(w, ", \"column\": {}", colno).unwrap();
                     }
-
                     if let Some(lineno) = symbol.lineno() {
-                        if comma_needed {
-                            write!(w, ", ").unwrap();
-                        }
-                        write!(w, "\"lineno\": {}", lineno).unwrap();
+                        write!(w, ", \"line\": {}", lineno).unwrap();
                     }
-
-                    write!(w, "}}").unwrap();
-
-                    first = false;
+                    writeln!(w, "}}").unwrap();
+                    resolved = true;
                 });
             }
-            write!(w, "]").unwrap();
+            if resolved {
+                // Send the frames one at a time, so that those before a frame which crashes or
+                // hangs the unwinding still make it to the receiver.
+                let _ = w.flush();
+                return true;
+            }
         }
+        write_addresses(w, frame).unwrap();
         writeln!(w, "}}").unwrap();
+        let _ = w.flush();
         true // keep going to the next frame
     });
     writeln!(w, "{DD_CRASHTRACK_END_STACKTRACE}").unwrap();
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn emit_crashreport(
     pipe: &mut impl Write,
     config: &CrashtrackerConfiguration,
     config_str: &str,
     metadata_string: &str,
     signum: i32,
+    si_code: Option<i32>,
     faulting_address: Option<usize>,
+    ucontext: *const libc::c_void,
 ) -> anyhow::Result<()> {
+    // Each block is flushed on its own, so that the receiver gets every block before the one
+    // which hangs or crashes, if any, and can tell which one it was.  The cheap and safe ones go
+    // first.
+    emit_protocol_version(pipe)?;
+    pipe.flush()?;
     emit_metadata(pipe, metadata_string)?;
+    pipe.flush()?;
     emit_config(pipe, config_str)?;
-    emit_siginfo(pipe, signum, faulting_address)?;
+    pipe.flush()?;
+    emit_siginfo(pipe, signum, si_code, faulting_address)?;
+    pipe.flush()?;
     emit_procinfo(pipe)?;
     pipe.flush()?;
+    // The receiver reads the memory around the registers as soon as it gets them, so send them
+    // early, while this process is still around to be read.
+    emit_ucontext(pipe, ucontext, faulting_address)?;
+    pipe.flush()?;
     emit_counters(pipe)?;
     pipe.flush()?;
     emit_spans(pipe)?;
@@ -117,6 +121,7 @@ pub(crate) fn emit_crashreport(
 
     #[cfg(target_os = "linux")]
     emit_proc_self_maps(pipe)?;
+    pipe.flush()?;
 
     // Getting a backtrace on rust is not guaranteed to be signal safe
     // https://github.com/rust-lang/backtrace-rs/issues/414
@@ -133,6 +138,50 @@ pub(crate) fn emit_crashreport(
     Ok(())
 }
 
+/// Emits a report which was built in full by the collector, for errors other
+/// than signals.  Unlike `emit_crashreport`, this is never called from a
+/// signal handler, so it can allocate.
+pub(crate) fn emit_error_report(
+    pipe: &mut impl Write,
+    config_str: &str,
+    crash_info: &crate::rfc5_crash_info::CrashInfo,
+) -> anyhow::Result<()> {
+    emit_protocol_version(pipe)?;
+    emit_config(pipe, config_str)?;
+    writeln!(pipe, "{DD_CRASHTRACK_BEGIN_CRASHINFO}")?;
+    serde_json::to_writer(&mut *pipe, crash_info)?;
+    writeln!(pipe)?;
+    writeln!(pipe, "{DD_CRASHTRACK_END_CRASHINFO}")?;
+    writeln!(pipe, "{DD_CRASHTRACK_DONE}")?;
+    pipe.flush()?;
+    Ok(())
+}
+
+/// Finishes the report after the crash handler itself crashed with `signum`,
+/// e.g. while unwinding a corrupted stack.  The blocks, and the stack frames,
+/// sent before are already with the receiver, so all that is left is to tell
+/// it what happened.  This only formats an integer, so it doesn't allocate.
+/// Unlike the other emitters it doesn't return `anyhow` errors, which may
+/// capture a backtrace, and the crash may have interrupted one.
+pub(crate) fn emit_crash_in_crash_handler(
+    pipe: &mut impl Write,
+    signum: i32,
+) -> std::io::Result<()> {
+    // The crash may have interrupted a line, which the receiver then ignores.
+    writeln!(pipe, "{DD_CRASHTRACK_CRASH_IN_HANDLER} {signum}")?;
+    writeln!(pipe, "{DD_CRASHTRACK_DONE}")?;
+    pipe.flush()?;
+    Ok(())
+}
+
+fn emit_protocol_version(w: &mut impl Write) -> anyhow::Result<()> {
+    writeln!(
+        w,
+        "{DD_CRASHTRACK_PROTOCOL_VERSION} {DD_CRASHTRACK_CURRENT_PROTOCOL_VERSION}"
+    )?;
+    Ok(())
+}
+
 fn emit_config(w: &mut impl Write, config_str: &str) -> anyhow::Result<()> {
     writeln!(w, "{DD_CRASHTRACK_BEGIN_CONFIG}")?;
     writeln!(w, "{}", config_str)?;
@@ -150,11 +199,111 @@ fn emit_metadata(w: &mut impl Write, metadata_str: &str) -> anyhow::Result<()> {
 fn emit_procinfo(w: &mut impl Write) -> anyhow::Result<()> {
     writeln!(w, "{DD_CRASHTRACK_BEGIN_PROCINFO}")?;
     let pid = nix::unistd::getpid();
+    #[cfg(target_os = "linux")]
+    {
+        // The crashing thread, so that the receiver can tell it apart from the others.
+        let tid = nix::unistd::gettid();
+        writeln!(w, "{{\"pid\": {pid}, \"tid\": {tid} }}")?;
+    }
+    #[cfg(not(target_os = "linux"))]
     writeln!(w, "{{\"pid\": {pid} }}")?;
     writeln!(w, "{DD_CRASHTRACK_END_PROCINFO}")?;
     Ok(())
 }
 
+/// Emits the registers of the crashing thread, saved by the kernel in the
+/// `ucontext_t` passed to the signal handler.  This only formats integers, so
+/// it doesn't allocate.
+fn emit_ucontext(
+    w: &mut impl Write,
+    ucontext: *const libc::c_void,
+    faulting_address: Option<usize>,
+) -> anyhow::Result<()> {
+    if ucontext.is_null() {
+        return Ok(());
+    }
+    // Safety: the kernel passes a valid `ucontext_t` to `SA_SIGINFO` handlers.
+    let Some((arch, registers)) = (unsafe { ucontext_registers(ucontext) }) else {
+        return Ok(());
+    };
+    writeln!(w, "{DD_CRASHTRACK_BEGIN_UCONTEXT}")?;
+    write!(w, "{{\"arch\": \"{arch}\"")?;
+    if let Some(addr) = faulting_address {
+        write!(w, ", \"fault_address\": \"{addr:#018x}\"")?;
+    }
+    write!(w, ", \"registers\": {{")?;
+    for (i, (name, value)) in registers.iter().enumerate() {
+        let separator = if i == 0 { "" } else { ", " };
+        write!(w, "{separator}\"{name}\": \"{value:#018x}\"")?;
+    }
+    writeln!(w, "}}}}")?;
+    writeln!(w, "{DD_CRASHTRACK_END_UCONTEXT}")?;
+    Ok(())
+}
+
+/// The general purpose registers in a `ucontext_t`, by name, along with the
+/// name of the architecture.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+unsafe fn ucontext_registers(
+    ucontext: *const libc::c_void,
+) -> Option<(&'static str, [(&'static str, u64); 18])> {
+    let gregs = &(*ucontext.cast::<libc::ucontext_t>()).uc_mcontext.gregs;
+    let reg = |index: libc::c_int| gregs[index as usize] as u64;
+    Some((
+        "x86_64",
+        [
+            ("rax", reg(libc::REG_RAX)),
+            ("rbx", reg(libc::REG_RBX)),
+            ("rcx", reg(libc::REG_RCX)),
+            ("rdx", reg(libc::REG_RDX)),
+            ("rsi", reg(libc::REG_RSI)),
+            ("rdi", reg(libc::REG_RDI)),
+            ("rbp", reg(libc::REG_RBP)),
+            ("rsp", reg(libc::REG_RSP)),
+            ("r8", reg(libc::REG_R8)),
+            ("r9", reg(libc::REG_R9)),
+            ("r10", reg(libc::REG_R10)),
+            ("r11", reg(libc::REG_R11)),
+            ("r12", reg(libc::REG_R12)),
+            ("r13", reg(libc::REG_R13)),
+            ("r14", reg(libc::REG_R14)),
+            ("r15", reg(libc::REG_R15)),
+            ("rip", reg(libc::REG_RIP)),
+            ("eflags", reg(libc::REG_EFL)),
+        ],
+    ))
+}
+
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+unsafe fn ucontext_registers(
+    ucontext: *const libc::c_void,
+) -> Option<(&'static str, [(&'static str, u64); 34])> {
+    const NAMES: [&str; 31] = [
+        "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13",
+        "x14", "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26",
+        "x27", "x28", "fp", "lr",
+    ];
+    let mcontext = &(*ucontext.cast::<libc::ucontext_t>()).uc_mcontext;
+    let mut registers = [("", 0u64); 34];
+    for (i, name) in NAMES.iter().enumerate() {
+        registers[i] = (name, mcontext.regs[i] as u64);
+    }
+    registers[31] = ("sp", mcontext.sp as u64);
+    registers[32] = ("pc", mcontext.pc as u64);
+    registers[33] = ("pstate", mcontext.pstate as u64);
+    Some(("aarch64", registers))
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+unsafe fn ucontext_registers(
+    _ucontext: *const libc::c_void,
+) -> Option<(&'static str, [(&'static str, u64); 0])> {
+    None
+}
+
 #[cfg(target_os = "linux")]
 /// `/proc/self/maps` is very useful for debugging, and difficult to get from
 /// the child process (permissions issues on Linux).  Emit it directly onto the
@@ -167,25 +316,31 @@ fn emit_proc_self_maps(w: &mut impl Write) -> anyhow::Result<()> {
 fn emit_siginfo(
     w: &mut impl Write,
     signum: i32,
+    si_code: Option<i32>,
     faulting_address: Option<usize>,
 ) -> anyhow::Result<()> {
-    let signame = if signum == libc::SIGSEGV {
-        "SIGSEGV"
-    } else if signum == libc::SIGBUS {
-        "SIGBUS"
-    } else {
-        "UNKNOWN"
+    // The names are fieldless enums, whose `Debug` doesn't allocate.
+    let si_signo_human_readable = SignalNames::from(signum);
+    let (si_code, si_code_human_readable) = match si_code {
+        Some(si_code) => (si_code, SiCodes::from_signo_and_code(signum, si_code)),
+        None => (-1, SiCodes::UNKNOWN),
     };
 
     writeln!(w, "{DD_CRASHTRACK_BEGIN_SIGINFO}")?;
+    write!(w, "{{\"si_signo\": {signum}")?;
+    write!(
+        w,
+        ", \"si_signo_human_readable\": \"{si_signo_human_readable:?}\""
+    )?;
+    write!(w, ", \"si_code\": {si_code}")?;
+    write!(
+        w,
+        ", \"si_code_human_readable\": \"{si_code_human_readable:?}\""
+    )?;
     if let Some(addr) = faulting_address {
-        writeln!(
-            w,
-            "{{\"signum\": {signum}, \"signame\": \"{signame}\", \"faulting_address\": {addr}}}"
-        )?;
-    } else {
-        writeln!(w, "{{\"signum\": {signum}, \"signame\": \"{signame}\"}}")?;
-    };
+        write!(w, ", \"si_addr\": \"{addr:#018x}\"")?;
+    }
+    writeln!(w, "}}")?;
     writeln!(w, "{DD_CRASHTRACK_END_SIGINFO}")?;
     Ok(())
 }
@@ -234,3 +389,59 @@ fn emit_text_file(w: &mut impl Write, path: &str) -> anyhow::Result<()> {
     w.flush()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emit_siginfo() -> anyhow::Result<()> {
+        let mut buf = Vec::new();
+        emit_siginfo(&mut buf, libc::SIGILL, Some(2), Some(0x1000))?;
+        let out = String::from_utf8(buf)?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], DD_CRASHTRACK_BEGIN_SIGINFO);
+        assert_eq!(lines[2], DD_CRASHTRACK_END_SIGINFO);
+        let siginfo: crate::rfc5_crash_info::SigInfo = serde_json::from_str(lines[1])?;
+        assert_eq!(
+            siginfo,
+            crate::rfc5_crash_info::SigInfo {
+                si_addr: Some("0x0000000000001000".to_string()),
+                si_code: 2,
+                si_code_human_readable: SiCodes::ILL_ILLOPN,
+                si_signo: libc::SIGILL,
+                si_signo_human_readable: SignalNames::SIGILL,
+            }
+        );
+        Ok(())
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[test]
+    fn test_emit_ucontext() -> anyhow::Result<()> {
+        let mut ucontext: libc::ucontext_t = unsafe { std::mem::zeroed() };
+        ucontext.uc_mcontext.gregs[libc::REG_RIP as usize] = 0x1234;
+        ucontext.uc_mcontext.gregs[libc::REG_RSP as usize] = 0x7ffc0000;
+
+        let mut buf = Vec::new();
+        emit_ucontext(&mut buf, std::ptr::null(), None)?;
+        assert!(buf.is_empty());
+
+        let ptr: *const libc::ucontext_t = &ucontext;
+        emit_ucontext(&mut buf, ptr.cast(), Some(0x10))?;
+        let out = String::from_utf8(buf)?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], DD_CRASHTRACK_BEGIN_UCONTEXT);
+        assert_eq!(lines[2], DD_CRASHTRACK_END_UCONTEXT);
+        let context: crate::rfc5_crash_info::FaultContext = serde_json::from_str(lines[1])?;
+        assert_eq!(context.arch, "x86_64");
+        assert_eq!(context.fault_address.as_deref(), Some("0x0000000000000010"));
+        assert_eq!(context.registers.len(), 18);
+        assert_eq!(context.registers["rip"], "0x0000000000001234");
+        assert_eq!(context.registers["rsp"], "0x000000007ffc0000");
+        assert_eq!(context.registers["rax"], "0x0000000000000000");
+        Ok(())
+    }
+}
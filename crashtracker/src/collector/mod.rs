@@ -5,10 +5,12 @@ mod api;
 mod counters;
 mod crash_handler;
 mod emitters;
+mod panic_hook;
 mod saguard;
 mod spans;
 
 pub use api::*;
 pub use counters::{begin_op, end_op, reset_counters, OpTypes};
 pub use crash_handler::{update_config, update_metadata};
+pub use panic_hook::{install_panic_hook, report_unhandled_exception};
 pub use spans::{clear_spans, clear_traces, insert_span, insert_trace, remove_span, remove_trace};
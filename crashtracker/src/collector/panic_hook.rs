@@ -0,0 +1,137 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+//! Reports crashes which aren't signals: Rust panics, and exceptions which a
+//! language runtime, or C++ code, didn't handle.
+
+use super::crash_handler::report_error;
+use crate::rfc5_crash_info::{ErrorKind, StackFrame, StackTrace};
+use std::any::Any;
+use std::panic::Location;
+
+/// Installs a panic hook which reports panics as crashes, with the panic
+/// message, its location and the stack of the panicking thread, then calls the
+/// previously installed hook.
+///
+/// Panics are reported whether they unwind or abort: in native extensions
+/// built with `panic = "unwind"`, a panic which isn't caught takes the process
+/// down without a signal the crash handler could see.  The hook runs before
+/// unwinding, so it can't tell whether the panic will be caught; a caught one
+/// is reported too.  Only the first panic of the process is reported, and with
+/// `panic = "abort"` the abort which follows it isn't reported again as a
+/// `SIGABRT`.  The hook doesn't wait for the receiver to process the report.
+///
+/// PRECONDITIONS:
+///     The crashtracker should be initialized, otherwise panics are not
+///     reported.
+pub fn install_panic_hook() {
+    let fatal = cfg!(panic = "abort");
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = panic_message(
+            std::thread::current().name(),
+            info.payload(),
+            info.location(),
+        );
+        report_error(ErrorKind::Panic, Some(message), current_stack(), fatal)
+            .unwrap_or_else(|err| eprintln!("Unable to report panic: {err}"));
+        previous(info);
+    }));
+}
+
+/// Reports an exception which a language runtime didn't handle, with the
+/// stack captured by the runtime.  This is also the way to report a call to
+/// `std::terminate` from a C++ terminate handler.
+///
+/// The process is expected to terminate after this, so the `SIGABRT` which
+/// usually follows isn't reported again.  Only the first exception of the
+/// process is reported.
+pub fn report_unhandled_exception(
+    message: Option<String>,
+    stack: StackTrace,
+) -> anyhow::Result<()> {
+    report_error(ErrorKind::UnhandledException, message, stack, true)
+}
+
+/// Formats the message like the default panic hook does.
+fn panic_message(
+    thread: Option<&str>,
+    payload: &(dyn Any + Send),
+    location: Option<&Location>,
+) -> String {
+    let thread = thread.unwrap_or("<unnamed>");
+    let payload = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+    match location {
+        Some(location) => format!("thread '{thread}' panicked at {location}:\n{payload}"),
+        None => format!("thread '{thread}' panicked:\n{payload}"),
+    }
+}
+
+/// The stack of the current thread, with symbols resolved.  Unlike in a
+/// signal handler, resolving them here is safe.  Inlined functions get a frame
+/// each, innermost first.
+fn current_stack() -> StackTrace {
+    let mut frames = vec![];
+    for frame in backtrace::Backtrace::new().frames() {
+        let base_frame = StackFrame {
+            ip: Some(format!("{:?}", frame.ip())),
+            module_base_address: frame.module_base_address().map(|a| format!("{a:?}")),
+            sp: None,
+            symbol_address: Some(format!("{:?}", frame.symbol_address())),
+            build_id: None,
+            build_id_type: None,
+            file_type: None,
+            path: None,
+            relative_address: None,
+            column: None,
+            file: None,
+            function: None,
+            line: None,
+        };
+        if frame.symbols().is_empty() {
+            frames.push(base_frame);
+            continue;
+        }
+        frames.extend(frame.symbols().iter().map(|symbol| StackFrame {
+            column: symbol.colno(),
+            file: symbol.filename().map(|f| f.display().to_string()),
+            function: symbol.name().map(|name| name.to_string()),
+            line: symbol.lineno(),
+            ..base_frame.clone()
+        }));
+    }
+    StackTrace::from_frames(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_message() {
+        let location = Location::caller();
+        let message = panic_message(Some("main"), &"oops", Some(location));
+        assert_eq!(
+            message,
+            format!("thread 'main' panicked at {location}:\noops")
+        );
+        let message = panic_message(None, &String::from("formatted 42"), None);
+        assert_eq!(message, "thread '<unnamed>' panicked:\nformatted 42");
+        assert!(panic_message(None, &42, None).ends_with("Box<dyn Any>"));
+    }
+
+    #[test]
+    fn test_current_stack() {
+        let stack = current_stack();
+        assert!(stack.frames().iter().any(|frame| {
+            frame
+                .function
+                .as_deref()
+                .is_some_and(|name| name.contains("test_current_stack"))
+        }));
+    }
+}
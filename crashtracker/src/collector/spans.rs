@@ -22,6 +22,11 @@ pub fn emit_spans(w: &mut impl Write) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// The ids of the active spans.
+pub fn snapshot_spans() -> anyhow::Result<Vec<u128>> {
+    ACTIVE_SPANS.values()
+}
+
 pub fn insert_span(value: u128) -> anyhow::Result<usize> {
     ACTIVE_SPANS.insert(value)
 }
@@ -43,6 +48,11 @@ pub fn emit_traces(w: &mut impl Write) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// The ids of the active traces.
+pub fn snapshot_traces() -> anyhow::Result<Vec<u128>> {
+    ACTIVE_TRACES.values()
+}
+
 pub fn insert_trace(value: u128) -> anyhow::Result<usize> {
     ACTIVE_TRACES.insert(value)
 }
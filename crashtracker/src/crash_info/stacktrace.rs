@@ -64,87 +64,3 @@ pub struct NormalizedAddress {
     pub file_offset: u64,
     pub meta: NormalizedAddressMeta,
 }
-
-#[cfg(unix)]
-mod unix {
-    use super::*;
-    use anyhow::anyhow;
-    use blazesym::{
-        helper::ElfResolver,
-        normalize::{Normalizer, UserMeta},
-        symbolize::{Input, Source, Sym, Symbolized, Symbolizer, TranslateFileOffset},
-        Pid,
-    };
-
-    impl<'src> From<&UserMeta<'src>> for NormalizedAddressMeta {
-        fn from(value: &UserMeta<'src>) -> Self {
-            match value {
-                UserMeta::Apk(a) => Self::Apk(a.path.clone()),
-                UserMeta::Elf(e) => Self::Elf {
-                    path: e.path.clone(),
-                    build_id: e.build_id.as_ref().map(|cow| cow.clone().into_owned()),
-                },
-                UserMeta::Unknown(_) => Self::Unknown,
-                _ => Self::Unexpected(format!("{value:?}")),
-            }
-        }
-    }
-
-    impl From<Sym<'_>> for StackFrameNames {
-        fn from(value: Sym) -> Self {
-            let mut rval = Self::default();
-            if let Some(c) = value.code_info {
-                rval.lineno = c.line;
-                rval.filename = Some(c.to_path().display().to_string());
-                rval.colno = c.column.map(|c| c.into());
-            }
-            rval.name = Some(value.name.into_owned());
-            rval
-        }
-    }
-
-    impl StackFrame {
-        pub fn normalize_ip(&mut self, normalizer: &Normalizer, pid: Pid) -> anyhow::Result<()> {
-            if let Some(ip) = &self.ip {
-                let ip = ip.trim_start_matches("0x");
-                let ip = u64::from_str_radix(ip, 16)?;
-                let normed = normalizer.normalize_user_addrs(pid, &[ip])?;
-                anyhow::ensure!(normed.outputs.len() == 1);
-                let (file_offset, meta_idx) = normed.outputs[0];
-                let meta = &normed.meta[meta_idx];
-                let elf = meta.as_elf().ok_or(anyhow::anyhow!("Not elf"))?;
-                let resolver = ElfResolver::open(&elf.path)?;
-                let virt_address = resolver
-                    .file_offset_to_virt_offset(file_offset)?
-                    .ok_or(anyhow!("No matching segment found"))?;
-                self.normalized_ip = Some(NormalizedAddress {
-                    file_offset: virt_address,
-                    meta: meta.into(),
-                });
-            }
-            Ok(())
-        }
-
-        pub fn resolve_names(
-            &mut self,
-            src: &Source,
-            symbolizer: &Symbolizer,
-        ) -> anyhow::Result<()> {
-            if let Some(ip) = &self.ip {
-                let ip = ip.trim_start_matches("0x");
-                let ip = u64::from_str_radix(ip, 16)?;
-                let input = Input::AbsAddr(ip);
-                match symbolizer.symbolize_single(src, input)? {
-                    Symbolized::Sym(s) => {
-                        //TODO: handle
-                        self.names = Some(vec![s.into()]);
-                    }
-                    Symbolized::Unknown(reason) => {
-                        anyhow::bail!("Couldn't symbolize {ip}: {reason}");
-                    }
-                }
-            }
-            Ok(())
-        }
-    }
-}
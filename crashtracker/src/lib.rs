@@ -5,9 +5,10 @@
 //! uploading the result to the backend.
 //!
 //! Architecturally, it consists of two parts:
-//! 1. A signal handler, which catches a UNIX signal (SIGSEGV, SIGBUS, SIGABRT)
-//!    associated with a crash, and and collects information about the state of
-//!    the program at crash time.  The signal handler runs under a constrained
+//! 1. A signal handler, which catches a UNIX signal associated with a crash
+//!    (by default SIGABRT, SIGBUS, SIGILL and SIGSEGV; SIGFPE, SIGSYS and SIGTRAP
+//!    can be enabled in the `CrashtrackerConfiguration`), and collects
+//!    information about the state of the program at crash time.  The signal handler runs under a constrained
 //!    environment where many standard operations are illegal.
 //!    <https://man7.org/linux/man-pages/man7/signal-safety.7.html>
 //!    In particular, memory allocation, and synchronization such as mutexes, are
@@ -28,6 +29,17 @@
 //!    and then exits. The signal handler must wait for the receiver in order to reap its exit
 //!    status.
 //!
+//! The collector starts by announcing the version of the protocol it speaks, so that a receiver
+//! from a different release can still make sense of what it is sent.  From version 2, every
+//! block is sent in the format of the RFC5 crash report, which the receiver assembles with a
+//! `CrashInfoBuilder`.
+//!
+//! From version 3, every block, and every stack frame, is flushed as soon as it is collected, so
+//! that a collector which hangs or crashes, e.g. while unwinding a corrupted stack, still delivers
+//! what it collected before.  The receiver's timeout acts as a watchdog: a partial report says in
+//! which stage the collection stalled.  A crash of the crash handler itself is caught by the
+//! handler, which tells the receiver about it and finishes the report.
+//!
 //! Data collected:
 //! 1. The data collected by the crash-handler includes:
 //!    1. The signal type leading to the crash
@@ -36,10 +48,36 @@
 //!       crashes the crash handler (ironic).
 //!    3. System level info (e.g. /proc/self/maps).
 //!    4. The result of counters describing the current state of the profiler.
+//!    5. The registers of the crashing thread, from the `ucontext_t` given to the handler.
+//!
+//!    Crashes which aren't signals, i.e. Rust panics (see `install_panic_hook`) and unhandled
+//!    exceptions reported by language runtimes (see `report_unhandled_exception`), are instead
+//!    sent as a complete RFC5 report, with the message and stack of the error.
 //! 2. Data augmented by the receiver includes:
 //!    1. Metadata provided by the caller (e.g. library & profiler versions).
 //!    2. System info: OS version, /proc/cpuinfo /proc/meminfo, etc.
 //!    3. A timestamp and GUID for tracking the crash report.
+//!    4. A hexdump of the memory around the instruction and stack pointers (Linux only), read from
+//!       the crashing process with `process_vm_readv` while it waits in its signal handler.
+//!    5. The stacks of the other threads (Linux only), unwound through their frame pointers after
+//!       stopping each of them in turn with ptrace.
+//!    6. Optionally, a Breakpad-compatible minidump of the crashing process (Linux x86_64 and
+//!       aarch64 only), written next to the report when the endpoint is a file.
+//!    7. With `StacktraceCollection::EnabledWithSymbolsInReceiver`, the names of the frames,
+//!       resolved against the crashing process.  Frames of stripped binaries are then resolved,
+//!       with their files, lines and inlined functions, from separate debug files looked up by
+//!       build id in the configured `SymbolStore`s: local directories such as
+//!       `/usr/lib/debug/.build-id`, or a debuginfod server.
+//!
+//! Uploading:
+//! The receiver uploads the report to the configured endpoint once it was received.  If a spool
+//! directory is configured, the report is first written there, and only deleted once uploaded.
+//! Reports left behind by failed uploads are retried by `upload_pending_reports`, e.g. when the
+//! next process, or the sidecar, starts.
+//! Each report carries a fingerprint, computed from the signal and the top of the stack, which is
+//! the same for crashes with the same cause.  With an `UploadRateLimit`, the receiver uploads at
+//! most so many reports per fingerprint per period, and the next report which does go out counts
+//! those which were dropped.
 //!
 //! Handling of forks
 //! Safety issues
@@ -50,21 +88,25 @@ mod crash_info;
 #[cfg(all(unix, feature = "receiver"))]
 mod receiver;
 
-// TODO: For now, we have name conflicts with the `crash_info` module.
-// Once that module is removed, those conflicts will go away
-// Till then, keep things in two name spaces
+// Reports are `rfc5_crash_info::CrashInfo`.  `crash_info` only keeps the caller's metadata and
+// the formats of version 1 of the collector protocol, some of which share names with their RFC5
+// counterparts, so the two stay in separate name spaces.
 pub mod rfc5_crash_info;
 #[cfg(all(unix, any(feature = "collector", feature = "receiver")))]
 mod shared;
 
 #[cfg(all(unix, feature = "collector"))]
 pub use collector::{
-    begin_op, clear_spans, clear_traces, end_op, init, insert_span, insert_trace, on_fork,
-    remove_span, remove_trace, reset_counters, shutdown_crash_handler, update_config,
-    update_metadata, OpTypes,
+    begin_op, clear_spans, clear_traces, end_op, init, insert_span, insert_trace,
+    install_panic_hook, on_fork, remove_span, remove_trace, report_unhandled_exception,
+    reset_counters, shutdown_crash_handler, update_config, update_metadata, OpTypes,
 };
 
 pub use crash_info::*;
+pub use rfc5_crash_info::{
+    async_upload_pending_reports, upload_pending_reports, PendingReportsSummary,
+    DEFAULT_MAX_PENDING_REPORT_AGE,
+};
 
 #[cfg(all(unix, feature = "receiver"))]
 pub use receiver::{
@@ -74,5 +116,10 @@ pub use receiver::{
 
 #[cfg(all(unix, any(feature = "collector", feature = "receiver")))]
 pub use shared::configuration::{
-    CrashtrackerConfiguration, CrashtrackerReceiverConfig, StacktraceCollection,
+    CrashtrackerConfiguration, CrashtrackerConfigurationBuilder, CrashtrackerReceiverConfig,
+    StacktraceCollection,
 };
+#[cfg(all(unix, any(feature = "collector", feature = "receiver")))]
+pub use shared::rate_limit::{UploadRateLimit, SUPPRESSED_DUPLICATES_COUNTER};
+#[cfg(all(unix, any(feature = "collector", feature = "receiver")))]
+pub use shared::symbol_stores::SymbolStore;
@@ -3,26 +3,76 @@
 #![cfg(unix)]
 
 use super::*;
+use crate::rfc5_crash_info::{CrashInfo, CrashInfoBuilder, StackFrame, StackTrace};
 use crate::shared::constants::*;
+use crate::shared::symbol_stores::SymbolStore;
 use anyhow::Context;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::net::UnixListener;
 
-pub fn resolve_frames(
+/// Resolves the frames against the crashing process.  For stripped binaries,
+/// the ips are also normalized, to be resolved later against their debug files
+/// by [resolve_frames_from_symbol_stores].
+pub async fn resolve_frames(
     config: &CrashtrackerConfiguration,
-    crash_info: &mut CrashInfo,
+    crash_info: &mut CrashInfoBuilder,
 ) -> anyhow::Result<()> {
     if config.resolve_frames == StacktraceCollection::EnabledWithSymbolsInReceiver {
-        let proc_info = crash_info
+        let pid = crash_info
             .proc_info
             .as_ref()
-            .context("Unable to resolve frames: No PID specified")?;
-        crash_info.resolve_names_from_process(proc_info.pid)?;
+            .context("Unable to resolve frames: No PID specified")?
+            .pid;
+        crash_info.resolve_names_from_process(pid)?;
+        if !config.symbol_stores.is_empty() {
+            crash_info.normalize_ips(pid)?;
+        }
     }
     Ok(())
 }
 
+/// Resolves the frames of stripped binaries against their debug files from
+/// the configured symbol stores.  The debug files are looked up concurrently,
+/// giving up on them at `deadline`.  Returns whether any was found.
+async fn resolve_frames_from_symbol_stores(
+    config: &CrashtrackerConfiguration,
+    crash_info: CrashInfo,
+    deadline: Instant,
+) -> anyhow::Result<(CrashInfo, bool)> {
+    if config.resolve_frames != StacktraceCollection::EnabledWithSymbolsInReceiver
+        || config.symbol_stores.is_empty()
+    {
+        return Ok((crash_info, false));
+    }
+    let stores: std::sync::Arc<[SymbolStore]> = config.symbol_stores.clone().into();
+    let mut lookups = tokio::task::JoinSet::new();
+    for build_id in crash_info.unsymbolized_build_ids() {
+        let stores = stores.clone();
+        lookups.spawn(async move {
+            let path = SymbolStore::find_debug_file(&stores, &build_id, deadline).await;
+            path.map(|path| (build_id, path))
+        });
+    }
+    let mut debug_files = HashMap::new();
+    while let Some(lookup) = lookups.join_next().await {
+        debug_files.extend(lookup?);
+    }
+    if debug_files.is_empty() {
+        return Ok((crash_info, false));
+    }
+    // Symbolizing reads the debug files.
+    let crash_info = tokio::task::spawn_blocking(move || {
+        let mut crash_info = crash_info;
+        crash_info.resolve_names_from_debug_files(&debug_files);
+        crash_info
+    })
+    .await?;
+    Ok((crash_info, true))
+}
+
 pub fn get_unix_socket(socket_path: impl AsRef<str>) -> anyhow::Result<UnixListener> {
     fn path_bind(socket_path: impl AsRef<str>) -> anyhow::Result<UnixListener> {
         let socket_path = socket_path.as_ref();
@@ -113,18 +163,123 @@ async fn receiver_entry_point(
     timeout: Duration,
     stream: impl AsyncBufReadExt + std::marker::Unpin,
 ) -> anyhow::Result<()> {
+    // The collector connects to, or spawns, the receiver when it crashes.
+    let report_started = Instant::now();
     match receive_report(timeout, stream).await? {
         CrashReportStatus::NoCrash => Ok(()),
-        CrashReportStatus::CrashReport(config, mut crash_info) => {
-            resolve_frames(&config, &mut crash_info)?;
-            crash_info.async_upload_to_endpoint(&config.endpoint).await
+        CrashReportStatus::CrashReport(config, mut builder) => {
+            resolve_frames(&config, &mut builder).await?;
+            upload(&config, builder.build()?, report_started).await
         }
-        CrashReportStatus::PartialCrashReport(config, mut crash_info, stdin_state) => {
+        CrashReportStatus::PartialCrashReport(config, mut builder, stdin_state) => {
             eprintln!("Failed to fully receive crash.  Exit state was: {stdin_state:?}");
-            resolve_frames(&config, &mut crash_info)?;
-            crash_info.async_upload_to_endpoint(&config.endpoint).await
+            resolve_frames(&config, &mut builder).await?;
+            upload(&config, builder.build()?, report_started).await
+        }
+        CrashReportStatus::ErrorReport(config, mut crash_info) => {
+            // Collectors which predate fingerprints leave it to us.
+            if crash_info.fingerprint.is_none() {
+                crash_info.fingerprint = Some(crash_info.compute_fingerprint());
+            }
+            upload(&config, crash_info, report_started).await
+        }
+    }
+}
+
+/// Writes the report to the spool directory, if any.  Failing to spool it
+/// isn't worth failing the upload for.
+async fn spool(config: &CrashtrackerConfiguration, crash_info: &CrashInfo) -> Option<PathBuf> {
+    let spool_dir = PathBuf::from(config.spool_dir.as_ref()?);
+    let crash_info = crash_info.clone();
+    let endpoint = config.endpoint.clone();
+    let rate_limit = config.upload_rate_limit.clone();
+    tokio::task::spawn_blocking(move || crash_info.spool(&spool_dir, &endpoint, &rate_limit))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|spooled| spooled)
+        .map_err(|err| eprintln!("Unable to spool crash report: {err}"))
+        .ok()
+}
+
+/// Uploads the report, keeping it in the spool directory, if any, until the
+/// upload succeeds.  The report is spooled first, so that it survives the
+/// collector killing the receiver while frames are resolved from the symbol
+/// stores, which is given up on three quarters of `timeout_ms` after the
+/// report started, leaving the rest for the upload.  Reports beyond the rate
+/// limit for their fingerprint, if any, are dropped, and only uploads which
+/// succeed count towards the limit.
+async fn upload(
+    config: &CrashtrackerConfiguration,
+    crash_info: CrashInfo,
+    report_started: Instant,
+) -> anyhow::Result<()> {
+    let mut spooled = spool(config, &crash_info).await;
+    let deadline = report_started + Duration::from_millis(config.timeout_ms.into()) * 3 / 4;
+    let (mut crash_info, mut changed) =
+        resolve_frames_from_symbol_stores(config, crash_info, deadline).await?;
+
+    // Counted in the rate limit once uploaded, with the number of suppressed reports it tells.
+    let mut admission = None;
+    if let (Some(rate_limit), Some(fingerprint)) = (
+        config.upload_rate_limit.clone(),
+        crash_info.fingerprint.clone(),
+    ) {
+        let (rate_limit, fingerprint, admitted) = tokio::task::spawn_blocking(move || {
+            let admitted = rate_limit.admit(&fingerprint, std::time::SystemTime::now());
+            (rate_limit, fingerprint, admitted)
+        })
+        .await?;
+        match admitted {
+            Ok(Some(suppressed)) => {
+                if suppressed > 0 {
+                    crash_info
+                        .counters
+                        .insert(SUPPRESSED_DUPLICATES_COUNTER.to_string(), suppressed as i64);
+                    changed = true;
+                }
+                admission = Some((rate_limit, fingerprint, suppressed));
+            }
+            Ok(None) => {
+                eprintln!("Dropping crash report {fingerprint}: over the upload rate limit");
+                if let Some(spooled) = spooled {
+                    remove_spooled(&spooled).await;
+                }
+                return Ok(());
+            }
+            // Better a duplicate than a lost report.
+            Err(err) => eprintln!("Unable to rate limit crash report: {err}"),
         }
     }
+    if changed && spooled.is_some() {
+        // Same report, so same file.
+        spooled = spool(config, &crash_info).await;
+    }
+    crash_info
+        .async_upload_to_endpoint(&config.endpoint)
+        .await?;
+    if let Some((rate_limit, fingerprint, suppressed)) = admission {
+        let recorded = tokio::task::spawn_blocking(move || {
+            rate_limit.record_upload(&fingerprint, std::time::SystemTime::now(), suppressed)
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|recorded| recorded);
+        if let Err(err) = recorded {
+            eprintln!("Unable to count crash report upload: {err}");
+        }
+    }
+    if let Some(spooled) = spooled {
+        // The report was uploaded, so failing to clean up is not worth failing for.  At worst it
+        // is uploaded again later.
+        remove_spooled(&spooled).await;
+    }
+    Ok(())
+}
+
+async fn remove_spooled(spooled: &Path) {
+    if let Err(err) = tokio::fs::remove_file(spooled).await {
+        eprintln!("Failed to remove {}: {err}", spooled.display());
+    }
 }
 
 /// The crashtracker collector sends data in blocks.
@@ -135,6 +290,7 @@ async fn receiver_entry_point(
 enum StdinState {
     Config,
     Counters,
+    CrashInfo,
     Done,
     File(String, Vec<String>),
     InternalError(String),
@@ -144,19 +300,62 @@ enum StdinState {
     SpanIds,
     StackTrace(Vec<StackFrame>),
     TraceIds,
+    UContext,
     Waiting,
 }
 
+impl StdinState {
+    /// The stage of the collection, named after the block being received.
+    fn stage(&self) -> &'static str {
+        match self {
+            StdinState::Config => "config",
+            StdinState::Counters => "counters",
+            StdinState::CrashInfo => "crashinfo",
+            StdinState::Done => "done",
+            StdinState::File(..) => "file",
+            StdinState::InternalError(_) => "internal_error",
+            StdinState::Metadata => "metadata",
+            StdinState::ProcInfo => "procinfo",
+            StdinState::SigInfo => "siginfo",
+            StdinState::SpanIds => "span_ids",
+            StdinState::StackTrace(_) => "stacktrace",
+            StdinState::TraceIds => "trace_ids",
+            StdinState::UContext => "ucontext",
+            StdinState::Waiting => "waiting",
+        }
+    }
+}
+
 /// A state machine that processes data from the crash-tracker collector line by
 /// line.  The crashtracker collector sends data in blocks, so we use a `state`
 /// variable to track which block we're in and collect partial data.
 /// Once we reach the end of a block, append the block's data to `crashinfo`.
+///
+/// `protocol_version` is the version announced by the collector, which tells
+/// the format of the siginfo and stacktrace blocks.
 fn process_line(
-    crashinfo: &mut CrashInfo,
+    crashinfo: &mut CrashInfoBuilder,
     config: &mut Option<CrashtrackerConfiguration>,
+    error_report: &mut Option<CrashInfo>,
+    protocol_version: &mut u32,
     line: String,
     state: StdinState,
 ) -> anyhow::Result<StdinState> {
+    // The crash handler crashed, possibly in the middle of a line.  What was received so far is
+    // kept, including the frames of a partial stacktrace.
+    if let Some(pos) = line.find(DD_CRASHTRACK_CRASH_IN_HANDLER) {
+        let signum = line[pos + DD_CRASHTRACK_CRASH_IN_HANDLER.len()..].trim();
+        crashinfo.add_log_message(format!(
+            "The crash handler crashed with signal {signum} while collecting the {}",
+            state.stage()
+        ))?;
+        crashinfo.set_incomplete(true)?;
+        if let StdinState::StackTrace(stacktrace) = state {
+            crashinfo.set_stack(StackTrace::from_frames(stacktrace))?;
+        }
+        return Ok(StdinState::Waiting);
+    }
+
     let next = match state {
         StdinState::Config if line.starts_with(DD_CRASHTRACK_END_CONFIG) => StdinState::Waiting,
         StdinState::Config => {
@@ -182,6 +381,15 @@ fn process_line(
             StdinState::Counters
         }
 
+        StdinState::CrashInfo if line.starts_with(DD_CRASHTRACK_END_CRASHINFO) => {
+            StdinState::Waiting
+        }
+        StdinState::CrashInfo => {
+            anyhow::ensure!(error_report.is_none());
+            *error_report = Some(serde_json::from_str(&line)?);
+            StdinState::CrashInfo
+        }
+
         StdinState::Done => {
             eprintln!("Unexpected line after crashreport is done: {line}");
             StdinState::Done
@@ -200,22 +408,26 @@ fn process_line(
 
         StdinState::Metadata if line.starts_with(DD_CRASHTRACK_END_METADATA) => StdinState::Waiting,
         StdinState::Metadata => {
-            let metadata = serde_json::from_str(&line)?;
-            crashinfo.set_metadata(metadata)?;
+            let metadata: CrashtrackerMetadata = serde_json::from_str(&line)?;
+            crashinfo.set_metadata(metadata.into())?;
             StdinState::Metadata
         }
 
         StdinState::ProcInfo if line.starts_with(DD_CRASHTRACK_END_PROCINFO) => StdinState::Waiting,
         StdinState::ProcInfo => {
             let proc_info = serde_json::from_str(&line)?;
-            crashinfo.set_procinfo(proc_info)?;
+            crashinfo.set_proc_info(proc_info)?;
             StdinState::ProcInfo
         }
 
         StdinState::SigInfo if line.starts_with(DD_CRASHTRACK_END_SIGINFO) => StdinState::Waiting,
         StdinState::SigInfo => {
-            let siginfo = serde_json::from_str(&line)?;
-            crashinfo.set_siginfo(siginfo)?;
+            let siginfo = if *protocol_version == 1 {
+                serde_json::from_str::<SigInfo>(&line)?.into()
+            } else {
+                serde_json::from_str(&line)?
+            };
+            crashinfo.set_sig_info(siginfo)?;
             crashinfo.set_timestamp_to_now()?;
             StdinState::SigInfo
         }
@@ -228,12 +440,17 @@ fn process_line(
         }
 
         StdinState::StackTrace(stacktrace) if line.starts_with(DD_CRASHTRACK_END_STACKTRACE) => {
-            crashinfo.set_stacktrace(None, stacktrace)?;
+            crashinfo.set_stack(StackTrace::from_frames(stacktrace))?;
             StdinState::Waiting
         }
         StdinState::StackTrace(mut stacktrace) => {
-            let frame = serde_json::from_str(&line).context(line)?;
-            stacktrace.push(frame);
+            if *protocol_version == 1 {
+                let frame: crate::StackFrame = serde_json::from_str(&line).context(line)?;
+                stacktrace.extend(StackFrame::from_legacy(frame));
+            } else {
+                let frame = serde_json::from_str(&line).context(line)?;
+                stacktrace.push(frame);
+            }
             StdinState::StackTrace(stacktrace)
         }
 
@@ -246,10 +463,41 @@ fn process_line(
             StdinState::TraceIds
         }
 
+        StdinState::UContext if line.starts_with(DD_CRASHTRACK_END_UCONTEXT) => {
+            // Like the memory around the registers, the minidump has to be written while the
+            // crashing process is still waiting for us.  Only file endpoints receive it, so don't
+            // hold up the process for nothing.
+            #[cfg(target_os = "linux")]
+            if config.as_ref().is_some_and(|config| {
+                config.minidump
+                    && config
+                        .endpoint
+                        .as_ref()
+                        .is_some_and(|endpoint| endpoint.url.scheme_str() == Some("file"))
+            }) {
+                crashinfo
+                    .capture_minidump()
+                    .unwrap_or_else(|err| eprintln!("Unable to write minidump: {err}"));
+            }
+            StdinState::Waiting
+        }
+        StdinState::UContext => {
+            let fault_context = serde_json::from_str(&line)?;
+            crashinfo.set_fault_context(fault_context)?;
+            // The crashing process waits for us while it sends the report, so its memory can
+            // still be read at this point.
+            #[cfg(target_os = "linux")]
+            crashinfo.read_memory_around_registers();
+            StdinState::UContext
+        }
+
         StdinState::Waiting if line.starts_with(DD_CRASHTRACK_BEGIN_CONFIG) => StdinState::Config,
         StdinState::Waiting if line.starts_with(DD_CRASHTRACK_BEGIN_COUNTERS) => {
             StdinState::Counters
         }
+        StdinState::Waiting if line.starts_with(DD_CRASHTRACK_BEGIN_CRASHINFO) => {
+            StdinState::CrashInfo
+        }
         StdinState::Waiting if line.starts_with(DD_CRASHTRACK_BEGIN_FILE) => {
             let (_, filename) = line.split_once(' ').unwrap_or(("", "MISSING_FILENAME"));
             StdinState::File(filename.to_string(), vec![])
@@ -270,7 +518,28 @@ fn process_line(
         StdinState::Waiting if line.starts_with(DD_CRASHTRACK_BEGIN_TRACE_IDS) => {
             StdinState::TraceIds
         }
+        StdinState::Waiting if line.starts_with(DD_CRASHTRACK_BEGIN_UCONTEXT) => {
+            StdinState::UContext
+        }
         StdinState::Waiting if line.starts_with(DD_CRASHTRACK_DONE) => StdinState::Done,
+        StdinState::Waiting if line.starts_with(DD_CRASHTRACK_PROTOCOL_VERSION) => {
+            let (_, version) = line
+                .split_once(' ')
+                .context("Missing collector protocol version")?;
+            let version: u32 = version.trim().parse()?;
+            // A newer collector may send blocks we don't know, which are skipped like any
+            // unexpected line.  The known ones are kept, read as the current version.
+            if version > DD_CRASHTRACK_CURRENT_PROTOCOL_VERSION {
+                crashinfo.add_log_message(format!(
+                    "Unsupported collector protocol version {version}, expected at most \
+                     {DD_CRASHTRACK_CURRENT_PROTOCOL_VERSION}: only the known parts of the \
+                     report were kept"
+                ))?;
+                crashinfo.set_incomplete(true)?;
+            }
+            *protocol_version = version.min(DD_CRASHTRACK_CURRENT_PROTOCOL_VERSION);
+            StdinState::Waiting
+        }
         StdinState::Waiting => {
             //TODO: Do something here?
             eprintln!("Unexpected line while receiving crashreport: {line}");
@@ -280,11 +549,45 @@ fn process_line(
     Ok(next)
 }
 
+/// Unwinds the other threads of the crashing process.  The collector kills
+/// the receiver `timeout_ms` after the report started, so unwinding stops at
+/// half of that, leaving the rest to receive, resolve and upload the report.
+/// This is skipped when stack collection is disabled, since it briefly stops
+/// every thread.
+#[cfg(target_os = "linux")]
+async fn collect_thread_stacks(
+    crashinfo: &mut CrashInfoBuilder,
+    config: &CrashtrackerConfiguration,
+    report_started: Instant,
+) -> anyhow::Result<()> {
+    if config.resolve_frames == StacktraceCollection::Disabled {
+        return Ok(());
+    }
+    let proc_info = crashinfo.proc_info.as_ref().context("No PID specified")?;
+    let (pid, tid) = (proc_info.pid, proc_info.tid);
+    let deadline = report_started + Duration::from_millis(config.timeout_ms.into()) / 2;
+    let (threads, timed_out) = tokio::task::spawn_blocking(move || {
+        crate::rfc5_crash_info::collect_thread_stacks(pid, tid, deadline)
+    })
+    .await?;
+    for thread in threads {
+        crashinfo.add_thread(thread)?;
+    }
+    if timed_out {
+        crashinfo.add_log_message(
+            "Ran out of time unwinding threads, the remaining ones have no stack".to_string(),
+        )?;
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 enum CrashReportStatus {
     NoCrash,
-    CrashReport(CrashtrackerConfiguration, CrashInfo),
-    PartialCrashReport(CrashtrackerConfiguration, CrashInfo, StdinState),
+    CrashReport(CrashtrackerConfiguration, CrashInfoBuilder),
+    PartialCrashReport(CrashtrackerConfiguration, CrashInfoBuilder, StdinState),
+    /// A crash other than a signal, for which the collector built the whole report.
+    ErrorReport(CrashtrackerConfiguration, CrashInfo),
 }
 
 /// Listens to `stream`, reading it line by line, until
@@ -298,25 +601,36 @@ async fn receive_report(
     timeout: Duration,
     stream: impl AsyncBufReadExt + std::marker::Unpin,
 ) -> anyhow::Result<CrashReportStatus> {
-    let mut crashinfo = CrashInfo::new();
+    let mut crashinfo = CrashInfoBuilder::new();
+    let mut error_report = None;
+    // Collectors which don't announce a version predate versioning.
+    let mut protocol_version = 1;
     let mut stdin_state = StdinState::Waiting;
     let mut config = None;
 
     let mut lines = stream.lines();
+    let mut report_started = None;
     let mut deadline = None;
     // Start the timeout counter when the deadline when the first crash message is recieved
     let mut remaining_timeout = Duration::MAX;
+    // The watchdog: where the collection was when the collector stopped sending, if it didn't
+    // finish.
+    let mut last_completed_stage = "start";
+    let mut stalled_stage = stdin_state.stage();
+    let mut interruption = "the collector stopped sending".to_string();
 
     //TODO: This assumes that the input is valid UTF-8.
     loop {
         let next = tokio::time::timeout(remaining_timeout, lines.next_line()).await;
         if let Err(elapsed) = next {
             eprintln!("Timeout: {elapsed}");
+            interruption = format!("timed out after {timeout:?}");
             break;
         };
         let next = next.unwrap();
         if let Err(io_err) = next {
             eprintln!("IO Error: {io_err}");
+            interruption = format!("failed to read: {io_err}");
             break;
         }
         let next = next.unwrap();
@@ -325,15 +639,42 @@ async fn receive_report(
         }
         let line = next.unwrap();
 
-        match process_line(&mut crashinfo, &mut config, line, stdin_state) {
+        stalled_stage = stdin_state.stage();
+        match process_line(
+            &mut crashinfo,
+            &mut config,
+            &mut error_report,
+            &mut protocol_version,
+            line,
+            stdin_state,
+        ) {
             Ok(next_state) => {
+                // The crashing process waits for us while it sends the report, so its other
+                // threads can be unwound once its pid is known.
+                #[cfg(target_os = "linux")]
+                if matches!(next_state, StdinState::Waiting) && stalled_stage == "procinfo" {
+                    if let Some(config) = &config {
+                        collect_thread_stacks(
+                            &mut crashinfo,
+                            config,
+                            report_started.unwrap_or_else(Instant::now),
+                        )
+                        .await
+                        .unwrap_or_else(|err| eprintln!("Unable to collect threads: {err}"));
+                    }
+                }
+                if matches!(next_state, StdinState::Waiting) && stalled_stage != "waiting" {
+                    last_completed_stage = stalled_stage;
+                }
                 stdin_state = next_state;
+                stalled_stage = stdin_state.stage();
                 if matches!(stdin_state, StdinState::Done) {
                     break;
                 }
             }
             Err(e) => {
                 // If the input is corrupted, stop and salvage what we can
+                interruption = format!("invalid data: {e}");
                 stdin_state = StdinState::InternalError(e.to_string());
                 break;
             }
@@ -343,22 +684,36 @@ async fn receive_report(
             remaining_timeout = deadline - Instant::now()
         } else {
             // We've recieved the first message from the collector, start the clock ticking.
+            report_started = Some(Instant::now());
             deadline = Some(Instant::now() + timeout);
             remaining_timeout = timeout;
         }
     }
 
+    if let Some(mut error_report) = error_report {
+        let config = config.context("Missing crashtracker configuration")?;
+        error_report.incomplete |= crashinfo.incomplete || !matches!(stdin_state, StdinState::Done);
+        error_report
+            .log_messages
+            .extend(std::mem::take(&mut crashinfo.log_messages));
+        return Ok(CrashReportStatus::ErrorReport(config, error_report));
+    }
     if !crashinfo.crash_seen() {
         return Ok(CrashReportStatus::NoCrash);
     }
 
     // Without a config, we don't even know the endpoint to transmit to.  Not much to do to recover.
     let config = config.context("Missing crashtracker configuration")?;
-    for filename in &config.additional_files {
+    let additional_files = config.additional_files.clone();
+    let mut crashinfo = tokio::task::spawn_blocking(move || {
+        for filename in &additional_files {
+            crashinfo
+                .add_file(filename)
+                .unwrap_or_else(|e| eprintln!("Unable to add file {filename}: {e}"));
+        }
         crashinfo
-            .add_file(filename)
-            .unwrap_or_else(|e| eprintln!("Unable to add file {filename}: {e}"));
-    }
+    })
+    .await?;
 
     // If we were waiting for data when stdin closed, let our caller know that
     // we only have partial data.
@@ -366,6 +721,18 @@ async fn receive_report(
         Ok(CrashReportStatus::CrashReport(config, crashinfo))
     } else {
         crashinfo.set_incomplete(true)?;
+        let stage = if stalled_stage == "waiting" {
+            format!("after the {last_completed_stage}")
+        } else {
+            format!("in the {stalled_stage}")
+        };
+        crashinfo.add_log_message(format!("Crash collection stalled {stage}: {interruption}"))?;
+        // The frames of a partial stacktrace are still worth having.
+        if let StdinState::StackTrace(stacktrace) = &stdin_state {
+            if !stacktrace.is_empty() {
+                crashinfo.set_stack(StackTrace::from_frames(stacktrace.clone()))?;
+            }
+        }
         Ok(CrashReportStatus::PartialCrashReport(
             config,
             crashinfo,
@@ -398,6 +765,7 @@ mod tests {
             serde_json::to_string(&SigInfo {
                 signame: Some("SIGSEGV".to_string()),
                 signum: 11,
+                si_code: None,
                 faulting_address: None,
             })?,
         )
@@ -407,15 +775,11 @@ mod tests {
         to_socket(sender, DD_CRASHTRACK_BEGIN_CONFIG).await?;
         to_socket(
             sender,
-            serde_json::to_string(&CrashtrackerConfiguration::new(
-                vec![],
-                false,
-                false,
-                None,
-                StacktraceCollection::Disabled,
-                3000,
-                None,
-            )?)?,
+            serde_json::to_string(
+                &CrashtrackerConfiguration::builder()
+                    .set_timeout_ms(3000)
+                    .build()?,
+            )?,
         )
         .await?;
         to_socket(sender, DD_CRASHTRACK_END_CONFIG).await?;
@@ -461,4 +825,211 @@ mod tests {
         join_handle2.await??;
         Ok(())
     }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_receive_error_report() -> anyhow::Result<()> {
+        let config = test_config()?;
+        let report = CrashInfo::new_error(
+            crate::rfc5_crash_info::ErrorKind::Panic,
+            Some("oops".to_string()),
+            StackTrace::default(),
+            crate::CrashtrackerMetadata {
+                library_name: "libdatadog".to_string(),
+                library_version: "1.0.0".to_string(),
+                family: "native".to_string(),
+                tags: vec![],
+            },
+        );
+        let input = [
+            DD_CRASHTRACK_BEGIN_CONFIG.to_string(),
+            serde_json::to_string(&config)?,
+            DD_CRASHTRACK_END_CONFIG.to_string(),
+            DD_CRASHTRACK_BEGIN_CRASHINFO.to_string(),
+            serde_json::to_string(&report)?,
+            DD_CRASHTRACK_END_CRASHINFO.to_string(),
+            DD_CRASHTRACK_DONE.to_string(),
+        ]
+        .join("\n");
+
+        let crash_report = receive_report(Duration::from_secs(1), input.as_bytes()).await?;
+        let CrashReportStatus::ErrorReport(received_config, received) = crash_report else {
+            panic!("Expected an error report, got {crash_report:?}");
+        };
+        assert_eq!(received_config, config);
+        assert_eq!(received, report);
+        Ok(())
+    }
+
+    fn test_config() -> anyhow::Result<CrashtrackerConfiguration> {
+        CrashtrackerConfiguration::builder()
+            .set_timeout_ms(3000)
+            .build()
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_receive_versioned_report() -> anyhow::Result<()> {
+        let input = [
+            format!("{DD_CRASHTRACK_PROTOCOL_VERSION} 2"),
+            DD_CRASHTRACK_BEGIN_CONFIG.to_string(),
+            serde_json::to_string(&test_config()?)?,
+            DD_CRASHTRACK_END_CONFIG.to_string(),
+            DD_CRASHTRACK_BEGIN_METADATA.to_string(),
+            r#"{"library_name": "libdatadog", "library_version": "1.0.0", "family": "native", "tags": []}"#.to_string(),
+            DD_CRASHTRACK_END_METADATA.to_string(),
+            DD_CRASHTRACK_BEGIN_SIGINFO.to_string(),
+            r#"{"si_signo": 11, "si_signo_human_readable": "SIGSEGV", "si_code": 1, "si_code_human_readable": "SEGV_MAPERR", "si_addr": "0x0000000000000010"}"#.to_string(),
+            DD_CRASHTRACK_END_SIGINFO.to_string(),
+            DD_CRASHTRACK_BEGIN_PROCINFO.to_string(),
+            r#"{"pid": 42, "tid": 43}"#.to_string(),
+            DD_CRASHTRACK_END_PROCINFO.to_string(),
+            DD_CRASHTRACK_BEGIN_STACKTRACE.to_string(),
+            r#"{"ip": "0x1234", "sp": "0x5678", "symbol_address": "0x1200", "function": "inlined"}"#.to_string(),
+            r#"{"ip": "0x1234", "sp": "0x5678", "symbol_address": "0x1200", "function": "caller"}"#.to_string(),
+            DD_CRASHTRACK_END_STACKTRACE.to_string(),
+            DD_CRASHTRACK_DONE.to_string(),
+        ]
+        .join("\n");
+
+        let crash_report = receive_report(Duration::from_secs(1), input.as_bytes()).await?;
+        let CrashReportStatus::CrashReport(_, builder) = crash_report else {
+            panic!("Expected a crash report, got {crash_report:?}");
+        };
+        let crash_info = builder.build()?;
+        let sig_info = crash_info.sig_info.unwrap();
+        assert_eq!(sig_info.si_signo, libc::SIGSEGV);
+        assert_eq!(sig_info.si_addr.as_deref(), Some("0x0000000000000010"));
+        assert_eq!(crash_info.proc_info.pid, 42);
+        assert_eq!(crash_info.proc_info.tid, Some(43));
+        let functions: Vec<_> = crash_info
+            .error
+            .stack
+            .frames()
+            .iter()
+            .map(|frame| frame.function.as_deref())
+            .collect();
+        assert_eq!(functions, [Some("inlined"), Some("caller")]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_receive_unsupported_version() -> anyhow::Result<()> {
+        let mut lines = report_start()?;
+        lines[0] = format!(
+            "{DD_CRASHTRACK_PROTOCOL_VERSION} {}",
+            DD_CRASHTRACK_CURRENT_PROTOCOL_VERSION + 1
+        );
+        lines.push(DD_CRASHTRACK_END_STACKTRACE.to_string());
+        // A block this receiver doesn't know.
+        lines.push("DD_CRASHTRACK_BEGIN_SOMETHING_NEW".to_string());
+        lines.push(r#"{"new": true}"#.to_string());
+        lines.push("DD_CRASHTRACK_END_SOMETHING_NEW".to_string());
+        lines.push(DD_CRASHTRACK_DONE.to_string());
+        let input = lines.join("\n");
+
+        let crash_report = receive_report(Duration::from_secs(1), input.as_bytes()).await?;
+        let CrashReportStatus::CrashReport(_, builder) = crash_report else {
+            panic!("Expected a crash report, got {crash_report:?}");
+        };
+        let crash_info = builder.build()?;
+        assert!(crash_info.incomplete);
+        assert_eq!(crash_info.sig_info.unwrap().si_signo, libc::SIGSEGV);
+        assert_eq!(crash_info.proc_info.pid, 42);
+        assert_eq!(crash_info.error.stack.frames().len(), 1);
+        assert_eq!(crash_info.log_messages.len(), 1);
+        assert!(crash_info.log_messages[0].starts_with("Unsupported collector protocol version"));
+        Ok(())
+    }
+
+    /// The beginning of a crash report, up to the stacktrace.
+    fn report_start() -> anyhow::Result<Vec<String>> {
+        Ok(vec![
+            format!("{DD_CRASHTRACK_PROTOCOL_VERSION} {DD_CRASHTRACK_CURRENT_PROTOCOL_VERSION}"),
+            DD_CRASHTRACK_BEGIN_CONFIG.to_string(),
+            serde_json::to_string(&test_config()?)?,
+            DD_CRASHTRACK_END_CONFIG.to_string(),
+            DD_CRASHTRACK_BEGIN_METADATA.to_string(),
+            r#"{"library_name": "libdatadog", "library_version": "1.0.0", "family": "native", "tags": []}"#.to_string(),
+            DD_CRASHTRACK_END_METADATA.to_string(),
+            DD_CRASHTRACK_BEGIN_SIGINFO.to_string(),
+            r#"{"si_signo": 11, "si_signo_human_readable": "SIGSEGV", "si_code": 1, "si_code_human_readable": "SEGV_MAPERR"}"#.to_string(),
+            DD_CRASHTRACK_END_SIGINFO.to_string(),
+            DD_CRASHTRACK_BEGIN_PROCINFO.to_string(),
+            r#"{"pid": 42}"#.to_string(),
+            DD_CRASHTRACK_END_PROCINFO.to_string(),
+            DD_CRASHTRACK_BEGIN_STACKTRACE.to_string(),
+            r#"{"ip": "0x1234"}"#.to_string(),
+        ])
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_receive_crash_in_crash_handler() -> anyhow::Result<()> {
+        let mut lines = report_start()?;
+        // The handler crashed while writing the second frame.
+        lines.push(format!(
+            r#"{{"ip": "0x56{DD_CRASHTRACK_CRASH_IN_HANDLER} 7"#
+        ));
+        lines.push(DD_CRASHTRACK_DONE.to_string());
+        let input = lines.join("\n");
+
+        let crash_report = receive_report(Duration::from_secs(1), input.as_bytes()).await?;
+        let CrashReportStatus::CrashReport(_, builder) = crash_report else {
+            panic!("Expected a crash report, got {crash_report:?}");
+        };
+        let crash_info = builder.build()?;
+        assert!(crash_info.incomplete);
+        assert_eq!(crash_info.sig_info.unwrap().si_signo, libc::SIGSEGV);
+        let ips: Vec<_> = crash_info
+            .error
+            .stack
+            .frames()
+            .iter()
+            .map(|frame| frame.ip.as_deref())
+            .collect();
+        assert_eq!(ips, [Some("0x1234")]);
+        assert_eq!(
+            crash_info.log_messages,
+            ["The crash handler crashed with signal 7 while collecting the stacktrace"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_watchdog_records_stalled_stage() -> anyhow::Result<()> {
+        let (mut sender, receiver) = tokio::net::UnixStream::pair()?;
+        for line in report_start()? {
+            to_socket(&mut sender, line).await?;
+        }
+
+        // The collector hangs while unwinding.
+        let crash_report =
+            receive_report(Duration::from_millis(100), BufReader::new(receiver)).await?;
+        let CrashReportStatus::PartialCrashReport(_, builder, _) = crash_report else {
+            panic!("Expected a partial crash report, got {crash_report:?}");
+        };
+        let crash_info = builder.build()?;
+        assert!(crash_info.incomplete);
+        assert_eq!(crash_info.error.stack.frames().len(), 1);
+        assert_eq!(
+            crash_info.log_messages,
+            ["Crash collection stalled in the stacktrace: timed out after 100ms"]
+        );
+
+        // Between blocks, the last one received tells where it stalled.
+        let input = report_start()?[..13].join("\n");
+        let crash_report = receive_report(Duration::from_secs(1), input.as_bytes()).await?;
+        let CrashReportStatus::PartialCrashReport(_, builder, _) = crash_report else {
+            panic!("Expected a partial crash report, got {crash_report:?}");
+        };
+        assert_eq!(
+            builder.build()?.log_messages,
+            ["Crash collection stalled after the procinfo: the collector stopped sending"]
+        );
+        drop(sender);
+        Ok(())
+    }
 }
@@ -0,0 +1,335 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{
+    CrashInfo, ErrorData, ErrorKind, FaultContext, Metadata, OsInfo, ProcInfo, SigInfo, SourceType,
+    Span, StackTrace, ThreadData, DATA_SCHEMA_VERSION,
+};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use uuid::Uuid;
+
+/// Collects the parts of a crash report as they become known, e.g. as the
+/// receiver reads them from the collector, and checks that the report is
+/// complete enough to be sent when it's built.
+#[derive(Clone, Debug, Default)]
+pub struct CrashInfoBuilder {
+    pub(crate) counters: HashMap<String, i64>,
+    pub(crate) error_kind: Option<ErrorKind>,
+    pub(crate) error_message: Option<String>,
+    pub(crate) fault_context: Option<FaultContext>,
+    pub(crate) files: HashMap<String, Vec<String>>,
+    pub(crate) incomplete: bool,
+    pub(crate) log_messages: Vec<String>,
+    pub(crate) metadata: Option<Metadata>,
+    pub(crate) minidump: Option<Vec<u8>>,
+    pub(crate) os_info: Option<OsInfo>,
+    pub(crate) proc_info: Option<ProcInfo>,
+    pub(crate) sig_info: Option<SigInfo>,
+    pub(crate) span_ids: Vec<Span>,
+    pub(crate) stack: Option<StackTrace>,
+    /// "key:value" tags, added to those of the metadata.
+    pub(crate) tags: Vec<String>,
+    pub(crate) threads: Vec<ThreadData>,
+    pub(crate) timestamp: Option<DateTime<Utc>>,
+    pub(crate) trace_ids: Vec<Span>,
+}
+
+/// Getters and predicates
+impl CrashInfoBuilder {
+    pub fn crash_seen(&self) -> bool {
+        self.sig_info.is_some()
+    }
+
+    pub fn proc_info(&self) -> Option<&ProcInfo> {
+        self.proc_info.as_ref()
+    }
+}
+
+/// Constructor and setters
+impl CrashInfoBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_counter(&mut self, name: &str, val: i64) -> anyhow::Result<()> {
+        let old = self.counters.insert(name.to_string(), val);
+        anyhow::ensure!(old.is_none(), "Double insert of counter {name}");
+        Ok(())
+    }
+
+    pub fn add_file(&mut self, filename: &str) -> anyhow::Result<()> {
+        let file = File::open(filename).with_context(|| filename.to_string())?;
+        let lines: std::io::Result<Vec<_>> = BufReader::new(file).lines().collect();
+        self.add_file_with_contents(filename, lines?)?;
+        Ok(())
+    }
+
+    pub fn add_file_with_contents(
+        &mut self,
+        filename: &str,
+        lines: Vec<String>,
+    ) -> anyhow::Result<()> {
+        let old = self.files.insert(filename.to_string(), lines);
+        anyhow::ensure!(
+            old.is_none(),
+            "Attempted to add file that was already there {filename}"
+        );
+        Ok(())
+    }
+
+    pub fn add_log_message(&mut self, message: String) -> anyhow::Result<()> {
+        self.log_messages.push(message);
+        Ok(())
+    }
+
+    pub fn add_tag(&mut self, key: String, value: String) -> anyhow::Result<()> {
+        let prefix = format!("{key}:");
+        anyhow::ensure!(
+            !self.tags.iter().any(|tag| tag.starts_with(&prefix)),
+            "Already had tag with key: {key}"
+        );
+        self.tags.push(format!("{prefix}{value}"));
+        Ok(())
+    }
+
+    pub fn add_thread(&mut self, thread: ThreadData) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !self.threads.iter().any(|t| t.name == thread.name),
+            "Already had thread {}",
+            thread.name
+        );
+        self.threads.push(thread);
+        Ok(())
+    }
+
+    pub fn set_error_kind(&mut self, kind: ErrorKind) -> anyhow::Result<()> {
+        anyhow::ensure!(self.error_kind.is_none());
+        self.error_kind = Some(kind);
+        Ok(())
+    }
+
+    pub fn set_error_message(&mut self, message: String) -> anyhow::Result<()> {
+        anyhow::ensure!(self.error_message.is_none());
+        self.error_message = Some(message);
+        Ok(())
+    }
+
+    pub fn set_fault_context(&mut self, fault_context: FaultContext) -> anyhow::Result<()> {
+        anyhow::ensure!(self.fault_context.is_none());
+        self.fault_context = Some(fault_context);
+        Ok(())
+    }
+
+    pub fn set_incomplete(&mut self, incomplete: bool) -> anyhow::Result<()> {
+        self.incomplete = incomplete;
+        Ok(())
+    }
+
+    pub fn set_metadata(&mut self, metadata: Metadata) -> anyhow::Result<()> {
+        anyhow::ensure!(self.metadata.is_none());
+        self.metadata = Some(metadata);
+        Ok(())
+    }
+
+    pub fn set_os_info(&mut self, os_info: OsInfo) -> anyhow::Result<()> {
+        anyhow::ensure!(self.os_info.is_none());
+        self.os_info = Some(os_info);
+        Ok(())
+    }
+
+    pub fn set_proc_info(&mut self, proc_info: ProcInfo) -> anyhow::Result<()> {
+        anyhow::ensure!(self.proc_info.is_none());
+        self.proc_info = Some(proc_info);
+        Ok(())
+    }
+
+    pub fn set_sig_info(&mut self, sig_info: SigInfo) -> anyhow::Result<()> {
+        anyhow::ensure!(self.sig_info.is_none());
+        self.sig_info = Some(sig_info);
+        Ok(())
+    }
+
+    pub fn set_span_ids(&mut self, ids: Vec<u128>) -> anyhow::Result<()> {
+        anyhow::ensure!(self.span_ids.is_empty());
+        self.span_ids = ids.into_iter().map(Span::from_id).collect();
+        Ok(())
+    }
+
+    pub fn set_stack(&mut self, stack: StackTrace) -> anyhow::Result<()> {
+        anyhow::ensure!(self.stack.is_none());
+        self.stack = Some(stack);
+        Ok(())
+    }
+
+    pub fn set_timestamp(&mut self, ts: DateTime<Utc>) -> anyhow::Result<()> {
+        anyhow::ensure!(self.timestamp.is_none());
+        self.timestamp = Some(ts);
+        Ok(())
+    }
+
+    pub fn set_timestamp_to_now(&mut self) -> anyhow::Result<()> {
+        self.set_timestamp(Utc::now())
+    }
+
+    pub fn set_trace_ids(&mut self, ids: Vec<u128>) -> anyhow::Result<()> {
+        anyhow::ensure!(self.trace_ids.is_empty());
+        self.trace_ids = ids.into_iter().map(Span::from_id).collect();
+        Ok(())
+    }
+}
+
+impl CrashInfoBuilder {
+    /// Builds the report.  The metadata and the process info are required.
+    /// Unless set, the error kind is deduced to be a signal, which is what the
+    /// FFI has always reported; the timestamp is now; and the OS info is that
+    /// of this machine.  The fingerprint is computed from the rest.
+    pub fn build(self) -> anyhow::Result<CrashInfo> {
+        let mut metadata = self.metadata.context("Missing crash metadata")?;
+        metadata.tags.extend(self.tags);
+        let proc_info = self.proc_info.context("Missing process info")?;
+        let error = ErrorData {
+            is_crash: true,
+            kind: self.error_kind.unwrap_or(ErrorKind::UnixSignal),
+            message: self.error_message,
+            source_type: SourceType::Crashtracking,
+            stack: self.stack.unwrap_or_default(),
+            threads: self.threads,
+        };
+        let mut crash_info = CrashInfo {
+            counters: self.counters,
+            data_schema_version: DATA_SCHEMA_VERSION.to_string(),
+            error,
+            fault_context: self.fault_context,
+            files: self.files,
+            fingerprint: None,
+            incomplete: self.incomplete,
+            log_messages: self.log_messages,
+            metadata,
+            minidump: self.minidump,
+            os_info: self.os_info.unwrap_or_else(|| ::os_info::get().into()),
+            proc_info,
+            sig_info: self.sig_info,
+            span_ids: self.span_ids,
+            timestamp: self.timestamp.unwrap_or_else(Utc::now).to_string(),
+            trace_ids: self.trace_ids,
+            uuid: Uuid::new_v4().to_string(),
+        };
+        crash_info.fingerprint = Some(crash_info.compute_fingerprint());
+        Ok(crash_info)
+    }
+}
+
+#[cfg(unix)]
+impl CrashInfoBuilder {
+    /// The stack of the crashing thread, followed by those of the others.
+    fn frames_mut(&mut self) -> impl Iterator<Item = &mut super::StackFrame> {
+        let thread_frames = self
+            .threads
+            .iter_mut()
+            .flat_map(|t| t.stack.frames_mut().iter_mut());
+        self.stack
+            .iter_mut()
+            .flat_map(|stack| stack.frames_mut().iter_mut())
+            .chain(thread_frames)
+    }
+
+    pub fn normalize_ips(&mut self, pid: u32) -> anyhow::Result<()> {
+        let normalizer = blazesym::normalize::Normalizer::new();
+        let pid = pid.into();
+        self.frames_mut().for_each(|frame| {
+            frame
+                .normalize_ip(&normalizer, pid)
+                .unwrap_or_else(|err| eprintln!("Error resolving name {err}"))
+        });
+        Ok(())
+    }
+
+    pub fn resolve_names(&mut self, src: &blazesym::symbolize::Source) -> anyhow::Result<()> {
+        let symbolizer = blazesym::symbolize::Symbolizer::new();
+        for frame in self.frames_mut() {
+            // Resolving names is best effort, just print the error and continue
+            frame
+                .resolve_names(src, &symbolizer)
+                .unwrap_or_else(|err| eprintln!("Error resolving name {err}"));
+        }
+        Ok(())
+    }
+
+    pub fn resolve_names_from_process(&mut self, pid: u32) -> anyhow::Result<()> {
+        let mut process = blazesym::symbolize::Process::new(pid.into());
+        // https://github.com/libbpf/blazesym/issues/518
+        process.map_files = false;
+        let src = blazesym::symbolize::Source::Process(process);
+        self.resolve_names(&src)
+    }
+
+    /// Adds dumps of the memory around the registers of the crashing thread
+    /// to the fault context, if there is one.
+    #[cfg(target_os = "linux")]
+    pub fn read_memory_around_registers(&mut self) {
+        if let (Some(proc_info), Some(fault_context)) = (&self.proc_info, &mut self.fault_context) {
+            fault_context.read_memory_around_registers(proc_info.pid);
+        }
+    }
+
+    /// Writes a minidump of the crashing process.  This must happen while the
+    /// process is held in its crash handler, after the fault context was
+    /// received.
+    #[cfg(target_os = "linux")]
+    pub fn capture_minidump(&mut self) -> anyhow::Result<()> {
+        anyhow::ensure!(self.minidump.is_none());
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        {
+            self.minidump = Some(super::minidump::write_minidump(self)?);
+            Ok(())
+        }
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        anyhow::bail!("Minidumps are not supported on this architecture")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rfc5_crash_info::test_utils::TestInstance;
+
+    #[test]
+    fn test_build() -> anyhow::Result<()> {
+        let mut builder = CrashInfoBuilder::new();
+        builder.add_counter("collecting_sample", 1)?;
+        assert!(builder.add_counter("collecting_sample", 1).is_err());
+        builder.add_tag("team".to_string(), "Habs".to_string())?;
+        assert!(builder
+            .add_tag("team".to_string(), "Leafs".to_string())
+            .is_err());
+        builder.set_span_ids(vec![42])?;
+        builder.set_stack(StackTrace::test_instance(1))?;
+        assert!(!builder.crash_seen());
+        builder.set_sig_info(SigInfo::test_instance(1))?;
+        assert!(builder.crash_seen());
+        builder.set_timestamp(DateTime::from_timestamp(1568898000, 0).unwrap())?;
+
+        let missing = CrashInfoBuilder::new().build().unwrap_err();
+        assert_eq!(missing.to_string(), "Missing crash metadata");
+
+        builder.set_metadata(Metadata::test_instance(1))?;
+        builder.set_proc_info(ProcInfo::test_instance(1))?;
+        let crash_info = builder.build()?;
+        assert_eq!(crash_info.data_schema_version, DATA_SCHEMA_VERSION);
+        assert_eq!(crash_info.error.kind, ErrorKind::UnixSignal);
+        assert_eq!(crash_info.error.stack, StackTrace::test_instance(1));
+        assert_eq!(crash_info.counters["collecting_sample"], 1);
+        assert!(crash_info.metadata.tags.contains(&"team:Habs".to_string()));
+        assert_eq!(crash_info.span_ids[0].id, "42");
+        assert_eq!(crash_info.timestamp, "2019-09-19 13:00:00 UTC");
+        assert_eq!(
+            crash_info.fingerprint.as_deref(),
+            Some(crash_info.compute_fingerprint().as_str())
+        );
+        Ok(())
+    }
+}
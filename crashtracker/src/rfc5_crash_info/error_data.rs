@@ -3,7 +3,6 @@
 use super::stacktrace::StackTrace;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct ErrorData {
@@ -53,15 +52,6 @@ impl From<(String, Vec<crate::StackFrame>)> for ThreadData {
     }
 }
 
-pub fn thread_data_from_additional_stacktraces(
-    additional_stacktraces: HashMap<String, Vec<crate::StackFrame>>,
-) -> Vec<ThreadData> {
-    additional_stacktraces
-        .into_iter()
-        .map(|x| x.into())
-        .collect()
-}
-
 #[cfg(test)]
 impl super::test_utils::TestInstance for ErrorData {
     fn test_instance(seed: u64) -> Self {
@@ -0,0 +1,219 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The register state of the crashing thread, and the memory around its
+/// instruction and stack pointers.  All values are hex encoded integers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FaultContext {
+    pub arch: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fault_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub memory: Vec<MemoryDump>,
+    pub registers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct MemoryDump {
+    pub address: String,
+    /// One line per 16 bytes, in the style of `hexdump -C`, with `??` for
+    /// bytes which couldn't be read.
+    pub hexdump: Vec<String>,
+    pub register: String,
+}
+
+#[cfg(target_os = "linux")]
+impl FaultContext {
+    /// The registers holding the instruction and stack pointers on `arch`.
+    fn ip_and_sp_registers(&self) -> Option<(&'static str, &'static str)> {
+        match self.arch.as_str() {
+            "x86_64" => Some(("rip", "rsp")),
+            "aarch64" => Some(("pc", "sp")),
+            _ => None,
+        }
+    }
+
+    fn register(&self, name: &str) -> Option<usize> {
+        let value = self.registers.get(name)?;
+        usize::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+    }
+
+    /// Reads a few hundred bytes around the instruction and stack pointers
+    /// from the crashing process `pid`.  Code before the instruction pointer
+    /// is as interesting as the code after it, while the interesting part of
+    /// the stack lies above the stack pointer.
+    pub fn read_memory_around_registers(&mut self, pid: u32) {
+        const IP_WINDOW: (usize, usize) = (64, 64);
+        const SP_WINDOW: (usize, usize) = (32, 224);
+
+        let Some((ip, sp)) = self.ip_and_sp_registers() else {
+            return;
+        };
+        for (register, (before, after)) in [(ip, IP_WINDOW), (sp, SP_WINDOW)] {
+            if let Some(address) = self.register(register) {
+                let start = address.saturating_sub(before) & !(HEXDUMP_WIDTH - 1);
+                let end = address.saturating_add(after);
+                let bytes = read_process_memory(pid, start, end - start);
+                self.memory.push(MemoryDump {
+                    register: register.to_string(),
+                    address: format!("{address:#018x}"),
+                    hexdump: hexdump(start, &bytes),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+const HEXDUMP_WIDTH: usize = 16;
+
+/// Reads `len` bytes at `address` in process `pid`.  Each page is read
+/// separately, so that the bytes from readable pages are returned even when
+/// the range crosses into an unmapped one.
+#[cfg(target_os = "linux")]
+pub(crate) fn read_process_memory(pid: u32, address: usize, len: usize) -> Vec<Option<u8>> {
+    let page_size = page_size::get();
+    let mut bytes = Vec::with_capacity(len);
+    let mut current = address;
+    let end = address + len;
+    while current < end {
+        let page_end = (current / page_size + 1).saturating_mul(page_size);
+        let chunk_len = page_end.min(end) - current;
+        let mut buf = vec![0u8; chunk_len];
+        let local = libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: chunk_len,
+        };
+        let remote = libc::iovec {
+            iov_base: current as *mut libc::c_void,
+            iov_len: chunk_len,
+        };
+        // Safety: the local iovec covers `buf`, which outlives the call, and the remote one is
+        // only read by the kernel, which checks it against the target's address space.
+        let read = unsafe { libc::process_vm_readv(pid as libc::pid_t, &local, 1, &remote, 1, 0) };
+        let read = usize::try_from(read).unwrap_or(0);
+        bytes.extend(buf[..read].iter().copied().map(Some));
+        bytes.resize(bytes.len() + chunk_len - read, None);
+        current += chunk_len;
+    }
+    bytes
+}
+
+#[cfg(target_os = "linux")]
+fn hexdump(start: usize, bytes: &[Option<u8>]) -> Vec<String> {
+    bytes
+        .chunks(HEXDUMP_WIDTH)
+        .enumerate()
+        .map(|(i, line)| {
+            let hex: Vec<String> = line
+                .iter()
+                .map(|b| b.map_or_else(|| "??".to_string(), |b| format!("{b:02x}")))
+                .collect();
+            let ascii: String = line
+                .iter()
+                .map(|b| match b {
+                    Some(b) if b.is_ascii_graphic() || *b == b' ' => *b as char,
+                    _ => '.',
+                })
+                .collect();
+            format!(
+                "{:#018x}  {:<47}  |{ascii}|",
+                start + i * HEXDUMP_WIDTH,
+                hex.join(" ")
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+impl super::test_utils::TestInstance for FaultContext {
+    fn test_instance(seed: u64) -> Self {
+        let registers = BTreeMap::from([
+            ("rip".to_string(), format!("{:#018x}", 0x1000 + seed)),
+            ("rsp".to_string(), format!("{:#018x}", 0x7ffc0000 + seed)),
+        ]);
+        Self {
+            arch: "x86_64".to_string(),
+            fault_address: Some("0x0000000000000000".to_string()),
+            memory: vec![MemoryDump {
+                address: format!("{:#018x}", 0x1000 + seed),
+                hexdump: vec![
+                    "0x0000000000001000  55 48 89 e5 ?? ?? ?? ?? ?? ?? ?? ?? ?? ?? ?? ??  |UH..............|"
+                        .to_string(),
+                ],
+                register: "rip".to_string(),
+            }],
+            registers,
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hexdump() {
+        let mut bytes: Vec<Option<u8>> =
+            b"Hello, world!\n\0\x7f".iter().copied().map(Some).collect();
+        bytes.extend([Some(b'A'), None]);
+        assert_eq!(
+            hexdump(0x1000, &bytes),
+            vec![
+                "0x0000000000001000  48 65 6c 6c 6f 2c 20 77 6f 72 6c 64 21 0a 00 7f  |Hello, world!...|",
+                "0x0000000000001010  41 ??                                            |A.|",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_memory_around_registers() {
+        // Reading our own memory is allowed without any ptrace permission.
+        let page_size = page_size::get();
+        let data = [0xabu8; 64];
+        let mut context = FaultContext {
+            arch: "x86_64".to_string(),
+            fault_address: None,
+            memory: vec![],
+            registers: BTreeMap::from([
+                (
+                    "rip".to_string(),
+                    format!("{:#x}", data.as_ptr() as usize + 64),
+                ),
+                (
+                    "rsp".to_string(),
+                    format!("{:#x}", data.as_ptr() as usize + 32),
+                ),
+            ]),
+        };
+        context.read_memory_around_registers(std::process::id());
+        assert_eq!(context.memory.len(), 2);
+        assert_eq!(context.memory[0].register, "rip");
+        assert!(context.memory[0]
+            .hexdump
+            .iter()
+            .any(|l| l.contains("ab ab ab ab")));
+
+        // A read running off the end of a mapping still returns the readable part.
+        let unmapped = unsafe {
+            let p = libc::mmap(
+                std::ptr::null_mut(),
+                2 * page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            assert_ne!(p, libc::MAP_FAILED);
+            libc::munmap(p.cast::<u8>().add(page_size).cast(), page_size);
+            p as usize
+        };
+        let bytes = read_process_memory(std::process::id(), unmapped + page_size - 8, 16);
+        assert_eq!(&bytes[..8], &[Some(0); 8]);
+        assert_eq!(&bytes[8..], &[None; 8]);
+        unsafe { libc::munmap(unmapped as *mut libc::c_void, page_size) };
+    }
+}
@@ -0,0 +1,237 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+//! The fingerprint of a crash identifies crashes with the same cause, e.g. the
+//! same process crashing over and over again, whichever host or process it
+//! happens in.  It is derived from the error, the signal, and the frames at the
+//! top of the stack where the crash happened.
+//!
+//! Frames are identified by their function, when it is known.  Otherwise they
+//! are identified by the build id of their module and their offset in it, and
+//! failing that by their offset from the base of their module, which unlike
+//! their address doesn't change from one process to the next.  Frames which
+//! can't be identified at all are left out.
+
+use super::{CrashInfo, StackFrame};
+use sha2::{Digest, Sha256};
+
+/// The number of frames, from the top of the stack, which make up the fingerprint.
+pub const FINGERPRINT_FRAMES: usize = 8;
+
+/// Functions of the crash handling itself, which are at the top of the stack
+/// of any crash, or any panic, and say nothing about its cause.
+const CRASH_HANDLING_PREFIXES: [&str; 12] = [
+    "<alloc::boxed::Box<",
+    "__restore_rt",
+    "__rust",
+    "_sigtramp",
+    "backtrace::",
+    "core::panic::",
+    "core::panicking::",
+    "datadog_crashtracker::",
+    "rust_begin_unwind",
+    "std::panic::",
+    "std::panicking::",
+    "std::sys",
+];
+
+impl CrashInfo {
+    /// Computes the fingerprint of the crash, see the module documentation.
+    pub fn compute_fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{:?}", self.error.kind));
+        if let Some(sig_info) = &self.sig_info {
+            hasher.update(format!("|{}", sig_info.si_signo));
+        }
+        for key in self
+            .crash_site()
+            .iter()
+            .filter_map(frame_key)
+            .take(FINGERPRINT_FRAMES)
+        {
+            hasher.update("|");
+            hasher.update(key);
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// The stack from the frame which crashed.  That is the frame whose
+    /// instruction pointer is the one in the registers, when they were
+    /// captured.  Otherwise, the frames of the crash handling are skipped, as
+    /// far as they can be recognized by their names.
+    fn crash_site(&self) -> &[StackFrame] {
+        let frames = self.error.stack.frames();
+        let pc = self
+            .fault_context
+            .as_ref()
+            .and_then(|context| {
+                context
+                    .registers
+                    .get("rip")
+                    .or_else(|| context.registers.get("pc"))
+            })
+            .and_then(|pc| parse_hex(pc));
+        if let Some(pc) = pc {
+            let crashed = frames
+                .iter()
+                .position(|frame| frame.ip.as_deref().and_then(parse_hex) == Some(pc));
+            if let Some(crashed) = crashed {
+                return &frames[crashed..];
+            }
+        }
+        let handling = frames
+            .iter()
+            .take_while(|frame| {
+                frame.function.as_deref().is_some_and(|function| {
+                    CRASH_HANDLING_PREFIXES
+                        .iter()
+                        .any(|prefix| function.starts_with(prefix))
+                })
+            })
+            .count();
+        &frames[handling..]
+    }
+}
+
+/// What identifies the frame across processes, if anything.
+fn frame_key(frame: &StackFrame) -> Option<String> {
+    if let Some(function) = &frame.function {
+        return Some(strip_hash(function).to_string());
+    }
+    if let (Some(build_id), Some(relative_address)) = (&frame.build_id, &frame.relative_address) {
+        return Some(format!("{build_id}+{relative_address}"));
+    }
+    let ip = frame.ip.as_deref().and_then(parse_hex)?;
+    let base = frame.module_base_address.as_deref().and_then(parse_hex)?;
+    Some(format!("+{:#x}", ip.checked_sub(base)?))
+}
+
+/// Removes the hash which ends Rust symbols, e.g. `::h0123456789abcdef`,
+/// since it changes from one build to the next.
+fn strip_hash(function: &str) -> &str {
+    match function.rsplit_once("::h") {
+        Some((name, hash)) if hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            name
+        }
+        _ => function,
+    }
+}
+
+fn parse_hex(s: &str) -> Option<u64> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rfc5_crash_info::{
+        test_utils::TestInstance, ErrorKind, FaultContext, SigInfo, StackTrace,
+    };
+
+    fn frame(ip: u64, function: Option<&str>) -> StackFrame {
+        StackFrame {
+            ip: Some(format!("{ip:#x}")),
+            module_base_address: Some("0x1000".to_string()),
+            sp: None,
+            symbol_address: None,
+            build_id: None,
+            build_id_type: None,
+            file_type: None,
+            path: None,
+            relative_address: None,
+            column: None,
+            file: None,
+            function: function.map(str::to_string),
+            line: None,
+        }
+    }
+
+    fn crash_info(frames: Vec<StackFrame>) -> CrashInfo {
+        let mut crash_info = CrashInfo::test_instance(1);
+        crash_info.error.stack = StackTrace::from_frames(frames);
+        crash_info.fault_context = None;
+        crash_info
+    }
+
+    #[test]
+    fn test_same_crash_same_fingerprint() {
+        // Another build of the same code, loaded elsewhere.
+        let a = crash_info(vec![
+            frame(0x1100, Some("app::crash::h0123456789abcdef")),
+            frame(0x1200, None),
+        ]);
+        let mut b = crash_info(vec![
+            frame(0x5500, Some("app::crash::hfedcba9876543210")),
+            frame(0x5600, None),
+        ]);
+        b.error.stack.frames_mut()[1].module_base_address = Some("0x5400".to_string());
+        assert_eq!(a.compute_fingerprint(), b.compute_fingerprint());
+        assert_eq!(a.compute_fingerprint().len(), 64);
+
+        let elsewhere = crash_info(vec![frame(0x1100, Some("app::other")), frame(0x1200, None)]);
+        assert_ne!(a.compute_fingerprint(), elsewhere.compute_fingerprint());
+        let mut other_signal = a.clone();
+        other_signal.sig_info = Some(SigInfo {
+            si_signo: libc::SIGBUS,
+            ..SigInfo::test_instance(1)
+        });
+        assert_ne!(a.compute_fingerprint(), other_signal.compute_fingerprint());
+        let mut panic = a.clone();
+        panic.error.kind = ErrorKind::Panic;
+        assert_ne!(a.compute_fingerprint(), panic.compute_fingerprint());
+    }
+
+    #[test]
+    fn test_crash_handling_is_skipped() {
+        let expected = crash_info(vec![frame(0x1100, Some("app::crash"))]).compute_fingerprint();
+
+        let panic = crash_info(vec![
+            frame(0x1010, Some("backtrace::capture::Backtrace::new")),
+            frame(
+                0x1020,
+                Some("datadog_crashtracker::collector::panic_hook::current_stack"),
+            ),
+            frame(
+                0x1030,
+                Some("<alloc::boxed::Box<F,A> as core::ops::function::Fn<Args>>::call"),
+            ),
+            frame(0x1040, Some("std::panicking::rust_panic_with_hook")),
+            frame(0x1050, Some("core::panicking::panic_fmt")),
+            frame(0x1100, Some("app::crash")),
+        ]);
+        assert_eq!(panic.compute_fingerprint(), expected);
+
+        // Without symbols, the registers tell where the crash was.
+        let mut signal = crash_info(vec![
+            frame(0x1010, None),
+            frame(0x1020, None),
+            frame(0x1100, Some("app::crash")),
+        ]);
+        let mut context = FaultContext::test_instance(1);
+        context
+            .registers
+            .insert("rip".to_string(), "0x0000000000001100".to_string());
+        signal.fault_context = Some(context);
+        assert_eq!(signal.compute_fingerprint(), expected);
+    }
+
+    #[test]
+    fn test_frame_key() {
+        let mut normalized = frame(0x1100, None);
+        assert_eq!(frame_key(&normalized).as_deref(), Some("+0x100"));
+        normalized.build_id = Some("abcd".to_string());
+        normalized.relative_address = Some("0x0000000000000100".to_string());
+        assert_eq!(
+            frame_key(&normalized).as_deref(),
+            Some("abcd+0x0000000000000100")
+        );
+        normalized.module_base_address = None;
+        normalized.build_id = None;
+        assert_eq!(frame_key(&normalized), None);
+        assert_eq!(strip_hash("app::h0123"), "app::h0123");
+    }
+}
@@ -0,0 +1,673 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+//! Writes a crash as a minidump, in the format produced by Breakpad on Linux, so it can be
+//! analyzed with standard tooling (`minidump-stackwalk`, `minidump_stackwalk`, symbol servers).
+//!
+//! The dump contains a thread list with the registers and stack of every thread, the module list
+//! with the build id of each module, the exception which crashed the process, and the Linux
+//! specific streams with copies of `/proc` files.  `/proc/<pid>/environ` is deliberately left
+//! out, since it commonly holds secrets.
+//!
+//! Like the rest of the receiver's collection, this must run while the crashing process is held
+//! in its crash handler, and needs permission to ptrace it.
+
+use super::{fault_context::read_process_memory, CrashInfoBuilder, StoppedThread};
+use anyhow::Context;
+
+const MINIDUMP_SIGNATURE: u32 = 0x504d444d; // "MDMP"
+const MINIDUMP_VERSION: u32 = 0xa793;
+const HEADER_SIZE: usize = 32;
+
+const THREAD_LIST_STREAM: u32 = 3;
+const MODULE_LIST_STREAM: u32 = 4;
+const MEMORY_LIST_STREAM: u32 = 5;
+const EXCEPTION_STREAM: u32 = 6;
+const SYSTEM_INFO_STREAM: u32 = 7;
+const LINUX_CPU_INFO_STREAM: u32 = 0x47670003;
+const LINUX_PROC_STATUS_STREAM: u32 = 0x47670004;
+const LINUX_LSB_RELEASE_STREAM: u32 = 0x47670005;
+const LINUX_CMD_LINE_STREAM: u32 = 0x47670006;
+const LINUX_AUXV_STREAM: u32 = 0x47670008;
+const LINUX_MAPS_STREAM: u32 = 0x47670009;
+
+const PLATFORM_LINUX: u32 = 0x8201;
+const CV_SIGNATURE_ELF: u32 = 0x4270454c; // "BpEL"
+const NT_GNU_BUILD_ID: u32 = 3;
+
+/// How much of each stack, above the stack pointer, goes in the dump.
+const MAX_STACK_SIZE: u64 = 32 * 1024;
+/// How much memory around the instruction pointer of the crashing thread goes in the dump.
+const IP_MEMORY_SIZE: u64 = 256;
+const MAX_THREADS: usize = 128;
+
+#[cfg(target_arch = "x86_64")]
+mod arch {
+    pub const PROCESSOR_ARCHITECTURE: u16 = 9; // AMD64
+    pub const IP: &str = "rip";
+    pub const SP: &str = "rsp";
+    /// The System V ABI lets leaf functions use the 128 bytes below the stack pointer.
+    pub const RED_ZONE: u64 = 128;
+
+    const CONTEXT_SIZE: usize = 1232;
+    const CONTEXT_AMD64: u32 = 0x00100000;
+    const CONTEXT_CONTROL: u32 = CONTEXT_AMD64 | 0x1;
+    const CONTEXT_INTEGER: u32 = CONTEXT_AMD64 | 0x2;
+    /// The general purpose registers, in the order of `CONTEXT_AMD64`.
+    const GPRS: [&str; 16] = [
+        "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12",
+        "r13", "r14", "r15",
+    ];
+
+    /// Lays out `CONTEXT_AMD64`.  Only the control and integer registers are filled in.
+    pub fn context(register: impl Fn(&str) -> u64) -> Vec<u8> {
+        let mut context = vec![0u8; CONTEXT_SIZE];
+        let flags = CONTEXT_CONTROL | CONTEXT_INTEGER;
+        context[48..52].copy_from_slice(&flags.to_le_bytes());
+        context[68..72].copy_from_slice(&(register("eflags") as u32).to_le_bytes());
+        for (i, name) in GPRS.iter().enumerate() {
+            let offset = 120 + 8 * i;
+            context[offset..offset + 8].copy_from_slice(&register(name).to_le_bytes());
+        }
+        context[248..256].copy_from_slice(&register("rip").to_le_bytes());
+        context
+    }
+
+    pub fn registers(regs: &libc::user_regs_struct) -> Vec<(&'static str, u64)> {
+        vec![
+            ("rax", regs.rax),
+            ("rbx", regs.rbx),
+            ("rcx", regs.rcx),
+            ("rdx", regs.rdx),
+            ("rsi", regs.rsi),
+            ("rdi", regs.rdi),
+            ("rbp", regs.rbp),
+            ("rsp", regs.rsp),
+            ("r8", regs.r8),
+            ("r9", regs.r9),
+            ("r10", regs.r10),
+            ("r11", regs.r11),
+            ("r12", regs.r12),
+            ("r13", regs.r13),
+            ("r14", regs.r14),
+            ("r15", regs.r15),
+            ("rip", regs.rip),
+            ("eflags", regs.eflags),
+        ]
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arch {
+    pub const PROCESSOR_ARCHITECTURE: u16 = 12; // ARM64
+    pub const IP: &str = "pc";
+    pub const SP: &str = "sp";
+    pub const RED_ZONE: u64 = 0;
+
+    const CONTEXT_SIZE: usize = 912;
+    const CONTEXT_ARM64: u32 = 0x00400000;
+    const CONTEXT_CONTROL: u32 = CONTEXT_ARM64 | 0x1;
+    const CONTEXT_INTEGER: u32 = CONTEXT_ARM64 | 0x2;
+    /// The registers following `Cpsr`, in the order of `CONTEXT_ARM64`.
+    const GPRS: [&str; 33] = [
+        "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13",
+        "x14", "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26",
+        "x27", "x28", "fp", "lr", "sp", "pc",
+    ];
+
+    /// Lays out `CONTEXT_ARM64`.  Only the control and integer registers are filled in.
+    pub fn context(register: impl Fn(&str) -> u64) -> Vec<u8> {
+        let mut context = vec![0u8; CONTEXT_SIZE];
+        let flags = CONTEXT_CONTROL | CONTEXT_INTEGER;
+        context[0..4].copy_from_slice(&flags.to_le_bytes());
+        context[4..8].copy_from_slice(&(register("pstate") as u32).to_le_bytes());
+        for (i, name) in GPRS.iter().enumerate() {
+            let offset = 8 + 8 * i;
+            context[offset..offset + 8].copy_from_slice(&register(name).to_le_bytes());
+        }
+        context
+    }
+
+    pub fn registers(regs: &libc::user_regs_struct) -> Vec<(&'static str, u64)> {
+        let mut registers: Vec<(&'static str, u64)> =
+            GPRS[..31].iter().copied().zip(regs.regs).collect();
+        registers.extend([("sp", regs.sp), ("pc", regs.pc), ("pstate", regs.pstate)]);
+        registers
+    }
+}
+
+/// The location of some data in the dump.
+#[derive(Clone, Copy, Default)]
+struct Location {
+    data_size: u32,
+    rva: u32,
+}
+
+struct MinidumpWriter {
+    buf: Vec<u8>,
+    directory: Vec<(u32, Location)>,
+}
+
+impl MinidumpWriter {
+    fn new() -> Self {
+        Self {
+            buf: vec![0; HEADER_SIZE],
+            directory: vec![],
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Location {
+        // Everything is 4 byte aligned, as some readers expect.
+        self.buf.resize(self.buf.len().next_multiple_of(4), 0);
+        let location = Location {
+            data_size: data.len() as u32,
+            rva: self.buf.len() as u32,
+        };
+        self.buf.extend_from_slice(data);
+        location
+    }
+
+    /// Writes a `MINIDUMP_STRING`: its length in bytes, then its UTF-16 code units, then a
+    /// terminating null.
+    fn write_string(&mut self, s: &str) -> u32 {
+        let units: Vec<u16> = s.encode_utf16().collect();
+        let mut data = Vec::with_capacity(4 + 2 * units.len() + 2);
+        data.extend_from_slice(&(2 * units.len() as u32).to_le_bytes());
+        units
+            .iter()
+            .chain([&0])
+            .for_each(|unit| data.extend_from_slice(&unit.to_le_bytes()));
+        self.write(&data).rva
+    }
+
+    fn write_stream(&mut self, stream_type: u32, data: &[u8]) {
+        let location = self.write(data);
+        self.directory.push((stream_type, location));
+    }
+
+    fn finish(mut self, time_date_stamp: u32) -> Vec<u8> {
+        let directory: Vec<u8> = self
+            .directory
+            .iter()
+            .flat_map(|(stream_type, location)| {
+                [*stream_type, location.data_size, location.rva].map(u32::to_le_bytes)
+            })
+            .flatten()
+            .collect();
+        let directory = self.write(&directory);
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(&MINIDUMP_SIGNATURE.to_le_bytes());
+        header.extend_from_slice(&MINIDUMP_VERSION.to_le_bytes());
+        header.extend_from_slice(&(self.directory.len() as u32).to_le_bytes());
+        header.extend_from_slice(&directory.rva.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes()); // Checksum
+        header.extend_from_slice(&time_date_stamp.to_le_bytes());
+        header.extend_from_slice(&0u64.to_le_bytes()); // Flags
+        self.buf[..HEADER_SIZE].copy_from_slice(&header);
+        self.buf
+    }
+}
+
+/// A line of `/proc/<pid>/maps`.
+struct Mapping {
+    start: u64,
+    end: u64,
+    offset: u64,
+    path: Option<String>,
+}
+
+fn read_maps(pid: u32) -> anyhow::Result<(String, Vec<Mapping>)> {
+    let maps = std::fs::read_to_string(format!("/proc/{pid}/maps"))?;
+    let mappings = maps
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_ascii_whitespace();
+            let (start, end) = fields.next()?.split_once('-')?;
+            let offset = fields.nth(1)?;
+            let path = fields.nth(2).map(|path| {
+                // Paths may contain spaces, so take the rest of the line.
+                line[path.as_ptr() as usize - line.as_ptr() as usize..].to_string()
+            });
+            Some(Mapping {
+                start: u64::from_str_radix(start, 16).ok()?,
+                end: u64::from_str_radix(end, 16).ok()?,
+                offset: u64::from_str_radix(offset, 16).ok()?,
+                path,
+            })
+        })
+        .collect();
+    Ok((maps, mappings))
+}
+
+struct Thread {
+    tid: u32,
+    context: Vec<u8>,
+    ip: u64,
+    /// The thread's stack, starting at `stack_start`, read while it was stopped.
+    stack: Vec<u8>,
+    stack_start: u64,
+}
+
+fn read_memory(pid: u32, address: u64, len: u64) -> Vec<u8> {
+    read_process_memory(pid, address as usize, len as usize)
+        .into_iter()
+        .map(|b| b.unwrap_or_default())
+        .collect()
+}
+
+fn read_thread(pid: u32, tid: u32, registers: &[(&str, u64)], mappings: &[Mapping]) -> Thread {
+    let register = |name: &str| {
+        registers
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map_or(0, |(_, value)| *value)
+    };
+    let sp = register(arch::SP);
+    let stack_start = sp.saturating_sub(arch::RED_ZONE) & !0xf;
+    // Don't read past the end of the stack's mapping, into whatever happens to follow it.
+    let stack_end = mappings
+        .iter()
+        .find(|m| (m.start..m.end).contains(&sp))
+        .map_or(sp, |m| m.end)
+        .min(sp.saturating_add(MAX_STACK_SIZE));
+    Thread {
+        tid,
+        context: arch::context(register),
+        ip: register(arch::IP),
+        stack: read_memory(pid, stack_start, stack_end.saturating_sub(stack_start)),
+        stack_start,
+    }
+}
+
+/// Reads the threads of `pid`.  The registers of the crashing thread come from the fault
+/// context, since ptrace would only show it inside the crash handler.
+fn read_threads(crash_info: &CrashInfoBuilder, pid: u32, mappings: &[Mapping]) -> Vec<Thread> {
+    let crashing_tid = crash_info.proc_info.as_ref().and_then(|p| p.tid);
+    let crashing_registers: Option<Vec<(&str, u64)>> =
+        crash_info.fault_context.as_ref().map(|fault_context| {
+            fault_context
+                .registers
+                .iter()
+                .filter_map(|(name, value)| {
+                    let value = u64::from_str_radix(value.trim_start_matches("0x"), 16).ok()?;
+                    Some((name.as_str(), value))
+                })
+                .collect()
+        });
+
+    let Ok(tasks) = std::fs::read_dir(format!("/proc/{pid}/task")) else {
+        return vec![];
+    };
+    let mut tids: Vec<u32> = tasks
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect();
+    tids.sort_unstable();
+    // Put the crashing thread first, so it's never the one left out.
+    tids.sort_by_key(|tid| Some(*tid) != crashing_tid);
+
+    tids.into_iter()
+        .take(MAX_THREADS)
+        .filter_map(|tid| match &crashing_registers {
+            Some(registers) if Some(tid) == crashing_tid => {
+                Some(read_thread(pid, tid, registers, mappings))
+            }
+            _ => {
+                let thread = StoppedThread::stop(tid, None).and_then(|thread| {
+                    let regs = thread.registers()?;
+                    // The stack is read before `thread` is dropped, which resumes it.
+                    Ok(read_thread(pid, tid, &arch::registers(&regs), mappings))
+                });
+                thread
+                    .map_err(|err| eprintln!("Unable to read thread {tid}: {err}"))
+                    .ok()
+            }
+        })
+        .collect()
+}
+
+/// Finds the GNU build id in the program headers of a 64 bit little endian ELF file.  Only the
+/// ELF header, the program headers and the note segments are read, since the modules can be
+/// huge.  All offsets and sizes come from the file, so they are checked before use.
+fn read_build_id(path: &str) -> Option<Vec<u8>> {
+    use std::os::unix::fs::FileExt;
+
+    /// Note segments hold a handful of small notes, anything bigger is bogus.
+    const MAX_NOTE_SEGMENT_SIZE: usize = 64 * 1024;
+    const PT_NOTE: u32 = 4;
+    const PHDR_SIZE: usize = 56;
+
+    let file = std::fs::File::open(path).ok()?;
+    let read_at = |offset: u64, len: usize| {
+        let mut buf = vec![0; len];
+        file.read_exact_at(&mut buf, offset).ok()?;
+        Some(buf)
+    };
+    let u16_at =
+        |b: &[u8], at: usize| Some(u16::from_le_bytes(b.get(at..at + 2)?.try_into().ok()?));
+    let u32_at =
+        |b: &[u8], at: usize| Some(u32::from_le_bytes(b.get(at..at + 4)?.try_into().ok()?));
+    let u64_at =
+        |b: &[u8], at: usize| Some(u64::from_le_bytes(b.get(at..at + 8)?.try_into().ok()?));
+
+    let header = read_at(0, 64)?;
+    if header.get(..6)? != b"\x7fELF\x02\x01" {
+        return None;
+    }
+    let phoff = u64_at(&header, 32)?;
+    let phentsize = u16_at(&header, 54)? as usize;
+    let phnum = u16_at(&header, 56)? as usize;
+    if phentsize < PHDR_SIZE {
+        return None;
+    }
+    let phdrs = read_at(phoff, phentsize.checked_mul(phnum)?)?;
+    for phdr in phdrs.chunks_exact(phentsize) {
+        if u32_at(phdr, 0)? != PT_NOTE {
+            continue;
+        }
+        let size = usize::try_from(u64_at(phdr, 32)?).ok()?;
+        if size > MAX_NOTE_SEGMENT_SIZE {
+            continue;
+        }
+        let Some(notes) = read_at(u64_at(phdr, 8)?, size) else {
+            continue;
+        };
+        let mut note = 0usize;
+        while note.checked_add(12)? <= notes.len() {
+            let namesz = u32_at(&notes, note)? as usize;
+            let descsz = u32_at(&notes, note + 4)? as usize;
+            let name = note + 12;
+            let desc = name.checked_add(namesz.checked_next_multiple_of(4)?)?;
+            let desc_end = desc.checked_add(descsz)?;
+            if u32_at(&notes, note + 8)? == NT_GNU_BUILD_ID
+                && notes.get(name..name.checked_add(namesz)?)? == b"GNU\0"
+            {
+                return notes.get(desc..desc_end).map(<[u8]>::to_vec);
+            }
+            note = desc.checked_add(descsz.checked_next_multiple_of(4)?)?;
+        }
+    }
+    None
+}
+
+fn write_thread_list(
+    writer: &mut MinidumpWriter,
+    threads: &[Thread],
+) -> (Vec<(u64, Location)>, Vec<Location>) {
+    let mut memory = vec![];
+    let mut contexts = vec![];
+    let mut data = (threads.len() as u32).to_le_bytes().to_vec();
+    for thread in threads {
+        let stack = writer.write(&thread.stack);
+        let context = writer.write(&thread.context);
+        memory.push((thread.stack_start, stack));
+        contexts.push(context);
+        // MINIDUMP_THREAD: ThreadId, SuspendCount, PriorityClass, Priority, Teb, Stack,
+        // ThreadContext.
+        data.extend_from_slice(&thread.tid.to_le_bytes());
+        data.extend_from_slice(&[0; 12]);
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&thread.stack_start.to_le_bytes());
+        data.extend_from_slice(&stack.data_size.to_le_bytes());
+        data.extend_from_slice(&stack.rva.to_le_bytes());
+        data.extend_from_slice(&context.data_size.to_le_bytes());
+        data.extend_from_slice(&context.rva.to_le_bytes());
+    }
+    writer.write_stream(THREAD_LIST_STREAM, &data);
+    (memory, contexts)
+}
+
+fn write_module_list(writer: &mut MinidumpWriter, pid: u32, mappings: &[Mapping]) {
+    // A module is mapped in several segments, the first of which maps the start of the file.
+    let mut modules: Vec<(&str, u64, u64)> = vec![];
+    for mapping in mappings {
+        let Some(path) = mapping.path.as_deref() else {
+            continue;
+        };
+        if !path.starts_with('/') || path.starts_with("/dev/") {
+            continue;
+        }
+        match modules.last_mut() {
+            Some((last, _, end)) if *last == path => *end = mapping.end,
+            _ if mapping.offset == 0 => modules.push((path, mapping.start, mapping.end)),
+            _ => {}
+        }
+    }
+
+    let mut data = (modules.len() as u32).to_le_bytes().to_vec();
+    for (path, start, end) in modules {
+        let name = writer.write_string(path);
+        // Read the file as the crashing process sees it, in case it's in another mount
+        // namespace.
+        let build_id =
+            read_build_id(&format!("/proc/{pid}/root{path}")).or_else(|| read_build_id(path));
+        let cv_record = build_id.map_or_else(Location::default, |build_id| {
+            let mut record = CV_SIGNATURE_ELF.to_le_bytes().to_vec();
+            record.extend_from_slice(&build_id);
+            writer.write(&record)
+        });
+        // MINIDUMP_MODULE: BaseOfImage, SizeOfImage, CheckSum, TimeDateStamp, ModuleNameRva,
+        // VersionInfo, CvRecord, MiscRecord, Reserved0, Reserved1.
+        data.extend_from_slice(&start.to_le_bytes());
+        data.extend_from_slice(&((end - start).min(u32::MAX as u64) as u32).to_le_bytes());
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&name.to_le_bytes());
+        data.extend_from_slice(&[0; 52]);
+        data.extend_from_slice(&cv_record.data_size.to_le_bytes());
+        data.extend_from_slice(&cv_record.rva.to_le_bytes());
+        data.extend_from_slice(&[0; 24]);
+    }
+    writer.write_stream(MODULE_LIST_STREAM, &data);
+}
+
+fn write_memory_list(writer: &mut MinidumpWriter, memory: &[(u64, Location)]) {
+    let mut data = (memory.len() as u32).to_le_bytes().to_vec();
+    for (start, location) in memory {
+        data.extend_from_slice(&start.to_le_bytes());
+        data.extend_from_slice(&location.data_size.to_le_bytes());
+        data.extend_from_slice(&location.rva.to_le_bytes());
+    }
+    writer.write_stream(MEMORY_LIST_STREAM, &data);
+}
+
+/// The exception stream follows Breakpad's Linux convention: the code is the signal number, the
+/// flags are the `si_code` and the address is the faulting address.
+fn write_exception(
+    writer: &mut MinidumpWriter,
+    crash_info: &CrashInfoBuilder,
+    tid: u32,
+    context: Location,
+) {
+    let Some(siginfo) = &crash_info.sig_info else {
+        return;
+    };
+    let address = siginfo
+        .si_addr
+        .as_ref()
+        .and_then(|addr| u64::from_str_radix(addr.trim_start_matches("0x"), 16).ok());
+    let mut data = Vec::with_capacity(168);
+    data.extend_from_slice(&tid.to_le_bytes());
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(&(siginfo.si_signo as u32).to_le_bytes());
+    data.extend_from_slice(&(siginfo.si_code as u32).to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes()); // ExceptionRecord
+    data.extend_from_slice(&address.unwrap_or_default().to_le_bytes());
+    data.extend_from_slice(&[0; 128]); // NumberParameters, alignment, ExceptionInformation
+    data.extend_from_slice(&context.data_size.to_le_bytes());
+    data.extend_from_slice(&context.rva.to_le_bytes());
+    writer.write_stream(EXCEPTION_STREAM, &data);
+}
+
+fn write_system_info(writer: &mut MinidumpWriter) {
+    let read = |path: &str| {
+        std::fs::read_to_string(path)
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_default()
+    };
+    let release = read("/proc/sys/kernel/osrelease");
+    let version = read("/proc/sys/kernel/version");
+    let csd_version = writer.write_string(&format!(
+        "Linux {release} {version} {}",
+        std::env::consts::ARCH
+    ));
+    // The release looks like "6.1.0-18-amd64": the first three numbers are the versions.
+    let mut numbers = release
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|n| n.parse::<u32>().unwrap_or_default());
+    let processors = std::thread::available_parallelism().map_or(0, |n| n.get().min(255) as u8);
+
+    let mut data = Vec::with_capacity(56);
+    data.extend_from_slice(&arch::PROCESSOR_ARCHITECTURE.to_le_bytes());
+    data.extend_from_slice(&[0; 4]); // ProcessorLevel, ProcessorRevision
+    data.push(processors);
+    data.push(0); // ProductType
+    for _ in 0..3 {
+        data.extend_from_slice(&numbers.next().unwrap_or_default().to_le_bytes());
+    }
+    data.extend_from_slice(&PLATFORM_LINUX.to_le_bytes());
+    data.extend_from_slice(&csd_version.to_le_bytes());
+    data.extend_from_slice(&[0; 28]); // SuiteMask, Reserved2, Cpu
+    writer.write_stream(SYSTEM_INFO_STREAM, &data);
+}
+
+/// Writes a minidump of the crashing process described by `crash_info`.
+pub(crate) fn write_minidump(crash_info: &CrashInfoBuilder) -> anyhow::Result<Vec<u8>> {
+    let pid = crash_info
+        .proc_info
+        .as_ref()
+        .context("Unable to write a minidump: No PID specified")?
+        .pid;
+    let (maps, mappings) = read_maps(pid)?;
+    let threads = read_threads(crash_info, pid, &mappings);
+
+    let mut writer = MinidumpWriter::new();
+    let (mut memory, contexts) = write_thread_list(&mut writer, &threads);
+    write_module_list(&mut writer, pid, &mappings);
+    if let (Some(siginfo_thread), Some(context)) = (threads.first(), contexts.first()) {
+        let crashing_tid = crash_info.proc_info.as_ref().and_then(|p| p.tid);
+        if crash_info.crash_seen() && Some(siginfo_thread.tid) == crashing_tid {
+            write_exception(&mut writer, crash_info, siginfo_thread.tid, *context);
+            // The code around the crash is often what's needed to make sense of it.
+            let start = siginfo_thread.ip.saturating_sub(IP_MEMORY_SIZE / 2);
+            let code = writer.write(&read_memory(pid, start, IP_MEMORY_SIZE));
+            memory.push((start, code));
+        }
+    }
+    write_memory_list(&mut writer, &memory);
+    write_system_info(&mut writer);
+
+    for (stream_type, path) in [
+        (LINUX_CPU_INFO_STREAM, "/proc/cpuinfo".to_string()),
+        (LINUX_PROC_STATUS_STREAM, format!("/proc/{pid}/status")),
+        (LINUX_CMD_LINE_STREAM, format!("/proc/{pid}/cmdline")),
+        (LINUX_AUXV_STREAM, format!("/proc/{pid}/auxv")),
+    ] {
+        if let Ok(contents) = std::fs::read(path) {
+            writer.write_stream(stream_type, &contents);
+        }
+    }
+    if let Ok(release) =
+        std::fs::read("/etc/lsb-release").or_else(|_| std::fs::read("/etc/os-release"))
+    {
+        writer.write_stream(LINUX_LSB_RELEASE_STREAM, &release);
+    }
+    writer.write_stream(LINUX_MAPS_STREAM, maps.as_bytes());
+
+    let timestamp = crash_info.timestamp.unwrap_or_else(chrono::Utc::now);
+    Ok(writer.finish(timestamp.timestamp() as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rfc5_crash_info::ProcInfo;
+
+    fn u32_at(dump: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(dump[at..at + 4].try_into().unwrap())
+    }
+
+    fn streams(dump: &[u8]) -> Vec<(u32, usize, usize)> {
+        let count = u32_at(dump, 8) as usize;
+        let directory = u32_at(dump, 12) as usize;
+        (0..count)
+            .map(|i| {
+                let entry = directory + 12 * i;
+                (
+                    u32_at(dump, entry),
+                    u32_at(dump, entry + 4) as usize,
+                    u32_at(dump, entry + 8) as usize,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_read_build_id() {
+        // Any binary built by cargo has a build id.
+        let exe = std::env::current_exe().unwrap();
+        let build_id = read_build_id(exe.to_str().unwrap());
+        assert!(build_id.is_some_and(|id| !id.is_empty()));
+        assert_eq!(read_build_id("/proc/self/status"), None);
+    }
+
+    #[test]
+    fn test_write_minidump() {
+        // Ptracing a child is allowed, unlike our own threads.
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        // Give it time to exec.
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let exe = std::fs::read_link(format!("/proc/{}/exe", child.id())).unwrap();
+        let mut crash_info = CrashInfoBuilder::new();
+        crash_info
+            .set_proc_info(ProcInfo {
+                pid: child.id(),
+                tid: None,
+            })
+            .unwrap();
+        let dump = write_minidump(&crash_info);
+        child.kill().unwrap();
+        child.wait().unwrap();
+        let dump = dump.unwrap();
+
+        assert_eq!(u32_at(&dump, 0), MINIDUMP_SIGNATURE);
+        assert_eq!(u32_at(&dump, 4), MINIDUMP_VERSION);
+        let streams = streams(&dump);
+        let stream = |stream_type| {
+            streams
+                .iter()
+                .find(|(t, _, _)| *t == stream_type)
+                .map(|(_, size, rva)| &dump[*rva..*rva + *size])
+                .unwrap()
+        };
+
+        let threads = stream(THREAD_LIST_STREAM);
+        assert_eq!(u32_at(threads, 0), 1);
+        assert_eq!(u32_at(threads, 4), child.id());
+        assert_eq!(threads.len(), 4 + 48);
+
+        let modules = stream(MODULE_LIST_STREAM);
+        let count = u32_at(modules, 0) as usize;
+        assert!(count > 0);
+        assert_eq!(modules.len(), 4 + 108 * count);
+        let names: Vec<String> = (0..count)
+            .map(|i| {
+                let rva = u32_at(modules, 4 + 108 * i + 20) as usize;
+                let len = u32_at(&dump, rva) as usize;
+                let units: Vec<u16> = dump[rva + 4..rva + 4 + len]
+                    .chunks(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                String::from_utf16(&units).unwrap()
+            })
+            .collect();
+        assert!(names.iter().any(|name| *name == exe.to_str().unwrap()));
+
+        let cmdline = stream(LINUX_CMD_LINE_STREAM);
+        assert_eq!(cmdline, b"sleep\x0010\x00");
+        assert!(!stream(LINUX_MAPS_STREAM).is_empty());
+        // No exception stream without a signal.
+        assert!(streams.iter().all(|(t, _, _)| *t != EXCEPTION_STREAM));
+    }
+}
@@ -1,37 +1,64 @@
 // Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
 // SPDX-License-Identifier: Apache-2.0
 
+mod builder;
 mod error_data;
+mod fault_context;
+mod fingerprint;
 mod metadata;
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod minidump;
 mod os_info;
 mod proc_info;
 mod sig_info;
 mod spans;
+mod spool;
 mod stacktrace;
 mod telemetry;
 mod test_utils;
+mod threads;
 
-pub use error_data::{ErrorData, ErrorKind, SourceType};
+pub use builder::CrashInfoBuilder;
+pub use error_data::{ErrorData, ErrorKind, SourceType, ThreadData};
+pub use fault_context::{FaultContext, MemoryDump};
+pub use fingerprint::FINGERPRINT_FRAMES;
 pub use metadata::Metadata;
 pub use os_info::OsInfo;
 pub use proc_info::ProcInfo;
 pub use sig_info::{SiCodes, SigInfo, SignalNames};
 pub use spans::Span;
+pub use spool::{
+    async_upload_pending_reports, upload_pending_reports, PendingReportsSummary,
+    DEFAULT_MAX_PENDING_REPORT_AGE,
+};
 pub use stacktrace::{BuildIdType, FileType, StackFrame, StackTrace};
 pub use telemetry::TelemetryCrashUploader;
+#[cfg(target_os = "linux")]
+pub use threads::collect_thread_stacks;
+#[cfg(target_os = "linux")]
+pub(crate) use threads::StoppedThread;
 
 use anyhow::Context;
-use error_data::thread_data_from_additional_stacktraces;
+use ddcommon::Endpoint;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, fs::File, path::Path};
 
+/// The version of the schema of [CrashInfo], which is checked in next to this
+/// module as `crash_info.schema.json`.
+pub const DATA_SCHEMA_VERSION: &str = "1.0";
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct CrashInfo {
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub counters: HashMap<String, i64>,
     pub data_schema_version: String,
     pub error: ErrorData,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fault_context: Option<FaultContext>,
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub files: HashMap<String, Vec<String>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -40,6 +67,11 @@ pub struct CrashInfo {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub log_messages: Vec<String>,
     pub metadata: Metadata,
+    /// A Breakpad-compatible minidump of the crashing process, if one was
+    /// requested.  It isn't part of the report, and is only written out next
+    /// to it when the endpoint is a file.
+    #[serde(skip)]
+    pub minidump: Option<Vec<u8>>,
     pub os_info: OsInfo,
     pub proc_info: ProcInfo,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -52,73 +84,48 @@ pub struct CrashInfo {
     pub uuid: String,
 }
 
-impl From<crate::crash_info::CrashInfo> for CrashInfo {
-    fn from(value: crate::crash_info::CrashInfo) -> Self {
-        let counters = value.counters;
-        let data_schema_version = String::from("1.0");
-        let error = {
-            let is_crash = true;
-            let kind = ErrorKind::UnixSignal;
-            let message = None;
-            let source_type = SourceType::Crashtracking;
-            let stack = value.stacktrace.into();
-            let threads = thread_data_from_additional_stacktraces(value.additional_stacktraces);
-            ErrorData {
-                is_crash,
+impl CrashInfo {
+    /// A report of an error which isn't a signal, such as a panic or an
+    /// unhandled exception, in the current process.
+    pub fn new_error(
+        kind: ErrorKind,
+        message: Option<String>,
+        stack: StackTrace,
+        metadata: crate::CrashtrackerMetadata,
+    ) -> Self {
+        let mut crash_info = Self {
+            counters: HashMap::new(),
+            data_schema_version: DATA_SCHEMA_VERSION.to_string(),
+            error: ErrorData {
+                is_crash: true,
                 kind,
                 message,
-                source_type,
+                source_type: SourceType::Crashtracking,
                 stack,
-                threads,
-            }
+                threads: vec![],
+            },
+            fault_context: None,
+            files: HashMap::new(),
+            fingerprint: None,
+            incomplete: false,
+            log_messages: vec![],
+            metadata: metadata.into(),
+            minidump: None,
+            os_info: ::os_info::get().into(),
+            proc_info: ProcInfo {
+                pid: std::process::id(),
+                tid: None,
+            },
+            sig_info: None,
+            span_ids: vec![],
+            timestamp: chrono::Utc::now().to_string(),
+            trace_ids: vec![],
+            uuid: uuid::Uuid::new_v4().to_string(),
         };
-        let files = value.files;
-        let fingerprint = None;
-        let incomplete = value.incomplete;
-        let log_messages = vec![];
-        let metadata = value.metadata.unwrap().into();
-        let os_info = value.os_info.into();
-        let proc_info = value.proc_info.unwrap().into();
-        let sig_info = value.siginfo.map(SigInfo::from);
-        let span_ids = value
-            .span_ids
-            .into_iter()
-            .map(|s| Span {
-                id: s.to_string(),
-                thread_name: None,
-            })
-            .collect();
-        let trace_ids = value
-            .trace_ids
-            .into_iter()
-            .map(|s| Span {
-                id: s.to_string(),
-                thread_name: None,
-            })
-            .collect();
-        let timestamp = value.timestamp.unwrap().to_string();
-        let uuid = value.uuid.to_string();
-        Self {
-            counters,
-            data_schema_version,
-            error,
-            files,
-            fingerprint,
-            incomplete,
-            log_messages,
-            metadata,
-            os_info,
-            proc_info,
-            sig_info,
-            span_ids,
-            trace_ids,
-            timestamp,
-            uuid,
-        }
+        crash_info.fingerprint = Some(crash_info.compute_fingerprint());
+        crash_info
     }
-}
 
-impl CrashInfo {
     /// Emit the CrashInfo as structured json in file `path`.
     pub fn to_file(&self, path: &Path) -> anyhow::Result<()> {
         let file = File::options()
@@ -130,17 +137,131 @@ impl CrashInfo {
             .with_context(|| format!("Failed to write json to {}", path.display()))?;
         Ok(())
     }
+
+    /// Like [CrashInfo::to_file], without blocking the runtime.
+    async fn async_to_file(&self, path: &Path) -> anyhow::Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let json = serde_json::to_vec_pretty(self)
+            .with_context(|| format!("Failed to write json to {}", path.display()))?;
+        let mut file = tokio::fs::File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        // Writes are completed in the background until flushed.
+        file.write_all(&json)
+            .await
+            .and(file.flush().await)
+            .with_context(|| format!("Failed to write json to {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn upload_to_endpoint(&self, endpoint: &Option<Endpoint>) -> anyhow::Result<()> {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        rt.block_on(async { self.async_upload_to_endpoint(endpoint).await })
+    }
+
+    pub async fn async_upload_to_endpoint(
+        &self,
+        endpoint: &Option<Endpoint>,
+    ) -> anyhow::Result<()> {
+        // If we're debugging to a file, dump the actual crashinfo into a json
+        if let Some(endpoint) = endpoint {
+            if Some("file") == endpoint.url.scheme_str() {
+                let path = ddcommon::decode_uri_path_in_authority(&endpoint.url)
+                    .context("crash output file was not correctly formatted")?;
+                self.async_to_file(&path).await?;
+                // Minidumps are only configurable with file endpoints.
+                if let Some(minidump) = &self.minidump {
+                    let minidump_path = path.with_extension("dmp");
+                    tokio::fs::write(&minidump_path, minidump)
+                        .await
+                        .with_context(|| {
+                            format!("Failed to write minidump to {}", minidump_path.display())
+                        })?;
+                }
+            }
+        }
+
+        let uploader = TelemetryCrashUploader::new(&self.metadata, endpoint)?;
+        uploader.upload_to_telemetry(self).await
+    }
+}
+
+#[cfg(unix)]
+impl CrashInfo {
+    /// The build ids of the modules of the frames which have no file and line,
+    /// e.g. because the binary was stripped.  Requires normalized ips.
+    pub fn unsymbolized_build_ids(&self) -> std::collections::BTreeSet<String> {
+        let thread_frames = self.error.threads.iter().flat_map(|t| t.stack.frames());
+        self.error
+            .stack
+            .frames()
+            .iter()
+            .chain(thread_frames)
+            .filter(|frame| frame.file.is_none())
+            .filter_map(|frame| frame.build_id.clone())
+            .collect()
+    }
+
+    /// Resolves the frames of the modules in `debug_files`, indexed by build
+    /// id, from those files, including the functions inlined in them.  Frames
+    /// which can't be resolved are left as they are.  The fingerprint is
+    /// computed again from the resolved frames.
+    pub fn resolve_names_from_debug_files(
+        &mut self,
+        debug_files: &HashMap<String, std::path::PathBuf>,
+    ) {
+        let symbolizer = blazesym::symbolize::Symbolizer::new();
+        let sources: HashMap<&str, blazesym::symbolize::Source> = debug_files
+            .iter()
+            .map(|(build_id, path)| {
+                let elf = blazesym::symbolize::Elf::new(path);
+                (build_id.as_str(), blazesym::symbolize::Source::Elf(elf))
+            })
+            .collect();
+        let expand = |frame: &StackFrame| {
+            let src = sources.get(frame.build_id.as_deref()?)?;
+            frame
+                .symbolize_from_debug_file(src, &symbolizer)
+                .map_err(|err| eprintln!("Error resolving name {err}"))
+                .ok()
+        };
+        let stacks = std::iter::once(&mut self.error.stack)
+            .chain(self.error.threads.iter_mut().map(|t| &mut t.stack));
+        for stack in stacks {
+            stack.expand_frames(expand);
+        }
+        self.fingerprint = Some(self.compute_fingerprint());
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    #[ignore]
+    const SCHEMA_PATH: &str = "src/rfc5_crash_info/crash_info.schema.json";
+
     #[test]
-    /// Utility function to print the schema.
-    fn print_schema() {
+    #[cfg_attr(miri, ignore)]
+    /// The schema is checked in for the consumers of the reports.  Run with
+    /// `UPDATE_CRASH_INFO_SCHEMA=1` to update it after changing the model.
+    fn test_schema_is_up_to_date() {
         let schema = schemars::schema_for!(CrashInfo);
-        println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+        let schema = serde_json::to_string_pretty(&schema).unwrap() + "\n";
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(SCHEMA_PATH);
+        if std::env::var_os("UPDATE_CRASH_INFO_SCHEMA").is_some() {
+            std::fs::write(&path, &schema).unwrap();
+        }
+        let checked_in = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            checked_in == schema,
+            "{SCHEMA_PATH} is out of date, run with UPDATE_CRASH_INFO_SCHEMA=1 to update it"
+        );
     }
 
     impl test_utils::TestInstance for CrashInfo {
@@ -173,13 +294,15 @@ mod tests {
 
             Self {
                 counters,
-                data_schema_version: "1.0".to_string(),
+                data_schema_version: DATA_SCHEMA_VERSION.to_string(),
                 error: ErrorData::test_instance(seed),
+                fault_context: Some(FaultContext::test_instance(seed)),
                 files: HashMap::new(),
                 fingerprint: None,
                 incomplete: true,
                 log_messages: vec![],
                 metadata: Metadata::test_instance(seed),
+                minidump: None,
                 os_info: ::os_info::Info::unknown().into(),
                 proc_info: ProcInfo::test_instance(seed),
                 sig_info: Some(SigInfo::test_instance(seed)),
@@ -5,18 +5,18 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct ProcInfo {
-    pid: u32,
-}
-
-impl From<crate::crash_info::ProcessInfo> for ProcInfo {
-    fn from(value: crate::crash_info::ProcessInfo) -> Self {
-        Self { pid: value.pid }
-    }
+    pub pid: u32,
+    /// The thread which crashed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tid: Option<u32>,
 }
 
 #[cfg(test)]
 impl super::test_utils::TestInstance for ProcInfo {
     fn test_instance(seed: u64) -> Self {
-        Self { pid: seed as u32 }
+        Self {
+            pid: seed as u32,
+            tid: None,
+        }
     }
 }
@@ -16,12 +16,13 @@ pub struct SigInfo {
 impl From<crate::SigInfo> for SigInfo {
     fn from(value: crate::SigInfo) -> Self {
         let si_addr = value.faulting_address.map(|addr| format!("{addr:#018x}"));
-        // TODO, use the actual value when https://github.com/DataDog/libdatadog/pull/726 lands
-        let si_code = -1;
-        // TODO, use the actual value when https://github.com/DataDog/libdatadog/pull/726 lands
-        let si_code_human_readable = SiCodes::SI_USER;
         let si_signo: libc::c_int = value.signum.try_into().unwrap(); // libc uses c_int, so this should fit.
         let si_signo_human_readable = si_signo.into();
+        // Reports from older collectors don't include the si_code.
+        let (si_code, si_code_human_readable) = match value.si_code {
+            Some(si_code) => (si_code, SiCodes::from_signo_and_code(si_signo, si_code)),
+            None => (-1, SiCodes::UNKNOWN),
+        };
         Self {
             si_addr,
             si_code,
@@ -38,8 +39,12 @@ impl From<crate::SigInfo> for SigInfo {
 pub enum SignalNames {
     SIGABRT,
     SIGBUS,
+    SIGFPE,
+    SIGILL,
     SIGSEGV,
     SIGSYS,
+    SIGTRAP,
+    UNKNOWN,
 }
 
 #[cfg(unix)]
@@ -48,9 +53,12 @@ impl From<libc::c_int> for SignalNames {
         match value {
             libc::SIGABRT => SignalNames::SIGABRT,
             libc::SIGBUS => SignalNames::SIGBUS,
+            libc::SIGFPE => SignalNames::SIGFPE,
+            libc::SIGILL => SignalNames::SIGILL,
             libc::SIGSEGV => SignalNames::SIGSEGV,
             libc::SIGSYS => SignalNames::SIGSYS,
-            _ => panic!("Unexpected signal number: {value}"),
+            libc::SIGTRAP => SignalNames::SIGTRAP,
+            _ => SignalNames::UNKNOWN,
         }
     }
 }
@@ -58,7 +66,7 @@ impl From<libc::c_int> for SignalNames {
 #[cfg(not(unix))]
 impl From<libc::c_int> for SignalNames {
     fn from(_value: libc::c_int) -> Self {
-        unreachable!("Non-unix systems should not have Signals")
+        SignalNames::UNKNOWN
     }
 }
 
@@ -75,6 +83,22 @@ pub enum SiCodes {
     SEGV_BNDERR,
     SEGV_MAPERR,
     SEGV_PKUERR,
+    FPE_FLTDIV,
+    FPE_FLTINV,
+    FPE_FLTOVF,
+    FPE_FLTRES,
+    FPE_FLTSUB,
+    FPE_FLTUND,
+    FPE_INTDIV,
+    FPE_INTOVF,
+    ILL_BADSTK,
+    ILL_COPROC,
+    ILL_ILLADR,
+    ILL_ILLOPC,
+    ILL_ILLOPN,
+    ILL_ILLTRP,
+    ILL_PRVOPC,
+    ILL_PRVREG,
     SI_ASYNCIO,
     SI_KERNEL,
     SI_MESGQ,
@@ -84,6 +108,90 @@ pub enum SiCodes {
     SI_TKILL,
     SI_USER,
     SYS_SECCOMP,
+    TRAP_BRANCH,
+    TRAP_BRKPT,
+    TRAP_HWBKPT,
+    TRAP_TRACE,
+    TRAP_UNK,
+    UNKNOWN,
+}
+
+/// The values of the `si_code` constants on Linux, which `libc` only exposes
+/// in part. The signal-specific codes overlap, so they must be interpreted
+/// together with the signal number.
+mod si_code_values {
+    pub const SI_USER: i32 = 0;
+    pub const SI_KERNEL: i32 = 0x80;
+    pub const SI_QUEUE: i32 = -1;
+    pub const SI_TIMER: i32 = -2;
+    pub const SI_MESGQ: i32 = -3;
+    pub const SI_ASYNCIO: i32 = -4;
+    pub const SI_SIGIO: i32 = -5;
+    pub const SI_TKILL: i32 = -6;
+}
+
+impl SiCodes {
+    /// Decodes `si_code` as reported alongside the signal `si_signo`.
+    pub fn from_signo_and_code(si_signo: libc::c_int, si_code: libc::c_int) -> Self {
+        use si_code_values::*;
+        // Codes which aren't positive, or are SI_KERNEL, have the same
+        // meaning for every signal.
+        match si_code {
+            SI_USER => return SiCodes::SI_USER,
+            SI_KERNEL => return SiCodes::SI_KERNEL,
+            SI_QUEUE => return SiCodes::SI_QUEUE,
+            SI_TIMER => return SiCodes::SI_TIMER,
+            SI_MESGQ => return SiCodes::SI_MESGQ,
+            SI_ASYNCIO => return SiCodes::SI_ASYNCIO,
+            SI_SIGIO => return SiCodes::SI_SIGIO,
+            SI_TKILL => return SiCodes::SI_TKILL,
+            _ => (),
+        }
+        Self::from_signal_specific_code(si_signo, si_code)
+    }
+
+    #[cfg(unix)]
+    fn from_signal_specific_code(si_signo: libc::c_int, si_code: libc::c_int) -> Self {
+        match (si_signo, si_code) {
+            (libc::SIGBUS, 1) => SiCodes::BUS_ADRALN,
+            (libc::SIGBUS, 2) => SiCodes::BUS_ADRERR,
+            (libc::SIGBUS, 3) => SiCodes::BUS_OBJERR,
+            (libc::SIGBUS, 4) => SiCodes::BUS_MCEERR_AR,
+            (libc::SIGBUS, 5) => SiCodes::BUS_MCEERR_AO,
+            (libc::SIGFPE, 1) => SiCodes::FPE_INTDIV,
+            (libc::SIGFPE, 2) => SiCodes::FPE_INTOVF,
+            (libc::SIGFPE, 3) => SiCodes::FPE_FLTDIV,
+            (libc::SIGFPE, 4) => SiCodes::FPE_FLTOVF,
+            (libc::SIGFPE, 5) => SiCodes::FPE_FLTUND,
+            (libc::SIGFPE, 6) => SiCodes::FPE_FLTRES,
+            (libc::SIGFPE, 7) => SiCodes::FPE_FLTINV,
+            (libc::SIGFPE, 8) => SiCodes::FPE_FLTSUB,
+            (libc::SIGILL, 1) => SiCodes::ILL_ILLOPC,
+            (libc::SIGILL, 2) => SiCodes::ILL_ILLOPN,
+            (libc::SIGILL, 3) => SiCodes::ILL_ILLADR,
+            (libc::SIGILL, 4) => SiCodes::ILL_ILLTRP,
+            (libc::SIGILL, 5) => SiCodes::ILL_PRVOPC,
+            (libc::SIGILL, 6) => SiCodes::ILL_PRVREG,
+            (libc::SIGILL, 7) => SiCodes::ILL_COPROC,
+            (libc::SIGILL, 8) => SiCodes::ILL_BADSTK,
+            (libc::SIGSEGV, 1) => SiCodes::SEGV_MAPERR,
+            (libc::SIGSEGV, 2) => SiCodes::SEGV_ACCERR,
+            (libc::SIGSEGV, 3) => SiCodes::SEGV_BNDERR,
+            (libc::SIGSEGV, 4) => SiCodes::SEGV_PKUERR,
+            (libc::SIGSYS, 1) => SiCodes::SYS_SECCOMP,
+            (libc::SIGTRAP, 1) => SiCodes::TRAP_BRKPT,
+            (libc::SIGTRAP, 2) => SiCodes::TRAP_TRACE,
+            (libc::SIGTRAP, 3) => SiCodes::TRAP_BRANCH,
+            (libc::SIGTRAP, 4) => SiCodes::TRAP_HWBKPT,
+            (libc::SIGTRAP, 5) => SiCodes::TRAP_UNK,
+            _ => SiCodes::UNKNOWN,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from_signal_specific_code(_si_signo: libc::c_int, _si_code: libc::c_int) -> Self {
+        SiCodes::UNKNOWN
+    }
 }
 
 #[cfg(test)]
@@ -98,3 +206,58 @@ impl SigInfo {
         }
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_si_code_decoding() {
+        assert_eq!(
+            SiCodes::from_signo_and_code(libc::SIGSEGV, 1),
+            SiCodes::SEGV_MAPERR
+        );
+        assert_eq!(
+            SiCodes::from_signo_and_code(libc::SIGILL, 1),
+            SiCodes::ILL_ILLOPC
+        );
+        assert_eq!(
+            SiCodes::from_signo_and_code(libc::SIGFPE, 1),
+            SiCodes::FPE_INTDIV
+        );
+        assert_eq!(
+            SiCodes::from_signo_and_code(libc::SIGTRAP, 1),
+            SiCodes::TRAP_BRKPT
+        );
+        assert_eq!(
+            SiCodes::from_signo_and_code(libc::SIGABRT, -6),
+            SiCodes::SI_TKILL
+        );
+        assert_eq!(
+            SiCodes::from_signo_and_code(libc::SIGABRT, 1),
+            SiCodes::UNKNOWN
+        );
+    }
+
+    #[test]
+    fn test_from_legacy_siginfo() {
+        let legacy = crate::SigInfo {
+            signum: libc::SIGFPE as u64,
+            signame: Some("SIGFPE".to_string()),
+            si_code: Some(1),
+            faulting_address: Some(0x1234),
+        };
+        let siginfo = SigInfo::from(legacy.clone());
+        assert_eq!(siginfo.si_code, 1);
+        assert_eq!(siginfo.si_code_human_readable, SiCodes::FPE_INTDIV);
+        assert_eq!(siginfo.si_signo_human_readable, SignalNames::SIGFPE);
+        assert_eq!(siginfo.si_addr.as_deref(), Some("0x0000000000001234"));
+
+        let siginfo = SigInfo::from(crate::SigInfo {
+            si_code: None,
+            ..legacy
+        });
+        assert_eq!(siginfo.si_code, -1);
+        assert_eq!(siginfo.si_code_human_readable, SiCodes::UNKNOWN);
+    }
+}
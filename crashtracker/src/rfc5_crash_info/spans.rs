@@ -9,3 +9,12 @@ pub struct Span {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub thread_name: Option<String>,
 }
+
+impl Span {
+    pub fn from_id(id: u128) -> Self {
+        Self {
+            id: id.to_string(),
+            thread_name: None,
+        }
+    }
+}
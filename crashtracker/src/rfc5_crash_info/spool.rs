@@ -0,0 +1,442 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+//! A crash report is written to the spool directory before it is uploaded, and
+//! deleted once the upload succeeds.  If the upload fails, e.g. because the
+//! agent is already gone while the node shuts down, the report is left in the
+//! spool directory for [upload_pending_reports] to retry on the next start.
+//!
+//! Each report lives in `<uuid>.json`, only readable by its owner.  A process
+//! uploading it first claims it by renaming it to `<uuid>.json.<pid>.uploading`,
+//! so that processes starting at the same time don't upload the same report
+//! twice.
+//!
+//! The API key of the endpoint is not written to disk: it is given again to
+//! [upload_pending_reports].  The upload rate limit is, so that retries count
+//! towards it like the first attempt.
+
+use super::CrashInfo;
+use crate::shared::rate_limit::{UploadRateLimit, SUPPRESSED_DUPLICATES_COUNTER};
+use anyhow::Context;
+use ddcommon::Endpoint;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Reports older than this are deleted rather than uploaded, unless another
+/// limit is given.
+pub const DEFAULT_MAX_PENDING_REPORT_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// A claim older than this is assumed to belong to a process which died
+/// while uploading, and the report is retried.
+const STALE_CLAIM_AGE: Duration = Duration::from_secs(10 * 60);
+
+const REPORT_EXTENSION: &str = "json";
+const CLAIM_SUFFIX: &str = ".uploading";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SpooledReport {
+    crash_info: CrashInfo,
+    /// Where the report was going when it was spooled, without its API key.
+    endpoint: Option<Endpoint>,
+    /// Whether the endpoint had an API key, to be restored when uploading.
+    #[serde(default)]
+    needs_api_key: bool,
+    #[serde(default)]
+    upload_rate_limit: Option<UploadRateLimit>,
+}
+
+/// What [upload_pending_reports] did with the reports it found.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PendingReportsSummary {
+    pub uploaded: usize,
+    /// Reports whose upload failed again.  They are kept for the next attempt.
+    pub failed: usize,
+    /// Reports older than the maximum age, deleted without being uploaded.
+    pub expired: usize,
+    /// Copies of a report which was already seen, deleted.
+    pub duplicates: usize,
+    /// Files which couldn't be read as reports, deleted.
+    pub invalid: usize,
+    /// Reports beyond the upload rate limit for their fingerprint, deleted.
+    pub rate_limited: usize,
+}
+
+impl CrashInfo {
+    /// Writes the report to `spool_dir`, and returns the path of the file,
+    /// which should be deleted once the report was uploaded.  `upload_rate_limit`
+    /// is the limit the report is subject to, also when retried.
+    pub fn spool(
+        &self,
+        spool_dir: &Path,
+        endpoint: &Option<Endpoint>,
+        upload_rate_limit: &Option<UploadRateLimit>,
+    ) -> anyhow::Result<PathBuf> {
+        std::fs::create_dir_all(spool_dir)
+            .with_context(|| format!("Failed to create {}", spool_dir.display()))?;
+        let path = spool_dir.join(format!("{}.{REPORT_EXTENSION}", self.uuid));
+        // Write to a temporary file first, so a partial report is never picked up.
+        let tmp_path = spool_dir.join(format!(".{}.tmp", self.uuid));
+        let report = SpooledReport {
+            crash_info: self.clone(),
+            endpoint: endpoint.clone().map(|endpoint| Endpoint {
+                api_key: None,
+                ..endpoint
+            }),
+            needs_api_key: endpoint.as_ref().is_some_and(|e| e.api_key.is_some()),
+            upload_rate_limit: upload_rate_limit.clone(),
+        };
+        let bytes = serde_json::to_vec(&report)?;
+        let mut options = std::fs::File::options();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(&tmp_path)
+            .and_then(|mut file| file.write_all(&bytes))
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to rename {}", tmp_path.display()))?;
+        Ok(path)
+    }
+}
+
+/// Uploads the reports left in `spool_dir` by crashes whose reports couldn't
+/// be uploaded at the time, each to the endpoint it was meant for.  Reports
+/// are deleted once uploaded, or when older than `max_age`.  Failed uploads
+/// are kept for the next call.  `api_key` is used for the endpoints which had
+/// one, since it isn't spooled.  Reports beyond the upload rate limit they were
+/// spooled with are deleted.
+pub fn upload_pending_reports(
+    spool_dir: &Path,
+    max_age: Duration,
+    api_key: Option<&str>,
+) -> anyhow::Result<PendingReportsSummary> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    rt.block_on(async_upload_pending_reports(spool_dir, max_age, api_key))
+}
+
+/// The async version of [upload_pending_reports].
+pub async fn async_upload_pending_reports(
+    spool_dir: &Path,
+    max_age: Duration,
+    api_key: Option<&str>,
+) -> anyhow::Result<PendingReportsSummary> {
+    let mut summary = PendingReportsSummary::default();
+    let entries = match std::fs::read_dir(spool_dir) {
+        Ok(entries) => entries,
+        // Nothing ever crashed.
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(summary),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", spool_dir.display()))
+        }
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .collect();
+    paths.sort();
+
+    let mut seen = std::collections::HashSet::new();
+    for path in paths {
+        let Some(uuid) = report_uuid(&path) else {
+            continue;
+        };
+        let Some(claimed) = claim(&path) else {
+            continue;
+        };
+        if !seen.insert(uuid.to_string()) {
+            summary.duplicates += 1;
+            let _ = std::fs::remove_file(&claimed);
+            continue;
+        }
+
+        let report = std::fs::read(&claimed)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<SpooledReport>(&bytes).ok());
+        let Some(mut report) = report else {
+            summary.invalid += 1;
+            let _ = std::fs::remove_file(&claimed);
+            continue;
+        };
+        let crashed_at = report
+            .crash_info
+            .timestamp
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .ok()
+            .map(SystemTime::from)
+            .or_else(|| std::fs::metadata(&claimed).ok()?.modified().ok());
+        let age = crashed_at.and_then(|t| SystemTime::now().duration_since(t).ok());
+        if age.is_some_and(|age| age > max_age) {
+            summary.expired += 1;
+            let _ = std::fs::remove_file(&claimed);
+            continue;
+        }
+
+        // The number of suppressed reports this one tells, once admitted by the rate limit.
+        let mut admitted = None;
+        if let (Some(rate_limit), Some(fingerprint)) =
+            (&report.upload_rate_limit, &report.crash_info.fingerprint)
+        {
+            match rate_limit.admit(fingerprint, SystemTime::now()) {
+                Ok(Some(suppressed)) => {
+                    if suppressed > 0 {
+                        report
+                            .crash_info
+                            .counters
+                            .insert(SUPPRESSED_DUPLICATES_COUNTER.to_string(), suppressed as i64);
+                    }
+                    admitted = Some(suppressed);
+                }
+                Ok(None) => {
+                    summary.rate_limited += 1;
+                    let _ = std::fs::remove_file(&claimed);
+                    continue;
+                }
+                // Better a duplicate than a lost report.
+                Err(_) => (),
+            }
+        }
+
+        if report.needs_api_key {
+            if let Some(endpoint) = &mut report.endpoint {
+                endpoint.api_key = api_key.map(|key| key.to_string().into());
+            }
+        }
+        match report
+            .crash_info
+            .async_upload_to_endpoint(&report.endpoint)
+            .await
+        {
+            Ok(()) => {
+                summary.uploaded += 1;
+                if let (Some(rate_limit), Some(fingerprint), Some(suppressed)) = (
+                    &report.upload_rate_limit,
+                    &report.crash_info.fingerprint,
+                    admitted,
+                ) {
+                    let _ = rate_limit.record_upload(fingerprint, SystemTime::now(), suppressed);
+                }
+                let _ = std::fs::remove_file(&claimed);
+            }
+            Err(_) => {
+                summary.failed += 1;
+                // Release the claim, so the next attempt picks it up straight away.
+                let _ = std::fs::rename(
+                    &claimed,
+                    spool_dir.join(format!("{uuid}.{REPORT_EXTENSION}")),
+                );
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// The uuid of a report from its file name, whether it's claimed or not.
+fn report_uuid(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    let (uuid, rest) = name.split_once('.')?;
+    let is_report =
+        rest == REPORT_EXTENSION || rest.starts_with("json.") && rest.ends_with(CLAIM_SUFFIX);
+    (is_report && !uuid.is_empty()).then_some(uuid)
+}
+
+/// Renames the report at `path` to a name claimed by this process, and returns
+/// the new path, or None if another process claimed it first.  Claims by other
+/// processes are only taken over once stale.
+fn claim(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let uuid = report_uuid(path)?;
+    if name.ends_with(CLAIM_SUFFIX) {
+        let claimed_at = std::fs::metadata(path).ok()?.modified().ok()?;
+        if claimed_at.elapsed().ok()? < STALE_CLAIM_AGE {
+            return None;
+        }
+    }
+    let claimed = path.with_file_name(format!(
+        "{uuid}.{REPORT_EXTENSION}.{}{CLAIM_SUFFIX}",
+        std::process::id()
+    ));
+    std::fs::rename(path, &claimed).ok()?;
+    // Renaming keeps the modification time, so refresh it to date the claim.
+    let _ = std::fs::File::options()
+        .write(true)
+        .open(&claimed)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    Some(claimed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rfc5_crash_info::{
+        CrashInfoBuilder, Metadata, ProcInfo, SiCodes, SigInfo, SignalNames,
+    };
+    use chrono::{DateTime, Utc};
+
+    fn crash_info(timestamp: DateTime<Utc>) -> CrashInfo {
+        let mut builder = CrashInfoBuilder::new();
+        builder
+            .set_sig_info(SigInfo {
+                si_addr: None,
+                si_code: 1,
+                si_code_human_readable: SiCodes::SEGV_MAPERR,
+                si_signo: 11,
+                si_signo_human_readable: SignalNames::SIGSEGV,
+            })
+            .unwrap();
+        builder.set_timestamp(timestamp).unwrap();
+        builder
+            .set_metadata(Metadata {
+                library_name: "libdatadog".to_string(),
+                library_version: "1.0.0".to_string(),
+                family: "native".to_string(),
+                tags: vec![],
+            })
+            .unwrap();
+        builder
+            .set_proc_info(ProcInfo { pid: 1, tid: None })
+            .unwrap();
+        builder.build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upload_pending_reports() {
+        let spool_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        let output = output_dir.path().join("crash.json");
+        let endpoint = Some(Endpoint::from_slice(&format!(
+            "file://{}",
+            output.to_str().unwrap()
+        )));
+
+        let pending = crash_info(Utc::now());
+        pending.spool(spool_dir.path(), &endpoint, &None).unwrap();
+        let expired = crash_info(Utc::now() - chrono::Duration::days(30));
+        expired.spool(spool_dir.path(), &endpoint, &None).unwrap();
+        // A copy of the pending report, left behind by a process which died while uploading it.
+        let copy = spool_dir
+            .path()
+            .join(format!("{}.json.1.uploading", pending.uuid));
+        std::fs::copy(
+            spool_dir.path().join(format!("{}.json", pending.uuid)),
+            &copy,
+        )
+        .unwrap();
+        let old = SystemTime::now() - 2 * STALE_CLAIM_AGE;
+        std::fs::File::options()
+            .write(true)
+            .open(&copy)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        std::fs::write(spool_dir.path().join("garbage.json"), "{").unwrap();
+        std::fs::write(spool_dir.path().join("unrelated.txt"), "").unwrap();
+
+        let summary =
+            async_upload_pending_reports(spool_dir.path(), Duration::from_secs(3600), None)
+                .await
+                .unwrap();
+        assert_eq!(
+            summary,
+            PendingReportsSummary {
+                uploaded: 1,
+                failed: 0,
+                expired: 1,
+                duplicates: 1,
+                invalid: 1,
+                rate_limited: 0,
+            }
+        );
+        let uploaded: CrashInfo =
+            serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+        assert_eq!(uploaded.uuid, pending.uuid);
+        let left: Vec<_> = std::fs::read_dir(spool_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(left, ["unrelated.txt"]);
+
+        // Nothing left to do the second time around.
+        let summary =
+            async_upload_pending_reports(spool_dir.path(), Duration::from_secs(3600), None)
+                .await
+                .unwrap();
+        assert_eq!(summary, PendingReportsSummary::default());
+    }
+
+    #[tokio::test]
+    async fn test_pending_reports_are_rate_limited() {
+        let spool_dir = tempfile::tempdir().unwrap();
+        let state_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        let endpoint = Some(Endpoint::from_slice(&format!(
+            "file://{}",
+            output_dir.path().join("crash.json").to_str().unwrap()
+        )));
+        let rate_limit = Some(
+            UploadRateLimit::new(
+                1,
+                Duration::from_secs(3600),
+                state_dir.path().to_str().unwrap().to_string(),
+            )
+            .unwrap(),
+        );
+
+        for _ in 0..2 {
+            let mut crash_info = crash_info(Utc::now());
+            crash_info.fingerprint = Some("abc".to_string());
+            crash_info
+                .spool(spool_dir.path(), &endpoint, &rate_limit)
+                .unwrap();
+        }
+        let summary =
+            async_upload_pending_reports(spool_dir.path(), Duration::from_secs(3600), None)
+                .await
+                .unwrap();
+        assert_eq!(
+            summary,
+            PendingReportsSummary {
+                uploaded: 1,
+                rate_limited: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(std::fs::read_dir(spool_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_api_key_is_not_spooled() {
+        let spool_dir = tempfile::tempdir().unwrap();
+        let mut endpoint = Endpoint::from_slice("https://intake.example.com");
+        endpoint.api_key = Some("secret-key".into());
+        let path = crash_info(Utc::now())
+            .spool(spool_dir.path(), &Some(endpoint), &None)
+            .unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("secret-key"));
+        let report: SpooledReport = serde_json::from_str(&contents).unwrap();
+        assert!(report.needs_api_key);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_recent_claims_are_left_alone() {
+        let spool_dir = tempfile::tempdir().unwrap();
+        let claimed = spool_dir.path().join("abc.json.1.uploading");
+        std::fs::write(&claimed, "{").unwrap();
+        assert_eq!(claim(&claimed), None);
+        assert!(claimed.exists());
+
+        let summary =
+            upload_pending_reports(&spool_dir.path().join("missing"), Duration::MAX, None);
+        assert_eq!(summary.unwrap(), PendingReportsSummary::default());
+    }
+}
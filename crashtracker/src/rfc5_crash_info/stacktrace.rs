@@ -12,8 +12,63 @@ pub struct StackTrace {
     frames: Vec<StackFrame>,
 }
 
+const FORMAT: &str = "Datadog Crashtracker 1.0";
+
+impl StackTrace {
+    pub fn from_frames(frames: Vec<StackFrame>) -> Self {
+        Self {
+            format: FORMAT.to_string(),
+            frames,
+        }
+    }
+
+    pub fn frames(&self) -> &[StackFrame] {
+        &self.frames
+    }
+
+    pub(crate) fn frames_mut(&mut self) -> &mut [StackFrame] {
+        &mut self.frames
+    }
+
+    /// Replaces each frame for which `f` returns some frames with those, e.g.
+    /// one frame per inlined function.
+    pub(crate) fn expand_frames(
+        &mut self,
+        mut f: impl FnMut(&StackFrame) -> Option<Vec<StackFrame>>,
+    ) {
+        let mut frames = Vec::with_capacity(self.frames.len());
+        for frame in std::mem::take(&mut self.frames) {
+            match f(&frame) {
+                Some(expanded) => frames.extend(expanded),
+                None => frames.push(frame),
+            }
+        }
+        self.frames = frames;
+    }
+}
+
+impl Default for StackTrace {
+    fn default() -> Self {
+        Self::from_frames(vec![])
+    }
+}
+
 impl From<Vec<crate::StackFrame>> for StackTrace {
     fn from(value: Vec<crate::StackFrame>) -> Self {
+        // Todo: this will under-estimate the cap needed if there are inlined functions.
+        // Maybe not worth fixing this.
+        let mut frames = Vec::with_capacity(value.len());
+        for frame in value {
+            frames.extend(StackFrame::from_legacy(frame));
+        }
+        Self::from_frames(frames)
+    }
+}
+
+impl StackFrame {
+    /// Converts a frame in the format of version 1 of the collector protocol,
+    /// which gives one frame per inlined function.
+    pub(crate) fn from_legacy(frame: crate::StackFrame) -> Vec<StackFrame> {
         #[allow(clippy::type_complexity)]
         fn convert_normalized_address(
             value: Option<NormalizedAddress>,
@@ -21,8 +76,8 @@ impl From<Vec<crate::StackFrame>> for StackTrace {
             Option<String>,      // build_id
             Option<BuildIdType>, // build_id_type
             Option<FileType>,    // file_type
-            Option<String>,      // path
             Option<String>,      // relative_address
+            Option<String>,      // path
         ) {
             if let Some(normalized_address) = value {
                 let relative_address = Some(format!("{:#018x}", normalized_address.file_offset));
@@ -63,50 +118,38 @@ impl From<Vec<crate::StackFrame>> for StackTrace {
             }
         }
 
-        let format = String::from("Datadog Crashtracker 1.0");
-        // Todo: this will under-estimate the cap needed if there are inlined functions.
-        // Maybe not worth fixing this.
-        let mut frames = Vec::with_capacity(value.len());
-        for frame in value {
-            let ip = frame.ip;
-            let sp = frame.sp;
-            let symbol_address = frame.symbol_address;
-            let module_base_address = frame.module_base_address;
-
-            let (build_id, build_id_type, file_type, relative_address, path) =
-                convert_normalized_address(frame.normalized_ip);
-            let base_frame = StackFrame {
-                ip,
-                sp,
-                symbol_address,
-                module_base_address,
-                build_id,
-                build_id_type,
-                file_type,
-                relative_address,
-                path,
-                column: None,
-                file: None,
-                line: None,
-                function: None,
-            };
-            let names = frame.names.unwrap_or_default();
-            if names.is_empty() {
-                frames.push(base_frame);
-            } else {
-                for name in names {
-                    frames.push(StackFrame {
-                        column: name.colno,
-                        file: name.filename,
-                        function: name.name,
-                        line: name.lineno,
-                        ..base_frame.clone()
-                    })
-                }
-            }
+        let (build_id, build_id_type, file_type, relative_address, path) =
+            convert_normalized_address(frame.normalized_ip);
+        let base_frame = StackFrame {
+            ip: frame.ip,
+            sp: frame.sp,
+            symbol_address: frame.symbol_address,
+            module_base_address: frame.module_base_address,
+            build_id,
+            build_id_type,
+            file_type,
+            relative_address,
+            path,
+            column: None,
+            file: None,
+            line: None,
+            function: None,
+        };
+        let names = frame.names.unwrap_or_default();
+        if names.is_empty() {
+            vec![base_frame]
+        } else {
+            names
+                .into_iter()
+                .map(|name| StackFrame {
+                    column: name.colno,
+                    file: name.filename,
+                    function: name.name,
+                    line: name.lineno,
+                    ..base_frame.clone()
+                })
+                .collect()
         }
-
-        Self { format, frames }
     }
 }
 
@@ -177,14 +220,116 @@ fn byte_vec_as_hex(bv: Option<Vec<u8>>) -> Option<String> {
     }
 }
 
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use anyhow::anyhow;
+    use blazesym::{
+        helper::ElfResolver,
+        normalize::Normalizer,
+        symbolize::{CodeInfo, Input, Source, Symbolized, Symbolizer, TranslateFileOffset},
+        Pid,
+    };
+
+    fn parse_ip(ip: &str) -> anyhow::Result<u64> {
+        Ok(u64::from_str_radix(ip.trim_start_matches("0x"), 16)?)
+    }
+
+    impl StackFrame {
+        /// Fills in the module the `ip` belongs to, and the address relative
+        /// to it, so that the frame can be symbolized later on.
+        pub fn normalize_ip(&mut self, normalizer: &Normalizer, pid: Pid) -> anyhow::Result<()> {
+            let Some(ip) = &self.ip else {
+                return Ok(());
+            };
+            let ip = parse_ip(ip)?;
+            let normed = normalizer.normalize_user_addrs(pid, &[ip])?;
+            anyhow::ensure!(normed.outputs.len() == 1);
+            let (file_offset, meta_idx) = normed.outputs[0];
+            let meta = &normed.meta[meta_idx];
+            let elf = meta.as_elf().ok_or(anyhow!("Not elf"))?;
+            let resolver = ElfResolver::open(&elf.path)?;
+            let virt_address = resolver
+                .file_offset_to_virt_offset(file_offset)?
+                .ok_or(anyhow!("No matching segment found"))?;
+            self.build_id = byte_vec_as_hex(elf.build_id.as_ref().map(|id| id.to_vec()));
+            self.build_id_type = Some(BuildIdType::GNU);
+            self.file_type = Some(FileType::ELF);
+            self.path = Some(elf.path.to_string_lossy().to_string());
+            self.relative_address = Some(format!("{virt_address:#018x}"));
+            Ok(())
+        }
+
+        pub fn resolve_names(
+            &mut self,
+            src: &Source,
+            symbolizer: &Symbolizer,
+        ) -> anyhow::Result<()> {
+            let Some(ip) = &self.ip else {
+                return Ok(());
+            };
+            let ip = parse_ip(ip)?;
+            match symbolizer.symbolize_single(src, Input::AbsAddr(ip))? {
+                Symbolized::Sym(s) => {
+                    //TODO: handle inlined functions
+                    if let Some(code_info) = &s.code_info {
+                        self.column = code_info.column.map(u32::from);
+                        self.file = Some(code_info.to_path().display().to_string());
+                        self.line = code_info.line;
+                    }
+                    self.function = Some(s.name.into_owned());
+                }
+                Symbolized::Unknown(reason) => {
+                    anyhow::bail!("Couldn't symbolize {ip}: {reason}");
+                }
+            }
+            Ok(())
+        }
+
+        /// Resolves the frame from the debug file of its module, by its
+        /// `relative_address`.  Returns one frame per function inlined at the
+        /// address, innermost first, followed by the function they were
+        /// inlined into.
+        pub fn symbolize_from_debug_file(
+            &self,
+            src: &Source,
+            symbolizer: &Symbolizer,
+        ) -> anyhow::Result<Vec<StackFrame>> {
+            let relative_address = self
+                .relative_address
+                .as_deref()
+                .ok_or(anyhow!("No relative address"))?;
+            let offset = parse_ip(relative_address)?;
+            let sym = match symbolizer.symbolize_single(src, Input::VirtOffset(offset))? {
+                Symbolized::Sym(sym) => sym,
+                Symbolized::Unknown(reason) => {
+                    anyhow::bail!("Couldn't symbolize {relative_address}: {reason}");
+                }
+            };
+            let with_name = |name: &str, code_info: Option<&CodeInfo>| StackFrame {
+                column: code_info.and_then(|info| info.column.map(u32::from)),
+                file: code_info.map(|info| info.to_path().display().to_string()),
+                function: Some(name.to_string()),
+                line: code_info.and_then(|info| info.line),
+                ..self.clone()
+            };
+            let mut frames: Vec<StackFrame> = sym
+                .inlined
+                .iter()
+                .rev()
+                .map(|inlined| with_name(&inlined.name, inlined.code_info.as_ref()))
+                .collect();
+            frames.push(with_name(&sym.name, sym.code_info.as_ref()));
+            Ok(frames)
+        }
+    }
+}
+
 #[cfg(test)]
 impl super::test_utils::TestInstance for StackTrace {
     fn test_instance(_seed: u64) -> Self {
         let frames = (0..10).map(StackFrame::test_instance).collect();
-        Self {
-            format: "Datadog Crashtracker 1.0".to_string(),
-            frames,
-        }
+        Self::from_frames(frames)
     }
 }
 
@@ -0,0 +1,343 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+//! The stacks of the threads other than the crashing one, unwound by the
+//! receiver.
+
+#[cfg(target_os = "linux")]
+pub use linux::collect_thread_stacks;
+#[cfg(target_os = "linux")]
+pub(crate) use linux::StoppedThread;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use crate::rfc5_crash_info::fault_context::read_process_memory;
+    use crate::rfc5_crash_info::{StackFrame, StackTrace, ThreadData};
+    use std::time::{Duration, Instant};
+
+    /// Threads beyond this many are listed without a stack, to bound the time
+    /// the crashing process is held up.
+    const MAX_UNWOUND_THREADS: usize = 128;
+    const MAX_FRAMES: usize = 128;
+    /// How long to wait for a thread to stop after interrupting it.
+    const STOP_TIMEOUT: Duration = Duration::from_millis(50);
+
+    /// Lists the threads of `pid` and unwinds the stack of each, except
+    /// `crashing_tid`, whose stack the collector already sent.  The name of
+    /// each thread is its name from `/proc/<pid>/task/<tid>/comm`, followed
+    /// by its tid, since threads commonly share a name.
+    ///
+    /// Each thread is stopped in turn with `PTRACE_SEIZE` and `PTRACE_INTERRUPT`,
+    /// and its stack is unwound by walking the frame pointer chain, so frames
+    /// compiled without frame pointers are skipped.  A thread which can't be
+    /// stopped is still listed, without a stack.
+    ///
+    /// This blocks, until `deadline` at most: the threads left once it has
+    /// passed are listed without a stack.  The returned flag tells whether
+    /// that happened.
+    pub fn collect_thread_stacks(
+        pid: u32,
+        crashing_tid: Option<u32>,
+        deadline: Instant,
+    ) -> (Vec<ThreadData>, bool) {
+        let Ok(tasks) = std::fs::read_dir(format!("/proc/{pid}/task")) else {
+            return (vec![], false);
+        };
+        let mut tids: Vec<u32> = tasks
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .filter(|tid| Some(*tid) != crashing_tid)
+            .collect();
+        tids.sort_unstable();
+
+        let mut timed_out = false;
+        let threads = tids
+            .into_iter()
+            .enumerate()
+            .map(|(i, tid)| {
+                let task = format!("/proc/{pid}/task/{tid}");
+                let name = std::fs::read_to_string(format!("{task}/comm"))
+                    .map(|comm| comm.trim_end().to_string())
+                    .unwrap_or_default();
+                // The scheduler state, e.g. "S (sleeping)".
+                let state = std::fs::read_to_string(format!("{task}/status"))
+                    .ok()
+                    .and_then(|status| {
+                        status
+                            .lines()
+                            .find_map(|line| line.strip_prefix("State:"))
+                            .map(|state| state.trim().to_string())
+                    });
+                timed_out |= Instant::now() >= deadline;
+                let frames = if i < MAX_UNWOUND_THREADS && !timed_out {
+                    unwind_thread(pid, tid, deadline).unwrap_or_else(|err| {
+                        eprintln!("Unable to unwind thread {tid}: {err}");
+                        vec![]
+                    })
+                } else {
+                    vec![]
+                };
+                ThreadData {
+                    crashed: false,
+                    name: format!("{name} ({tid})"),
+                    stack: StackTrace::from_frames(frames),
+                    state,
+                }
+            })
+            .collect();
+        (threads, timed_out)
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    fn unwind_thread(pid: u32, tid: u32, deadline: Instant) -> anyhow::Result<Vec<StackFrame>> {
+        let thread = StoppedThread::stop(tid, Some(deadline))?;
+        let registers = Registers::from(&thread.registers()?);
+        Ok(walk_frame_pointers(pid, registers))
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    fn unwind_thread(_pid: u32, _tid: u32, _deadline: Instant) -> anyhow::Result<Vec<StackFrame>> {
+        anyhow::bail!("Unwinding other threads is not supported on this architecture")
+    }
+
+    /// A thread stopped with ptrace, which resumes when this is dropped.
+    pub(crate) struct StoppedThread {
+        tid: libc::pid_t,
+    }
+
+    impl StoppedThread {
+        /// Stops the thread, giving up after [STOP_TIMEOUT], or at `deadline` if it comes first.
+        pub(crate) fn stop(tid: u32, deadline: Option<Instant>) -> anyhow::Result<Self> {
+            let tid = tid as libc::pid_t;
+            // Safety: these ptrace requests don't take any pointers.
+            let res = unsafe { libc::ptrace(libc::PTRACE_SEIZE, tid, 0, 0) };
+            anyhow::ensure!(
+                res == 0,
+                "PTRACE_SEIZE: {}",
+                std::io::Error::last_os_error()
+            );
+            // From here on, dropping `thread` detaches from it.
+            let thread = Self { tid };
+            let res = unsafe { libc::ptrace(libc::PTRACE_INTERRUPT, tid, 0, 0) };
+            anyhow::ensure!(
+                res == 0,
+                "PTRACE_INTERRUPT: {}",
+                std::io::Error::last_os_error()
+            );
+            // A thread blocked in an uninterruptible sleep only stops once it wakes up, so don't
+            // wait for it forever.
+            let timeout = Instant::now() + STOP_TIMEOUT;
+            let deadline = deadline.map_or(timeout, |deadline| deadline.min(timeout));
+            loop {
+                let mut status = 0;
+                // Safety: `status` is a valid pointer for the duration of the call.
+                let res = unsafe { libc::waitpid(tid, &mut status, libc::__WALL | libc::WNOHANG) };
+                anyhow::ensure!(res >= 0, "waitpid: {}", std::io::Error::last_os_error());
+                if res == tid {
+                    anyhow::ensure!(libc::WIFSTOPPED(status), "Thread exited");
+                    return Ok(thread);
+                }
+                anyhow::ensure!(Instant::now() < deadline, "Timed out stopping thread");
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        /// The registers of the thread, as saved by the kernel when it stopped.
+        #[cfg(target_arch = "x86_64")]
+        pub(crate) fn registers(&self) -> anyhow::Result<libc::user_regs_struct> {
+            // Safety: an all zero `user_regs_struct` is valid, and the kernel writes at most its
+            // size.
+            let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+            let res = unsafe {
+                libc::ptrace(
+                    libc::PTRACE_GETREGS,
+                    self.tid,
+                    0,
+                    std::ptr::addr_of_mut!(regs),
+                )
+            };
+            anyhow::ensure!(
+                res == 0,
+                "PTRACE_GETREGS: {}",
+                std::io::Error::last_os_error()
+            );
+            Ok(regs)
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        pub(crate) fn registers(&self) -> anyhow::Result<libc::user_regs_struct> {
+            // Safety: an all zero `user_regs_struct` is valid, and the kernel writes at most the
+            // size given in the iovec.
+            let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+            let mut iov = libc::iovec {
+                iov_base: std::ptr::addr_of_mut!(regs).cast(),
+                iov_len: std::mem::size_of::<libc::user_regs_struct>(),
+            };
+            let res = unsafe {
+                libc::ptrace(
+                    libc::PTRACE_GETREGSET,
+                    self.tid,
+                    libc::NT_PRSTATUS,
+                    std::ptr::addr_of_mut!(iov),
+                )
+            };
+            anyhow::ensure!(
+                res == 0,
+                "PTRACE_GETREGSET: {}",
+                std::io::Error::last_os_error()
+            );
+            Ok(regs)
+        }
+    }
+
+    impl Drop for StoppedThread {
+        fn drop(&mut self) {
+            // Detaching resumes the thread.  It fails if the thread never stopped, in which case
+            // it's detached when we exit.
+            unsafe { libc::ptrace(libc::PTRACE_DETACH, self.tid, 0, 0) };
+        }
+    }
+
+    /// The instruction, stack and frame pointers.
+    pub(crate) struct Registers {
+        pub ip: u64,
+        pub sp: u64,
+        pub fp: u64,
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    impl From<&libc::user_regs_struct> for Registers {
+        fn from(regs: &libc::user_regs_struct) -> Self {
+            Self {
+                ip: regs.rip,
+                sp: regs.rsp,
+                fp: regs.rbp,
+            }
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    impl From<&libc::user_regs_struct> for Registers {
+        fn from(regs: &libc::user_regs_struct) -> Self {
+            Self {
+                ip: regs.pc,
+                sp: regs.sp,
+                fp: regs.regs[29],
+            }
+        }
+    }
+
+    fn read_word(pid: u32, address: u64) -> Option<u64> {
+        let bytes: Option<Vec<u8>> = read_process_memory(pid, address as usize, 8)
+            .into_iter()
+            .collect();
+        Some(u64::from_ne_bytes(bytes?.try_into().ok()?))
+    }
+
+    /// Follows the chain of saved frame pointers: on both x86_64 and aarch64,
+    /// a frame starts with the caller's frame pointer, followed by the return
+    /// address.
+    fn walk_frame_pointers(pid: u32, registers: Registers) -> Vec<StackFrame> {
+        let mut frames = vec![frame(registers.ip, registers.sp)];
+        let mut fp = registers.fp;
+        while frames.len() < MAX_FRAMES && fp != 0 && fp % 8 == 0 {
+            let (Some(next_fp), Some(return_address)) =
+                (read_word(pid, fp), read_word(pid, fp + 8))
+            else {
+                break;
+            };
+            if return_address == 0 {
+                break;
+            }
+            frames.push(frame(return_address, fp + 16));
+            // The stack grows down, so the caller's frame is at a higher address.  Anything else
+            // means the chain is broken, and following it could loop.
+            if next_fp <= fp {
+                break;
+            }
+            fp = next_fp;
+        }
+        frames
+    }
+
+    fn frame(ip: u64, sp: u64) -> StackFrame {
+        StackFrame {
+            ip: Some(format!("{ip:#x}")),
+            module_base_address: None,
+            sp: Some(format!("{sp:#x}")),
+            symbol_address: None,
+            build_id: None,
+            build_id_type: None,
+            file_type: None,
+            path: None,
+            relative_address: None,
+            column: None,
+            file: None,
+            function: None,
+            line: None,
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_walk_frame_pointers() {
+            // Two frames, laid out as a frame pointer chain in our own memory.
+            let mut stack = [0u64; 8];
+            let base = stack.as_ptr() as u64;
+            stack[0] = base + 32; // Saved frame pointer of the first frame
+            stack[1] = 0x2000; // Its return address
+            stack[5] = 0x3000; // The second frame's saved frame pointer is 0, ending the chain
+
+            // The stack is only read through `process_vm_readv`, which the compiler can't see.
+            std::hint::black_box(&mut stack);
+            let frames = walk_frame_pointers(
+                std::process::id(),
+                Registers {
+                    ip: 0x1000,
+                    sp: base,
+                    fp: base,
+                },
+            );
+            let ips: Vec<_> = frames.iter().map(|f| f.ip.as_deref().unwrap()).collect();
+            assert_eq!(ips, ["0x1000", "0x2000", "0x3000"]);
+        }
+
+        #[test]
+        fn test_collect_thread_stacks() {
+            let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+            let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+            let thread = std::thread::Builder::new()
+                .name("waiting".to_string())
+                .spawn(move || {
+                    ready_tx.send(()).unwrap();
+                    done_rx.recv().unwrap();
+                })
+                .unwrap();
+            ready_rx.recv().unwrap();
+
+            // Ptracing our own threads isn't allowed, so only the names and states are
+            // checked here.
+            let deadline = Instant::now() + Duration::from_secs(1);
+            let (threads, timed_out) = collect_thread_stacks(std::process::id(), None, deadline);
+            assert!(!timed_out);
+            let waiting = threads
+                .iter()
+                .find(|thread| thread.name.starts_with("waiting ("))
+                .unwrap();
+            assert!(waiting.state.is_some());
+
+            // Past the deadline, threads are still listed.
+            let (threads, timed_out) =
+                collect_thread_stacks(std::process::id(), None, Instant::now());
+            assert!(timed_out);
+            assert!(threads
+                .iter()
+                .any(|thread| thread.name.starts_with("waiting (")));
+
+            done_tx.send(()).unwrap();
+            thread.join().unwrap();
+        }
+    }
+}
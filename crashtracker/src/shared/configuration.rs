@@ -1,6 +1,8 @@
 // Copyright 2023-Present Datadog, Inc. https://www.datadoghq.com/
 // SPDX-License-Identifier: Apache-2.0
 use crate::shared::constants;
+use crate::shared::rate_limit::UploadRateLimit;
+use crate::shared::symbol_stores::SymbolStore;
 use ddcommon::Endpoint;
 use serde::{Deserialize, Serialize};
 
@@ -26,9 +28,31 @@ pub struct CrashtrackerConfiguration {
     pub create_alt_stack: bool,
     pub use_alt_stack: bool,
     pub endpoint: Option<Endpoint>,
+    /// Also write a Breakpad-compatible minidump of the crashing process.
+    /// Only supported on Linux x86_64 and aarch64.  The minidump is written
+    /// next to the report as `<path>.dmp`, so it requires a file:// endpoint:
+    /// it is not uploaded to other endpoints.
+    #[serde(default)]
+    pub minidump: bool,
     pub resolve_frames: StacktraceCollection,
+    /// The signals to catch, see [CrashtrackerConfiguration::SUPPORTED_SIGNALS].
+    #[serde(default = "CrashtrackerConfiguration::default_signals")]
+    pub signals: Vec<i32>,
+    /// If set, the report is written to this directory before being uploaded,
+    /// and only deleted once the upload succeeds.  Reports left behind are
+    /// retried by [crate::upload_pending_reports].
+    #[serde(default)]
+    pub spool_dir: Option<String>,
+    /// Where the receiver looks for the debug files of stripped binaries,
+    /// when it resolves the frames.
+    #[serde(default)]
+    pub symbol_stores: Vec<SymbolStore>,
     pub timeout_ms: u32,
     pub unix_socket_path: Option<String>,
+    /// If set, reports with the same fingerprint beyond this limit are
+    /// dropped rather than uploaded.
+    #[serde(default)]
+    pub upload_rate_limit: Option<UploadRateLimit>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -66,44 +90,250 @@ impl CrashtrackerReceiverConfig {
 }
 
 impl CrashtrackerConfiguration {
-    pub fn new(
-        additional_files: Vec<String>,
-        create_alt_stack: bool,
-        use_alt_stack: bool,
-        endpoint: Option<Endpoint>,
-        resolve_frames: StacktraceCollection,
-        timeout_ms: u32,
-        unix_socket_path: Option<String>,
-    ) -> anyhow::Result<Self> {
+    /// The signals which indicate a crash, and which the crashtracker can
+    /// therefore be registered for.
+    pub const SUPPORTED_SIGNALS: [i32; 7] = [
+        libc::SIGABRT,
+        libc::SIGBUS,
+        libc::SIGFPE,
+        libc::SIGILL,
+        libc::SIGSEGV,
+        libc::SIGSYS,
+        libc::SIGTRAP,
+    ];
+
+    /// The signals caught when none are specified. SIGFPE, SIGSYS and
+    /// SIGTRAP are opt-in: runtimes and debuggers commonly handle those
+    /// themselves.
+    pub fn default_signals() -> Vec<i32> {
+        vec![libc::SIGABRT, libc::SIGBUS, libc::SIGILL, libc::SIGSEGV]
+    }
+
+    pub fn builder() -> CrashtrackerConfigurationBuilder {
+        CrashtrackerConfigurationBuilder::default()
+    }
+}
+
+/// Builds a [CrashtrackerConfiguration], checking that the options go
+/// together.  Options which aren't set get their defaults.
+#[derive(Debug, Default)]
+pub struct CrashtrackerConfigurationBuilder {
+    additional_files: Vec<String>,
+    create_alt_stack: bool,
+    use_alt_stack: bool,
+    endpoint: Option<Endpoint>,
+    minidump: bool,
+    resolve_frames: Option<StacktraceCollection>,
+    signals: Vec<i32>,
+    spool_dir: Option<String>,
+    symbol_stores: Vec<SymbolStore>,
+    timeout_ms: u32,
+    unix_socket_path: Option<String>,
+    upload_rate_limit: Option<UploadRateLimit>,
+}
+
+impl CrashtrackerConfigurationBuilder {
+    /// Set the paths of additional files to send with the report
+    pub fn set_additional_files(mut self, additional_files: Vec<String>) -> Self {
+        self.additional_files = additional_files;
+        self
+    }
+
+    /// Set whether to create an alternate stack for the signal handler, which
+    /// requires using it
+    pub fn set_create_alt_stack(mut self, create_alt_stack: bool) -> Self {
+        self.create_alt_stack = create_alt_stack;
+        self
+    }
+
+    /// Set whether the signal handler runs on an alternate stack
+    pub fn set_use_alt_stack(mut self, use_alt_stack: bool) -> Self {
+        self.use_alt_stack = use_alt_stack;
+        self
+    }
+
+    /// Set where reports are sent
+    pub fn set_endpoint(mut self, endpoint: Option<Endpoint>) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    /// Set whether to also write a minidump, which requires a file:// endpoint
+    pub fn set_minidump(mut self, minidump: bool) -> Self {
+        self.minidump = minidump;
+        self
+    }
+
+    /// Set how the frames are resolved.  Defaults to
+    /// [StacktraceCollection::Disabled].
+    pub fn set_resolve_frames(mut self, resolve_frames: StacktraceCollection) -> Self {
+        self.resolve_frames = Some(resolve_frames);
+        self
+    }
+
+    /// Set the signals to catch, out of
+    /// [CrashtrackerConfiguration::SUPPORTED_SIGNALS].  If empty,
+    /// [CrashtrackerConfiguration::default_signals] are used.
+    pub fn set_signals(mut self, signals: Vec<i32>) -> Self {
+        self.signals = signals;
+        self
+    }
+
+    /// Set the directory where reports are kept until they are uploaded
+    pub fn set_spool_dir(mut self, spool_dir: Option<String>) -> Self {
+        self.spool_dir = spool_dir;
+        self
+    }
+
+    /// Set where the receiver looks for the debug files of stripped binaries
+    pub fn set_symbol_stores(mut self, symbol_stores: Vec<SymbolStore>) -> Self {
+        self.symbol_stores = symbol_stores;
+        self
+    }
+
+    /// Set the timeout of the collection and upload of a report.  If 0,
+    /// [constants::DD_CRASHTRACK_DEFAULT_TIMEOUT_MS] is used.
+    pub fn set_timeout_ms(mut self, timeout_ms: u32) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    /// Set the socket of an already running receiver
+    pub fn set_unix_socket_path(mut self, unix_socket_path: Option<String>) -> Self {
+        self.unix_socket_path = unix_socket_path;
+        self
+    }
+
+    /// Set the limit of uploads of reports with the same fingerprint
+    pub fn set_upload_rate_limit(mut self, upload_rate_limit: Option<UploadRateLimit>) -> Self {
+        self.upload_rate_limit = upload_rate_limit;
+        self
+    }
+
+    pub fn build(self) -> anyhow::Result<CrashtrackerConfiguration> {
         // Requesting to create, but not use, the altstack is considered paradoxical.
         anyhow::ensure!(
-            !create_alt_stack || use_alt_stack,
+            !self.create_alt_stack || self.use_alt_stack,
             "Cannot create an altstack without using it"
         );
-        let timeout_ms = if timeout_ms == 0 {
+        anyhow::ensure!(
+            !self.minidump
+                || self
+                    .endpoint
+                    .as_ref()
+                    .is_some_and(|endpoint| endpoint.url.scheme_str() == Some("file")),
+            "Minidumps are only written next to reports sent to a file:// endpoint"
+        );
+        let timeout_ms = if self.timeout_ms == 0 {
             constants::DD_CRASHTRACK_DEFAULT_TIMEOUT_MS
-        } else if timeout_ms > i32::MAX as u32 {
+        } else if self.timeout_ms > i32::MAX as u32 {
             anyhow::bail!("Timeout must be less than i32::MAX")
         } else {
-            timeout_ms
+            self.timeout_ms
+        };
+        let signals = if self.signals.is_empty() {
+            CrashtrackerConfiguration::default_signals()
+        } else {
+            let mut deduped = Vec::with_capacity(self.signals.len());
+            for signal in self.signals {
+                anyhow::ensure!(
+                    CrashtrackerConfiguration::SUPPORTED_SIGNALS.contains(&signal),
+                    "Signal {signal} is not supported by the crashtracker"
+                );
+                if !deduped.contains(&signal) {
+                    deduped.push(signal);
+                }
+            }
+            deduped
         };
         // Note:  don't check the receiver socket upfront, since a configuration can be interned
         // before the receiver is started when using an async-receiver.
-        Ok(Self {
-            additional_files,
-            create_alt_stack,
-            use_alt_stack,
-            endpoint,
-            resolve_frames,
+        Ok(CrashtrackerConfiguration {
+            additional_files: self.additional_files,
+            create_alt_stack: self.create_alt_stack,
+            use_alt_stack: self.use_alt_stack,
+            endpoint: self.endpoint,
+            minidump: self.minidump,
+            resolve_frames: self
+                .resolve_frames
+                .unwrap_or(StacktraceCollection::Disabled),
+            signals,
+            spool_dir: self.spool_dir,
+            symbol_stores: self.symbol_stores,
             timeout_ms,
-            unix_socket_path,
+            unix_socket_path: self.unix_socket_path,
+            upload_rate_limit: self.upload_rate_limit,
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::CrashtrackerReceiverConfig;
+    use super::{CrashtrackerConfiguration, CrashtrackerReceiverConfig, StacktraceCollection};
+
+    fn config_with_signals(signals: Vec<i32>) -> anyhow::Result<CrashtrackerConfiguration> {
+        CrashtrackerConfiguration::builder()
+            .set_signals(signals)
+            .build()
+    }
+
+    #[test]
+    fn test_config_signals() -> anyhow::Result<()> {
+        let config = config_with_signals(vec![])?;
+        assert_eq!(config.signals, CrashtrackerConfiguration::default_signals());
+
+        let config = config_with_signals(vec![libc::SIGFPE, libc::SIGTRAP, libc::SIGFPE])?;
+        assert_eq!(config.signals, vec![libc::SIGFPE, libc::SIGTRAP]);
+
+        config_with_signals(vec![libc::SIGSEGV, libc::SIGUSR1]).unwrap_err();
+        config_with_signals(vec![libc::SIGKILL]).unwrap_err();
+
+        // Configurations serialized before signals were configurable get the defaults.
+        let mut json = serde_json::to_value(config)?;
+        json.as_object_mut().unwrap().remove("signals");
+        let config: CrashtrackerConfiguration = serde_json::from_value(json)?;
+        assert_eq!(config.signals, CrashtrackerConfiguration::default_signals());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_builder() -> anyhow::Result<()> {
+        let config = CrashtrackerConfiguration::builder().build()?;
+        assert_eq!(config.resolve_frames, StacktraceCollection::Disabled);
+        assert_eq!(
+            config.timeout_ms,
+            crate::shared::constants::DD_CRASHTRACK_DEFAULT_TIMEOUT_MS
+        );
+
+        CrashtrackerConfiguration::builder()
+            .set_create_alt_stack(true)
+            .build()
+            .unwrap_err();
+        CrashtrackerConfiguration::builder()
+            .set_timeout_ms(u32::MAX)
+            .build()
+            .unwrap_err();
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_minidump() -> anyhow::Result<()> {
+        let config = |endpoint| {
+            CrashtrackerConfiguration::builder()
+                .set_endpoint(endpoint)
+                .set_minidump(true)
+                .build()
+        };
+        config(Some(ddcommon::Endpoint::from_slice(
+            "file:///tmp/report.json",
+        )))?;
+        config(Some(ddcommon::Endpoint::from_slice(
+            "http://localhost:8126",
+        )))
+        .unwrap_err();
+        config(None).unwrap_err();
+        Ok(())
+    }
 
     #[test]
     fn test_receiver_config_new() -> anyhow::Result<()> {
@@ -3,6 +3,7 @@
 
 pub const DD_CRASHTRACK_BEGIN_CONFIG: &str = "DD_CRASHTRACK_BEGIN_CONFIG";
 pub const DD_CRASHTRACK_BEGIN_COUNTERS: &str = "DD_CRASHTRACK_BEGIN_COUNTERS";
+pub const DD_CRASHTRACK_BEGIN_CRASHINFO: &str = "DD_CRASHTRACK_BEGIN_CRASHINFO";
 pub const DD_CRASHTRACK_BEGIN_FILE: &str = "DD_CRASHTRACK_BEGIN_FILE";
 pub const DD_CRASHTRACK_BEGIN_METADATA: &str = "DD_CRASHTRACK_BEGIN_METADATA";
 pub const DD_CRASHTRACK_BEGIN_PROCINFO: &str = "DD_CRASHTRACK_BEGIN_PROCESSINFO";
@@ -10,9 +11,14 @@ pub const DD_CRASHTRACK_BEGIN_SIGINFO: &str = "DD_CRASHTRACK_BEGIN_SIGINFO";
 pub const DD_CRASHTRACK_BEGIN_SPAN_IDS: &str = "DD_CRASHTRACK_BEGIN_SPAN_IDS";
 pub const DD_CRASHTRACK_BEGIN_STACKTRACE: &str = "DD_CRASHTRACK_BEGIN_STACKTRACE";
 pub const DD_CRASHTRACK_BEGIN_TRACE_IDS: &str = "DD_CRASHTRACK_BEGIN_TRACE_IDS";
+pub const DD_CRASHTRACK_BEGIN_UCONTEXT: &str = "DD_CRASHTRACK_BEGIN_UCONTEXT";
+/// Sent when the crash handler itself crashes while sending the report, followed by the signal,
+/// e.g. `DD_CRASHTRACK_CRASH_IN_HANDLER 11`.  It may follow a partial line.
+pub const DD_CRASHTRACK_CRASH_IN_HANDLER: &str = "DD_CRASHTRACK_CRASH_IN_HANDLER";
 pub const DD_CRASHTRACK_DONE: &str = "DD_CRASHTRACK_DONE";
 pub const DD_CRASHTRACK_END_CONFIG: &str = "DD_CRASHTRACK_END_CONFIG";
 pub const DD_CRASHTRACK_END_COUNTERS: &str = "DD_CRASHTRACK_END_COUNTERS";
+pub const DD_CRASHTRACK_END_CRASHINFO: &str = "DD_CRASHTRACK_END_CRASHINFO";
 pub const DD_CRASHTRACK_END_FILE: &str = "DD_CRASHTRACK_END_FILE";
 pub const DD_CRASHTRACK_END_METADATA: &str = "DD_CRASHTRACK_END_METADATA";
 pub const DD_CRASHTRACK_END_PROCINFO: &str = "DD_CRASHTRACK_END_PROCESSINFO";
@@ -20,6 +26,15 @@ pub const DD_CRASHTRACK_END_SIGINFO: &str = "DD_CRASHTRACK_END_SIGINFO";
 pub const DD_CRASHTRACK_END_SPAN_IDS: &str = "DD_CRASHTRACK_END_SPAN_IDS";
 pub const DD_CRASHTRACK_END_STACKTRACE: &str = "DD_CRASHTRACK_END_STACKTRACE";
 pub const DD_CRASHTRACK_END_TRACE_IDS: &str = "DD_CRASHTRACK_END_TRACE_IDS";
+pub const DD_CRASHTRACK_END_UCONTEXT: &str = "DD_CRASHTRACK_END_UCONTEXT";
+/// Sent first, followed by the version, e.g. `DD_CRASHTRACK_PROTOCOL_VERSION 2`.  Collectors
+/// which predate it speak version 1, where the siginfo and the stack frames are in the legacy
+/// formats of `crash_info`.  From version 2, every block is in the RFC5 format.  From version 3,
+/// every block is flushed as soon as it is written, stack frames one at a time, so that a
+/// collector which hangs or crashes still delivers the blocks before, and a crash of the crash
+/// handler itself is announced with [DD_CRASHTRACK_CRASH_IN_HANDLER].
+pub const DD_CRASHTRACK_PROTOCOL_VERSION: &str = "DD_CRASHTRACK_PROTOCOL_VERSION";
+pub const DD_CRASHTRACK_CURRENT_PROTOCOL_VERSION: u32 = 3;
 pub const DD_CRASHTRACK_DEFAULT_TIMEOUT_MS: u32 = 5_000;
 pub const DD_CRASHTRACK_MINIMUM_REAP_TIME_MS: u32 = 160; // 4ms per sched slice, give ~4x10 slices
                                                          // for safety
@@ -5,3 +5,5 @@
 
 pub(crate) mod configuration;
 pub(crate) mod constants;
+pub(crate) mod rate_limit;
+pub(crate) mod symbol_stores;
@@ -0,0 +1,154 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+//! Limits the uploads of reports with the same fingerprint, so that a process
+//! crashing in a loop doesn't flood the backend with copies of one report.
+//!
+//! Each receiver is a new process, so the uploads are counted in files, one
+//! per fingerprint, in a directory shared by the processes.  Crashes of
+//! several processes at the same time may race on the counts, so the limit is
+//! only approximate.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// The counter attached to a report which is uploaded after others with the
+/// same fingerprint were suppressed, with the number of suppressed reports.
+pub const SUPPRESSED_DUPLICATES_COUNTER: &str = "suppressed_duplicates";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadRateLimit {
+    /// At most this many reports with the same fingerprint are uploaded per
+    /// `period`.  The others are dropped.
+    pub max_uploads: u32,
+    pub period: Duration,
+    /// Where the uploads are counted.
+    pub state_dir: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FingerprintState {
+    /// When the last reports were uploaded, in seconds since the epoch.
+    uploads: Vec<u64>,
+    /// How many reports were dropped since the last upload.
+    suppressed: u64,
+}
+
+impl UploadRateLimit {
+    pub fn new(max_uploads: u32, period: Duration, state_dir: String) -> anyhow::Result<Self> {
+        anyhow::ensure!(max_uploads > 0, "The upload rate limit must be positive");
+        anyhow::ensure!(
+            !period.is_zero(),
+            "The upload rate limit period must be positive"
+        );
+        Ok(Self {
+            max_uploads,
+            period,
+            state_dir,
+        })
+    }
+
+    /// Tells whether a report with `fingerprint` may be uploaded at `now`.
+    /// Returns the number of reports dropped since the last upload if so, and
+    /// None if this one should be dropped, which is counted.  The upload is
+    /// only counted once done, by [UploadRateLimit::record_upload], so that a
+    /// failed upload doesn't use up the limit.
+    pub fn admit(&self, fingerprint: &str, now: SystemTime) -> anyhow::Result<Option<u64>> {
+        self.update(fingerprint, now, |state, _| {
+            if state.uploads.len() < self.max_uploads as usize {
+                Some(state.suppressed)
+            } else {
+                state.suppressed += 1;
+                None
+            }
+        })
+    }
+
+    /// Counts the upload of a report with `fingerprint` at `now`, which was
+    /// admitted with `suppressed` reports dropped before it.  Reports dropped
+    /// since it was admitted are left for the next upload to tell.
+    pub fn record_upload(
+        &self,
+        fingerprint: &str,
+        now: SystemTime,
+        suppressed: u64,
+    ) -> anyhow::Result<()> {
+        self.update(fingerprint, now, |state, now| {
+            state.uploads.push(now);
+            state.suppressed = state.suppressed.saturating_sub(suppressed);
+        })
+    }
+
+    /// Applies `f` to the state of `fingerprint`, with the uploads before the
+    /// current period forgotten, and saves it.
+    fn update<T>(
+        &self,
+        fingerprint: &str,
+        now: SystemTime,
+        f: impl FnOnce(&mut FingerprintState, u64) -> T,
+    ) -> anyhow::Result<T> {
+        let state_dir = Path::new(&self.state_dir);
+        std::fs::create_dir_all(state_dir)
+            .with_context(|| format!("Failed to create {}", state_dir.display()))?;
+        let path = state_dir.join(format!("{fingerprint}.json"));
+        // A missing or corrupted state is a fresh start.
+        let mut state: FingerprintState = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+
+        let now = now.duration_since(SystemTime::UNIX_EPOCH)?.as_secs();
+        let start = now.saturating_sub(self.period.as_secs());
+        state.uploads.retain(|&upload| upload > start);
+        let result = f(&mut state, now);
+
+        // Write to a temporary file first, so a partial state is never read.
+        let tmp_path = state_dir.join(format!(".{fingerprint}.{}.tmp", std::process::id()));
+        std::fs::write(&tmp_path, serde_json::to_vec(&state)?)
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to rename {}", tmp_path.display()))?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admit() -> anyhow::Result<()> {
+        let state_dir = tempfile::tempdir()?;
+        let period = Duration::from_secs(3600);
+        let limit =
+            UploadRateLimit::new(2, period, state_dir.path().to_str().unwrap().to_string())?;
+        let now = SystemTime::now();
+
+        // Admitting a report doesn't count it: only uploads do.
+        assert_eq!(limit.admit("abc", now)?, Some(0));
+        assert_eq!(limit.admit("abc", now)?, Some(0));
+        limit.record_upload("abc", now, 0)?;
+        limit.record_upload("abc", now, 0)?;
+        assert_eq!(limit.admit("abc", now)?, None);
+        assert_eq!(limit.admit("abc", now)?, None);
+        // Other crashes have limits of their own.
+        assert_eq!(limit.admit("def", now)?, Some(0));
+        // Once the period is over, the next report goes out, with the count of those dropped.
+        assert_eq!(limit.admit("abc", now + period)?, Some(2));
+        // Until it was uploaded, the next one is told about them too.
+        assert_eq!(limit.admit("abc", now + period)?, Some(2));
+        limit.record_upload("abc", now + period, 2)?;
+        assert_eq!(limit.admit("abc", now + period)?, Some(0));
+        limit.record_upload("abc", now + period, 0)?;
+        assert_eq!(limit.admit("abc", now + period)?, None);
+
+        std::fs::write(state_dir.path().join("ghi.json"), "{")?;
+        assert_eq!(limit.admit("ghi", now)?, Some(0));
+
+        UploadRateLimit::new(0, period, String::new()).unwrap_err();
+        UploadRateLimit::new(1, Duration::ZERO, String::new()).unwrap_err();
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+//! Where the receiver looks for the separate debug files of stripped binaries,
+//! by build id, to fill in the files, lines and inlined functions of their
+//! frames.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymbolStore {
+    /// A directory of debug files named by build id, either like
+    /// `/usr/lib/debug/.build-id` (`ab/cdef….debug`), or like the cache of
+    /// debuginfod clients (`abcdef…/debuginfo`).
+    Directory(String),
+    /// A debuginfod server, e.g. `http://localhost:8002`.  The debug files
+    /// are downloaded to `cache_dir`, in the layout of a debuginfod client
+    /// cache, and looked up there first.
+    Debuginfod { url: String, cache_dir: String },
+}
+
+impl SymbolStore {
+    /// Finds the debug file for `build_id`, a hex string, in the stores, in
+    /// order, giving up on downloads at `deadline`.  Lookups are best effort:
+    /// failures are reported and the next store is tried.
+    pub(crate) async fn find_debug_file(
+        stores: &[SymbolStore],
+        build_id: &str,
+        deadline: Instant,
+    ) -> Option<PathBuf> {
+        // The build id ends up in paths and urls.
+        if build_id.len() < 3 || !build_id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let build_id = build_id.to_ascii_lowercase();
+        for store in stores {
+            match store.find(&build_id, deadline).await {
+                Ok(Some(path)) => return Some(path),
+                Ok(None) => (),
+                Err(err) => eprintln!("Failed to look up the debug file of {build_id}: {err}"),
+            }
+        }
+        None
+    }
+
+    async fn find(&self, build_id: &str, deadline: Instant) -> anyhow::Result<Option<PathBuf>> {
+        match self {
+            SymbolStore::Directory(dir) => Ok(find_in_directory(Path::new(dir), build_id).await),
+            SymbolStore::Debuginfod { url, cache_dir } => {
+                let cache_dir = Path::new(cache_dir);
+                if let Some(path) = find_in_directory(cache_dir, build_id).await {
+                    return Ok(Some(path));
+                }
+                let deadline = tokio::time::Instant::from_std(deadline);
+                tokio::time::timeout_at(deadline, download(url, cache_dir, build_id))
+                    .await
+                    .with_context(|| format!("Timed out downloading from {url}"))?
+            }
+        }
+    }
+}
+
+async fn find_in_directory(dir: &Path, build_id: &str) -> Option<PathBuf> {
+    let (prefix, rest) = build_id.split_at(2);
+    for path in [
+        dir.join(prefix).join(format!("{rest}.debug")),
+        dir.join(build_id).join("debuginfo"),
+    ] {
+        if tokio::fs::metadata(&path)
+            .await
+            .is_ok_and(|metadata| metadata.is_file())
+        {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Downloads the debug file from a debuginfod server to the cache.  Returns
+/// None if the server doesn't have it.  Debug files can be large, so they are
+/// streamed to the cache rather than held in memory.
+async fn download(url: &str, cache_dir: &Path, build_id: &str) -> anyhow::Result<Option<PathBuf>> {
+    use hyper::body::HttpBody;
+    use tokio::io::AsyncWriteExt;
+
+    let uri: hyper::Uri = format!("{}/buildid/{build_id}/debuginfo", url.trim_end_matches('/'))
+        .parse()
+        .with_context(|| format!("Invalid debuginfod url {url}"))?;
+    let client: ddcommon::HttpClient =
+        hyper::Client::builder().build(ddcommon::connector::Connector::default());
+    let response = client.get(uri).await?;
+    if response.status() == hyper::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    anyhow::ensure!(
+        response.status().is_success(),
+        "Debuginfod request failed with status {}",
+        response.status()
+    );
+
+    let dir = cache_dir.join(build_id);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join("debuginfo");
+    // Write to a temporary file first, so a partial download is never used.
+    let tmp_path = RemoveOnDrop(dir.join(format!(".debuginfo.{}.tmp", std::process::id())));
+    async {
+        let mut file = tokio::fs::File::create(&tmp_path.0).await?;
+        let mut body = response.into_body();
+        while let Some(chunk) = body.data().await {
+            file.write_all(&chunk?).await?;
+        }
+        file.flush().await?;
+        anyhow::Ok(())
+    }
+    .await
+    .with_context(|| format!("Failed to write {}", tmp_path.0.display()))?;
+    tokio::fs::rename(&tmp_path.0, &path)
+        .await
+        .with_context(|| format!("Failed to rename {}", tmp_path.0.display()))?;
+    Ok(Some(path))
+}
+
+/// Removes the file, if it is still there, when dropped: also when the future
+/// writing it is dropped, e.g. because the download timed out.
+struct RemoveOnDrop(PathBuf);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use std::time::Duration;
+
+    fn deadline() -> Instant {
+        Instant::now() + Duration::from_secs(5)
+    }
+
+    #[tokio::test]
+    async fn test_find_in_directories() -> anyhow::Result<()> {
+        let build_id_dir = tempfile::tempdir()?;
+        std::fs::create_dir(build_id_dir.path().join("ab"))?;
+        std::fs::write(build_id_dir.path().join("ab/cdef.debug"), "one")?;
+        let cache_dir = tempfile::tempdir()?;
+        std::fs::create_dir(cache_dir.path().join("abcdef"))?;
+        std::fs::write(cache_dir.path().join("abcdef/debuginfo"), "two")?;
+        std::fs::create_dir(cache_dir.path().join("123456"))?;
+        std::fs::write(cache_dir.path().join("123456/debuginfo"), "three")?;
+
+        let stores = [
+            SymbolStore::Directory(build_id_dir.path().to_str().unwrap().to_string()),
+            SymbolStore::Directory(cache_dir.path().to_str().unwrap().to_string()),
+        ];
+        let found = SymbolStore::find_debug_file(&stores, "ABCDEF", deadline()).await;
+        assert_eq!(std::fs::read_to_string(found.unwrap())?, "one");
+        let found = SymbolStore::find_debug_file(&stores, "123456", deadline()).await;
+        assert_eq!(std::fs::read_to_string(found.unwrap())?, "three");
+        assert_eq!(
+            SymbolStore::find_debug_file(&stores, "fedcba", deadline()).await,
+            None
+        );
+        assert_eq!(
+            SymbolStore::find_debug_file(&stores, "../ab", deadline()).await,
+            None
+        );
+        Ok(())
+    }
+
+    /// Serves `abcdef` like a debuginfod server, and nothing else.  Returns
+    /// its url.
+    async fn debuginfod_server() -> anyhow::Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = vec![0; 1024];
+                let len = stream.read(&mut request).await.unwrap_or_default();
+                let request = String::from_utf8_lossy(&request[..len]);
+                let response = if request.starts_with("GET /buildid/abcdef/debuginfo ") {
+                    "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\ndebug"
+                } else {
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                };
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        Ok(url)
+    }
+
+    #[tokio::test]
+    async fn test_download_from_debuginfod() -> anyhow::Result<()> {
+        let cache_dir = tempfile::tempdir()?;
+        let stores = [SymbolStore::Debuginfod {
+            url: debuginfod_server().await?,
+            cache_dir: cache_dir.path().to_str().unwrap().to_string(),
+        }];
+
+        let found = SymbolStore::find_debug_file(&stores, "abcdef", deadline()).await;
+        let expected = cache_dir.path().join("abcdef/debuginfo");
+        assert_eq!(found.as_ref(), Some(&expected));
+        assert_eq!(std::fs::read_to_string(&expected)?, "debug");
+        assert_eq!(
+            SymbolStore::find_debug_file(&stores, "fedcba", deadline()).await,
+            None
+        );
+
+        // Once downloaded, the debug file is found without the server.
+        let offline = [SymbolStore::Debuginfod {
+            url: "http://127.0.0.1:1".to_string(),
+            cache_dir: cache_dir.path().to_str().unwrap().to_string(),
+        }];
+        let found = SymbolStore::find_debug_file(&offline, "abcdef", deadline()).await;
+        assert_eq!(found, Some(expected));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_timeout_leaves_no_file() -> anyhow::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = vec![0; 1024];
+                let _ = stream.read(&mut request).await;
+                // Only part of the body, then nothing.
+                let response = "HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\npartial";
+                let _ = stream.write_all(response.as_bytes()).await;
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+        });
+        let cache_dir = tempfile::tempdir()?;
+        let stores = [SymbolStore::Debuginfod {
+            url,
+            cache_dir: cache_dir.path().to_str().unwrap().to_string(),
+        }];
+
+        let deadline = Instant::now() + Duration::from_millis(500);
+        let found = SymbolStore::find_debug_file(&stores, "abcdef", deadline).await;
+        assert_eq!(found, None);
+        let left = std::fs::read_dir(cache_dir.path().join("abcdef"))?.count();
+        assert_eq!(left, 0);
+        Ok(())
+    }
+}
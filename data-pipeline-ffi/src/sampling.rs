@@ -0,0 +1,332 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use datadog_trace_protobuf::pb;
+use datadog_trace_utils::sampling::{
+    SamplingRule, SpanSampler, TraceSampler, SPAN_SAMPLING_MAX_PER_SECOND_KEY,
+    SPAN_SAMPLING_RULE_RATE_KEY,
+};
+use ddcommon_ffi::{slice::AsBytes, CharSlice, Error, MaybeError, Slice};
+use std::ptr::NonNull;
+
+#[repr(C)]
+pub struct SpanTag<'a> {
+    pub key: CharSlice<'a>,
+    pub value: CharSlice<'a>,
+}
+
+#[repr(C)]
+pub struct SpanMetric<'a> {
+    pub key: CharSlice<'a>,
+    pub value: f64,
+}
+
+/// The fields of a span which sampling decisions depend on.
+#[repr(C)]
+pub struct SamplingSpan<'a> {
+    pub trace_id: u64,
+    pub span_id: u64,
+    pub service: CharSlice<'a>,
+    pub name: CharSlice<'a>,
+    pub resource: CharSlice<'a>,
+    pub meta: Slice<'a, SpanTag<'a>>,
+    pub metrics: Slice<'a, SpanMetric<'a>>,
+}
+
+/// A sampling decision, to set on the root span as `_sampling_priority_v1`,
+/// `_dd.p.dm` (`-<mechanism>`, only if the trace is kept), `_dd.rule_psr`,
+/// `_dd.limit_psr` and `_dd.agent_psr`.  Rates which don't apply are NaN.
+#[repr(C)]
+pub struct SamplingDecision {
+    pub priority: i8,
+    pub mechanism: u8,
+    pub rule_rate: f64,
+    pub limit_rate: f64,
+    pub agent_rate: f64,
+}
+
+/// A single span sampling decision.  If the span is kept, it is to be tagged
+/// with `_dd.span_sampling.mechanism` (8), `_dd.span_sampling.rule_rate` and
+/// `_dd.span_sampling.max_per_second`, which is NaN if the rule has no limit.
+#[repr(C)]
+pub struct SpanSamplingDecision {
+    pub keep: bool,
+    pub rule_rate: f64,
+    pub max_per_second: f64,
+}
+
+impl<'a> From<&SamplingSpan<'a>> for pb::Span {
+    fn from(span: &SamplingSpan<'a>) -> Self {
+        pb::Span {
+            trace_id: span.trace_id,
+            span_id: span.span_id,
+            service: span.service.to_utf8_lossy().into_owned(),
+            name: span.name.to_utf8_lossy().into_owned(),
+            resource: span.resource.to_utf8_lossy().into_owned(),
+            meta: span
+                .meta
+                .as_slice()
+                .iter()
+                .map(|tag| {
+                    (
+                        tag.key.to_utf8_lossy().into_owned(),
+                        tag.value.to_utf8_lossy().into_owned(),
+                    )
+                })
+                .collect(),
+            metrics: span
+                .metrics
+                .as_slice()
+                .iter()
+                .map(|metric| (metric.key.to_utf8_lossy().into_owned(), metric.value))
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Create a new TraceSampler instance.
+///
+/// # Arguments
+///
+/// * `out_handle` - The handle to write the TraceSampler instance in.
+/// * `local_rules` - The sampling rules in the JSON format of `DD_TRACE_SAMPLING_RULES`, or empty.
+/// * `sample_rate` - The sample rate of traces which match no rule, or NaN to use the agent rates.
+/// * `rate_limit` - The maximum number of traces kept by rules per second, unlimited if negative.
+#[no_mangle]
+pub unsafe extern "C" fn ddog_trace_sampler_new(
+    out_handle: NonNull<Box<TraceSampler>>,
+    local_rules: CharSlice,
+    sample_rate: f64,
+    rate_limit: f64,
+) -> MaybeError {
+    let local_rules = if local_rules.is_empty() {
+        vec![]
+    } else {
+        match SamplingRule::parse_local_rules(&local_rules.to_utf8_lossy()) {
+            Ok(rules) => rules,
+            Err(err) => return MaybeError::Some(Error::from(err)),
+        }
+    };
+    let sample_rate = (!sample_rate.is_nan()).then_some(sample_rate);
+    let sampler = TraceSampler::new(local_rules, sample_rate, rate_limit);
+    out_handle.as_ptr().write(Box::new(sampler));
+    MaybeError::None
+}
+
+/// Free the TraceSampler instance.
+///
+/// # Arguments
+///
+/// * handle - The handle to the TraceSampler instance.
+#[no_mangle]
+pub unsafe extern "C" fn ddog_trace_sampler_free(handle: Box<TraceSampler>) {
+    drop(handle);
+}
+
+/// Apply the dynamic configuration of the tracer, overriding the local
+/// sampling rules and sample rate.
+///
+/// # Arguments
+///
+/// * `handle` - The handle to the TraceSampler instance.
+/// * `config` - The contents of the APM_TRACING remote config file, or empty when it is removed.
+#[no_mangle]
+pub unsafe extern "C" fn ddog_trace_sampler_apply_dynamic_config(
+    handle: &TraceSampler,
+    config: CharSlice,
+) -> MaybeError {
+    if config.is_empty() {
+        handle.apply_dynamic_config(vec![]);
+        return MaybeError::None;
+    }
+    match datadog_dynamic_configuration::parse_json(config.as_bytes()) {
+        Ok(file) => {
+            let configs: Vec<datadog_dynamic_configuration::Configs> = file.lib_config.into();
+            handle.apply_dynamic_config(configs);
+            MaybeError::None
+        }
+        Err(err) => MaybeError::Some(Error::from(format!("Invalid dynamic configuration: {err}"))),
+    }
+}
+
+/// Update the sample rates by service from a JSON response of the agent.
+///
+/// # Arguments
+///
+/// * `handle` - The handle to the TraceSampler instance.
+/// * `response` - The response of the agent to a trace payload.
+#[no_mangle]
+pub unsafe extern "C" fn ddog_trace_sampler_update_agent_rates(
+    handle: &TraceSampler,
+    response: CharSlice,
+) -> MaybeError {
+    match handle.update_agent_rates(&response.to_utf8_lossy()) {
+        Ok(()) => MaybeError::None,
+        Err(err) => MaybeError::Some(Error::from(err)),
+    }
+}
+
+/// Decide whether a trace is kept.
+///
+/// # Arguments
+///
+/// * `handle` - The handle to the TraceSampler instance.
+/// * `root` - The root span of the trace.
+#[no_mangle]
+pub unsafe extern "C" fn ddog_trace_sampler_sample(
+    handle: &TraceSampler,
+    root: &SamplingSpan,
+) -> SamplingDecision {
+    let span = pb::Span::from(root);
+    let decision = handle.sample(&span);
+    SamplingDecision {
+        priority: decision.priority,
+        mechanism: decision.mechanism as u8,
+        rule_rate: decision.rule_rate.unwrap_or(f64::NAN),
+        limit_rate: decision.limit_rate.unwrap_or(f64::NAN),
+        agent_rate: decision.agent_rate.unwrap_or(f64::NAN),
+    }
+}
+
+/// Create a new SpanSampler instance.
+///
+/// # Arguments
+///
+/// * `out_handle` - The handle to write the SpanSampler instance in.
+/// * `rules` - The rules in the JSON format of `DD_SPAN_SAMPLING_RULES`, or empty to read them from
+///   the `DD_SPAN_SAMPLING_RULES` or `DD_SPAN_SAMPLING_RULES_FILE` environment variables.
+#[no_mangle]
+pub unsafe extern "C" fn ddog_span_sampler_new(
+    out_handle: NonNull<Box<SpanSampler>>,
+    rules: CharSlice,
+) -> MaybeError {
+    let sampler = if rules.is_empty() {
+        SpanSampler::from_env()
+    } else {
+        SpanSampler::parse_rules(&rules.to_utf8_lossy())
+    };
+    match sampler {
+        Ok(sampler) => {
+            out_handle.as_ptr().write(Box::new(sampler));
+            MaybeError::None
+        }
+        Err(err) => MaybeError::Some(Error::from(err)),
+    }
+}
+
+/// Free the SpanSampler instance.
+///
+/// # Arguments
+///
+/// * handle - The handle to the SpanSampler instance.
+#[no_mangle]
+pub unsafe extern "C" fn ddog_span_sampler_free(handle: Box<SpanSampler>) {
+    drop(handle);
+}
+
+/// Decide whether a span of a dropped trace is kept.
+///
+/// # Arguments
+///
+/// * `handle` - The handle to the SpanSampler instance.
+/// * `span` - The span.
+#[no_mangle]
+pub unsafe extern "C" fn ddog_span_sampler_sample(
+    handle: &SpanSampler,
+    span: &SamplingSpan,
+) -> SpanSamplingDecision {
+    let mut span = pb::Span::from(span);
+    let keep = handle.sample(&mut span);
+    let metric = |key| span.metrics.get(key).copied().unwrap_or(f64::NAN);
+    SpanSamplingDecision {
+        keep,
+        rule_rate: metric(SPAN_SAMPLING_RULE_RATE_KEY),
+        max_per_second: metric(SPAN_SAMPLING_MAX_PER_SECOND_KEY),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::MaybeUninit;
+
+    #[test]
+    fn test_sampler() {
+        let mut sampler = MaybeUninit::<Box<TraceSampler>>::uninit();
+        let rules = CharSlice::from(r#"[{"service": "web", "sample_rate": 1}]"#);
+        unsafe {
+            let error = ddog_trace_sampler_new(
+                NonNull::new_unchecked(sampler.as_mut_ptr()),
+                rules,
+                f64::NAN,
+                -1.0,
+            );
+            assert!(error.to_std().is_none());
+            let sampler = sampler.assume_init();
+
+            let meta = [SpanTag {
+                key: CharSlice::from("env"),
+                value: CharSlice::from("prod"),
+            }];
+            let mut root = SamplingSpan {
+                trace_id: 1,
+                span_id: 1,
+                service: CharSlice::from("web"),
+                name: CharSlice::from("http.request"),
+                resource: CharSlice::from("GET /"),
+                meta: Slice::from(&meta[..]),
+                metrics: Slice::empty(),
+            };
+            let decision = ddog_trace_sampler_sample(&sampler, &root);
+            assert_eq!((decision.priority, decision.mechanism), (2, 3));
+            assert_eq!(decision.rule_rate, 1.0);
+            assert!(decision.agent_rate.is_nan());
+
+            let response = CharSlice::from(r#"{"rate_by_service": {"service:db,env:prod": 0}}"#);
+            let error = ddog_trace_sampler_update_agent_rates(&sampler, response);
+            assert!(error.to_std().is_none());
+            root.service = CharSlice::from("db");
+            let decision = ddog_trace_sampler_sample(&sampler, &root);
+            assert_eq!((decision.priority, decision.mechanism), (0, 1));
+            assert_eq!(decision.agent_rate, 0.0);
+
+            let error = ddog_trace_sampler_apply_dynamic_config(&sampler, CharSlice::from("{"));
+            assert!(error.to_std().is_some());
+
+            ddog_trace_sampler_free(sampler);
+        }
+    }
+
+    #[test]
+    fn test_span_sampler() {
+        let mut sampler = MaybeUninit::<Box<SpanSampler>>::uninit();
+        let rules = CharSlice::from(r#"[{"name": "db.*", "max_per_second": 10}]"#);
+        unsafe {
+            let error = ddog_span_sampler_new(NonNull::new_unchecked(sampler.as_mut_ptr()), rules);
+            assert!(error.to_std().is_none());
+            let sampler = sampler.assume_init();
+
+            let mut span = SamplingSpan {
+                trace_id: 1,
+                span_id: 2,
+                service: CharSlice::from("web"),
+                name: CharSlice::from("db.query"),
+                resource: CharSlice::from("SELECT 1"),
+                meta: Slice::empty(),
+                metrics: Slice::empty(),
+            };
+            let decision = ddog_span_sampler_sample(&sampler, &span);
+            assert!(decision.keep);
+            assert_eq!(decision.rule_rate, 1.0);
+            assert_eq!(decision.max_per_second, 10.0);
+
+            span.name = CharSlice::from("http.request");
+            let decision = ddog_span_sampler_sample(&sampler, &span);
+            assert!(!decision.keep);
+            assert!(decision.rule_rate.is_nan());
+
+            ddog_span_sampler_free(sampler);
+        }
+    }
+}
@@ -7,7 +7,9 @@ use crate::{
 };
 use arc_swap::{ArcSwap, ArcSwapOption};
 use bytes::Bytes;
-use datadog_trace_utils::trace_utils::{self, SendData, TracerHeaderTags};
+use datadog_trace_protobuf::pb;
+use datadog_trace_utils::sampling::{is_span_sampled, SAMPLING_PRIORITY_KEY};
+use datadog_trace_utils::trace_utils::{self, has_top_level, SendData, TracerHeaderTags};
 use datadog_trace_utils::tracer_payload::TraceCollection;
 use datadog_trace_utils::{msgpack_decoder, tracer_payload};
 use ddcommon::tag::Tag;
@@ -29,10 +31,6 @@ const STATS_ENDPOINT: &str = "/v0.6/stats";
 const INFO_ENDPOINT: &str = "/info";
 
 // Keys used for sampling
-#[allow(dead_code)] // TODO (APMSP-1583) these will be used with client side stats
-const SAMPLING_PRIORITY_KEY: &str = "_sampling_priority_v1";
-#[allow(dead_code)] // TODO (APMSP-1584) these will be used with client side stats
-const SAMPLING_SINGLE_SPAN_MECHANISM: &str = "_dd.span_sampling.mechanism";
 #[allow(dead_code)] // TODO (APMSP-1584) these will be used with client side stats
 const SAMPLING_ANALYTICS_RATE_KEY: &str = "_dd1.sr.eausr";
 
@@ -99,13 +97,14 @@ fn add_path(url: &Uri, path: &str) -> Uri {
     Uri::from_parts(parts).unwrap()
 }
 
-/* TODO (APMSP-1583) re-enable client side stats
+#[allow(dead_code)] // TODO (APMSP-1583) re-enable client side stats
 struct DroppedP0Counts {
     pub dropped_p0_traces: usize,
     pub dropped_p0_spans: usize,
 }
 
-Remove spans and chunks only keeping the ones that may be sampled by the agent
+/// Remove spans and chunks only keeping the ones that may be sampled by the agent
+#[allow(dead_code)] // TODO (APMSP-1583) re-enable client side stats
 fn drop_chunks(traces: &mut Vec<Vec<pb::Span>>) -> DroppedP0Counts {
     let mut dropped_p0_traces = 0;
     let mut dropped_p0_spans = 0;
@@ -120,15 +119,12 @@ fn drop_chunks(traces: &mut Vec<Vec<pb::Span>>) -> DroppedP0Counts {
             }
             // PrioritySampler and NoPrioritySampler
             let priority = span.metrics.get(SAMPLING_PRIORITY_KEY);
-            if has_top_level(span) && (priority.is_none() || priority.is_some_and(|p| *p > 0.0))
-{                 // We send chunks with positive priority or no priority
+            if has_top_level(span) && (priority.is_none() || priority.is_some_and(|p| *p > 0.0)) {
+                // We send chunks with positive priority or no priority
                 return true;
             }
             // SingleSpanSampler and AnalyzedSpansSampler
-            else if span
-                .metrics
-                .get(SAMPLING_SINGLE_SPAN_MECHANISM)
-                .is_some_and(|m| *m == 8.0)
+            else if is_span_sampled(span)
                 || span.metrics.contains_key(SAMPLING_ANALYTICS_RATE_KEY)
             {
                 // We send spans sampled by single-span sampling or analyzed spans
@@ -153,7 +149,6 @@ fn drop_chunks(traces: &mut Vec<Vec<pb::Span>>) -> DroppedP0Counts {
         dropped_p0_spans,
     }
 }
- */
 
 #[derive(Clone, Default, Debug)]
 pub struct TracerMetadata {
@@ -908,6 +903,7 @@ pub trait ResponseCallback {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use datadog_trace_utils::sampling::{SpanSampler, SPAN_SAMPLING_MECHANISM_KEY};
     use datadog_trace_utils::span_v04::Span;
     use httpmock::prelude::*;
     use httpmock::MockServer;
@@ -1028,119 +1024,148 @@ mod tests {
         assert!(hashmap.contains_key("datadog-client-computed-stats"));
         assert!(hashmap.contains_key("datadog-client-computed-top-level"));
     }
-    //
-    // #[test]
-    // fn test_drop_chunks() {
-    //     let chunk_with_priority = vec![
-    //         pb::Span {
-    //             span_id: 1,
-    //             metrics: HashMap::from([
-    //                 (SAMPLING_PRIORITY_KEY.to_string(), 1.0),
-    //                 ("_dd.top_level".to_string(), 1.0),
-    //             ]),
-    //             ..Default::default()
-    //         },
-    //         pb::Span {
-    //             span_id: 2,
-    //             parent_id: 1,
-    //             ..Default::default()
-    //         },
-    //     ];
-    //     let chunk_with_null_priority = vec![
-    //         pb::Span {
-    //             span_id: 1,
-    //             metrics: HashMap::from([
-    //                 (SAMPLING_PRIORITY_KEY.to_string(), 0.0),
-    //                 ("_dd.top_level".to_string(), 1.0),
-    //             ]),
-    //             ..Default::default()
-    //         },
-    //         pb::Span {
-    //             span_id: 2,
-    //             parent_id: 1,
-    //             ..Default::default()
-    //         },
-    //     ];
-    //     let chunk_without_priority = vec![
-    //         pb::Span {
-    //             span_id: 1,
-    //             metrics: HashMap::from([("_dd.top_level".to_string(), 1.0)]),
-    //             ..Default::default()
-    //         },
-    //         pb::Span {
-    //             span_id: 2,
-    //             parent_id: 1,
-    //             ..Default::default()
-    //         },
-    //     ];
-    //     let chunk_with_error = vec![
-    //         pb::Span {
-    //             span_id: 1,
-    //             error: 1,
-    //             metrics: HashMap::from([
-    //                 (SAMPLING_PRIORITY_KEY.to_string(), 0.0),
-    //                 ("_dd.top_level".to_string(), 1.0),
-    //             ]),
-    //             ..Default::default()
-    //         },
-    //         pb::Span {
-    //             span_id: 2,
-    //             parent_id: 1,
-    //             ..Default::default()
-    //         },
-    //     ];
-    //     let chunk_with_a_single_span = vec![
-    //         pb::Span {
-    //             span_id: 1,
-    //             metrics: HashMap::from([
-    //                 (SAMPLING_PRIORITY_KEY.to_string(), 0.0),
-    //                 ("_dd.top_level".to_string(), 1.0),
-    //             ]),
-    //             ..Default::default()
-    //         },
-    //         pb::Span {
-    //             span_id: 2,
-    //             parent_id: 1,
-    //             metrics: HashMap::from([(SAMPLING_SINGLE_SPAN_MECHANISM.to_string(), 8.0)]),
-    //             ..Default::default()
-    //         },
-    //     ];
-    //     let chunk_with_analyzed_span = vec![
-    //         pb::Span {
-    //             span_id: 1,
-    //             metrics: HashMap::from([
-    //                 (SAMPLING_PRIORITY_KEY.to_string(), 0.0),
-    //                 ("_dd.top_level".to_string(), 1.0),
-    //             ]),
-    //             ..Default::default()
-    //         },
-    //         pb::Span {
-    //             span_id: 2,
-    //             parent_id: 1,
-    //             metrics: HashMap::from([(SAMPLING_ANALYTICS_RATE_KEY.to_string(), 1.0)]),
-    //             ..Default::default()
-    //         },
-    //     ];
-    //
-    //     let chunks_and_expected_sampled_spans = vec![
-    //         (chunk_with_priority, 2),
-    //         (chunk_with_null_priority, 0),
-    //         (chunk_without_priority, 2),
-    //         (chunk_with_error, 2),
-    //         (chunk_with_a_single_span, 1),
-    //         (chunk_with_analyzed_span, 1),
-    //     ];
-    //
-    //     for (chunk, expected_count) in chunks_and_expected_sampled_spans.into_iter() {
-    //         let mut traces = vec![chunk];
-    //         drop_chunks(&mut traces);
-    //         if expected_count == 0 {
-    //             assert!(traces.is_empty());
-    //         } else {
-    //             assert_eq!(traces[0].len(), expected_count);
-    //         }
-    //     }
-    // }
+
+    #[test]
+    fn test_drop_chunks() {
+        let chunk_with_priority = vec![
+            pb::Span {
+                span_id: 1,
+                metrics: HashMap::from([
+                    (SAMPLING_PRIORITY_KEY.to_string(), 1.0),
+                    ("_dd.top_level".to_string(), 1.0),
+                ]),
+                ..Default::default()
+            },
+            pb::Span {
+                span_id: 2,
+                parent_id: 1,
+                ..Default::default()
+            },
+        ];
+        let chunk_with_null_priority = vec![
+            pb::Span {
+                span_id: 1,
+                metrics: HashMap::from([
+                    (SAMPLING_PRIORITY_KEY.to_string(), 0.0),
+                    ("_dd.top_level".to_string(), 1.0),
+                ]),
+                ..Default::default()
+            },
+            pb::Span {
+                span_id: 2,
+                parent_id: 1,
+                ..Default::default()
+            },
+        ];
+        let chunk_without_priority = vec![
+            pb::Span {
+                span_id: 1,
+                metrics: HashMap::from([("_dd.top_level".to_string(), 1.0)]),
+                ..Default::default()
+            },
+            pb::Span {
+                span_id: 2,
+                parent_id: 1,
+                ..Default::default()
+            },
+        ];
+        let chunk_with_error = vec![
+            pb::Span {
+                span_id: 1,
+                error: 1,
+                metrics: HashMap::from([
+                    (SAMPLING_PRIORITY_KEY.to_string(), 0.0),
+                    ("_dd.top_level".to_string(), 1.0),
+                ]),
+                ..Default::default()
+            },
+            pb::Span {
+                span_id: 2,
+                parent_id: 1,
+                ..Default::default()
+            },
+        ];
+        let chunk_with_a_single_span = vec![
+            pb::Span {
+                span_id: 1,
+                metrics: HashMap::from([
+                    (SAMPLING_PRIORITY_KEY.to_string(), 0.0),
+                    ("_dd.top_level".to_string(), 1.0),
+                ]),
+                ..Default::default()
+            },
+            pb::Span {
+                span_id: 2,
+                parent_id: 1,
+                metrics: HashMap::from([(SPAN_SAMPLING_MECHANISM_KEY.to_string(), 8.0)]),
+                ..Default::default()
+            },
+        ];
+        let chunk_with_analyzed_span = vec![
+            pb::Span {
+                span_id: 1,
+                metrics: HashMap::from([
+                    (SAMPLING_PRIORITY_KEY.to_string(), 0.0),
+                    ("_dd.top_level".to_string(), 1.0),
+                ]),
+                ..Default::default()
+            },
+            pb::Span {
+                span_id: 2,
+                parent_id: 1,
+                metrics: HashMap::from([(SAMPLING_ANALYTICS_RATE_KEY.to_string(), 1.0)]),
+                ..Default::default()
+            },
+        ];
+
+        let chunks_and_expected_sampled_spans = vec![
+            (chunk_with_priority, 2),
+            (chunk_with_null_priority, 0),
+            (chunk_without_priority, 2),
+            (chunk_with_error, 2),
+            (chunk_with_a_single_span, 1),
+            (chunk_with_analyzed_span, 1),
+        ];
+
+        for (chunk, expected_count) in chunks_and_expected_sampled_spans.into_iter() {
+            let mut traces = vec![chunk];
+            drop_chunks(&mut traces);
+            if expected_count == 0 {
+                assert!(traces.is_empty());
+            } else {
+                assert_eq!(traces[0].len(), expected_count);
+            }
+        }
+    }
+
+    #[test]
+    fn test_drop_chunks_keeps_span_sampled_spans() {
+        let span_sampler = SpanSampler::parse_rules(r#"[{"name": "db.query"}]"#).unwrap();
+        let mut chunk = vec![
+            pb::Span {
+                span_id: 1,
+                name: "http.request".to_string(),
+                metrics: HashMap::from([
+                    (SAMPLING_PRIORITY_KEY.to_string(), -1.0),
+                    ("_dd.top_level".to_string(), 1.0),
+                ]),
+                ..Default::default()
+            },
+            pb::Span {
+                span_id: 2,
+                parent_id: 1,
+                name: "db.query".to_string(),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(span_sampler.sample_chunk(&mut chunk), 1);
+
+        let mut traces = vec![chunk];
+        let dropped_counts = drop_chunks(&mut traces);
+        assert_eq!(dropped_counts.dropped_p0_spans, 1);
+        assert_eq!(traces[0].len(), 1);
+        assert_eq!(traces[0][0].name, "db.query");
+    }
 
     #[cfg_attr(miri, ignore)]
     #[test]
@@ -57,3 +57,13 @@ pub extern "C" fn ddog_Option_U32_some(v: u32) -> Option<u32> {
 pub extern "C" fn ddog_Option_U32_none() -> Option<u32> {
     Option::None
 }
+
+#[no_mangle]
+pub extern "C" fn ddog_Option_I32_some(v: i32) -> Option<i32> {
+    Option::Some(v)
+}
+
+#[no_mangle]
+pub extern "C" fn ddog_Option_I32_none() -> Option<i32> {
+    Option::None
+}
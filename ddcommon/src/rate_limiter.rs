@@ -102,6 +102,12 @@ impl LocalLimiter {
         }
         previous_hits
     }
+
+    /// Returns whether a call to inc() with the same limit would currently succeed, without
+    /// taking from the limit.
+    pub fn has_capacity(&self, limit: u32) -> bool {
+        self.update(limit, 0) / self.granularity < limit as i64
+    }
 }
 
 impl Limiter for LocalLimiter {
@@ -195,4 +201,18 @@ mod tests {
         // And now 1 succeeds again.
         assert!(limiter.inc(1));
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_has_capacity() {
+        let limiter = LocalLimiter::default();
+        // Checking does not take from the limit
+        for _ in 0..5 {
+            assert!(limiter.has_capacity(2));
+        }
+        while limiter.inc(2) {
+            sleep(Duration::from_micros(100));
+        }
+        assert!(!limiter.has_capacity(2));
+    }
 }
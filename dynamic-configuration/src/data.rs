@@ -3,7 +3,7 @@
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[cfg_attr(feature = "test", derive(Default, Serialize))]
 pub struct DynamicConfigTarget {
     pub service: String,
@@ -25,22 +25,20 @@ pub(crate) struct TracingHeaderTag {
     pub tag_name: String,
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TracingSamplingRuleProvenance {
     Customer,
     Dynamic,
 }
 
-#[derive(Debug, Deserialize)]
-#[cfg_attr(feature = "test", derive(Serialize))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TracingSamplingRuleTag {
     pub key: String,
     pub value_glob: String,
 }
 
-#[derive(Debug, Deserialize)]
-#[cfg_attr(feature = "test", derive(Serialize))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TracingSamplingRule {
     pub service: String,
     pub name: Option<String>,
@@ -0,0 +1,354 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::data::{DynamicConfigFile, DynamicConfigTarget};
+use crate::Configs;
+use ddtelemetry::data::{Configuration, ConfigurationOrigin};
+use std::collections::{BTreeMap, HashMap};
+
+/// The settings which can be configured remotely.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ConfigName {
+    TracingHeaderTags,
+    TracingSampleRate,
+    LogInjectionEnabled,
+    TracingTags,
+    TracingEnabled,
+    TracingSamplingRules,
+}
+
+impl ConfigName {
+    /// The environment variable configuring the setting locally, which is also its name in
+    /// telemetry.
+    pub fn env_var(self) -> &'static str {
+        match self {
+            ConfigName::TracingHeaderTags => "DD_TRACE_HEADER_TAGS",
+            ConfigName::TracingSampleRate => "DD_TRACE_SAMPLE_RATE",
+            ConfigName::LogInjectionEnabled => "DD_LOGS_INJECTION",
+            ConfigName::TracingTags => "DD_TAGS",
+            ConfigName::TracingEnabled => "DD_TRACE_ENABLED",
+            ConfigName::TracingSamplingRules => "DD_TRACE_SAMPLING_RULES",
+        }
+    }
+}
+
+impl Configs {
+    pub fn name(&self) -> ConfigName {
+        match self {
+            Configs::TracingHeaderTags(_) => ConfigName::TracingHeaderTags,
+            Configs::TracingSampleRate(_) => ConfigName::TracingSampleRate,
+            Configs::LogInjectionEnabled(_) => ConfigName::LogInjectionEnabled,
+            Configs::TracingTags(_) => ConfigName::TracingTags,
+            Configs::TracingEnabled(_) => ConfigName::TracingEnabled,
+            Configs::TracingSamplingRules(_) => ConfigName::TracingSamplingRules,
+        }
+    }
+
+    /// The value as reported in telemetry, in the format of the environment variable.
+    pub fn telemetry_value(&self) -> String {
+        match self {
+            Configs::TracingHeaderTags(tags) => {
+                let mut tags: Vec<_> = tags
+                    .iter()
+                    .map(|(header, tag)| format!("{header}:{tag}"))
+                    .collect();
+                tags.sort();
+                tags.join(",")
+            }
+            Configs::TracingSampleRate(rate) => rate.to_string(),
+            Configs::LogInjectionEnabled(enabled) | Configs::TracingEnabled(enabled) => {
+                enabled.to_string()
+            }
+            Configs::TracingTags(tags) => tags.join(","),
+            Configs::TracingSamplingRules(rules) => {
+                serde_json::to_string(rules).unwrap_or_default()
+            }
+        }
+    }
+}
+
+/// A change of the effective value of a setting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    pub name: ConfigName,
+    /// The new value, or None if the setting reverted to its default.
+    pub value: Option<Configs>,
+    pub origin: ConfigurationOrigin,
+}
+
+impl From<&ConfigChange> for Configuration {
+    fn from(change: &ConfigChange) -> Self {
+        Configuration {
+            name: change.name.env_var().to_string(),
+            value: change
+                .value
+                .as_ref()
+                .map(Configs::telemetry_value)
+                .unwrap_or_default(),
+            origin: change.origin.clone(),
+        }
+    }
+}
+
+struct RemoteFile {
+    target: DynamicConfigTarget,
+    configs: Vec<Configs>,
+}
+
+/// Merges the dynamic configuration files of a tracer with its local configuration.
+///
+/// Remote values take precedence over local ones, which take precedence over the defaults.
+/// Among the files targeting the tracer, those targeting its service take precedence over
+/// those targeting its env, which take precedence over those targeting any service and env
+/// (`*`). Ties are broken by the order of the file ids.
+pub struct DynamicConfigEngine {
+    service: String,
+    env: String,
+    local: HashMap<ConfigName, Configs>,
+    files: BTreeMap<String, RemoteFile>,
+    effective: HashMap<ConfigName, (Configs, ConfigurationOrigin)>,
+}
+
+impl DynamicConfigEngine {
+    pub fn new(service: &str, env: &str) -> Self {
+        DynamicConfigEngine {
+            service: service.to_string(),
+            env: env.to_string(),
+            local: HashMap::new(),
+            files: BTreeMap::new(),
+            effective: HashMap::new(),
+        }
+    }
+
+    /// Sets a value configured locally, e.g. from the environment.
+    pub fn set_local(&mut self, config: Configs) -> Vec<ConfigChange> {
+        self.local.insert(config.name(), config);
+        self.update()
+    }
+
+    /// Adds or replaces the file with the given id, e.g. its remote config path.
+    pub fn add_file(&mut self, id: &str, file: DynamicConfigFile) -> Vec<ConfigChange> {
+        self.files.insert(
+            id.to_string(),
+            RemoteFile {
+                target: file.service_target,
+                configs: file.lib_config.into(),
+            },
+        );
+        self.update()
+    }
+
+    pub fn remove_file(&mut self, id: &str) -> Vec<ConfigChange> {
+        if self.files.remove(id).is_none() {
+            return vec![];
+        }
+        self.update()
+    }
+
+    /// The effective value of a setting and where it comes from, or None if it has its default
+    /// value.
+    pub fn get(&self, name: ConfigName) -> Option<(&Configs, &ConfigurationOrigin)> {
+        self.effective
+            .get(&name)
+            .map(|(config, origin)| (config, origin))
+    }
+
+    /// The effective remote values, e.g. to apply them all at once.
+    pub fn remote_configs(&self) -> Vec<Configs> {
+        let mut configs: Vec<_> = self
+            .effective
+            .values()
+            .filter(|(_, origin)| *origin == ConfigurationOrigin::RemoteConfig)
+            .map(|(config, _)| config.clone())
+            .collect();
+        configs.sort_by_key(Configs::name);
+        configs
+    }
+
+    /// The precedence of a file targeting `target`, or None if it does not target the tracer.
+    fn precedence(&self, target: &DynamicConfigTarget) -> Option<u8> {
+        let service = if target.service == self.service {
+            2
+        } else if target.service == "*" {
+            0
+        } else {
+            return None;
+        };
+        let env = if target.env == self.env {
+            1
+        } else if target.env == "*" {
+            0
+        } else {
+            return None;
+        };
+        Some(service + env)
+    }
+
+    fn update(&mut self) -> Vec<ConfigChange> {
+        let mut files: Vec<_> = self
+            .files
+            .values()
+            .filter_map(|file| Some((self.precedence(&file.target)?, file)))
+            .collect();
+        // Stable, so ties remain ordered by file id, and later files override earlier ones.
+        files.sort_by_key(|(precedence, _)| *precedence);
+
+        let mut effective: HashMap<_, _> = self
+            .local
+            .iter()
+            .map(|(name, config)| (*name, (config.clone(), ConfigurationOrigin::EnvVar)))
+            .collect();
+        for (_, file) in files {
+            for config in file.configs.iter() {
+                effective.insert(
+                    config.name(),
+                    (config.clone(), ConfigurationOrigin::RemoteConfig),
+                );
+            }
+        }
+
+        let mut changes: Vec<_> = effective
+            .iter()
+            .filter(|(name, value)| self.effective.get(name) != Some(value))
+            .map(|(name, (config, origin))| ConfigChange {
+                name: *name,
+                value: Some(config.clone()),
+                origin: origin.clone(),
+            })
+            .chain(
+                self.effective
+                    .keys()
+                    .filter(|name| !effective.contains_key(name))
+                    .map(|name| ConfigChange {
+                        name: *name,
+                        value: None,
+                        origin: ConfigurationOrigin::Default,
+                    }),
+            )
+            .collect();
+        changes.sort_by_key(|change| change.name);
+        self.effective = effective;
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json;
+
+    fn file(service: &str, env: &str, lib_config: &str) -> DynamicConfigFile {
+        parse_json(
+            format!(
+                r#"{{"action": "enable", "service_target": {{"service": "{service}", "env": "{env}"}}, "lib_config": {lib_config}}}"#
+            )
+            .as_bytes(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_precedence() {
+        let mut engine = DynamicConfigEngine::new("web", "prod");
+        let changes = engine.set_local(Configs::TracingSampleRate(0.5));
+        assert_eq!(
+            changes,
+            vec![ConfigChange {
+                name: ConfigName::TracingSampleRate,
+                value: Some(Configs::TracingSampleRate(0.5)),
+                origin: ConfigurationOrigin::EnvVar,
+            }]
+        );
+
+        let changes = engine.add_file(
+            "any",
+            file(
+                "*",
+                "*",
+                r#"{"tracing_sample_rate": 0.1, "log_injection_enabled": true}"#,
+            ),
+        );
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].name, ConfigName::TracingSampleRate);
+        assert_eq!(changes[0].origin, ConfigurationOrigin::RemoteConfig);
+
+        // More specific targets take precedence, whatever the order of the files.
+        engine.add_file("a", file("web", "prod", r#"{"tracing_sample_rate": 0.3}"#));
+        let changes = engine.add_file("b", file("*", "prod", r#"{"tracing_sample_rate": 0.2}"#));
+        assert!(changes.is_empty());
+        assert_eq!(
+            engine.get(ConfigName::TracingSampleRate),
+            Some((
+                &Configs::TracingSampleRate(0.3),
+                &ConfigurationOrigin::RemoteConfig
+            ))
+        );
+
+        // Files targeting other services are ignored.
+        let changes = engine.add_file("other", file("db", "prod", r#"{"tracing_enabled": false}"#));
+        assert!(changes.is_empty());
+        assert_eq!(engine.remote_configs().len(), 2);
+    }
+
+    #[test]
+    fn test_revert() {
+        let mut engine = DynamicConfigEngine::new("web", "prod");
+        engine.set_local(Configs::TracingSampleRate(0.5));
+        engine.add_file(
+            "a",
+            file(
+                "web",
+                "prod",
+                r#"{"tracing_sample_rate": 0.1, "tracing_tags": ["a:b", "c:d"]}"#,
+            ),
+        );
+        assert!(engine.remove_file("unknown").is_empty());
+
+        let changes = engine.remove_file("a");
+        assert_eq!(
+            changes,
+            vec![
+                ConfigChange {
+                    name: ConfigName::TracingSampleRate,
+                    value: Some(Configs::TracingSampleRate(0.5)),
+                    origin: ConfigurationOrigin::EnvVar,
+                },
+                ConfigChange {
+                    name: ConfigName::TracingTags,
+                    value: None,
+                    origin: ConfigurationOrigin::Default,
+                },
+            ]
+        );
+        assert_eq!(engine.get(ConfigName::TracingTags), None);
+    }
+
+    #[test]
+    fn test_telemetry() {
+        let mut engine = DynamicConfigEngine::new("web", "prod");
+        let changes = engine.add_file(
+            "a",
+            file(
+                "web",
+                "*",
+                r#"{"tracing_header_tags": [{"header": "X-B", "tag_name": "b"}, {"header": "X-A", "tag_name": "a"}], "tracing_tags": ["a:b", "c:d"], "tracing_enabled": true}"#,
+            ),
+        );
+        let configurations: Vec<Configuration> = changes.iter().map(Configuration::from).collect();
+        let values: Vec<_> = configurations
+            .iter()
+            .map(|c| (c.name.as_str(), c.value.as_str()))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("DD_TRACE_HEADER_TAGS", "X-A:a,X-B:b"),
+                ("DD_TAGS", "a:b,c:d"),
+                ("DD_TRACE_ENABLED", "true"),
+            ]
+        );
+        assert!(configurations
+            .iter()
+            .all(|c| c.origin == ConfigurationOrigin::RemoteConfig));
+    }
+}
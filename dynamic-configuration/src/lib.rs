@@ -5,6 +5,7 @@ use crate::data::{DynamicConfig, DynamicConfigFile, TracingSamplingRule};
 use std::collections::HashMap;
 
 pub mod data;
+pub mod engine;
 
 impl From<DynamicConfig> for Vec<Configs> {
     fn from(value: DynamicConfig) -> Self {
@@ -33,6 +34,7 @@ impl From<DynamicConfig> for Vec<Configs> {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum Configs {
     TracingHeaderTags(HashMap<String, String>),
     TracingSampleRate(f64),
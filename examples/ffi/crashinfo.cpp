@@ -166,7 +166,8 @@ int main(void) {
                "Failed to set timestamp");
 
   ddog_crasht_ProcInfo procinfo = {
-    .pid = 42
+    .pid = 42,
+    .tid = ddog_Option_U32_some(43),
   };
 
   check_result(ddog_crasht_CrashInfo_set_procinfo(crashinfo.get(), procinfo),
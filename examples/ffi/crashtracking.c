@@ -89,7 +89,8 @@ int main(int argc, char **argv) {
   return 0;
 }
 
-/* Example output file:
+/* Example output file, with the stack trimmed. Its schema is
+ * crashtracker/src/rfc5_crash_info/crash_info.schema.json:
 {
   "counters": {
     "unwinding": 0,
@@ -97,120 +98,74 @@ int main(int argc, char **argv) {
     "serializing": 1,
     "collecting_sample": 0
   },
+  "data_schema_version": "1.0",
+  "error": {
+    "is_crash": true,
+    "kind": "UnixSignal",
+    "source_type": "Crashtracking",
+    "stack": {
+      "format": "Datadog Crashtracker 1.0",
+      "frames": [
+        {
+          "ip": "0x100f6f518",
+          "sp": "0x16f965940",
+          "symbol_address": "0x100f6f518",
+          "column": 18,
+          "file": "/Users/daniel.schwartznarbonne/go/src/github.com/DataDog/libdatadog/crashtracker/src/collector/crash_handler.rs",
+          "function": "emit_crashreport<std::process::ChildStdin>",
+          "line": 379
+        },
+        {
+          "ip": "0x100f6f518",
+          "sp": "0x16f965940",
+          "symbol_address": "0x100f6f518",
+          "column": 23,
+          "file": "/Users/daniel.schwartznarbonne/go/src/github.com/DataDog/libdatadog/crashtracker/src/collector/crash_handler.rs",
+          "function": "handle_posix_signal_impl",
+          "line": 414
+        },
+        {
+          "ip": "0x10049bd94",
+          "sp": "0x16f965b10",
+          "symbol_address": "0x10049bd94",
+          "function": "_main"
+        }
+      ]
+    }
+  },
   "incomplete": false,
   "metadata": {
     "library_name": "crashtracking-test",
     "library_version": "12.34.56",
-    "family": "crashtracking-test",
-    "tags": []
+    "family": "crashtracking-test"
   },
   "os_info": {
-    "os_type": "Macos",
-    "version": {
-      "Semantic": [
-        14,
-        5,
-        0
-      ]
-    },
-    "edition": null,
-    "codename": null,
-    "bitness": "X64",
-    "architecture": "arm64"
+    "architecture": "arm64",
+    "bitness": "64-bit",
+    "os_type": "Mac OS",
+    "version": "14.5.0"
   },
   "proc_info": {
     "pid": 95565
   },
-  "siginfo": {
-    "signum": 11,
-    "signame": "SIGSEGV"
+  "sig_info": {
+    "si_addr": "0x0000000000000000",
+    "si_code": 2,
+    "si_code_human_readable": "SEGV_ACCERR",
+    "si_signo": 11,
+    "si_signo_human_readable": "SIGSEGV"
   },
   "span_ids": [
-    42
-  ],
-  "stacktrace": [
-    {
-      "ip": "0x100f702ac",
-      "names": [
-        {
-          "colno": 5,
-          "filename":
-"/Users/daniel.schwartznarbonne/.cargo/registry/src/index.crates.io-6f17d22bba15001f/backtrace-0.3.71/src/backtrace/libunwind.rs",
-          "lineno": 105,
-          "name": "trace"
-        },
-        {
-          "colno": 5,
-          "filename":
-"/Users/daniel.schwartznarbonne/.cargo/registry/src/index.crates.io-6f17d22bba15001f/backtrace-0.3.71/src/backtrace/mod.rs",
-          "lineno": 66,
-          "name":
-"trace_unsynchronized<datadog_crashtracker::collectors::emit_backtrace_by_frames::{closure_env#0}<std::process::ChildStdin>>"
-        },
-        {
-          "colno": 5,
-          "filename":
-"/Users/daniel.schwartznarbonne/go/src/github.com/DataDog/libdatadog/crashtracker/src/collectors.rs",
-          "lineno": 33,
-          "name": "emit_backtrace_by_frames<std::process::ChildStdin>"
-        }
-      ],
-      "sp": "0x16f9658c0",
-      "symbol_address": "0x100f702ac"
-    },
-    {
-      "ip": "0x100f6f518",
-      "names": [
-        {
-          "colno": 18,
-          "filename":
-"/Users/daniel.schwartznarbonne/go/src/github.com/DataDog/libdatadog/crashtracker/src/crash_handler.rs",
-          "lineno": 379,
-          "name": "emit_crashreport<std::process::ChildStdin>"
-        },
-        {
-          "colno": 23,
-          "filename":
-"/Users/daniel.schwartznarbonne/go/src/github.com/DataDog/libdatadog/crashtracker/src/crash_handler.rs",
-          "lineno": 414,
-          "name": "handle_posix_signal_impl"
-        },
-        {
-          "colno": 13,
-          "filename":
-"/Users/daniel.schwartznarbonne/go/src/github.com/DataDog/libdatadog/crashtracker/src/crash_handler.rs",
-          "lineno": 264,
-          "name": "handle_posix_sigaction"
-        }
-      ],
-      "sp": "0x16f965940",
-      "symbol_address": "0x100f6f518"
-    },
-    {
-      "ip": "0x186b9b584",
-      "names": [
-        {
-          "name": "__simple_esappend"
-        }
-      ],
-      "sp": "0x16f965ae0",
-      "symbol_address": "0x186b9b584"
-    },
     {
-      "ip": "0x10049bd94",
-      "names": [
-        {
-          "name": "_main"
-        }
-      ],
-      "sp": "0x16f965b10",
-      "symbol_address": "0x10049bd94"
+      "id": "42"
     }
   ],
+  "timestamp": "2024-07-19 16:52:16.422378 UTC",
   "trace_ids": [
-    18446744073709551617
+    {
+      "id": "18446744073709551617"
+    }
   ],
-  "timestamp": "2024-07-19T16:52:16.422378Z",
   "uuid": "a42add90-0e60-4799-b9f7-cbe0ebec4f27"
 }
 */
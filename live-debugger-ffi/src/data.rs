@@ -63,6 +63,8 @@ pub struct LogProbe<'a> {
     pub when: &'a ProbeCondition,
     pub capture: &'a CaptureConfiguration,
     pub capture_snapshot: bool,
+    /// Defaults to 1 for probes capturing snapshots and to 5000 for the other probes, when no
+    /// sampling is configured.
     pub sampling_snapshots_per_second: u32,
 }
 
@@ -242,6 +244,118 @@ impl<'a> From<&'a datadog_live_debugger::Probe> for Probe<'a> {
     }
 }
 
+fn to_string_option(s: &CharSlice) -> core::option::Option<String> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s.to_utf8_lossy().into_owned())
+    }
+}
+
+impl From<&CharSliceVec<'_>> for Vec<String> {
+    fn from(from: &CharSliceVec<'_>) -> Self {
+        let strings = unsafe { std::slice::from_raw_parts(from.strings, from.string_count) };
+        strings
+            .iter()
+            .map(|s| s.to_utf8_lossy().into_owned())
+            .collect()
+    }
+}
+
+impl From<&ProbeTarget<'_>> for datadog_live_debugger::ProbeTarget {
+    fn from(from: &ProbeTarget<'_>) -> Self {
+        datadog_live_debugger::ProbeTarget {
+            type_name: to_string_option(&from.type_name),
+            method_name: to_string_option(&from.method_name),
+            source_file: to_string_option(&from.source_file),
+            signature: from
+                .signature
+                .to_std_ref()
+                .map(|s| s.to_utf8_lossy().into_owned()),
+            lines: unsafe { std::slice::from_raw_parts(from.lines, from.lines_count as usize) }
+                .to_vec(),
+            in_body_location: from.in_body_location,
+        }
+    }
+}
+
+impl From<&ProbeType<'_>> for datadog_live_debugger::ProbeType {
+    fn from(from: &ProbeType<'_>) -> Self {
+        match from {
+            ProbeType::Metric(metric) => {
+                datadog_live_debugger::ProbeType::Metric(datadog_live_debugger::MetricProbe {
+                    kind: metric.kind,
+                    name: metric.name.to_utf8_lossy().into_owned(),
+                    value: metric.value.clone(),
+                })
+            }
+            ProbeType::Log(log) => {
+                datadog_live_debugger::ProbeType::Log(datadog_live_debugger::LogProbe {
+                    segments: log.segments.clone(),
+                    when: log.when.clone(),
+                    capture: log.capture.clone(),
+                    capture_snapshot: log.capture_snapshot,
+                    sampling_snapshots_per_second: log.sampling_snapshots_per_second,
+                })
+            }
+            ProbeType::Span => {
+                datadog_live_debugger::ProbeType::Span(datadog_live_debugger::SpanProbe {})
+            }
+            ProbeType::SpanDecoration(span_decoration) => {
+                let tags = unsafe {
+                    std::slice::from_raw_parts(
+                        span_decoration.span_tags,
+                        span_decoration.span_tags_num,
+                    )
+                };
+                let num_conditions = tags.iter().filter(|tag| tag.next_condition).count();
+                let mut conditions = unsafe {
+                    std::slice::from_raw_parts(span_decoration.conditions, num_conditions)
+                }
+                .iter();
+                let mut decorations: Vec<datadog_live_debugger::SpanProbeDecoration> = vec![];
+                for tag in tags {
+                    if tag.next_condition {
+                        if let Some(condition) = conditions.next() {
+                            decorations.push(datadog_live_debugger::SpanProbeDecoration {
+                                condition: (*condition).clone(),
+                                tags: vec![],
+                            });
+                        }
+                    }
+                    if let Some(decoration) = decorations.last_mut() {
+                        decoration.tags.push((
+                            tag.tag.name.to_utf8_lossy().into_owned(),
+                            tag.tag.value.clone(),
+                        ));
+                    }
+                }
+                datadog_live_debugger::ProbeType::SpanDecoration(
+                    datadog_live_debugger::SpanDecorationProbe {
+                        target: span_decoration.target,
+                        decorations,
+                    },
+                )
+            }
+        }
+    }
+}
+
+/// Copies a probe back into its rust representation, to use the core functions on it.
+impl From<&Probe<'_>> for datadog_live_debugger::Probe {
+    fn from(from: &Probe<'_>) -> Self {
+        datadog_live_debugger::Probe {
+            id: from.id.to_utf8_lossy().into_owned(),
+            version: from.version,
+            language: to_string_option(&from.language),
+            tags: (&from.tags).into(),
+            target: (&from.target).into(),
+            evaluate_at: from.evaluate_at,
+            probe: (&from.probe).into(),
+        }
+    }
+}
+
 impl<'a> From<&Probe<'a>> for ProbeMetadata<'a> {
     fn from(val: &Probe<'a>) -> Self {
         fn to_cow_option<'a>(s: &CharSlice<'a>) -> core::option::Option<Cow<'a, str>> {
@@ -1,8 +1,12 @@
 // Unless explicitly stated otherwise all files in this repository are licensed under the Apache
 // License Version 2.0. This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2021-Present Datadog, Inc.
 
+use crate::data::{Probe, ProbeType};
 use datadog_live_debugger::debugger_defs::SnapshotEvaluationError;
-use datadog_live_debugger::{DslString, ProbeCondition, ProbeValue, ResultError, ResultValue};
+use datadog_live_debugger::{
+    CaptureDecision, CompiledCondition, DslString, ProbeCondition, ProbeRateLimiter, ProbeValue,
+    ResultError, ResultValue,
+};
 use ddcommon_ffi::slice::AsBytes;
 use ddcommon_ffi::CharSlice;
 use std::borrow::Cow;
@@ -230,6 +234,67 @@ pub extern "C" fn ddog_evaluate_condition(
     }
 }
 
+/// Compiles a condition once, for conditions evaluated repeatedly.
+#[no_mangle]
+pub extern "C" fn ddog_compile_condition(condition: &ProbeCondition) -> Box<CompiledCondition> {
+    Box::new(datadog_live_debugger::compile_condition(condition))
+}
+
+#[no_mangle]
+pub extern "C" fn ddog_drop_compiled_condition(_: Box<CompiledCondition>) {}
+
+#[no_mangle]
+pub extern "C" fn ddog_evaluate_compiled_condition(
+    condition: &CompiledCondition,
+    context: &mut c_void,
+) -> ConditionEvaluationResult {
+    let mut ctx = EvalCtx::new(context);
+    match datadog_live_debugger::eval_compiled_condition(&mut ctx, condition) {
+        Ok(true) => ConditionEvaluationResult::Success,
+        Ok(false) => ConditionEvaluationResult::Failure,
+        Err(error) => ConditionEvaluationResult::Error(Box::new(vec![error])),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn ddog_probe_rate_limiter_new(
+    global_snapshots_per_second: u32,
+    global_logs_per_second: u32,
+) -> Box<ProbeRateLimiter> {
+    Box::new(ProbeRateLimiter::new(
+        global_snapshots_per_second,
+        global_logs_per_second,
+    ))
+}
+
+#[no_mangle]
+pub extern "C" fn ddog_probe_rate_limiter_drop(_: Box<ProbeRateLimiter>) {}
+
+/// To be called once the condition of a probe is met, before capturing. On ThrottlingStarted,
+/// a diagnostic is to be sent with ddog_debugger_throttled_diagnostics_create.
+#[no_mangle]
+pub extern "C" fn ddog_probe_rate_limiter_try_capture(
+    limiter: &ProbeRateLimiter,
+    probe: &Probe,
+) -> CaptureDecision {
+    match probe.probe {
+        ProbeType::Log(ref log) => limiter.try_capture(
+            &probe.id.to_utf8_lossy(),
+            log.capture_snapshot,
+            log.sampling_snapshots_per_second,
+        ),
+        _ => CaptureDecision::Capture,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn ddog_probe_rate_limiter_remove_probe(
+    limiter: &ProbeRateLimiter,
+    probe_id: CharSlice,
+) {
+    limiter.remove_probe(&probe_id.to_utf8_lossy());
+}
+
 pub fn ddog_evaluate_string<'a>(
     condition: &'a DslString,
     context: &'a mut c_void,
@@ -6,7 +6,8 @@ use std::borrow::Cow;
 use std::collections::hash_map;
 use std::mem::transmute;
 // Alias to prevent cbindgen panic
-use crate::data::Probe;
+use crate::data::{Probe, ProbeType};
+use crate::evaluator::VoidCollection;
 use datadog_live_debugger::debugger_defs::{
     Capture as DebuggerCaptureAlias, Capture, Captures, DebuggerData, DebuggerPayload, Diagnostics,
     DiagnosticsError, Entry, Fields, ProbeMetadata, ProbeMetadataLocation, ProbeStatus, Snapshot,
@@ -14,9 +15,11 @@ use datadog_live_debugger::debugger_defs::{
 };
 use datadog_live_debugger::sender::generate_new_id;
 use datadog_live_debugger::{
-    add_redacted_name, add_redacted_type, is_redacted_name, is_redacted_type,
+    add_redacted_name, add_redacted_type, capture_named_value, is_redacted_name, is_redacted_type,
+    validate_probe, CaptureConfiguration, ValueKind,
 };
 use ddcommon_ffi::slice::AsBytes;
+use std::ffi::c_void;
 
 #[repr(C)]
 pub enum FieldType {
@@ -310,6 +313,128 @@ pub extern "C" fn ddog_capture_value_add_field<'a, 'b: 'a, 'c: 'a>(
     fields.insert(key.to_utf8_lossy(), element.into());
 }
 
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct IntrospectedField<'a> {
+    pub name: CharSlice<'a>,
+    pub value: &'a c_void,
+}
+
+/// The callbacks to introspect the values of the language, for capturing them with
+/// ddog_snapshot_capture_field. The collections returned are copied, then freed.
+#[repr(C)]
+#[derive(Clone)]
+pub struct Introspector {
+    pub kind: for<'a> extern "C" fn(&'a mut c_void, &'a c_void) -> ValueKind,
+    /// The returned string is copied: it only needs to stay valid until the next callback.
+    pub type_name: for<'a> extern "C" fn(&'a mut c_void, &'a c_void) -> CharSlice<'a>,
+    /// The returned string is copied: it only needs to stay valid until the next callback.
+    pub stringify: for<'a> extern "C" fn(&'a mut c_void, &'a c_void) -> CharSlice<'a>,
+    pub length: for<'a> extern "C" fn(&'a mut c_void, &'a c_void) -> usize,
+    /// At most the given number of elements, as pointers to values.
+    pub elements: for<'a> extern "C" fn(&'a mut c_void, &'a c_void, usize) -> VoidCollection,
+    /// At most the given number of entries, as pointers to the key and the value of each entry.
+    pub entries: for<'a> extern "C" fn(&'a mut c_void, &'a c_void, usize) -> VoidCollection,
+    /// The fields, as IntrospectedField.
+    pub fields: for<'a> extern "C" fn(&'a mut c_void, &'a c_void) -> VoidCollection,
+}
+
+struct IntrospectCtx<'e> {
+    context: &'e mut c_void,
+    introspector: &'e Introspector,
+}
+
+fn copy_void_collection<T: Copy>(collection: VoidCollection) -> Vec<T> {
+    let mut vec = Vec::with_capacity(collection.count.max(0) as usize);
+    if collection.count > 0 {
+        unsafe {
+            vec.extend_from_slice(std::slice::from_raw_parts(
+                collection.elements as *const T,
+                collection.count as usize,
+            ))
+        };
+    }
+    (collection.free)(collection);
+    vec
+}
+
+impl<'e> datadog_live_debugger::Introspector<'e, c_void> for IntrospectCtx<'e> {
+    fn kind(&mut self, value: &'e c_void) -> ValueKind {
+        (self.introspector.kind)(self.context, value)
+    }
+
+    fn type_name(&mut self, value: &'e c_void) -> Cow<'e, str> {
+        Cow::Owned(
+            (self.introspector.type_name)(self.context, value)
+                .to_utf8_lossy()
+                .into_owned(),
+        )
+    }
+
+    fn stringify(&mut self, value: &'e c_void) -> Cow<'e, str> {
+        Cow::Owned(
+            (self.introspector.stringify)(self.context, value)
+                .to_utf8_lossy()
+                .into_owned(),
+        )
+    }
+
+    fn length(&mut self, value: &'e c_void) -> usize {
+        (self.introspector.length)(self.context, value)
+    }
+
+    fn elements(&mut self, value: &'e c_void, max: usize) -> Vec<&'e c_void> {
+        copy_void_collection((self.introspector.elements)(self.context, value, max))
+    }
+
+    fn entries(&mut self, value: &'e c_void, max: usize) -> Vec<(&'e c_void, &'e c_void)> {
+        copy_void_collection::<[&'e c_void; 2]>((self.introspector.entries)(
+            self.context,
+            value,
+            max,
+        ))
+        .into_iter()
+        .map(|[key, value]| (key, value))
+        .collect()
+    }
+
+    fn fields(&mut self, value: &'e c_void) -> Vec<(Cow<'e, str>, &'e c_void)> {
+        copy_void_collection::<IntrospectedField<'e>>((self.introspector.fields)(
+            self.context,
+            value,
+        ))
+        .into_iter()
+        .map(|field| (field.name.to_utf8_lossy(), field.value))
+        .collect()
+    }
+}
+
+/// Captures a value into a field of the capture, applying the limits of the configuration and
+/// redaction.
+#[no_mangle]
+pub extern "C" fn ddog_snapshot_capture_field<'a>(
+    capture: &mut DebuggerCapture<'a>,
+    r#type: FieldType,
+    name: CharSlice<'a>,
+    value: &'a c_void,
+    introspector: &'a Introspector,
+    context: &'a mut c_void,
+    config: &CaptureConfiguration,
+) {
+    let name = name.to_utf8_lossy();
+    let mut ctx = IntrospectCtx {
+        context,
+        introspector,
+    };
+    let value = capture_named_value(&mut ctx, config, &name, value);
+    let fields = match r#type {
+        FieldType::STATIC => &mut capture.0.static_fields,
+        FieldType::ARG => &mut capture.0.arguments,
+        FieldType::LOCAL => &mut capture.0.locals,
+    };
+    fields.insert(name, value);
+}
+
 #[no_mangle]
 pub extern "C" fn ddog_snapshot_format_new_uuid(buf: &mut [u8; 36]) {
     generate_new_id().as_hyphenated().encode_lower(buf);
@@ -364,6 +489,41 @@ pub extern "C" fn ddog_serialize_debugger_payload(
     callback(CharSlice::from(payload.as_str()))
 }
 
+/// Serializes the payload, reducing the depth of the captured values of a snapshot until it
+/// takes at most max_size bytes. If it is still too large, the captures are dropped.
+pub fn serialize_debugger_payload_bounded(
+    payload: &mut DebuggerPayload,
+    max_size: usize,
+) -> String {
+    loop {
+        let serialized = serialize_debugger_payload(payload);
+        if serialized.len() <= max_size {
+            return serialized;
+        }
+        let DebuggerData::Snapshot(ref mut snapshot) = payload.debugger else {
+            return serialized;
+        };
+        match snapshot.captures_depth() {
+            0 => {
+                if snapshot.captures.take().is_none() {
+                    return serialized;
+                }
+            }
+            depth => snapshot.prune_captures(depth - 1),
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn ddog_serialize_debugger_payload_bounded(
+    payload: &mut DebuggerPayload,
+    max_size: usize,
+    callback: extern "C" fn(CharSlice),
+) {
+    let payload = serialize_debugger_payload_bounded(payload, max_size);
+    callback(CharSlice::from(payload.as_str()))
+}
+
 #[no_mangle]
 pub extern "C" fn ddog_drop_debugger_payload(_: Box<DebuggerPayload>) {}
 
@@ -443,6 +603,79 @@ pub extern "C" fn ddog_debugger_diagnostics_create<'a>(
     ))
 }
 
+/// The warning diagnostic of a probe whose captures are being throttled.
+#[no_mangle]
+pub extern "C" fn ddog_debugger_throttled_diagnostics_create<'a>(
+    probe: &'a Probe,
+    service: CharSlice<'a>,
+    runtime_id: CharSlice<'a>,
+    timestamp: u64,
+) -> Box<DebuggerPayload<'a>> {
+    let probe_id = probe.id.to_utf8_lossy();
+    let details = match probe.probe {
+        ProbeType::Log(ref log) => format!(
+            "Captures exceed the rate limit of {} per second or the global budget",
+            log.sampling_snapshots_per_second
+        ),
+        _ => "Captures exceed the global budget".to_string(),
+    };
+    Box::new(DebuggerPayload {
+        service: service.to_utf8_lossy(),
+        ddsource: Cow::Borrowed("dd_debugger"),
+        timestamp,
+        message: Some(Cow::Owned(format!("Probe {probe_id} warning: {details}"))),
+        debugger: DebuggerData::Diagnostics(Diagnostics {
+            probe_id,
+            probe_version: probe.version,
+            status: ProbeStatus::Warning,
+            runtime_id: runtime_id.to_utf8_lossy(),
+            details: Some(Cow::Owned(details)),
+            ..Default::default()
+        }),
+    })
+}
+
+/// Validates the expressions of a probe when it is installed. Returns the ERROR diagnostic to send
+/// if they are invalid, in which case the probe is not to be installed, or null.
+#[no_mangle]
+pub extern "C" fn ddog_debugger_validation_diagnostics_create<'a>(
+    probe: &'a Probe,
+    service: CharSlice<'a>,
+    runtime_id: CharSlice<'a>,
+    timestamp: u64,
+) -> Option<Box<DebuggerPayload<'a>>> {
+    let errors = validate_probe(&probe.into());
+    if errors.is_empty() {
+        return None;
+    }
+    let probe_id = probe.id.to_utf8_lossy();
+    let message = errors
+        .iter()
+        .map(|error| format!("{}: {}", error.expr, error.message))
+        .collect::<Vec<_>>()
+        .join("; ");
+    Some(Box::new(DebuggerPayload {
+        service: service.to_utf8_lossy(),
+        ddsource: Cow::Borrowed("dd_debugger"),
+        timestamp,
+        message: Some(Cow::Owned(format!(
+            "Encountered error while instrumenting probe {probe_id}: {message}"
+        ))),
+        debugger: DebuggerData::Diagnostics(Diagnostics {
+            probe_id,
+            probe_version: probe.version,
+            status: ProbeStatus::Error,
+            runtime_id: runtime_id.to_utf8_lossy(),
+            exception: Some(DiagnosticsError {
+                r#type: Cow::Borrowed("InvalidExpression"),
+                message: Cow::Owned(message),
+                stacktrace: None,
+            }),
+            ..Default::default()
+        }),
+    }))
+}
+
 #[no_mangle]
 pub extern "C" fn ddog_debugger_diagnostics_set_parent_id<'a>(
     payload: &mut DebuggerPayload<'a>,
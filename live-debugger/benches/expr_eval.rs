@@ -0,0 +1,164 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use datadog_live_debugger::{
+    compile_condition, eval_compiled_condition, eval_condition, parse_json, Evaluator,
+    IntermediateValue, LiveDebuggingData, ProbeType, ResultError, ResultValue,
+};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hint::black_box;
+
+enum Val {
+    Num(f64),
+    Str(String),
+    Vec(Vec<Val>),
+}
+
+struct EvalCtx<'e> {
+    variables: &'e HashMap<&'static str, Val>,
+}
+
+impl<'e> EvalCtx<'e> {
+    fn num(&self, value: IntermediateValue<'e, Val>) -> f64 {
+        match value {
+            IntermediateValue::Number(n) => n,
+            IntermediateValue::Referenced(Val::Num(n)) => *n,
+            _ => f64::NAN,
+        }
+    }
+}
+
+impl<'e> Evaluator<'e, Val> for EvalCtx<'e> {
+    fn equals(&mut self, a: IntermediateValue<'e, Val>, b: IntermediateValue<'e, Val>) -> bool {
+        self.num(a) == self.num(b)
+    }
+
+    fn greater_than(
+        &mut self,
+        a: IntermediateValue<'e, Val>,
+        b: IntermediateValue<'e, Val>,
+    ) -> bool {
+        self.num(a) > self.num(b)
+    }
+
+    fn greater_or_equals(
+        &mut self,
+        a: IntermediateValue<'e, Val>,
+        b: IntermediateValue<'e, Val>,
+    ) -> bool {
+        self.num(a) >= self.num(b)
+    }
+
+    fn fetch_identifier(&mut self, identifier: &str) -> ResultValue<&'e Val> {
+        self.variables.get(identifier).ok_or(ResultError::Undefined)
+    }
+
+    fn fetch_index(&mut self, _: &'e Val, _: IntermediateValue<'e, Val>) -> ResultValue<&'e Val> {
+        Err(ResultError::Undefined)
+    }
+
+    fn fetch_nested(&mut self, _: &'e Val, _: IntermediateValue<'e, Val>) -> ResultValue<&'e Val> {
+        Err(ResultError::Undefined)
+    }
+
+    fn length(&mut self, value: &'e Val) -> usize {
+        match value {
+            Val::Str(s) => s.len(),
+            Val::Vec(v) => v.len(),
+            Val::Num(_) => 0,
+        }
+    }
+
+    fn try_enumerate(&mut self, value: &'e Val) -> ResultValue<Vec<&'e Val>> {
+        match value {
+            Val::Vec(v) => Ok(v.iter().collect()),
+            _ => Err(ResultError::Invalid),
+        }
+    }
+
+    fn stringify(&mut self, value: &'e Val) -> Cow<'e, str> {
+        match value {
+            Val::Num(n) => Cow::Owned(n.to_string()),
+            Val::Str(s) => Cow::Borrowed(s),
+            Val::Vec(_) => Cow::Borrowed("[...]"),
+        }
+    }
+
+    fn get_string(&mut self, value: &'e Val) -> Cow<'e, str> {
+        self.stringify(value)
+    }
+
+    fn convert_index(&mut self, value: &'e Val) -> ResultValue<usize> {
+        match value {
+            Val::Num(n) => Ok(*n as usize),
+            _ => Err(ResultError::Invalid),
+        }
+    }
+
+    fn instanceof(&mut self, _: &'e Val, _: &'e str) -> bool {
+        false
+    }
+}
+
+const PROBE: &str = r#"{
+  "id": "bench",
+  "version": 1,
+  "type": "LOG_PROBE",
+  "where": {"typeName": "Bench", "methodName": "run"},
+  "segments": [{"str": "hit"}],
+  "when": {
+    "dsl": "(duration > 10 && (true || missing == 1)) && matches(path, \"^/api/v[0-9]+/users$\") && any(items, {@it >= 3})",
+    "json": {"and": [
+      {"and": [
+        {"gt": [{"ref": "duration"}, 10]},
+        {"or": [true, {"eq": [{"ref": "missing"}, 1]}]}
+      ]},
+      {"and": [
+        {"matches": [{"ref": "path"}, "^/api/v[0-9]+/users$"]},
+        {"any": [{"ref": "items"}, {"ge": [{"ref": "@it"}, 3]}]}
+      ]}
+    ]}
+  }
+}"#;
+
+fn condition_benchmark(c: &mut Criterion) {
+    let Ok(LiveDebuggingData::Probe(probe)) = parse_json(PROBE) else {
+        panic!("Invalid benchmark probe");
+    };
+    let ProbeType::Log(log) = probe.probe else {
+        panic!("Benchmark probe must be a log probe");
+    };
+    let variables = HashMap::from([
+        ("duration", Val::Num(20.)),
+        ("path", Val::Str("/api/v2/users".to_string())),
+        (
+            "items",
+            Val::Vec(vec![Val::Num(1.), Val::Num(2.), Val::Num(3.)]),
+        ),
+    ]);
+    let compiled = compile_condition(&log.when);
+
+    let mut group = c.benchmark_group("live_debugger/condition");
+    group.bench_function("tree_walker", |b| {
+        b.iter(|| {
+            let mut ctx = EvalCtx {
+                variables: &variables,
+            };
+            black_box(eval_condition(&mut ctx, black_box(&log.when)).unwrap())
+        })
+    });
+    group.bench_function("compiled", |b| {
+        b.iter(|| {
+            let mut ctx = EvalCtx {
+                variables: &variables,
+            };
+            black_box(eval_compiled_condition(&mut ctx, black_box(&compiled)).unwrap())
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, condition_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,244 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::debugger_defs::SnapshotEvaluationError;
+use crate::expr_defs::{Condition, StringComparison, StringSource};
+use crate::expr_eval::{Eval, EvalResult};
+use crate::{Evaluator, ProbeCondition};
+use regex::Regex;
+
+#[derive(Debug)]
+enum Instruction {
+    Const(bool),
+    /// Evaluates a condition which isn't broken down further with the tree walker.
+    Eval(Condition),
+    Matches(StringSource, Regex),
+    Not,
+    JumpIfFalse(usize),
+    JumpIfTrue(usize),
+}
+
+/// A condition compiled into a flat list of instructions, for conditions evaluated repeatedly.
+///
+/// The boolean operators are turned into jumps, constant sub-conditions are folded and regexes
+/// are compiled once. The result, including errors, is the same as evaluating the condition.
+#[derive(Debug)]
+pub struct CompiledCondition {
+    expr: String,
+    instructions: Vec<Instruction>,
+}
+
+/// Folds the sub-conditions whose result is known without evaluating anything. Sub-conditions
+/// which may fail are kept, so that their errors are still reported. Comparisons of values are
+/// not folded, even of literals: they are up to the evaluator of the language.
+fn fold(condition: &Condition) -> Condition {
+    match condition {
+        Condition::Disjunction(boxed) => {
+            let (a, b) = &**boxed;
+            match (fold(a), fold(b)) {
+                (Condition::Always, _) => Condition::Always,
+                (Condition::Never, b) => b,
+                (a, Condition::Never) => a,
+                (a, b) => Condition::Disjunction(Box::new((a, b))),
+            }
+        }
+        Condition::Conjunction(boxed) => {
+            let (a, b) = &**boxed;
+            match (fold(a), fold(b)) {
+                (Condition::Never, _) => Condition::Never,
+                (Condition::Always, b) => b,
+                (a, Condition::Always) => a,
+                (a, b) => Condition::Conjunction(Box::new((a, b))),
+            }
+        }
+        Condition::Negation(boxed) => match fold(boxed) {
+            Condition::Always => Condition::Never,
+            Condition::Never => Condition::Always,
+            Condition::Negation(inner) => *inner,
+            folded => Condition::Negation(Box::new(folded)),
+        },
+        Condition::StringComparison(comparison, StringSource::String(haystack), needle) => {
+            let result = match comparison {
+                StringComparison::StartsWith => haystack.starts_with(needle.as_str()),
+                StringComparison::EndsWith => haystack.ends_with(needle.as_str()),
+                StringComparison::Contains => haystack.contains(needle.as_str()),
+                StringComparison::Matches => match Regex::new(needle) {
+                    Ok(regex) => regex.is_match(haystack),
+                    Err(_) => return condition.clone(),
+                },
+            };
+            if result {
+                Condition::Always
+            } else {
+                Condition::Never
+            }
+        }
+        _ => condition.clone(),
+    }
+}
+
+fn emit(instructions: &mut Vec<Instruction>, condition: Condition) {
+    match condition {
+        Condition::Always => instructions.push(Instruction::Const(true)),
+        Condition::Never => instructions.push(Instruction::Const(false)),
+        Condition::Disjunction(boxed) => {
+            let (a, b) = *boxed;
+            emit(instructions, a);
+            let jump = instructions.len();
+            instructions.push(Instruction::JumpIfTrue(0));
+            emit(instructions, b);
+            instructions[jump] = Instruction::JumpIfTrue(instructions.len());
+        }
+        Condition::Conjunction(boxed) => {
+            let (a, b) = *boxed;
+            emit(instructions, a);
+            let jump = instructions.len();
+            instructions.push(Instruction::JumpIfFalse(0));
+            emit(instructions, b);
+            instructions[jump] = Instruction::JumpIfFalse(instructions.len());
+        }
+        Condition::Negation(boxed) => {
+            emit(instructions, *boxed);
+            instructions.push(Instruction::Not);
+        }
+        Condition::StringComparison(StringComparison::Matches, source, needle) => {
+            match Regex::new(&needle) {
+                Ok(regex) => instructions.push(Instruction::Matches(source, regex)),
+                // Keep failing at evaluation, with the same error.
+                Err(_) => instructions.push(Instruction::Eval(Condition::StringComparison(
+                    StringComparison::Matches,
+                    source,
+                    needle,
+                ))),
+            }
+        }
+        condition => instructions.push(Instruction::Eval(condition)),
+    }
+}
+
+pub fn compile_condition(condition: &ProbeCondition) -> CompiledCondition {
+    let mut instructions = vec![];
+    emit(&mut instructions, fold(&condition.0));
+    CompiledCondition {
+        expr: condition.to_string(),
+        instructions,
+    }
+}
+
+impl CompiledCondition {
+    fn run<'e, I, E: Evaluator<'e, I>>(
+        &'e self,
+        eval: &mut Eval<'_, 'e, I, E>,
+    ) -> EvalResult<bool> {
+        let mut result = false;
+        let mut next = 0;
+        while let Some(instruction) = self.instructions.get(next) {
+            next += 1;
+            match instruction {
+                Instruction::Const(value) => result = *value,
+                Instruction::Eval(condition) => result = eval.condition(condition)?,
+                Instruction::Matches(source, regex) => {
+                    result = regex.is_match(&eval.stringify(source)?)
+                }
+                Instruction::Not => result = !result,
+                Instruction::JumpIfFalse(target) => {
+                    if !result {
+                        next = *target
+                    }
+                }
+                Instruction::JumpIfTrue(target) => {
+                    if result {
+                        next = *target
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+pub fn eval_compiled_condition<'e, I: 'e, E: Evaluator<'e, I>>(
+    eval: &mut E,
+    condition: &'e CompiledCondition,
+) -> Result<bool, SnapshotEvaluationError> {
+    condition
+        .run(&mut Eval { eval, it: None })
+        .map_err(|e| SnapshotEvaluationError {
+            expr: condition.expr.clone(),
+            message: e.0,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr_defs::{BinaryComparison, NumberSource, Reference, Value};
+
+    fn defined(name: &str) -> Condition {
+        Condition::IsDefinedReference(Reference::Base(name.to_string()))
+    }
+
+    #[test]
+    fn test_folding() {
+        let condition = ProbeCondition(Condition::Conjunction(Box::new((
+            Condition::Disjunction(Box::new((
+                Condition::StringComparison(
+                    StringComparison::StartsWith,
+                    StringSource::String("abc".to_string()),
+                    "a".to_string(),
+                ),
+                defined("a"),
+            ))),
+            Condition::Negation(Box::new(Condition::Negation(Box::new(defined("b"))))),
+        ))));
+        let compiled = compile_condition(&condition);
+        assert_eq!(compiled.instructions.len(), 1);
+        assert!(matches!(
+            compiled.instructions[0],
+            Instruction::Eval(Condition::IsDefinedReference(_))
+        ));
+        assert_eq!(compiled.expr, condition.to_string());
+
+        // A failing sub-condition is kept, even if the result doesn't depend on it.
+        let condition = ProbeCondition(Condition::Conjunction(Box::new((
+            defined("a"),
+            Condition::StringComparison(
+                StringComparison::Matches,
+                StringSource::String("abc".to_string()),
+                "(".to_string(),
+            ),
+        ))));
+        let compiled = compile_condition(&condition);
+        assert!(matches!(
+            compiled.instructions[..],
+            [
+                Instruction::Eval(_),
+                Instruction::JumpIfFalse(3),
+                Instruction::Eval(Condition::StringComparison(..))
+            ]
+        ));
+
+        // Comparisons of strings and numbers are left to the evaluator of the language.
+        for (a, b) in [
+            (
+                Value::String(StringSource::String("a".to_string())),
+                Value::String(StringSource::String("a".to_string())),
+            ),
+            (
+                Value::Number(NumberSource::Number(1.)),
+                Value::Number(NumberSource::Number(2.)),
+            ),
+        ] {
+            let condition = ProbeCondition(Condition::BinaryComparison(
+                a,
+                BinaryComparison::LowerThan,
+                b,
+            ));
+            let compiled = compile_condition(&condition);
+            assert!(matches!(
+                compiled.instructions[..],
+                [Instruction::Eval(Condition::BinaryComparison(..))]
+            ));
+        }
+    }
+}
@@ -3,7 +3,7 @@
 
 use std::fmt::{Display, Formatter};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum CollectionSource {
     Reference(Reference),
     FilterOperator(Box<(CollectionSource, Condition)>),
@@ -21,7 +21,7 @@ impl Display for CollectionSource {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Reference {
     IteratorVariable,
     Base(String),
@@ -50,7 +50,7 @@ impl Display for Reference {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum BinaryComparison {
     Equals,
     NotEquals,
@@ -73,7 +73,7 @@ impl Display for BinaryComparison {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum StringComparison {
     StartsWith,
     EndsWith,
@@ -92,7 +92,7 @@ impl Display for StringComparison {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum CollectionMatch {
     All,
     Any,
@@ -107,7 +107,7 @@ impl Display for CollectionMatch {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Condition {
     Always,
     Never,
@@ -176,7 +176,7 @@ impl Display for Condition {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum NumberSource {
     Number(f64),
     CollectionSize(CollectionSource),
@@ -193,7 +193,7 @@ impl Display for NumberSource {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum StringSource {
     String(String),
     Substring(Box<(StringSource, NumberSource, NumberSource)>),
@@ -217,7 +217,7 @@ impl Display for StringSource {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Value {
     Bool(Box<Condition>),
     String(StringSource),
@@ -234,7 +234,7 @@ impl Display for Value {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DslPart {
     Ref(CollectionSource),
     Value(Value),
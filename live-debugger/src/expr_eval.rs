@@ -12,11 +12,11 @@ use std::cmp::min;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DslString(pub(crate) Vec<DslPart>);
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ProbeValue(pub(crate) Value);
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ProbeCondition(pub(crate) Condition);
 
 impl Display for DslString {
@@ -112,14 +112,14 @@ enum InvalidFetch<'a, I> {
 }
 
 #[derive(Debug)]
-struct EvErr(String);
+pub(crate) struct EvErr(pub(crate) String);
 
 impl EvErr {
     pub fn str<T: ToString>(str: T) -> Self {
         EvErr(str.to_string())
     }
 
-    pub fn refed<'a, 'e, I, E: Evaluator<'e, I>>(
+    fn refed<'a, 'e, I, E: Evaluator<'e, I>>(
         eval: &mut Eval<'a, 'e, I, E>,
         reference: InvalidFetch<'e, I>,
     ) -> Self {
@@ -190,7 +190,7 @@ impl EvErr {
     }
 }
 
-type EvalResult<T> = Result<T, EvErr>;
+pub(crate) type EvalResult<T> = Result<T, EvErr>;
 
 struct DefOrUndefRef<'a, I>(Result<&'a I, InvalidFetch<'a, I>>);
 
@@ -230,9 +230,9 @@ impl<'e, I> InternalImm<'e, I> {
     }
 }
 
-struct Eval<'a, 'e, I, E: Evaluator<'e, I>> {
-    eval: &'a mut E,
-    it: Option<&'e I>,
+pub(crate) struct Eval<'a, 'e, I, E: Evaluator<'e, I>> {
+    pub(crate) eval: &'a mut E,
+    pub(crate) it: Option<&'e I>,
 }
 
 impl<'e, I, E: Evaluator<'e, I>> Eval<'_, 'e, I, E> {
@@ -424,12 +424,12 @@ impl<'e, I, E: Evaluator<'e, I>> Eval<'_, 'e, I, E> {
         }
     }
 
-    fn stringify(&mut self, value: &'e StringSource) -> EvalResult<Cow<'e, str>> {
+    pub(crate) fn stringify(&mut self, value: &'e StringSource) -> EvalResult<Cow<'e, str>> {
         let value = self.string_source(value)?.try_use(self)?;
         Ok(self.stringify_intermediate(value))
     }
 
-    fn condition(&mut self, condition: &'e Condition) -> EvalResult<bool> {
+    pub(crate) fn condition(&mut self, condition: &'e Condition) -> EvalResult<bool> {
         Ok(match condition {
             Condition::Always => true,
             Condition::Never => false,
@@ -612,8 +612,9 @@ mod tests {
         Reference, StringComparison, StringSource,
     };
     use crate::{
-        eval_condition, eval_intermediate_to_string, eval_string, eval_value, DslString, Evaluator,
-        IntermediateValue, ProbeCondition, ProbeValue, ResultError, ResultValue,
+        compile_condition, eval_compiled_condition, eval_condition, eval_intermediate_to_string,
+        eval_string, eval_value, DslString, Evaluator, IntermediateValue, ProbeCondition,
+        ProbeValue, ResultError, ResultValue,
     };
     use std::borrow::Cow;
     use std::cmp::Ordering;
@@ -814,6 +815,11 @@ mod tests {
                 Ok(_) => unreachable!(),
                 Err(e) => assert_eq!(e.message, $err),
             }
+            let compiled = compile_condition(&cond);
+            match eval_compiled_condition(&mut ctx, &compiled) {
+                Ok(_) => unreachable!(),
+                Err(e) => assert_eq!(e.message, $err),
+            }
         };
     }
     macro_rules! assert_cond_true {
@@ -821,6 +827,8 @@ mod tests {
             let cond = ProbeCondition($expr);
             let mut ctx = EvalCtx { variables: &$vars };
             assert!(eval_condition(&mut ctx, &cond).unwrap());
+            let compiled = compile_condition(&cond);
+            assert!(eval_compiled_condition(&mut ctx, &compiled).unwrap());
         };
     }
     macro_rules! assert_cond_false {
@@ -828,6 +836,8 @@ mod tests {
             let cond = ProbeCondition($expr);
             let mut ctx = EvalCtx { variables: &$vars };
             assert!(!eval_condition(&mut ctx, &cond).unwrap());
+            let compiled = compile_condition(&cond);
+            assert!(!eval_compiled_condition(&mut ctx, &compiled).unwrap());
         };
     }
 
@@ -0,0 +1,325 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::debugger_defs::SnapshotEvaluationError;
+use crate::expr_defs::{
+    CollectionSource, Condition, DslPart, NumberSource, Reference, StringComparison, StringSource,
+    Value,
+};
+use crate::{DslString, Probe, ProbeCondition, ProbeType, ProbeValue};
+use regex::Regex;
+use std::fmt::Display;
+
+fn literal_index(number: &NumberSource) -> Option<f64> {
+    match number {
+        NumberSource::Number(n) => Some(*n),
+        _ => None,
+    }
+}
+
+/// Collects the errors an expression would fail with on every evaluation, or which are
+/// unsupported.
+struct Validator {
+    errors: Vec<String>,
+    in_iterator: bool,
+}
+
+impl Validator {
+    fn iterate(&mut self, condition: &Condition) {
+        let in_iterator = std::mem::replace(&mut self.in_iterator, true);
+        self.condition(condition);
+        self.in_iterator = in_iterator;
+    }
+
+    fn condition(&mut self, condition: &Condition) {
+        match condition {
+            Condition::Always | Condition::Never => {}
+            Condition::Disjunction(boxed) | Condition::Conjunction(boxed) => {
+                let (a, b) = &**boxed;
+                self.condition(a);
+                self.condition(b);
+            }
+            Condition::Negation(condition) => self.condition(condition),
+            Condition::StringComparison(comparison, source, needle) => {
+                self.string_source(source);
+                if let StringComparison::Matches = comparison {
+                    if let Err(e) = Regex::new(needle) {
+                        self.errors
+                            .push(format!("{needle} is an invalid regex: {e}"));
+                    }
+                }
+            }
+            Condition::BinaryComparison(a, _, b) => {
+                self.value(a);
+                self.value(b);
+            }
+            Condition::CollectionMatch(_, reference, condition) => {
+                self.reference(reference);
+                self.iterate(condition);
+            }
+            Condition::Instanceof(reference, class) => {
+                self.reference(reference);
+                if class.is_empty() {
+                    self.errors
+                        .push("instanceof requires a type name".to_string());
+                }
+            }
+            Condition::IsDefinedReference(reference) | Condition::IsEmptyReference(reference) => {
+                self.reference(reference)
+            }
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Bool(condition) => self.condition(condition),
+            Value::String(source) => self.string_source(source),
+            Value::Number(source) => self.number_source(source),
+        }
+    }
+
+    fn number_source(&mut self, source: &NumberSource) {
+        match source {
+            NumberSource::Number(_) => {}
+            NumberSource::CollectionSize(collection) => self.collection_source(collection),
+            NumberSource::Reference(reference) => self.reference(reference),
+        }
+    }
+
+    fn index(&mut self, index: f64, expr: &dyn Display) {
+        if index < 0. || index.fract() != 0. {
+            self.errors
+                .push(format!("{index} is not a valid index (from {expr})"));
+        }
+    }
+
+    fn string_source(&mut self, source: &StringSource) {
+        match source {
+            StringSource::String(_) | StringSource::Null => {}
+            StringSource::Substring(boxed) => {
+                let (string, start, end) = &**boxed;
+                self.string_source(string);
+                self.number_source(start);
+                self.number_source(end);
+                let (start, end) = (literal_index(start), literal_index(end));
+                for index in [start, end].into_iter().flatten() {
+                    self.index(index, source);
+                }
+                if let (Some(start), Some(end)) = (start, end) {
+                    if start > end {
+                        self.errors
+                            .push(format!("[{start}..{end}] is out of bounds of {source}"));
+                    }
+                }
+            }
+            StringSource::Reference(reference) => self.reference(reference),
+        }
+    }
+
+    fn reference(&mut self, reference: &Reference) {
+        match reference {
+            Reference::IteratorVariable => {
+                if !self.in_iterator {
+                    self.errors
+                        .push("Attempted to use @it in non-iterator context".to_string());
+                }
+            }
+            Reference::Base(_) => {}
+            Reference::Index(boxed) => {
+                let (source, dimension) = &**boxed;
+                self.collection_source(source);
+                self.value(dimension);
+                // Filtered collections are indexed by position, other collections by the
+                // evaluator.
+                if let CollectionSource::FilterOperator(_) = source {
+                    match dimension {
+                        Value::Bool(_) => {
+                            self.errors.push("Cannot take index of boolean".to_string())
+                        }
+                        Value::Number(NumberSource::Number(index)) => self.index(*index, dimension),
+                        _ => {}
+                    }
+                }
+            }
+            Reference::Nested(boxed) => {
+                let (source, member) = &**boxed;
+                self.reference(source);
+                self.value(member);
+                if let Value::Bool(_) = member {
+                    self.errors
+                        .push(format!("Cannot access the property {member} of {source}"));
+                }
+            }
+        }
+    }
+
+    fn collection_source(&mut self, source: &CollectionSource) {
+        match source {
+            CollectionSource::Reference(reference) => self.reference(reference),
+            CollectionSource::FilterOperator(boxed) => {
+                let (source, condition) = &**boxed;
+                self.collection_source(source);
+                self.iterate(condition);
+            }
+        }
+    }
+}
+
+fn validate(
+    expr: &dyn Display,
+    validate: impl FnOnce(&mut Validator),
+) -> Vec<SnapshotEvaluationError> {
+    let mut validator = Validator {
+        errors: vec![],
+        in_iterator: false,
+    };
+    validate(&mut validator);
+    validator
+        .errors
+        .into_iter()
+        .map(|message| SnapshotEvaluationError {
+            expr: expr.to_string(),
+            message,
+        })
+        .collect()
+}
+
+pub fn validate_condition(condition: &ProbeCondition) -> Vec<SnapshotEvaluationError> {
+    validate(condition, |validator| validator.condition(&condition.0))
+}
+
+pub fn validate_value(value: &ProbeValue) -> Vec<SnapshotEvaluationError> {
+    validate(value, |validator| validator.value(&value.0))
+}
+
+pub fn validate_string(dsl: &DslString) -> Vec<SnapshotEvaluationError> {
+    let mut errors = vec![];
+    for part in dsl.0.iter() {
+        match part {
+            DslPart::String(_) => {}
+            DslPart::Value(value) => {
+                errors.extend(validate(value, |validator| validator.value(value)))
+            }
+            DslPart::Ref(source) => errors.extend(validate(source, |validator| {
+                validator.collection_source(source)
+            })),
+        }
+    }
+    errors
+}
+
+/// Checks the expressions of a probe for errors which would make every evaluation fail, so that
+/// the probe can be reported as erroneous when it is installed.
+pub fn validate_probe(probe: &Probe) -> Vec<SnapshotEvaluationError> {
+    match probe.probe {
+        ProbeType::Metric(ref metric) => validate_value(&metric.value),
+        ProbeType::Log(ref log) => {
+            let mut errors = validate_condition(&log.when);
+            errors.extend(validate_string(&log.segments));
+            errors
+        }
+        ProbeType::Span(_) => vec![],
+        ProbeType::SpanDecoration(ref span_decoration) => {
+            let mut errors = vec![];
+            for decoration in span_decoration.decorations.iter() {
+                errors.extend(validate_condition(&decoration.condition));
+                for (_, value) in decoration.tags.iter() {
+                    errors.extend(validate_string(value));
+                }
+            }
+            errors
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr_defs::{BinaryComparison, CollectionMatch};
+
+    fn num(n: f64) -> Value {
+        Value::Number(NumberSource::Number(n))
+    }
+
+    fn var(name: &str) -> Reference {
+        Reference::Base(name.to_string())
+    }
+
+    fn messages(errors: Vec<SnapshotEvaluationError>) -> Vec<String> {
+        errors.into_iter().map(|e| e.message).collect()
+    }
+
+    #[test]
+    fn test_validate_condition() {
+        let valid = ProbeCondition(Condition::CollectionMatch(
+            CollectionMatch::Any,
+            var("list"),
+            Box::new(Condition::BinaryComparison(
+                Value::Number(NumberSource::Reference(Reference::IteratorVariable)),
+                BinaryComparison::GreaterThan,
+                num(1.),
+            )),
+        ));
+        assert!(validate_condition(&valid).is_empty());
+
+        let invalid = ProbeCondition(Condition::Conjunction(Box::new((
+            Condition::BinaryComparison(
+                Value::Bool(Box::new(Condition::Always)),
+                BinaryComparison::LowerThan,
+                Value::String(StringSource::Reference(Reference::IteratorVariable)),
+            ),
+            Condition::StringComparison(
+                StringComparison::Matches,
+                StringSource::Reference(var("name")),
+                "[a-".to_string(),
+            ),
+        ))));
+        let errors = validate_condition(&invalid);
+        assert_eq!(errors[0].expr, invalid.to_string());
+        let invalid_messages = messages(errors);
+        assert_eq!(invalid_messages.len(), 2);
+        assert_eq!(
+            invalid_messages[0],
+            "Attempted to use @it in non-iterator context"
+        );
+        assert!(invalid_messages[1].starts_with("[a- is an invalid regex"));
+
+        let mismatch = ProbeCondition(Condition::BinaryComparison(
+            Value::String(StringSource::String("a".to_string())),
+            BinaryComparison::GreaterOrEquals,
+            num(1.),
+        ));
+        // Comparing mismatched types is up to the evaluator of the language, and does not fail.
+        assert!(validate_condition(&mismatch).is_empty());
+    }
+
+    #[test]
+    fn test_validate_value() {
+        let substring = ProbeValue(Value::String(StringSource::Substring(Box::new((
+            StringSource::Reference(var("name")),
+            NumberSource::Number(3.),
+            NumberSource::Number(1.5),
+        )))));
+        assert_eq!(
+            messages(validate_value(&substring)),
+            [
+                "1.5 is not a valid index (from substring(name, 3, 1.5))",
+                "[3..1.5] is out of bounds of substring(name, 3, 1.5)"
+            ]
+        );
+
+        let index = ProbeValue(Value::String(StringSource::Reference(Reference::Index(
+            Box::new((
+                CollectionSource::FilterOperator(Box::new((
+                    CollectionSource::Reference(var("list")),
+                    Condition::Always,
+                ))),
+                Value::Bool(Box::new(Condition::Always)),
+            )),
+        ))));
+        assert_eq!(
+            messages(validate_value(&index)),
+            ["Cannot take index of boolean"]
+        );
+    }
+}
@@ -1,16 +1,24 @@
 // Unless explicitly stated otherwise all files in this repository are licensed under the Apache
 // License Version 2.0. This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2021-Present Datadog, Inc.
 
+mod expr_compile;
 mod expr_defs;
 mod expr_eval;
+mod expr_validate;
 mod parse_json;
 mod probe_defs;
+mod rate_limiter;
 
 pub mod debugger_defs;
 mod redacted_names;
 pub mod sender;
+mod snapshot;
 
+pub use expr_compile::*;
 pub use expr_eval::*;
+pub use expr_validate::*;
 pub use parse_json::parse as parse_json;
 pub use probe_defs::*;
+pub use rate_limiter::*;
 pub use redacted_names::*;
+pub use snapshot::*;
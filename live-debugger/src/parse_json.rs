@@ -9,6 +9,7 @@ use crate::{
     CaptureConfiguration, DslString, EvaluateAt, FilterList, InBodyLocation, LiveDebuggingData,
     LogProbe, MetricKind, MetricProbe, Probe, ProbeCondition, ProbeTarget, ProbeType, ProbeValue,
     ServiceConfiguration, SpanDecorationProbe, SpanProbe, SpanProbeDecoration, SpanProbeTarget,
+    DEFAULT_LOGS_PER_SECOND, DEFAULT_SNAPSHOTS_PER_SECOND,
 };
 use anyhow::Context;
 use serde::Deserialize;
@@ -89,7 +90,11 @@ pub fn parse(json: &str) -> anyhow::Result<LiveDebuggingData> {
                         sampling_snapshots_per_second: parsed
                             .sampling
                             .map(|s| s.snapshots_per_second)
-                            .unwrap_or(5000),
+                            .unwrap_or(if parsed.capture_snapshot == Some(true) {
+                                DEFAULT_SNAPSHOTS_PER_SECOND
+                            } else {
+                                DEFAULT_LOGS_PER_SECOND
+                            }),
                     }),
                     ContentType::SpanProbe => ProbeType::Span(SpanProbe {}),
                     ContentType::SpanDecorationProbe => {
@@ -4,7 +4,7 @@
 use crate::{DslString, ProbeCondition, ProbeValue};
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[repr(C)]
 pub struct CaptureConfiguration {
@@ -79,6 +79,9 @@ pub struct LogProbe {
     pub when: ProbeCondition,
     pub capture: CaptureConfiguration,
     pub capture_snapshot: bool,
+    /// The configured sampling rate. When the probe has no sampling configured, this defaults to
+    /// DEFAULT_SNAPSHOTS_PER_SECOND (1) for probes capturing snapshots, and to
+    /// DEFAULT_LOGS_PER_SECOND (5000) for the other probes.
     pub sampling_snapshots_per_second: u32,
 }
 
@@ -0,0 +1,168 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{Probe, ProbeType};
+use ddcommon::rate_limiter::{Limiter, LocalLimiter};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// The rate of a log probe capturing snapshots, unless its sampling is configured.
+pub const DEFAULT_SNAPSHOTS_PER_SECOND: u32 = 1;
+/// The rate of a log probe not capturing snapshots, unless its sampling is configured.
+pub const DEFAULT_LOGS_PER_SECOND: u32 = 5000;
+pub const DEFAULT_GLOBAL_SNAPSHOTS_PER_SECOND: u32 = 25;
+pub const DEFAULT_GLOBAL_LOGS_PER_SECOND: u32 = 5000;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CaptureDecision {
+    Capture,
+    /// The probe or the global budget exceeded its rate.
+    Throttled,
+    /// Like Throttled, but the previous capture attempt of the probe succeeded: this is the time
+    /// to emit a warning diagnostic for the probe.
+    ThrottlingStarted,
+}
+
+struct ProbeLimiter {
+    limiter: LocalLimiter,
+    throttled: bool,
+}
+
+/// Enforces the sampling configured on the log probes, as well as global rates shared by all log
+/// probes capturing snapshots, respectively by all the other log probes.
+pub struct ProbeRateLimiter {
+    global_snapshots_per_second: u32,
+    global_logs_per_second: u32,
+    global_snapshots: LocalLimiter,
+    global_logs: LocalLimiter,
+    probes: Mutex<HashMap<String, ProbeLimiter>>,
+}
+
+impl Default for ProbeRateLimiter {
+    fn default() -> Self {
+        ProbeRateLimiter::new(
+            DEFAULT_GLOBAL_SNAPSHOTS_PER_SECOND,
+            DEFAULT_GLOBAL_LOGS_PER_SECOND,
+        )
+    }
+}
+
+impl ProbeRateLimiter {
+    pub fn new(global_snapshots_per_second: u32, global_logs_per_second: u32) -> Self {
+        ProbeRateLimiter {
+            global_snapshots_per_second,
+            global_logs_per_second,
+            global_snapshots: LocalLimiter::default(),
+            global_logs: LocalLimiter::default(),
+            probes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Decides whether the probe with the given id may capture now. Each probe is limited to
+    /// `per_second` captures, and to the global rate of its kind.
+    pub fn try_capture(
+        &self,
+        probe_id: &str,
+        capture_snapshot: bool,
+        per_second: u32,
+    ) -> CaptureDecision {
+        let mut probes = self.probes.lock().unwrap();
+        if !probes.contains_key(probe_id) {
+            probes.insert(
+                probe_id.to_string(),
+                ProbeLimiter {
+                    limiter: LocalLimiter::default(),
+                    throttled: false,
+                },
+            );
+        }
+        let probe = probes.get_mut(probe_id).unwrap();
+        let (global, global_per_second) = if capture_snapshot {
+            (&self.global_snapshots, self.global_snapshots_per_second)
+        } else {
+            (&self.global_logs, self.global_logs_per_second)
+        };
+        // Only take from either budget once both have room, so that a capture refused by one
+        // does not consume the other.
+        let allowed = probe.limiter.has_capacity(per_second)
+            && global.has_capacity(global_per_second)
+            && probe.limiter.inc(per_second)
+            && global.inc(global_per_second);
+        let was_throttled = std::mem::replace(&mut probe.throttled, !allowed);
+        if allowed {
+            CaptureDecision::Capture
+        } else if was_throttled {
+            CaptureDecision::Throttled
+        } else {
+            CaptureDecision::ThrottlingStarted
+        }
+    }
+
+    /// Decides whether the probe may capture now, according to its sampling. Only log probes
+    /// are limited.
+    pub fn try_capture_probe(&self, probe: &Probe) -> CaptureDecision {
+        match probe.probe {
+            ProbeType::Log(ref log) => self.try_capture(
+                &probe.id,
+                log.capture_snapshot,
+                log.sampling_snapshots_per_second,
+            ),
+            _ => CaptureDecision::Capture,
+        }
+    }
+
+    /// Forgets the state of a probe, e.g. when it is removed.
+    pub fn remove_probe(&self, probe_id: &str) {
+        self.probes.lock().unwrap().remove(probe_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_probe_limits() {
+        let limiter = ProbeRateLimiter::new(100, 0);
+        // The limiter replenishes continuously, so a capture more may slip through.
+        let mut captures = 0;
+        let decision = loop {
+            match limiter.try_capture("a", true, 2) {
+                CaptureDecision::Capture => captures += 1,
+                decision => break decision,
+            }
+        };
+        assert!((2..=3).contains(&captures));
+        assert_eq!(decision, CaptureDecision::ThrottlingStarted);
+        assert_eq!(
+            limiter.try_capture("a", true, 2),
+            CaptureDecision::Throttled
+        );
+
+        // Other probes have their own budget.
+        assert_eq!(limiter.try_capture("b", true, 2), CaptureDecision::Capture);
+
+        // Log probes share a separate global budget.
+        assert_eq!(
+            limiter.try_capture("c", false, 2),
+            CaptureDecision::ThrottlingStarted
+        );
+
+        limiter.remove_probe("a");
+        assert_eq!(limiter.try_capture("a", true, 2), CaptureDecision::Capture);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_global_refusal_keeps_probe_budget() {
+        let limiter = ProbeRateLimiter::new(1, 0);
+        while limiter.try_capture("a", true, 100) == CaptureDecision::Capture {}
+        // The global budget is exhausted: refusing "b" must not consume its own budget.
+        for _ in 0..3 {
+            assert_ne!(limiter.try_capture("b", true, 1), CaptureDecision::Capture);
+        }
+        assert!(limiter.probes.lock().unwrap()["b"].limiter.has_capacity(1));
+    }
+}
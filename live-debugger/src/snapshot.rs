@@ -0,0 +1,480 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::debugger_defs::{Capture, Captures, Entry, Fields, Snapshot, Value};
+use crate::{is_redacted_name, is_redacted_type, CaptureConfiguration};
+use std::borrow::Cow;
+
+pub const NOT_CAPTURED_DEPTH: &str = "depth";
+pub const NOT_CAPTURED_COLLECTION_SIZE: &str = "collectionSize";
+pub const NOT_CAPTURED_FIELD_COUNT: &str = "fieldCount";
+pub const NOT_CAPTURED_REDACTED_IDENT: &str = "redactedIdent";
+pub const NOT_CAPTURED_REDACTED_TYPE: &str = "redactedType";
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ValueKind {
+    Null,
+    /// Numbers, booleans and the like, captured by their string representation.
+    Primitive,
+    String,
+    /// A list of elements.
+    Collection,
+    /// A list of key-value entries.
+    Map,
+    /// A value with named fields.
+    Object,
+}
+
+/// Gives access to the values of the language, for capturing them into snapshots.
+pub trait Introspector<'e, I> {
+    fn kind(&mut self, value: &'e I) -> ValueKind;
+    fn type_name(&mut self, value: &'e I) -> Cow<'e, str>;
+    /// The representation of a primitive, or the contents of a string.
+    fn stringify(&mut self, value: &'e I) -> Cow<'e, str>;
+    /// The number of elements of a collection or entries of a map.
+    fn length(&mut self, value: &'e I) -> usize;
+    /// The first `max` elements of a collection.
+    fn elements(&mut self, value: &'e I, max: usize) -> Vec<&'e I>;
+    /// The first `max` entries of a map.
+    fn entries(&mut self, value: &'e I, max: usize) -> Vec<(&'e I, &'e I)>;
+    fn fields(&mut self, value: &'e I) -> Vec<(Cow<'e, str>, &'e I)>;
+}
+
+fn not_captured<'e>(r#type: Cow<'e, str>, reason: &'static str) -> Value<'e> {
+    Value {
+        r#type,
+        not_captured_reason: Some(Cow::Borrowed(reason)),
+        ..Default::default()
+    }
+}
+
+/// Captures a value, within the limits of the configuration, redacting the values of redacted
+/// types as well as fields and map entries with a redacted name.
+pub fn capture_value<'e, I>(
+    introspector: &mut impl Introspector<'e, I>,
+    config: &CaptureConfiguration,
+    value: &'e I,
+) -> Value<'e> {
+    capture_at_depth(introspector, config, value, 0)
+}
+
+/// Captures a variable, redacting it if its name is redacted.
+pub fn capture_named_value<'e, I>(
+    introspector: &mut impl Introspector<'e, I>,
+    config: &CaptureConfiguration,
+    name: &str,
+    value: &'e I,
+) -> Value<'e> {
+    if is_redacted_name(name) {
+        not_captured(introspector.type_name(value), NOT_CAPTURED_REDACTED_IDENT)
+    } else {
+        capture_value(introspector, config, value)
+    }
+}
+
+/// Captures the variables of a capture point, e.g. the arguments of a method.
+pub fn capture_fields<'e, I: 'e>(
+    introspector: &mut impl Introspector<'e, I>,
+    config: &CaptureConfiguration,
+    variables: impl IntoIterator<Item = (Cow<'e, str>, &'e I)>,
+) -> Fields<'e> {
+    variables
+        .into_iter()
+        .map(|(name, value)| {
+            let value = capture_named_value(introspector, config, &name, value);
+            (name, value)
+        })
+        .collect()
+}
+
+fn capture_at_depth<'e, I>(
+    introspector: &mut impl Introspector<'e, I>,
+    config: &CaptureConfiguration,
+    value: &'e I,
+    depth: u32,
+) -> Value<'e> {
+    let r#type = introspector.type_name(value);
+    if is_redacted_type(r#type.as_ref()) {
+        return not_captured(r#type, NOT_CAPTURED_REDACTED_TYPE);
+    }
+    let kind = introspector.kind(value);
+    match kind {
+        ValueKind::Null => Value {
+            r#type,
+            is_null: true,
+            ..Default::default()
+        },
+        ValueKind::Primitive => Value {
+            r#type,
+            value: Some(introspector.stringify(value)),
+            ..Default::default()
+        },
+        ValueKind::String => {
+            let string = introspector.stringify(value);
+            let max_length = config.max_length as usize;
+            match string.char_indices().nth(max_length) {
+                Some((end, _)) => Value {
+                    r#type,
+                    value: Some(Cow::Owned(string[..end].to_string())),
+                    truncated: true,
+                    size: Some(Cow::Owned(string.chars().count().to_string())),
+                    ..Default::default()
+                },
+                None => Value {
+                    r#type,
+                    value: Some(string),
+                    ..Default::default()
+                },
+            }
+        }
+        _ if depth >= config.max_reference_depth => not_captured(r#type, NOT_CAPTURED_DEPTH),
+        ValueKind::Collection | ValueKind::Map => {
+            let length = introspector.length(value);
+            let max = length.min(config.max_collection_size as usize);
+            let mut captured = Value {
+                r#type,
+                size: Some(Cow::Owned(length.to_string())),
+                ..Default::default()
+            };
+            if kind == ValueKind::Collection {
+                for element in introspector.elements(value, max).into_iter().take(max) {
+                    let element = capture_at_depth(introspector, config, element, depth + 1);
+                    captured.elements.push(element);
+                }
+            } else {
+                for (key, entry) in introspector.entries(value, max).into_iter().take(max) {
+                    let redacted = introspector.kind(key) == ValueKind::String
+                        && is_redacted_name(introspector.stringify(key).as_ref());
+                    let key = capture_at_depth(introspector, config, key, depth + 1);
+                    let entry = if redacted {
+                        not_captured(introspector.type_name(entry), NOT_CAPTURED_REDACTED_IDENT)
+                    } else {
+                        capture_at_depth(introspector, config, entry, depth + 1)
+                    };
+                    captured.entries.push(Entry(key, entry));
+                }
+            }
+            if length > max {
+                captured.not_captured_reason = Some(Cow::Borrowed(NOT_CAPTURED_COLLECTION_SIZE));
+            }
+            captured
+        }
+        ValueKind::Object => {
+            let fields = introspector.fields(value);
+            let max = config.max_field_count as usize;
+            let mut captured = Value {
+                r#type,
+                ..Default::default()
+            };
+            if fields.len() > max {
+                captured.not_captured_reason = Some(Cow::Borrowed(NOT_CAPTURED_FIELD_COUNT));
+            }
+            for (name, field) in fields.into_iter().take(max) {
+                let field = if is_redacted_name(name.as_ref()) {
+                    not_captured(introspector.type_name(field), NOT_CAPTURED_REDACTED_IDENT)
+                } else {
+                    capture_at_depth(introspector, config, field, depth + 1)
+                };
+                captured.fields.insert(name, field);
+            }
+            captured
+        }
+    }
+}
+
+fn value_depth(value: &Value) -> u32 {
+    value
+        .fields
+        .values()
+        .chain(value.elements.iter())
+        .chain(
+            value
+                .entries
+                .iter()
+                .flat_map(|Entry(key, value)| [key, value]),
+        )
+        .map(|value| value_depth(value) + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+fn prune_value(value: &mut Value, depth: u32) {
+    if depth == 0 {
+        if !value.fields.is_empty() || !value.elements.is_empty() || !value.entries.is_empty() {
+            value.fields.clear();
+            value.elements.clear();
+            value.entries.clear();
+            value.not_captured_reason = Some(Cow::Borrowed(NOT_CAPTURED_DEPTH));
+        }
+        return;
+    }
+    for value in value.fields.values_mut().chain(value.elements.iter_mut()) {
+        prune_value(value, depth - 1);
+    }
+    for Entry(key, value) in value.entries.iter_mut() {
+        prune_value(key, depth - 1);
+        prune_value(value, depth - 1);
+    }
+}
+
+fn capture_values<'c, 'a>(capture: &'c mut Capture<'a>) -> impl Iterator<Item = &'c mut Value<'a>> {
+    capture
+        .static_fields
+        .values_mut()
+        .chain(capture.arguments.values_mut())
+        .chain(capture.locals.values_mut())
+        .chain(capture.throwable.iter_mut())
+}
+
+fn captures_values<'c, 'a>(
+    captures: &'c mut Captures<'a>,
+) -> impl Iterator<Item = &'c mut Value<'a>> {
+    captures
+        .lines
+        .values_mut()
+        .chain(captures.entry.iter_mut())
+        .chain(captures.r#return.iter_mut())
+        .flat_map(capture_values)
+}
+
+impl<'a> Snapshot<'a> {
+    /// The depth of the deepest captured value.
+    pub fn captures_depth(&self) -> u32 {
+        let Some(captures) = &self.captures else {
+            return 0;
+        };
+        captures
+            .lines
+            .values()
+            .chain(captures.entry.iter())
+            .chain(captures.r#return.iter())
+            .flat_map(|capture| {
+                capture
+                    .static_fields
+                    .values()
+                    .chain(capture.arguments.values())
+                    .chain(capture.locals.values())
+                    .chain(capture.throwable.iter())
+            })
+            .map(value_depth)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Reduces the depth of the captured values to at most `depth`, e.g. to shrink a snapshot
+    /// which is too large to be sent.
+    pub fn prune_captures(&mut self, depth: u32) {
+        for value in self.captures.iter_mut().flat_map(captures_values) {
+            prune_value(value, depth);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    enum TestValue {
+        Null,
+        Int(i64),
+        Str(&'static str),
+        List(Vec<TestValue>),
+        Map(Vec<(TestValue, TestValue)>),
+        Object(&'static str, Vec<(&'static str, TestValue)>),
+    }
+
+    struct TestIntrospector;
+
+    impl<'e> Introspector<'e, TestValue> for TestIntrospector {
+        fn kind(&mut self, value: &'e TestValue) -> ValueKind {
+            match value {
+                TestValue::Null => ValueKind::Null,
+                TestValue::Int(_) => ValueKind::Primitive,
+                TestValue::Str(_) => ValueKind::String,
+                TestValue::List(_) => ValueKind::Collection,
+                TestValue::Map(_) => ValueKind::Map,
+                TestValue::Object(..) => ValueKind::Object,
+            }
+        }
+
+        fn type_name(&mut self, value: &'e TestValue) -> Cow<'e, str> {
+            Cow::Borrowed(match value {
+                TestValue::Null => "null",
+                TestValue::Int(_) => "int",
+                TestValue::Str(_) => "string",
+                TestValue::List(_) => "list",
+                TestValue::Map(_) => "map",
+                TestValue::Object(name, _) => name,
+            })
+        }
+
+        fn stringify(&mut self, value: &'e TestValue) -> Cow<'e, str> {
+            match value {
+                TestValue::Int(i) => Cow::Owned(i.to_string()),
+                TestValue::Str(s) => Cow::Borrowed(s),
+                _ => unreachable!(),
+            }
+        }
+
+        fn length(&mut self, value: &'e TestValue) -> usize {
+            match value {
+                TestValue::List(list) => list.len(),
+                TestValue::Map(map) => map.len(),
+                _ => unreachable!(),
+            }
+        }
+
+        // Ignores max, to check the capture does not rely on the introspector to honor it.
+        fn elements(&mut self, value: &'e TestValue, _max: usize) -> Vec<&'e TestValue> {
+            let TestValue::List(list) = value else {
+                unreachable!();
+            };
+            list.iter().collect()
+        }
+
+        fn entries(
+            &mut self,
+            value: &'e TestValue,
+            _max: usize,
+        ) -> Vec<(&'e TestValue, &'e TestValue)> {
+            let TestValue::Map(map) = value else {
+                unreachable!();
+            };
+            map.iter().map(|(k, v)| (k, v)).collect()
+        }
+
+        fn fields(&mut self, value: &'e TestValue) -> Vec<(Cow<'e, str>, &'e TestValue)> {
+            let TestValue::Object(_, fields) = value else {
+                unreachable!();
+            };
+            fields
+                .iter()
+                .map(|(name, value)| (Cow::Borrowed(*name), value))
+                .collect()
+        }
+    }
+
+    fn config() -> CaptureConfiguration {
+        CaptureConfiguration {
+            max_reference_depth: 2,
+            max_collection_size: 2,
+            max_length: 5,
+            max_field_count: 2,
+        }
+    }
+
+    #[test]
+    fn test_capture_limits() {
+        let value = TestValue::Object(
+            "Request",
+            vec![
+                ("path", TestValue::Str("/some/long/path")),
+                (
+                    "params",
+                    TestValue::List(vec![
+                        TestValue::Int(1),
+                        TestValue::List(vec![]),
+                        TestValue::Null,
+                    ]),
+                ),
+                ("ignored", TestValue::Int(3)),
+            ],
+        );
+        let captured = capture_value(&mut TestIntrospector, &config(), &value);
+        assert_eq!(captured.r#type, "Request");
+        assert_eq!(
+            captured.not_captured_reason.as_deref(),
+            Some(NOT_CAPTURED_FIELD_COUNT)
+        );
+        assert_eq!(captured.fields.len(), 2);
+
+        let path = &captured.fields["path"];
+        assert_eq!(path.value.as_deref(), Some("/some"));
+        assert!(path.truncated);
+        assert_eq!(path.size.as_deref(), Some("15"));
+
+        let params = &captured.fields["params"];
+        assert_eq!(params.size.as_deref(), Some("3"));
+        assert_eq!(
+            params.not_captured_reason.as_deref(),
+            Some(NOT_CAPTURED_COLLECTION_SIZE)
+        );
+        assert_eq!(params.elements.len(), 2);
+        assert_eq!(params.elements[0].value.as_deref(), Some("1"));
+        assert_eq!(
+            params.elements[1].not_captured_reason.as_deref(),
+            Some(NOT_CAPTURED_DEPTH)
+        );
+    }
+
+    #[test]
+    fn test_redaction() {
+        let value = TestValue::Map(vec![
+            (TestValue::Str("password"), TestValue::Str("hunter2")),
+            (
+                TestValue::Str("user"),
+                TestValue::Object("User", vec![("api_key", TestValue::Str("abc"))]),
+            ),
+        ]);
+        let fields = capture_fields(
+            &mut TestIntrospector,
+            &config(),
+            [
+                (Cow::Borrowed("headers"), &value),
+                (Cow::Borrowed("secret"), &TestValue::Int(1)),
+            ],
+        );
+        assert_eq!(
+            fields["secret"].not_captured_reason.as_deref(),
+            Some(NOT_CAPTURED_REDACTED_IDENT)
+        );
+        let Entry(key, password) = &fields["headers"].entries[0];
+        assert_eq!(key.value.as_deref(), Some("passw"));
+        assert_eq!(password.value, None);
+        assert_eq!(
+            password.not_captured_reason.as_deref(),
+            Some(NOT_CAPTURED_REDACTED_IDENT)
+        );
+        let Entry(_, user) = &fields["headers"].entries[1];
+        assert_eq!(
+            user.fields["api_key"].not_captured_reason.as_deref(),
+            Some(NOT_CAPTURED_REDACTED_IDENT)
+        );
+    }
+
+    #[test]
+    fn test_prune() {
+        let value = TestValue::List(vec![TestValue::List(vec![TestValue::Int(1)])]);
+        let mut snapshot = Snapshot {
+            captures: Some(Captures {
+                entry: Some(Capture {
+                    locals: capture_fields(
+                        &mut TestIntrospector,
+                        &config(),
+                        [(Cow::Borrowed("list"), &value)],
+                    ),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(snapshot.captures_depth(), 2);
+        snapshot.prune_captures(1);
+        assert_eq!(snapshot.captures_depth(), 1);
+        let list = &snapshot
+            .captures
+            .as_ref()
+            .unwrap()
+            .entry
+            .as_ref()
+            .unwrap()
+            .locals["list"];
+        assert!(list.elements[0].elements.is_empty());
+        assert_eq!(
+            list.elements[0].not_captured_reason.as_deref(),
+            Some(NOT_CAPTURED_DEPTH)
+        );
+    }
+}
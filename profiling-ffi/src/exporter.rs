@@ -208,6 +208,65 @@ pub unsafe extern "C" fn ddog_prof_Exporter_set_timeout(
     }
 }
 
+/// Enables the on-disk spool: requests that cannot be delivered are persisted to `dir` and
+/// retried with backoff on subsequent sends, including by later processes using the same `dir`.
+/// # Arguments
+/// * `exporter` - ProfileExporter instance.
+/// * `dir` - Spool directory. Created if it doesn't exist.
+/// * `max_bytes` - Upper bound for the total size of the spool; 0 keeps the default.
+/// * `max_age_secs` - Spooled requests older than this are discarded; 0 keeps the default.
+///
+/// # Safety
+/// The `exporter` may be null, but if non-null it MUST have been created by apis in this module.
+/// `dir` must be a valid CharSlice.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn ddog_prof_Exporter_set_spool(
+    exporter: Option<&mut ProfileExporter>,
+    dir: CharSlice,
+    max_bytes: u64,
+    max_age_secs: u64,
+) -> MaybeError {
+    let Some(exporter) = exporter else {
+        return MaybeError::Some(Error::from("Invalid argument"));
+    };
+    let mut config = exporter::spool::SpoolConfig::new(dir.to_utf8_lossy().into_owned());
+    if max_bytes != 0 {
+        config.max_bytes = max_bytes;
+    }
+    if max_age_secs != 0 {
+        config.max_age = std::time::Duration::from_secs(max_age_secs);
+    }
+    match exporter.set_spool(config) {
+        Ok(()) => MaybeError::None,
+        Err(err) => MaybeError::Some(Error::from(
+            err.context("failed ddog_prof_Exporter_set_spool"),
+        )),
+    }
+}
+
+/// Tries to deliver every spooled request now, ignoring retry backoff. Meant to be called e.g.
+/// at startup. Does nothing if no spool is configured.
+///
+/// # Safety
+/// All non-null arguments MUST have been created by created by apis in this module.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn ddog_prof_Exporter_drain_spool(
+    exporter: Option<&mut ProfileExporter>,
+    cancel: Option<&CancellationToken>,
+) -> MaybeError {
+    let Some(exporter) = exporter else {
+        return MaybeError::Some(Error::from("Invalid argument"));
+    };
+    match exporter.drain_spool(cancel.map(|ptr| &ptr.0)) {
+        Ok(_) => MaybeError::None,
+        Err(err) => MaybeError::Some(Error::from(
+            err.context("failed ddog_prof_Exporter_drain_spool"),
+        )),
+    }
+}
+
 /// # Safety
 /// The `exporter` may be null, but if non-null the pointer must point to a
 /// valid `ddog_prof_Exporter_Request` object made by the Rust Global
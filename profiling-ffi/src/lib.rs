@@ -5,6 +5,7 @@
 pub use symbolizer_ffi::*;
 
 mod exporter;
+mod live_heap;
 mod profiles;
 
 // re-export crashtracker ffi
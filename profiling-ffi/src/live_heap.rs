@@ -0,0 +1,207 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::profiles::{profile_ptr_to_inner, Profile, ProfileResult, Sample};
+use anyhow::Context;
+use datadog_profiling::internal;
+
+/// Tracks sampled allocations until they are freed, see `ddog_prof_LiveHeapTracker_track`. Do
+/// not access its member for any reason, only use the C API functions on this struct.
+#[repr(C)]
+pub struct LiveHeapTracker {
+    // This may be null, but if not it will point to a valid LiveHeapTracker.
+    inner: *mut internal::LiveHeapTracker,
+}
+
+impl LiveHeapTracker {
+    fn new(tracker: internal::LiveHeapTracker) -> Self {
+        LiveHeapTracker {
+            inner: Box::into_raw(Box::new(tracker)),
+        }
+    }
+
+    fn take(&mut self) -> Option<Box<internal::LiveHeapTracker>> {
+        let raw = std::mem::replace(&mut self.inner, std::ptr::null_mut());
+
+        if raw.is_null() {
+            None
+        } else {
+            Some(unsafe { Box::from_raw(raw) })
+        }
+    }
+}
+
+impl Drop for LiveHeapTracker {
+    fn drop(&mut self) {
+        drop(self.take())
+    }
+}
+
+unsafe fn tracker_ptr_to_inner<'a>(
+    tracker_ptr: *mut LiveHeapTracker,
+) -> anyhow::Result<&'a mut internal::LiveHeapTracker> {
+    match tracker_ptr.as_mut() {
+        None => anyhow::bail!("tracker pointer was null"),
+        Some(inner_ptr) => match inner_ptr.inner.as_mut() {
+            Some(tracker) => Ok(tracker),
+            None => anyhow::bail!("tracker's inner pointer was null (indicates use-after-free)"),
+        },
+    }
+}
+
+/// Creates a new live heap tracker which holds at most `max_tracked` allocations, to be folded
+/// into profiles with `num_sample_types` sample types. Must call
+/// `ddog_prof_LiveHeapTracker_drop` when you are done with the tracker.
+#[no_mangle]
+#[must_use]
+pub extern "C" fn ddog_prof_LiveHeapTracker_new(
+    max_tracked: usize,
+    num_sample_types: usize,
+) -> LiveHeapTracker {
+    LiveHeapTracker::new(internal::LiveHeapTracker::new(
+        max_tracked,
+        num_sample_types,
+    ))
+}
+
+/// # Safety
+/// The `tracker` can be null, but if non-null it must point to a LiveHeapTracker made by this
+/// module, which has not previously been dropped.
+#[no_mangle]
+pub unsafe extern "C" fn ddog_prof_LiveHeapTracker_drop(tracker: *mut LiveHeapTracker) {
+    if !tracker.is_null() {
+        drop((*tracker).take())
+    }
+}
+
+/// Starts tracking a sampled allocation. Tracking an `id` that is already tracked replaces the
+/// previous entry. Fails if the tracker is full, or if the sample couldn't be added to a profile.
+///
+/// # Arguments
+/// * `tracker` - the tracker to record the allocation in.
+/// * `id` - identifies the allocation, e.g. its address, for `ddog_prof_LiveHeapTracker_untrack`.
+/// * `sample` - the sample to emit while the allocation is alive. It is copied, so it doesn't need
+///   to outlive this call.
+/// * `weight` - how many allocations the sample represents; its values are multiplied by this
+///   when folded into a profile. Must be positive.
+///
+/// # Safety
+/// The `tracker` ptr must point to a valid LiveHeapTracker object created by this module. All
+/// pointers inside the `sample` need to be valid for the duration of this call.
+/// This call is _NOT_ thread-safe.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn ddog_prof_LiveHeapTracker_track(
+    tracker: *mut LiveHeapTracker,
+    id: u64,
+    sample: Sample,
+    weight: i64,
+) -> ProfileResult {
+    (|| {
+        let tracker = tracker_ptr_to_inner(tracker)?;
+        let sample = sample.try_into()?;
+        tracker.track(id, &sample, weight)
+    })()
+    .context("ddog_prof_LiveHeapTracker_track failed")
+    .into()
+}
+
+/// Stops tracking the allocation identified by `id`. Returns false if it wasn't tracked (or if
+/// `tracker` is invalid), which is expected for allocations that were not sampled.
+///
+/// # Safety
+/// The `tracker` ptr must point to a valid LiveHeapTracker object created by this module.
+/// This call is _NOT_ thread-safe.
+#[no_mangle]
+pub unsafe extern "C" fn ddog_prof_LiveHeapTracker_untrack(
+    tracker: *mut LiveHeapTracker,
+    id: u64,
+) -> bool {
+    match tracker_ptr_to_inner(tracker) {
+        Ok(tracker) => tracker.untrack(id),
+        Err(_) => false,
+    }
+}
+
+/// Returns the number of allocations currently tracked, or 0 if `tracker` is invalid.
+///
+/// # Safety
+/// The `tracker` ptr must point to a valid LiveHeapTracker object created by this module.
+#[no_mangle]
+pub unsafe extern "C" fn ddog_prof_LiveHeapTracker_len(tracker: *mut LiveHeapTracker) -> usize {
+    match tracker_ptr_to_inner(tracker) {
+        Ok(tracker) => tracker.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Adds a sample for each allocation that is still tracked to `profile`. Call this right before
+/// `ddog_prof_Profile_serialize`. The allocations remain tracked for the next profile.
+///
+/// # Safety
+/// The `tracker` ptr must point to a valid LiveHeapTracker object created by this module, and
+/// the `profile` ptr must point to a valid Profile object created by this module.
+/// This call is _NOT_ thread-safe.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn ddog_prof_LiveHeapTracker_add_to_profile(
+    tracker: *mut LiveHeapTracker,
+    profile: *mut Profile,
+) -> ProfileResult {
+    (|| {
+        let tracker = tracker_ptr_to_inner(tracker)?;
+        let profile = profile_ptr_to_inner(profile)?;
+        tracker.add_to_profile(profile)
+    })()
+    .context("ddog_prof_LiveHeapTracker_add_to_profile failed")
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiles::{ddog_prof_Profile_drop, ddog_prof_Profile_new, ValueType};
+    use ddcommon_ffi::slice::Slice;
+    use ddcommon_ffi::Error;
+
+    #[test]
+    fn track_untrack_and_fold() -> Result<(), Error> {
+        unsafe {
+            let sample_type: *const ValueType = &ValueType::new("heap-live-size", "bytes");
+            let mut profile = Result::from(ddog_prof_Profile_new(
+                Slice::from_raw_parts(sample_type, 1),
+                None,
+                None,
+            ))?;
+            let mut tracker = ddog_prof_LiveHeapTracker_new(2, 1);
+
+            let values: &[i64] = &[64];
+            let sample = Sample {
+                locations: Slice::empty(),
+                values: Slice::from(values),
+                labels: Slice::empty(),
+            };
+
+            Result::from(ddog_prof_LiveHeapTracker_track(&mut tracker, 1, sample, 1))?;
+            Result::from(ddog_prof_LiveHeapTracker_track(&mut tracker, 2, sample, 2))?;
+            Result::from(ddog_prof_LiveHeapTracker_track(&mut tracker, 3, sample, 1))
+                .expect_err("tracker to be full");
+            assert_eq!(ddog_prof_LiveHeapTracker_len(&mut tracker), 2);
+
+            assert!(ddog_prof_LiveHeapTracker_untrack(&mut tracker, 1));
+            assert!(!ddog_prof_LiveHeapTracker_untrack(&mut tracker, 1));
+
+            Result::from(ddog_prof_LiveHeapTracker_add_to_profile(
+                &mut tracker,
+                &mut profile,
+            ))?;
+            let inner = profile_ptr_to_inner(&mut profile).unwrap();
+            assert_eq!(inner.only_for_testing_num_aggregated_samples(), 1);
+
+            ddog_prof_LiveHeapTracker_drop(&mut tracker);
+            assert_eq!(ddog_prof_LiveHeapTracker_len(&mut tracker), 0);
+            ddog_prof_Profile_drop(&mut profile);
+            Ok(())
+        }
+    }
+}
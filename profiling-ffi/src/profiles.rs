@@ -440,7 +440,7 @@ pub unsafe extern "C" fn ddog_prof_Profile_add(
     .into()
 }
 
-unsafe fn profile_ptr_to_inner<'a>(
+pub(crate) unsafe fn profile_ptr_to_inner<'a>(
     profile_ptr: *mut Profile,
 ) -> anyhow::Result<&'a mut internal::Profile> {
     match profile_ptr.as_mut() {
@@ -510,6 +510,72 @@ pub unsafe extern "C" fn ddog_prof_Profile_add_endpoint_count(
     .into()
 }
 
+/// Associate an arbitrary attribute of the root span, such as its resource or
+/// span type, to a given local root span id. During the serialization of the
+/// profile, a label with the `key` and `value` will be added to all samples
+/// that contain a matching local root span id label, unless they already
+/// have a label with that key. Setting the same key again replaces the value.
+///
+/// # Arguments
+/// * `profile` - a reference to the profile that will contain the samples.
+/// * `local_root_span_id`
+/// * `key` - the key of the label to add. Must not be empty, nor one of the keys managed by
+///   libdatadog such as "trace endpoint" (see `ddog_prof_Profile_set_endpoint`).
+/// * `value` - the value of the label to add for matching samples.
+///
+/// # Safety
+/// The `profile` ptr must point to a valid Profile object created by this
+/// module.
+/// This call is _NOT_ thread-safe.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn ddog_prof_Profile_set_root_span_attribute(
+    profile: *mut Profile,
+    local_root_span_id: u64,
+    key: CharSlice,
+    value: CharSlice,
+) -> ProfileResult {
+    (|| {
+        let profile = profile_ptr_to_inner(profile)?;
+        let key = key.to_utf8_lossy();
+        let value = value.to_utf8_lossy();
+        profile.add_root_span_attribute(local_root_span_id, key, value)
+    })()
+    .context("ddog_prof_Profile_set_root_span_attribute failed")
+    .into()
+}
+
+/// Count the number of times an endpoint of a given type has been seen, e.g.
+/// a background job of type "queue". `ddog_prof_Profile_add_endpoint_count`
+/// counts endpoints of type "web".
+///
+/// # Arguments
+/// * `profile` - a reference to the profile that will contain the samples.
+/// * `endpoint_type` - the type of the endpoint. Must not be empty.
+/// * `endpoint` - the name of the endpoint for which the count will be incremented
+///
+/// # Safety
+/// The `profile` ptr must point to a valid Profile object created by this
+/// module.
+/// This call is _NOT_ thread-safe.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn ddog_prof_Profile_add_typed_endpoint_count(
+    profile: *mut Profile,
+    endpoint_type: CharSlice,
+    endpoint: CharSlice,
+    value: i64,
+) -> ProfileResult {
+    (|| {
+        let profile = profile_ptr_to_inner(profile)?;
+        let endpoint_type = endpoint_type.to_utf8_lossy();
+        let endpoint = endpoint.to_utf8_lossy();
+        profile.add_typed_endpoint_count(endpoint_type, endpoint, value)
+    })()
+    .context("ddog_prof_Profile_add_typed_endpoint_count failed")
+    .into()
+}
+
 /// Add a poisson-based upscaling rule which will be use to adjust values and make them
 /// closer to reality.
 ///
@@ -0,0 +1,434 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::profile_index::ProfileIndex;
+use anyhow::Result;
+use datadog_profiling::pprof;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Self and total (inclusive) values of a function, one per sample type.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FunctionStats {
+    pub self_values: Vec<i64>,
+    pub total_values: Vec<i64>,
+}
+
+/// Values aggregated by function name.
+pub struct FunctionTable {
+    /// "type/unit" for each sample type, in pprof order.
+    pub sample_types: Vec<String>,
+    pub functions: HashMap<String, FunctionStats>,
+    /// Sum of all sample values, per sample type.
+    pub totals: Vec<i64>,
+}
+
+fn location_name(profile_index: &ProfileIndex, location: &pprof::Location) -> Result<String> {
+    // Inlined frames are represented by several lines; the first one is the
+    // innermost function.
+    match location.lines.first() {
+        Some(line) if line.function_id != 0 => {
+            let function = profile_index.get_function(line.function_id)?;
+            let name = profile_index.get_string(function.name)?;
+            if name.is_empty() {
+                Ok(format!("{:#x}", location.address))
+            } else {
+                Ok(name.to_string())
+            }
+        }
+        _ => Ok(format!("{:#x}", location.address)),
+    }
+}
+
+/// Returns the frame names of a location from innermost to outermost,
+/// expanding inlined functions into separate frames.
+fn location_frames(
+    profile_index: &ProfileIndex,
+    location: &pprof::Location,
+) -> Result<Vec<String>> {
+    if location.lines.is_empty() {
+        return Ok(vec![format!("{:#x}", location.address)]);
+    }
+    let mut frames = Vec::with_capacity(location.lines.len());
+    for line in location.lines.iter() {
+        let name = if line.function_id == 0 {
+            String::new()
+        } else {
+            let function = profile_index.get_function(line.function_id)?;
+            profile_index.get_string(function.name)?.to_string()
+        };
+        frames.push(if name.is_empty() {
+            format!("{:#x}", location.address)
+        } else {
+            name
+        });
+    }
+    Ok(frames)
+}
+
+pub fn sample_type_names(profile_index: &ProfileIndex) -> Result<Vec<String>> {
+    profile_index
+        .pprof
+        .sample_types
+        .iter()
+        .map(|t| {
+            Ok(format!(
+                "{}/{}",
+                profile_index.get_string(t.r#type)?,
+                profile_index.get_string(t.unit)?
+            ))
+        })
+        .collect()
+}
+
+impl FunctionTable {
+    pub fn new(profile_index: &ProfileIndex) -> Result<Self> {
+        let sample_types = sample_type_names(profile_index)?;
+        let n = sample_types.len();
+        let mut functions: HashMap<String, FunctionStats> = HashMap::new();
+        let mut totals = vec![0i64; n];
+        let mut seen = HashSet::new();
+
+        for sample in profile_index.pprof.samples.iter() {
+            anyhow::ensure!(
+                sample.values.len() == n,
+                "sample has {} values but the profile has {n} sample types",
+                sample.values.len()
+            );
+            for (total, value) in totals.iter_mut().zip(sample.values.iter()) {
+                *total = total.saturating_add(*value);
+            }
+
+            seen.clear();
+            for (depth, location_id) in sample.location_ids.iter().enumerate() {
+                let location = profile_index.get_location(*location_id)?;
+                let name = location_name(profile_index, location)?;
+                let stats = functions
+                    .entry(name.clone())
+                    .or_insert_with(|| FunctionStats {
+                        self_values: vec![0; n],
+                        total_values: vec![0; n],
+                    });
+                if depth == 0 {
+                    for (v, value) in stats.self_values.iter_mut().zip(sample.values.iter()) {
+                        *v = v.saturating_add(*value);
+                    }
+                }
+                // Recursive functions must only be counted once per sample.
+                if seen.insert(name) {
+                    for (v, value) in stats.total_values.iter_mut().zip(sample.values.iter()) {
+                        *v = v.saturating_add(*value);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            sample_types,
+            functions,
+            totals,
+        })
+    }
+
+    /// Returns the `n` functions with the highest self value for the given
+    /// sample type index, highest first.
+    pub fn top(&self, sample_type: usize, n: usize) -> Vec<(&str, &FunctionStats)> {
+        let mut entries: Vec<_> = self
+            .functions
+            .iter()
+            .map(|(name, stats)| (name.as_str(), stats))
+            .filter(|(_, stats)| stats.self_values[sample_type] != 0)
+            .collect();
+        entries.sort_by(|(a_name, a), (b_name, b)| {
+            b.self_values[sample_type]
+                .cmp(&a.self_values[sample_type])
+                .then_with(|| a_name.cmp(b_name))
+        });
+        entries.truncate(n);
+        entries
+    }
+}
+
+fn percent(value: i64, total: i64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        value as f64 * 100.0 / total as f64
+    }
+}
+
+pub fn format_top(table: &FunctionTable, n: usize) -> String {
+    let mut out = String::new();
+    for (index, sample_type) in table.sample_types.iter().enumerate() {
+        let total = table.totals[index];
+        let _ = writeln!(out, "Top {n} functions by {sample_type} (total {total}):");
+        let _ = writeln!(
+            out,
+            "{:>14} {:>7} {:>14} {:>7}  function",
+            "self", "self%", "total", "total%"
+        );
+        for (name, stats) in table.top(index, n) {
+            let self_value = stats.self_values[index];
+            let total_value = stats.total_values[index];
+            let _ = writeln!(
+                out,
+                "{:>14} {:>6.2}% {:>14} {:>6.2}%  {name}",
+                self_value,
+                percent(self_value, total),
+                total_value,
+                percent(total_value, total),
+            );
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Per-function change in self value between two profiles.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionDelta {
+    pub name: String,
+    pub before: i64,
+    pub after: i64,
+}
+
+impl FunctionDelta {
+    pub fn delta(&self) -> i64 {
+        self.after.saturating_sub(self.before)
+    }
+}
+
+/// Compares self values by function for every sample type present in both
+/// profiles. Returns, per sample type, the `n` functions with the largest
+/// absolute change.
+pub fn diff(
+    before: &FunctionTable,
+    after: &FunctionTable,
+    n: usize,
+) -> Vec<(String, Vec<FunctionDelta>)> {
+    let mut result = vec![];
+    for (after_index, sample_type) in after.sample_types.iter().enumerate() {
+        let Some(before_index) = before.sample_types.iter().position(|t| t == sample_type) else {
+            continue;
+        };
+        let names: HashSet<&String> = before
+            .functions
+            .keys()
+            .chain(after.functions.keys())
+            .collect();
+        let mut deltas: Vec<FunctionDelta> = names
+            .into_iter()
+            .map(|name| FunctionDelta {
+                name: name.clone(),
+                before: before
+                    .functions
+                    .get(name)
+                    .map_or(0, |s| s.self_values[before_index]),
+                after: after
+                    .functions
+                    .get(name)
+                    .map_or(0, |s| s.self_values[after_index]),
+            })
+            .filter(|d| d.delta() != 0)
+            .collect();
+        deltas.sort_by(|a, b| {
+            b.delta()
+                .unsigned_abs()
+                .cmp(&a.delta().unsigned_abs())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        deltas.truncate(n);
+        result.push((sample_type.clone(), deltas));
+    }
+    result
+}
+
+pub fn format_diff(
+    before: &FunctionTable,
+    after: &FunctionTable,
+    diffs: &[(String, Vec<FunctionDelta>)],
+) -> String {
+    let mut out = String::new();
+    for (sample_type, deltas) in diffs {
+        let before_total = before
+            .sample_types
+            .iter()
+            .position(|t| t == sample_type)
+            .map_or(0, |i| before.totals[i]);
+        let after_total = after
+            .sample_types
+            .iter()
+            .position(|t| t == sample_type)
+            .map_or(0, |i| after.totals[i]);
+        let _ = writeln!(
+            out,
+            "{sample_type}: total {before_total} -> {after_total} ({:+})",
+            after_total.saturating_sub(before_total)
+        );
+        let _ = writeln!(
+            out,
+            "{:>14} {:>14} {:>14}  function",
+            "before", "after", "delta"
+        );
+        for delta in deltas {
+            let _ = writeln!(
+                out,
+                "{:>14} {:>14} {:>+14}  {}",
+                delta.before,
+                delta.after,
+                delta.delta(),
+                delta.name
+            );
+        }
+        out.push('\n');
+    }
+    for sample_type in before.sample_types.iter() {
+        if !after.sample_types.contains(sample_type) {
+            let _ = writeln!(out, "{sample_type}: only present in the first profile");
+        }
+    }
+    for sample_type in after.sample_types.iter() {
+        if !before.sample_types.contains(sample_type) {
+            let _ = writeln!(out, "{sample_type}: only present in the second profile");
+        }
+    }
+    out
+}
+
+/// Converts the profile into the "folded stacks" format consumed by
+/// flamegraph tools: one `root;...;leaf value` line per distinct stack,
+/// using the values of the given sample type.
+pub fn folded(profile_index: &ProfileIndex, sample_type: usize) -> Result<String> {
+    let mut stacks: HashMap<String, i64> = HashMap::new();
+    for sample in profile_index.pprof.samples.iter() {
+        let Some(value) = sample.values.get(sample_type).copied() else {
+            anyhow::bail!("sample is missing a value for sample type {sample_type}");
+        };
+        if value == 0 {
+            continue;
+        }
+        let mut frames = vec![];
+        for location_id in sample.location_ids.iter().rev() {
+            let location = profile_index.get_location(*location_id)?;
+            let mut names = location_frames(profile_index, location)?;
+            names.reverse();
+            frames.extend(names);
+        }
+        // Semicolons separate frames in the folded format.
+        let stack = frames
+            .iter()
+            .map(|f| f.replace(';', ":"))
+            .collect::<Vec<_>>()
+            .join(";");
+        let total = stacks.entry(stack).or_default();
+        *total = total.saturating_add(value);
+    }
+
+    let mut stacks: Vec<_> = stacks.into_iter().collect();
+    stacks.sort();
+    let mut out = String::new();
+    for (stack, value) in stacks {
+        let _ = writeln!(out, "{stack} {value}");
+    }
+    Ok(out)
+}
+
+/// Resolves a sample type given either as an index or as a type name, e.g.
+/// "cpu-time" or "cpu-time/nanoseconds".
+pub fn find_sample_type(sample_types: &[String], needle: &str) -> Result<usize> {
+    if let Ok(index) = needle.parse::<usize>() {
+        anyhow::ensure!(
+            index < sample_types.len(),
+            "sample type index {index} is out of range, the profile has {} sample types",
+            sample_types.len()
+        );
+        return Ok(index);
+    }
+    sample_types
+        .iter()
+        .position(|t| t == needle || t.split('/').next() == Some(needle))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "sample type {needle} not found, available: {}",
+                sample_types.join(", ")
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::tests::test_profile;
+
+    #[test]
+    fn top_and_total() {
+        let pprof = test_profile();
+        let index = ProfileIndex::try_from(&pprof).unwrap();
+        let table = FunctionTable::new(&index).unwrap();
+
+        assert_eq!(table.sample_types, vec!["samples/count", "cpu/nanoseconds"]);
+        assert_eq!(table.totals, vec![3, 60]);
+
+        let top = table.top(1, 10);
+        assert_eq!(top[0].0, "leaf");
+        assert_eq!(top[0].1.self_values, vec![2, 50]);
+        assert_eq!(top[1].0, "main");
+        assert_eq!(top[1].1.self_values, vec![1, 10]);
+        // main is on every stack, and counted once per sample.
+        assert_eq!(top[1].1.total_values, vec![3, 60]);
+    }
+
+    #[test]
+    fn large_values_saturate() {
+        let mut pprof = test_profile();
+        for sample in pprof.samples.iter_mut() {
+            sample.values = vec![i64::MAX, i64::MAX];
+        }
+        let index = ProfileIndex::try_from(&pprof).unwrap();
+        let table = FunctionTable::new(&index).unwrap();
+        assert_eq!(table.totals, vec![i64::MAX, i64::MAX]);
+        folded(&index, 1).unwrap();
+    }
+
+    #[test]
+    fn diff_reports_changes() {
+        let before = test_profile();
+        let mut after = test_profile();
+        after.samples[0].values = vec![2, 120];
+
+        let before_index = ProfileIndex::try_from(&before).unwrap();
+        let after_index = ProfileIndex::try_from(&after).unwrap();
+        let before = FunctionTable::new(&before_index).unwrap();
+        let after = FunctionTable::new(&after_index).unwrap();
+        let diffs = diff(&before, &after, 10);
+
+        assert_eq!(diffs.len(), 2);
+        assert!(diffs[0].1.is_empty());
+        assert_eq!(
+            diffs[1].1,
+            vec![FunctionDelta {
+                name: "leaf".to_string(),
+                before: 50,
+                after: 120,
+            }]
+        );
+    }
+
+    #[test]
+    fn folded_stacks() {
+        let pprof = test_profile();
+        let index = ProfileIndex::try_from(&pprof).unwrap();
+        let out = folded(&index, 1).unwrap();
+        assert_eq!(out, "main 10\nmain;leaf 50\n");
+    }
+
+    #[test]
+    fn sample_type_lookup() {
+        let types = vec!["samples/count".to_string(), "cpu/nanoseconds".to_string()];
+        assert_eq!(find_sample_type(&types, "cpu").unwrap(), 1);
+        assert_eq!(find_sample_type(&types, "samples/count").unwrap(), 0);
+        assert_eq!(find_sample_type(&types, "1").unwrap(), 1);
+        find_sample_type(&types, "2").unwrap_err();
+        find_sample_type(&types, "wall").unwrap_err();
+    }
+}
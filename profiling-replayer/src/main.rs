@@ -1,13 +1,16 @@
 // Copyright 2023-Present Datadog, Inc. https://www.datadoghq.com/
 // SPDX-License-Identifier: Apache-2.0
 
+mod analysis;
 mod profile_index;
 mod replayer;
+mod validate;
 
-use clap::{command, Arg, ArgAction};
+use clap::{command, value_parser, Arg, ArgAction, ArgMatches, Command};
+use profile_index::ProfileIndex;
 use prost::Message;
 use std::borrow::Cow;
-use std::io::Cursor;
+use std::io::{Cursor, Read};
 use std::time::Instant;
 use sysinfo::{Pid, ProcessExt, RefreshKind, System, SystemExt};
 
@@ -105,14 +108,27 @@ impl Sysinfo {
     }
 }
 
+fn input_arg() -> Arg {
+    Arg::new("input")
+        .short('i')
+        .help("the pprof to read, either raw or lz4-compressed as produced by libdatadog")
+        .required(true)
+}
+
+fn count_arg() -> Arg {
+    Arg::new("count")
+        .short('n')
+        .long("count")
+        .value_parser(value_parser!(usize))
+        .default_value("20")
+        .help("how many functions to print per sample type")
+}
+
 fn main() -> anyhow::Result<()> {
     let matches = command!()
-        .arg(
-            Arg::new("input")
-                .short('i')
-                .help("the pprof to replay")
-                .required(true),
-        )
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .arg(input_arg().help("the pprof to replay"))
         .arg(
             Arg::new("mem")
                 .short('m')
@@ -127,8 +143,126 @@ fn main() -> anyhow::Result<()> {
                 .help("the path to save the result to")
                 .required(false),
         )
+        .subcommand(
+            Command::new("top")
+                .about("Prints the functions with the highest self value for each sample type")
+                .arg(input_arg())
+                .arg(count_arg()),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Prints the per-function change in self value between two pprofs")
+                .arg(Arg::new("before").help("the baseline pprof").required(true))
+                .arg(
+                    Arg::new("after")
+                        .help("the pprof to compare")
+                        .required(true),
+                )
+                .arg(count_arg()),
+        )
+        .subcommand(
+            Command::new("validate")
+                .about("Checks a pprof against the invariants libdatadog relies on")
+                .arg(input_arg()),
+        )
+        .subcommand(
+            Command::new("folded")
+                .about("Converts a pprof to folded stacks, as consumed by flamegraph tools")
+                .arg(input_arg())
+                .arg(
+                    Arg::new("type")
+                        .short('t')
+                        .long("type")
+                        .default_value("0")
+                        .help("the sample type to use, by index or name, e.g. cpu-time"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .help("the path to save the result to, defaults to stdout"),
+                ),
+        )
         .get_matches();
 
+    match matches.subcommand() {
+        Some(("top", matches)) => top(matches),
+        Some(("diff", matches)) => diff(matches),
+        Some(("validate", matches)) => validate(matches),
+        Some(("folded", matches)) => folded(matches),
+        _ => replay(&matches),
+    }
+}
+
+/// Reads a pprof file. Files compressed with lz4, like the ones produced by
+/// libdatadog, are decompressed first.
+fn read_pprof(path: &str) -> anyhow::Result<datadog_profiling::pprof::Profile> {
+    const LZ4_FRAME_MAGIC: [u8; 4] = [0x04, 0x22, 0x4d, 0x18];
+
+    let source = std::fs::read(path)
+        .map_err(|err| anyhow::anyhow!("failed to read pprof from file '{path}': {err}"))?;
+    let source = if source.starts_with(&LZ4_FRAME_MAGIC) {
+        let mut decompressed = Vec::with_capacity(source.len() * 4);
+        lz4_flex::frame::FrameDecoder::new(source.as_slice()).read_to_end(&mut decompressed)?;
+        decompressed
+    } else {
+        source
+    };
+    Ok(datadog_profiling::pprof::Profile::decode(
+        &mut Cursor::new(source),
+    )?)
+}
+
+fn top(matches: &ArgMatches) -> anyhow::Result<()> {
+    let pprof = read_pprof(matches.get_one::<String>("input").unwrap())?;
+    let count = *matches.get_one::<usize>("count").unwrap();
+    let index = ProfileIndex::try_from(&pprof)?;
+    let table = analysis::FunctionTable::new(&index)?;
+    print!("{}", analysis::format_top(&table, count));
+    Ok(())
+}
+
+fn diff(matches: &ArgMatches) -> anyhow::Result<()> {
+    let before = read_pprof(matches.get_one::<String>("before").unwrap())?;
+    let after = read_pprof(matches.get_one::<String>("after").unwrap())?;
+    let count = *matches.get_one::<usize>("count").unwrap();
+    let before_index = ProfileIndex::try_from(&before)?;
+    let after_index = ProfileIndex::try_from(&after)?;
+    let before = analysis::FunctionTable::new(&before_index)?;
+    let after = analysis::FunctionTable::new(&after_index)?;
+    let diffs = analysis::diff(&before, &after, count);
+    print!("{}", analysis::format_diff(&before, &after, &diffs));
+    Ok(())
+}
+
+fn validate(matches: &ArgMatches) -> anyhow::Result<()> {
+    let input = matches.get_one::<String>("input").unwrap();
+    let pprof = read_pprof(input)?;
+    let errors = validate::validate(&pprof);
+    if errors.is_empty() {
+        println!("'{input}' is valid.");
+        return Ok(());
+    }
+    for error in errors.iter() {
+        println!("{error}");
+    }
+    anyhow::bail!("'{input}' has {} problem(s)", errors.len())
+}
+
+fn folded(matches: &ArgMatches) -> anyhow::Result<()> {
+    let pprof = read_pprof(matches.get_one::<String>("input").unwrap())?;
+    let index = ProfileIndex::try_from(&pprof)?;
+    let sample_types = analysis::sample_type_names(&index)?;
+    let sample_type =
+        analysis::find_sample_type(&sample_types, matches.get_one::<String>("type").unwrap())?;
+    let out = analysis::folded(&index, sample_type)?;
+    match matches.get_one::<String>("output") {
+        Some(file) => std::fs::write(file, out)?,
+        None => print!("{out}"),
+    }
+    Ok(())
+}
+
+fn replay(matches: &ArgMatches) -> anyhow::Result<()> {
     let input = matches.get_one::<String>("input").unwrap();
     let output = matches.get_one::<String>("output");
     let collect_memory_stats = matches.get_flag("mem");
@@ -138,12 +272,8 @@ fn main() -> anyhow::Result<()> {
         None
     };
 
-    let source = {
-        println!("Reading in pprof from file '{input}'");
-        std::fs::read(input)?
-    };
-
-    let pprof = datadog_profiling::pprof::Profile::decode(&mut Cursor::new(source))?;
+    println!("Reading in pprof from file '{input}'");
+    let pprof = read_pprof(input)?;
 
     let mut replayer = Replayer::try_from(&pprof)?;
 
@@ -0,0 +1,262 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use datadog_profiling::pprof;
+use std::collections::HashSet;
+
+/// Checks the pprof against the invariants libdatadog relies on when
+/// producing and consuming profiles. Returns one message per violation; an
+/// empty result means the profile is valid.
+pub fn validate(pprof: &pprof::Profile) -> Vec<String> {
+    let mut errors = vec![];
+    let strings = pprof.string_table.len();
+
+    match pprof.string_table.first() {
+        None => errors.push("string table is empty, index 0 must be \"\"".to_string()),
+        Some(s) if !s.is_empty() => {
+            errors.push(format!("string table index 0 must be \"\", found {s:?}"))
+        }
+        Some(_) => {}
+    }
+
+    let check_string = |errors: &mut Vec<String>, what: &str, index: i64| {
+        if index < 0 || index as u64 >= strings as u64 {
+            errors.push(format!(
+                "{what} refers to string index {index}, but the string table has {strings} entries"
+            ));
+        }
+    };
+
+    for (i, sample_type) in pprof.sample_types.iter().enumerate() {
+        check_string(
+            &mut errors,
+            &format!("sample type {i} type"),
+            sample_type.r#type,
+        );
+        check_string(
+            &mut errors,
+            &format!("sample type {i} unit"),
+            sample_type.unit,
+        );
+    }
+    if let Some(period_type) = &pprof.period_type {
+        check_string(&mut errors, "period type", period_type.r#type);
+        check_string(&mut errors, "period unit", period_type.unit);
+    }
+
+    let mut mapping_ids = HashSet::new();
+    for mapping in pprof.mappings.iter() {
+        let id = mapping.id;
+        if id == 0 {
+            errors.push("mapping has id 0, ids must be non-zero".to_string());
+        } else if !mapping_ids.insert(id) {
+            errors.push(format!("mapping id {id} is used more than once"));
+        }
+        check_string(
+            &mut errors,
+            &format!("mapping {id} filename"),
+            mapping.filename,
+        );
+        check_string(
+            &mut errors,
+            &format!("mapping {id} build id"),
+            mapping.build_id,
+        );
+    }
+
+    let mut function_ids = HashSet::new();
+    for function in pprof.functions.iter() {
+        let id = function.id;
+        if id == 0 {
+            errors.push("function has id 0, ids must be non-zero".to_string());
+        } else if !function_ids.insert(id) {
+            errors.push(format!("function id {id} is used more than once"));
+        }
+        check_string(&mut errors, &format!("function {id} name"), function.name);
+        check_string(
+            &mut errors,
+            &format!("function {id} system name"),
+            function.system_name,
+        );
+        check_string(
+            &mut errors,
+            &format!("function {id} filename"),
+            function.filename,
+        );
+    }
+
+    let mut location_ids = HashSet::new();
+    for location in pprof.locations.iter() {
+        let id = location.id;
+        if id == 0 {
+            errors.push("location has id 0, ids must be non-zero".to_string());
+        } else if !location_ids.insert(id) {
+            errors.push(format!("location id {id} is used more than once"));
+        }
+        if location.mapping_id != 0 && !mapping_ids.contains(&location.mapping_id) {
+            errors.push(format!(
+                "location {id} refers to mapping {}, which does not exist",
+                location.mapping_id
+            ));
+        }
+        for line in location.lines.iter() {
+            if line.function_id == 0 {
+                errors.push(format!("location {id} has a line with function id 0"));
+            } else if !function_ids.contains(&line.function_id) {
+                errors.push(format!(
+                    "location {id} refers to function {}, which does not exist",
+                    line.function_id
+                ));
+            }
+        }
+    }
+
+    let num_sample_types = pprof.sample_types.len();
+    for (i, sample) in pprof.samples.iter().enumerate() {
+        if sample.values.len() != num_sample_types {
+            errors.push(format!(
+                "sample {i} has {} values, but the profile has {num_sample_types} sample types",
+                sample.values.len()
+            ));
+        }
+        for location_id in sample.location_ids.iter() {
+            if !location_ids.contains(location_id) {
+                errors.push(format!(
+                    "sample {i} refers to location {location_id}, which does not exist"
+                ));
+            }
+        }
+        let mut keys = HashSet::new();
+        for label in sample.labels.iter() {
+            check_string(&mut errors, &format!("sample {i} label key"), label.key);
+            check_string(&mut errors, &format!("sample {i} label str"), label.str);
+            check_string(
+                &mut errors,
+                &format!("sample {i} label unit"),
+                label.num_unit,
+            );
+            if label.key == 0 {
+                errors.push(format!("sample {i} has a label with an empty key"));
+            }
+            if label.str != 0 && (label.num != 0 || label.num_unit != 0) {
+                errors.push(format!(
+                    "sample {i} has a label with both a string and a number value"
+                ));
+            }
+            if !keys.insert(label.key) {
+                errors.push(format!(
+                    "sample {i} has more than one label with key index {}",
+                    label.key
+                ));
+            }
+        }
+    }
+
+    if pprof.duration_nanos < 0 {
+        errors.push(format!(
+            "duration must not be negative, found {}",
+            pprof.duration_nanos
+        ));
+    }
+
+    errors
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A small, valid profile with two sample types, where `leaf` is called
+    /// from `main`.
+    pub(crate) fn test_profile() -> pprof::Profile {
+        let string_table = ["", "samples", "count", "cpu", "nanoseconds", "main", "leaf"]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let function = |id, name| pprof::Function {
+            id,
+            name,
+            system_name: name,
+            filename: 0,
+            start_line: 0,
+        };
+        let location = |id, function_id| pprof::Location {
+            id,
+            mapping_id: 0,
+            address: 0,
+            lines: vec![pprof::Line {
+                function_id,
+                line: 0,
+            }],
+            is_folded: false,
+        };
+        pprof::Profile {
+            sample_types: vec![
+                pprof::ValueType { r#type: 1, unit: 2 },
+                pprof::ValueType { r#type: 3, unit: 4 },
+            ],
+            samples: vec![
+                pprof::Sample {
+                    location_ids: vec![2, 1],
+                    values: vec![2, 50],
+                    labels: vec![],
+                },
+                pprof::Sample {
+                    location_ids: vec![1],
+                    values: vec![1, 10],
+                    labels: vec![],
+                },
+            ],
+            locations: vec![location(1, 1), location(2, 2)],
+            functions: vec![function(1, 5), function(2, 6)],
+            string_table,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn valid_profile() {
+        assert_eq!(validate(&test_profile()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn string_table_must_start_with_empty_string() {
+        let mut pprof = test_profile();
+        pprof.string_table[0] = "oops".to_string();
+        assert_eq!(
+            validate(&pprof),
+            vec!["string table index 0 must be \"\", found \"oops\""]
+        );
+    }
+
+    #[test]
+    fn dangling_references() {
+        let mut pprof = test_profile();
+        pprof.functions[0].id = 0;
+        pprof.locations[1].lines[0].function_id = 9;
+        pprof.samples[1].location_ids.push(7);
+        pprof.functions[1].name = 100;
+
+        let errors = validate(&pprof);
+        assert_eq!(
+            errors,
+            vec![
+                "function has id 0, ids must be non-zero",
+                "function 2 name refers to string index 100, but the string table has 7 entries",
+                "location 1 refers to function 1, which does not exist",
+                "location 2 refers to function 9, which does not exist",
+                "sample 1 refers to location 7, which does not exist",
+            ]
+        );
+    }
+
+    #[test]
+    fn sample_values_must_match_sample_types() {
+        let mut pprof = test_profile();
+        pprof.samples[0].values.pop();
+        assert_eq!(
+            validate(&pprof),
+            vec!["sample 0 has 1 values, but the profile has 2 sample types"]
+        );
+    }
+}
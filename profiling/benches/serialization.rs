@@ -0,0 +1,196 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use criterion::*;
+use datadog_profiling::serializer::ChunkedBuffer;
+use datadog_profiling::{api, internal, pprof};
+use lz4_flex::frame::FrameDecoder;
+use prost::Message;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::Read;
+use std::num::NonZeroI64;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+/// Tracks the bytes currently allocated and the high-water mark since the
+/// last [PeakAllocator::reset_peak], so the benchmark can report how much
+/// memory serialization needs on top of the profile itself. This is why this
+/// benchmark is a separate target: it would skew the other benchmarks.
+struct PeakAllocator {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl PeakAllocator {
+    fn current(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    fn reset_peak(&self) {
+        self.peak.store(self.current(), Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = self.current.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            self.peak.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        self.current.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakAllocator = PeakAllocator {
+    current: AtomicUsize::new(0),
+    peak: AtomicUsize::new(0),
+};
+
+/// Each wordpress sample is added this many times with distinct timestamps,
+/// to get a profile large enough for the serialization overhead to matter.
+const REPETITIONS: i64 = 50;
+
+fn wordpress_pprof() -> pprof::Profile {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/wordpress.pprof.lz4");
+    let mut decoder = FrameDecoder::new(std::fs::File::open(path).unwrap());
+    let mut bytes = Vec::new();
+    decoder.read_to_end(&mut bytes).unwrap();
+    pprof::Profile::decode(bytes.as_slice()).unwrap()
+}
+
+fn wordpress_profile(pprof: &pprof::Profile) -> internal::Profile {
+    let api = api::Profile::try_from(pprof).unwrap();
+    let period = api
+        .period
+        .map(|(value, r#type)| api::Period { r#type, value });
+    let mut profile = internal::Profile::new(api.start_time, &api.sample_types, period);
+    for repetition in 1..=REPETITIONS {
+        for sample in api.samples.iter() {
+            let timestamp = NonZeroI64::new(repetition);
+            profile.add_sample(sample.clone(), timestamp).unwrap();
+        }
+    }
+    profile
+}
+
+/// Returns how many bytes were allocated at the peak of `f`, on top of what
+/// was already allocated before it, and the size of its output.
+fn peak_overhead(f: impl FnOnce() -> usize) -> (usize, usize) {
+    ALLOCATOR.reset_peak();
+    let baseline = ALLOCATOR.current();
+    let len = f();
+    (ALLOCATOR.peak() - baseline, len)
+}
+
+fn report_peak_memory(pprof: &pprof::Profile) {
+    let end = Some(SystemTime::now());
+    let duration = Some(Duration::from_secs(60));
+
+    let profile = wordpress_profile(pprof);
+    let (vec_peak, vec_len) = peak_overhead(|| {
+        let encoded = profile
+            .serialize_into_compressed_pprof(end, duration)
+            .unwrap();
+        encoded.buffer.len()
+    });
+
+    let profile = wordpress_profile(pprof);
+    let (chunked_peak, chunked_len) = peak_overhead(|| {
+        let encoded = profile
+            .serialize_into_compressed_pprof_writer(ChunkedBuffer::new(), end, duration)
+            .unwrap();
+        encoded.buffer.len()
+    });
+
+    // Reading the stream into a sink stands in for the request body.
+    let profile = wordpress_profile(pprof);
+    let (stream_peak, stream_len) = peak_overhead(|| {
+        let mut encoded = profile
+            .serialize_into_compressed_pprof_stream(end, duration)
+            .unwrap();
+        std::io::copy(&mut encoded.buffer, &mut std::io::sink()).unwrap() as usize
+    });
+
+    println!("wordpress profile x{REPETITIONS}, compressed to {vec_len} bytes:");
+    println!("  peak serialization overhead into Vec<u8>:       {vec_peak} bytes");
+    println!("  peak serialization overhead into ChunkedBuffer: {chunked_peak} bytes");
+    println!("  peak serialization overhead when streamed:      {stream_peak} bytes");
+    assert_eq!(stream_len, chunked_len);
+
+    // Serialization frees the profile's data as it goes, so the peak must
+    // stay within the output plus a fixed amount of working memory: the
+    // decoder and encoder block buffers and the largest single message.
+    const WORKING_MEMORY: usize = 1024 * 1024;
+    assert!(
+        chunked_peak <= chunked_len + WORKING_MEMORY,
+        "peak overhead of {chunked_peak} bytes is not bounded by the output size {chunked_len}"
+    );
+    // When streamed, the output isn't held in memory at all.
+    assert!(
+        stream_peak <= WORKING_MEMORY,
+        "peak overhead of {stream_peak} bytes when streamed is not bounded"
+    );
+}
+
+pub fn serialize_wordpress_profile(c: &mut Criterion) {
+    let pprof = wordpress_pprof();
+    report_peak_memory(&pprof);
+
+    let end = Some(SystemTime::now());
+    let duration = Some(Duration::from_secs(60));
+    let mut group = c.benchmark_group("serializing wordpress profile");
+    group.sample_size(10);
+    group.bench_function("into Vec<u8>", |b| {
+        b.iter_batched(
+            || wordpress_profile(&pprof),
+            |profile| {
+                black_box(
+                    profile
+                        .serialize_into_compressed_pprof(end, duration)
+                        .unwrap(),
+                )
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("into ChunkedBuffer", |b| {
+        b.iter_batched(
+            || wordpress_profile(&pprof),
+            |profile| {
+                black_box(
+                    profile
+                        .serialize_into_compressed_pprof_writer(ChunkedBuffer::new(), end, duration)
+                        .unwrap(),
+                )
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("streamed", |b| {
+        b.iter_batched(
+            || wordpress_profile(&pprof),
+            |profile| {
+                let mut encoded = profile
+                    .serialize_into_compressed_pprof_stream(end, duration)
+                    .unwrap();
+                black_box(std::io::copy(&mut encoded.buffer, &mut std::io::sink()).unwrap())
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, serialize_wordpress_profile);
+criterion_main!(benches);
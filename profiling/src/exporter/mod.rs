@@ -3,11 +3,12 @@
 
 use std::borrow::Cow;
 use std::future;
-use std::io::{Cursor, Write};
+use std::io::{Cursor, Read, Write};
 
 use bytes::Bytes;
 pub use chrono::{DateTime, Utc};
 pub use ddcommon::tag::Tag;
+use hyper::body::HttpBody;
 pub use hyper::Uri;
 use hyper_multipart_rfc7578::client::multipart;
 use lz4_flex::frame::FrameEncoder;
@@ -19,6 +20,7 @@ use ddcommon::{azure_app_services, connector, Endpoint, HttpClient, HttpResponse
 
 pub mod config;
 mod errors;
+pub mod spool;
 
 #[cfg(unix)]
 pub use connector::uds::{socket_path_from_uri, socket_path_to_uri};
@@ -27,6 +29,7 @@ pub use connector::uds::{socket_path_from_uri, socket_path_to_uri};
 pub use connector::named_pipe::{named_pipe_path_from_uri, named_pipe_path_to_uri};
 
 use crate::internal::ProfiledEndpointsStats;
+use spool::{DrainResult, Spool, SpoolConfig, SpoolEntry};
 
 const DURATION_ZERO: std::time::Duration = std::time::Duration::from_millis(0);
 
@@ -47,6 +50,7 @@ pub struct ProfileExporter {
     profiling_library_name: Cow<'static, str>,
     profiling_library_version: Cow<'static, str>,
     tags: Option<Vec<Tag>>,
+    spool: Option<Spool>,
 }
 
 pub struct File<'a> {
@@ -54,6 +58,24 @@ pub struct File<'a> {
     pub bytes: &'a [u8],
 }
 
+/// A file whose contents are read into the request body while it is sent,
+/// instead of being copied into the request when it is built. The contents
+/// are exported as-is, so they should already be compressed, e.g. by
+/// [crate::internal::Profile::serialize_into_compressed_pprof_stream].
+pub struct ChunkedFile {
+    pub name: String,
+    pub data: Box<dyn Read + Send + Sync>,
+}
+
+impl ChunkedFile {
+    pub fn new<N: Into<String>, R: Read + Send + Sync + 'static>(name: N, data: R) -> Self {
+        Self {
+            name: name.into(),
+            data: Box::new(data),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Request {
     timeout: Option<std::time::Duration>,
@@ -147,6 +169,7 @@ impl ProfileExporter {
             profiling_library_name: profiling_library_name.into(),
             profiling_library_version: profiling_library_version.into(),
             tags,
+            spool: None,
         })
     }
 
@@ -170,6 +193,40 @@ impl ProfileExporter {
         endpoint_counts: Option<&ProfiledEndpointsStats>,
         internal_metadata: Option<serde_json::Value>,
         info: Option<serde_json::Value>,
+    ) -> anyhow::Result<Request> {
+        self.build_with_chunked_files(
+            start,
+            end,
+            files_to_compress_and_export,
+            files_to_export_unmodified,
+            vec![],
+            additional_tags,
+            endpoint_counts,
+            internal_metadata,
+            info,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    /// Like [ProfileExporter::build], but additionally takes `chunked_files`, which are read
+    /// into the request body as it is sent rather than copied into it beforehand. A file from
+    /// [crate::internal::Profile::serialize_into_compressed_pprof_stream] is thus serialized
+    /// while it is uploaded, without ever being held in memory as a whole. Reading the files
+    /// may block the upload, e.g. until the next chunk has been serialized.
+    ///
+    /// If a spool is configured, the body is buffered in full when the request is sent, so
+    /// that it can still be spooled if sending fails.
+    pub fn build_with_chunked_files(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        files_to_compress_and_export: &[File],
+        files_to_export_unmodified: &[File],
+        chunked_files: Vec<ChunkedFile>,
+        additional_tags: Option<&Vec<Tag>>,
+        endpoint_counts: Option<&ProfiledEndpointsStats>,
+        internal_metadata: Option<serde_json::Value>,
+        info: Option<serde_json::Value>,
     ) -> anyhow::Result<Request> {
         let mut form = multipart::Form::default();
 
@@ -217,6 +274,7 @@ impl ProfileExporter {
             .iter()
             .chain(files_to_export_unmodified.iter())
             .map(|file| file.name.to_owned())
+            .chain(chunked_files.iter().map(|file| file.name.clone()))
             .collect();
 
         let event = json!({
@@ -227,6 +285,7 @@ impl ProfileExporter {
             "family": self.family.as_ref(),
             "version": "4",
             "endpoint_counts" : endpoint_counts,
+            "endpoint_counts_by_type": endpoint_counts.map(ProfiledEndpointsStats::counts_by_type),
             "internal": internal_metadata.unwrap_or_else(|| json!({})),
             "info": info.unwrap_or_else(|| json!({})),
         })
@@ -271,6 +330,10 @@ impl ProfileExporter {
             form.add_reader_file(file.name, Cursor::new(encoded), file.name)
         }
 
+        for file in chunked_files {
+            form.add_reader_file(file.name.clone(), file.data, file.name)
+        }
+
         let builder = self
             .endpoint
             .into_request_builder(concat!("DDProf/", env!("CARGO_PKG_VERSION")))?
@@ -288,14 +351,148 @@ impl ProfileExporter {
         )
     }
 
+    /// Sends the request. If a spool is configured (see [`ProfileExporter::set_spool`]), a
+    /// request that fails with a connection error, a timeout, or a retryable status code is
+    /// persisted to the spool. After a successful upload, at most one previously spooled request
+    /// that is due for a retry is re-sent, so that a send never waits on the whole spool; use
+    /// [`ProfileExporter::drain_spool`] to deliver the others sooner.
     pub fn send(
         &self,
         request: Request,
         cancel: Option<&CancellationToken>,
     ) -> anyhow::Result<HttpResponse> {
-        self.exporter
-            .runtime
-            .block_on(request.send(&self.exporter.client, cancel))
+        match &self.spool {
+            None => self
+                .exporter
+                .runtime
+                .block_on(request.send(&self.exporter.client, cancel)),
+            Some(spool) => self
+                .exporter
+                .runtime
+                .block_on(self.send_with_spool(spool, request, cancel)),
+        }
+    }
+
+    async fn send_with_spool(
+        &self,
+        spool: &Spool,
+        request: Request,
+        cancel: Option<&CancellationToken>,
+    ) -> anyhow::Result<HttpResponse> {
+        // The body needs to be buffered so that it can still be spooled if sending fails.
+        let timeout = request.timeout;
+        let (parts, body) = request.req.into_parts();
+        let body = body.collect().await?.to_bytes();
+        let uri = parts.uri.clone();
+        let headers = parts.headers.clone();
+        let request = Request {
+            timeout,
+            req: hyper::Request::from_parts(parts, hyper::Body::from(body.clone())),
+        };
+
+        let result = request.send(&self.exporter.client, cancel).await;
+        let delivered = match &result {
+            Ok(response) => !spool::is_retryable_status(response.status()),
+            // A cancelled request is neither spooled nor followed by other attempts.
+            Err(err) => {
+                if matches!(
+                    err.downcast_ref::<errors::Error>(),
+                    Some(errors::Error::UserRequestedCancellation)
+                ) {
+                    return result;
+                }
+                false
+            }
+        };
+        if delivered {
+            // The endpoint is reachable again, so give an earlier failure another chance.
+            let _ = self.resend_spooled(spool, cancel, false, 1).await;
+        } else if let Err(err) = spool.store(&uri, &headers, timeout, &body, 1) {
+            return Err(err.context("failed to send profile and to spool it for a later retry"));
+        }
+        result
+    }
+
+    async fn resend_spooled(
+        &self,
+        spool: &Spool,
+        cancel: Option<&CancellationToken>,
+        ignore_backoff: bool,
+        max_attempts: usize,
+    ) -> anyhow::Result<DrainResult> {
+        spool.enforce_limits()?;
+        let now = std::time::SystemTime::now();
+        let mut result = DrainResult::default();
+        let mut failed_once = false;
+        for entry in spool.list()? {
+            if failed_once
+                || result.sent + result.failed >= max_attempts
+                || (!ignore_backoff && entry.next_attempt_at > now)
+            {
+                result.skipped += 1;
+                continue;
+            }
+            let spooled = match spool.load(&entry) {
+                Ok(spooled) => spooled,
+                Err(_) => {
+                    // Unreadable entries can never be delivered.
+                    spool.remove(&entry);
+                    continue;
+                }
+            };
+            let delivered = match spooled.to_request(self.endpoint.api_key.as_deref()) {
+                Ok(request) => match request.send(&self.exporter.client, cancel).await {
+                    Ok(response) => !spool::is_retryable_status(response.status()),
+                    Err(_) => false,
+                },
+                Err(_) => {
+                    spool.remove(&entry);
+                    continue;
+                }
+            };
+            if delivered {
+                spool.remove(&entry);
+                result.sent += 1;
+            } else {
+                spool.reschedule(&spooled)?;
+                result.failed += 1;
+                // Don't hammer an endpoint that is still down.
+                failed_once = true;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Enables persisting undeliverable requests to `config.dir`. Entries already present in
+    /// the directory, e.g. from a previous process, are picked up as well.
+    pub fn set_spool(&mut self, config: SpoolConfig) -> anyhow::Result<()> {
+        self.spool = Some(Spool::new(config)?);
+        Ok(())
+    }
+
+    pub fn spool(&self) -> Option<&Spool> {
+        self.spool.as_ref()
+    }
+
+    /// Lists spooled requests, oldest first. Returns an empty list if no spool is configured.
+    pub fn spooled_requests(&self) -> anyhow::Result<Vec<SpoolEntry>> {
+        match &self.spool {
+            None => Ok(vec![]),
+            Some(spool) => spool.list(),
+        }
+    }
+
+    /// Tries to deliver every spooled request now, regardless of its backoff. Stops at the first
+    /// failure; remaining entries stay spooled.
+    pub fn drain_spool(&self, cancel: Option<&CancellationToken>) -> anyhow::Result<DrainResult> {
+        match &self.spool {
+            None => Ok(DrainResult::default()),
+            Some(spool) => {
+                self.exporter
+                    .runtime
+                    .block_on(self.resend_spooled(spool, cancel, true, usize::MAX))
+            }
+        }
     }
 
     pub fn set_timeout(&mut self, timeout_ms: u64) {
@@ -0,0 +1,458 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+//! On-disk spool for profile uploads that could not be delivered.
+//!
+//! Each spooled request is stored in a single `*.req` file: a little-endian `u32` holding the
+//! length of a JSON metadata header (uri, headers, timestamps, attempt count), followed by the
+//! header itself and then the raw multipart body. Files are written to a temporary name and
+//! renamed into place, so a crash while spooling never leaves a half-written entry behind.
+//! The API key is never written to disk: it is taken from the exporter's endpoint when the
+//! request is sent again.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use bytes::Bytes;
+use ddcommon::header;
+use serde::{Deserialize, Serialize};
+
+const SPOOL_FILE_EXTENSION: &str = "req";
+const SPOOL_TMP_EXTENSION: &str = "tmp";
+/// Upper bound for the metadata header, so that a corrupt file can't cause a huge allocation.
+const MAX_HEADER_LEN: usize = 1024 * 1024;
+
+/// Configuration for the profile upload spool.
+#[derive(Clone, Debug)]
+pub struct SpoolConfig {
+    /// Directory where undelivered requests are persisted. Created if missing.
+    pub dir: PathBuf,
+    /// Upper bound for the total size of all spooled requests. The oldest entries are evicted
+    /// first when this is exceeded.
+    pub max_bytes: u64,
+    /// Spooled requests older than this are discarded instead of being retried.
+    pub max_age: Duration,
+    /// Delay before the first retry. Doubles with every failed attempt.
+    pub initial_backoff: Duration,
+    /// Upper bound for the retry delay.
+    pub max_backoff: Duration,
+}
+
+impl SpoolConfig {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes: 32 * 1024 * 1024,
+            max_age: Duration::from_secs(60 * 60),
+            initial_backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct SpoolHeader {
+    uri: String,
+    headers: Vec<(String, Vec<u8>)>,
+    timeout_ms: Option<u64>,
+    created_at_ms: u64,
+    attempts: u32,
+    next_attempt_at_ms: u64,
+}
+
+/// Description of a spooled request, as returned by [`Spool::list`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpoolEntry {
+    pub path: PathBuf,
+    pub uri: String,
+    pub created_at: SystemTime,
+    pub next_attempt_at: SystemTime,
+    pub attempts: u32,
+    pub size: u64,
+}
+
+/// A request loaded back from the spool, ready to be sent again.
+#[derive(Debug)]
+pub(crate) struct SpooledRequest {
+    pub(crate) entry: SpoolEntry,
+    header: SpoolHeader,
+    body: Bytes,
+}
+
+impl SpooledRequest {
+    /// Rebuilds the request, adding the `api_key` header which isn't spooled.
+    pub(crate) fn to_request(&self, api_key: Option<&str>) -> anyhow::Result<super::Request> {
+        let mut builder = hyper::Request::builder()
+            .method(http::Method::POST)
+            .uri(self.header.uri.as_str());
+        for (name, value) in &self.header.headers {
+            builder = builder.header(name.as_str(), value.as_slice());
+        }
+        if let Some(api_key) = api_key {
+            builder = builder.header(header::DATADOG_API_KEY, api_key);
+        }
+        let request = super::Request::from(builder.body(hyper::Body::from(self.body.clone()))?);
+        Ok(request.with_timeout(Duration::from_millis(
+            self.header.timeout_ms.unwrap_or_default(),
+        )))
+    }
+}
+
+/// Outcome of [`crate::exporter::ProfileExporter::drain_spool`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrainResult {
+    /// Requests that were delivered and removed from the spool.
+    pub sent: usize,
+    /// Requests that failed again and remain spooled.
+    pub failed: usize,
+    /// Requests that were not yet due for a retry, or were skipped because an earlier attempt
+    /// failed during this drain.
+    pub skipped: usize,
+}
+
+#[derive(Debug)]
+pub struct Spool {
+    config: SpoolConfig,
+}
+
+fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn from_millis(ms: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(ms)
+}
+
+impl Spool {
+    pub fn new(config: SpoolConfig) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&config.dir).with_context(|| {
+            format!("failed to create spool directory {}", config.dir.display())
+        })?;
+        Ok(Self { config })
+    }
+
+    pub fn config(&self) -> &SpoolConfig {
+        &self.config
+    }
+
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempts.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.config
+            .initial_backoff
+            .saturating_mul(factor)
+            .min(self.config.max_backoff)
+    }
+
+    fn unique_path(&self, now: SystemTime) -> PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let counter = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let nanos = now
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        self.config.dir.join(format!(
+            "{nanos:024}-{}-{counter}.{SPOOL_FILE_EXTENSION}",
+            std::process::id()
+        ))
+    }
+
+    /// Persists a request that could not be delivered. `attempts` is the number of delivery
+    /// attempts made so far. The API key header is left out.
+    pub(crate) fn store(
+        &self,
+        uri: &hyper::Uri,
+        headers: &hyper::HeaderMap,
+        timeout: Option<Duration>,
+        body: &[u8],
+        attempts: u32,
+    ) -> anyhow::Result<PathBuf> {
+        let now = SystemTime::now();
+        let header = SpoolHeader {
+            uri: uri.to_string(),
+            headers: headers
+                .iter()
+                .filter(|(name, _)| **name != header::DATADOG_API_KEY)
+                .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+                .collect(),
+            timeout_ms: timeout.map(|t| t.as_millis() as u64),
+            created_at_ms: to_millis(now),
+            attempts,
+            next_attempt_at_ms: to_millis(now + self.backoff(attempts)),
+        };
+        let path = self.unique_path(now);
+        self.write(&path, &header, body)?;
+        self.enforce_limits()?;
+        Ok(path)
+    }
+
+    fn write(&self, path: &Path, header: &SpoolHeader, body: &[u8]) -> anyhow::Result<()> {
+        let header = serde_json::to_vec(header)?;
+        let tmp = path.with_extension(SPOOL_TMP_EXTENSION);
+        {
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            // The requests contain the profiles, which should only be readable by their owner.
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            let mut file = std::io::BufWriter::new(options.open(&tmp)?);
+            file.write_u32::<LittleEndian>(header.len().try_into()?)?;
+            file.write_all(&header)?;
+            file.write_all(body)?;
+            file.flush()?;
+        }
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn read_header(path: &Path) -> anyhow::Result<(SpoolHeader, std::fs::File)> {
+        let mut file = std::fs::File::open(path)?;
+        let len = file.read_u32::<LittleEndian>()? as usize;
+        if len > MAX_HEADER_LEN {
+            anyhow::bail!("spool header of {len} bytes exceeds {MAX_HEADER_LEN} bytes");
+        }
+        let mut header = vec![0; len];
+        file.read_exact(&mut header)?;
+        Ok((serde_json::from_slice(&header)?, file))
+    }
+
+    fn entry(path: PathBuf, header: &SpoolHeader, size: u64) -> SpoolEntry {
+        SpoolEntry {
+            path,
+            uri: header.uri.clone(),
+            created_at: from_millis(header.created_at_ms),
+            next_attempt_at: from_millis(header.next_attempt_at_ms),
+            attempts: header.attempts,
+            size,
+        }
+    }
+
+    /// Lists all spooled requests, oldest first. Entries that cannot be parsed are removed.
+    pub fn list(&self) -> anyhow::Result<Vec<SpoolEntry>> {
+        let mut entries = vec![];
+        for dir_entry in std::fs::read_dir(&self.config.dir)? {
+            let path = dir_entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SPOOL_FILE_EXTENSION) {
+                continue;
+            }
+            let size = match std::fs::metadata(&path) {
+                Ok(metadata) => metadata.len(),
+                // Another process may have drained it concurrently.
+                Err(_) => continue,
+            };
+            match Self::read_header(&path) {
+                Ok((header, _)) => entries.push(Self::entry(path, &header, size)),
+                Err(_) => {
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+        }
+        entries.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.path.cmp(&b.path)));
+        Ok(entries)
+    }
+
+    pub(crate) fn load(&self, entry: &SpoolEntry) -> anyhow::Result<SpooledRequest> {
+        let (header, mut file) = Self::read_header(&entry.path)?;
+        let mut body = vec![];
+        file.read_to_end(&mut body)?;
+        Ok(SpooledRequest {
+            entry: entry.clone(),
+            header,
+            body: body.into(),
+        })
+    }
+
+    /// Removes expired entries, then evicts the oldest entries until the spool fits in
+    /// `max_bytes`.
+    pub fn enforce_limits(&self) -> anyhow::Result<()> {
+        let now = SystemTime::now();
+        let mut total: u64 = 0;
+        let mut kept = vec![];
+        for entry in self.list()? {
+            let age = now.duration_since(entry.created_at).unwrap_or_default();
+            if age > self.config.max_age {
+                self.remove(&entry);
+            } else {
+                total += entry.size;
+                kept.push(entry);
+            }
+        }
+        for entry in kept {
+            if total <= self.config.max_bytes {
+                break;
+            }
+            total -= entry.size;
+            self.remove(&entry);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn remove(&self, entry: &SpoolEntry) {
+        let _ = std::fs::remove_file(&entry.path);
+    }
+
+    /// Records a failed retry, pushing the next attempt further out.
+    pub(crate) fn reschedule(&self, request: &SpooledRequest) -> anyhow::Result<()> {
+        let mut header = request.header.clone();
+        header.attempts = header.attempts.saturating_add(1);
+        header.next_attempt_at_ms = to_millis(SystemTime::now() + self.backoff(header.attempts));
+        self.write(&request.entry.path, &header, &request.body)
+    }
+
+    /// Removes every spooled request.
+    pub fn clear(&self) -> anyhow::Result<()> {
+        for entry in self.list()? {
+            self.remove(&entry);
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if the response status indicates the request may succeed if retried later.
+pub(crate) fn is_retryable_status(status: http::StatusCode) -> bool {
+    status.is_server_error()
+        || status == http::StatusCode::REQUEST_TIMEOUT
+        || status == http::StatusCode::TOO_MANY_REQUESTS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::HttpBody;
+
+    fn spool(dir: &Path) -> Spool {
+        Spool::new(SpoolConfig::new(dir)).unwrap()
+    }
+
+    fn headers() -> hyper::HeaderMap {
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert("DD-EVP-ORIGIN", "dd-trace-foo".parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn store_and_load_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = spool(dir.path());
+        let uri: hyper::Uri = "http://localhost:8126/profiling/v1/input".parse().unwrap();
+        spool
+            .store(&uri, &headers(), Some(Duration::from_secs(3)), b"body", 1)
+            .unwrap();
+
+        let entries = spool.list().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].uri, uri.to_string());
+        assert_eq!(entries[0].attempts, 1);
+        assert!(entries[0].next_attempt_at > entries[0].created_at);
+
+        let loaded = spool.load(&entries[0]).unwrap();
+        let request = loaded.to_request(Some("current-key")).unwrap();
+        assert_eq!(request.uri(), &uri);
+        assert_eq!(request.headers()["DD-EVP-ORIGIN"], "dd-trace-foo");
+        assert_eq!(request.headers()["DD-API-KEY"], "current-key");
+        assert_eq!(request.timeout(), &Some(Duration::from_secs(3)));
+        let body = futures::executor::block_on(request.body().collect())
+            .unwrap()
+            .to_bytes();
+        assert_eq!(body.as_ref(), b"body");
+    }
+
+    #[test]
+    fn reschedule_increases_attempts() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = spool(dir.path());
+        let uri: hyper::Uri = "http://localhost:8126/".parse().unwrap();
+        spool.store(&uri, &headers(), None, b"body", 1).unwrap();
+
+        let entry = spool.list().unwrap().remove(0);
+        let loaded = spool.load(&entry).unwrap();
+        spool.reschedule(&loaded).unwrap();
+
+        let entry = spool.list().unwrap().remove(0);
+        assert_eq!(entry.attempts, 2);
+        assert_eq!(spool.load(&entry).unwrap().body.as_ref(), b"body");
+    }
+
+    #[test]
+    fn size_cap_evicts_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = SpoolConfig::new(dir.path());
+        config.max_bytes = 2500;
+        let spool = Spool::new(config).unwrap();
+        let uri: hyper::Uri = "http://localhost:8126/".parse().unwrap();
+
+        let first = spool.store(&uri, &headers(), None, &[1; 1000], 1).unwrap();
+        let second = spool.store(&uri, &headers(), None, &[2; 1000], 1).unwrap();
+        let third = spool.store(&uri, &headers(), None, &[3; 1000], 1).unwrap();
+
+        let paths: Vec<_> = spool.list().unwrap().into_iter().map(|e| e.path).collect();
+        assert!(!paths.contains(&first));
+        assert_eq!(paths, vec![second, third]);
+    }
+
+    #[test]
+    fn age_cap_discards_expired() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = SpoolConfig::new(dir.path());
+        config.max_age = Duration::ZERO;
+        let spool = Spool::new(config).unwrap();
+        let uri: hyper::Uri = "http://localhost:8126/".parse().unwrap();
+
+        spool.store(&uri, &headers(), None, b"body", 1).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        spool.enforce_limits().unwrap();
+        assert!(spool.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn api_key_is_not_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = spool(dir.path());
+        let uri: hyper::Uri = "http://localhost:8126/".parse().unwrap();
+        let mut headers = headers();
+        headers.insert(header::DATADOG_API_KEY, "secret-key".parse().unwrap());
+        let path = spool.store(&uri, &headers, None, b"body", 1).unwrap();
+
+        let contents = std::fs::read(&path).unwrap();
+        assert!(!contents.windows(10).any(|w| w == b"secret-key"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let entry = spool.list().unwrap().remove(0);
+        let request = spool.load(&entry).unwrap().to_request(None).unwrap();
+        assert!(!request.headers().contains_key(header::DATADOG_API_KEY));
+    }
+
+    #[test]
+    fn corrupt_entries_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = spool(dir.path());
+        // The header length is way out of bounds.
+        std::fs::write(dir.path().join("garbage.req"), b"\xff\xff\xff\xff").unwrap();
+        std::fs::write(dir.path().join("unrelated.txt"), b"hello").unwrap();
+
+        assert!(spool.list().unwrap().is_empty());
+        assert!(!dir.path().join("garbage.req").exists());
+        assert!(dir.path().join("unrelated.txt").exists());
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = spool(dir.path());
+        assert_eq!(spool.backoff(1), Duration::from_secs(5));
+        assert_eq!(spool.backoff(2), Duration::from_secs(10));
+        assert_eq!(spool.backoff(40), Duration::from_secs(300));
+    }
+}
@@ -1,29 +1,131 @@
 // Copyright 2021-Present Datadog, Inc. https://www.datadoghq.com/
 // SPDX-License-Identifier: Apache-2.0
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 
-#[derive(Default, PartialEq, Eq, Debug, Clone, Serialize)]
-#[serde(transparent)]
+/// Identifies an endpoint by what kind of root span it comes from, e.g.
+/// "web" for HTTP requests or "queue" for background jobs, and its name.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct EndpointKey {
+    pub r#type: String,
+    pub name: String,
+}
+
+impl EndpointKey {
+    pub fn new(r#type: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            r#type: r#type.into(),
+            name: name.into(),
+        }
+    }
+}
+
+/// Counts how many times each endpoint was seen during the profile.
+///
+/// When serialized, only endpoints of type [ProfiledEndpointsStats::DEFAULT_TYPE]
+/// are emitted, as a map of name to count, which is what the backend expects
+/// for the "endpoint_counts" field. Use [ProfiledEndpointsStats::counts_by_type]
+/// for all of them.
+#[derive(Default, PartialEq, Eq, Debug, Clone)]
 pub struct ProfiledEndpointsStats {
-    count: HashMap<String, i64>,
+    count: HashMap<EndpointKey, i64>,
 }
 
 impl From<HashMap<String, i64>> for ProfiledEndpointsStats {
     fn from(count: HashMap<String, i64>) -> Self {
+        let count = count
+            .into_iter()
+            .map(|(name, value)| (EndpointKey::new(Self::DEFAULT_TYPE, name), value))
+            .collect();
+        ProfiledEndpointsStats { count }
+    }
+}
+
+impl From<HashMap<EndpointKey, i64>> for ProfiledEndpointsStats {
+    fn from(count: HashMap<EndpointKey, i64>) -> Self {
         ProfiledEndpointsStats { count }
     }
 }
 
 impl ProfiledEndpointsStats {
+    /// The type of endpoints counted with [ProfiledEndpointsStats::add_endpoint_count].
+    pub const DEFAULT_TYPE: &'static str = "web";
+
     pub fn add_endpoint_count(&mut self, endpoint_name: String, value: i64) {
-        let entry = self.count.entry(endpoint_name).or_insert(0);
+        self.add_typed_endpoint_count(EndpointKey::new(Self::DEFAULT_TYPE, endpoint_name), value)
+    }
+
+    pub fn add_typed_endpoint_count(&mut self, endpoint: EndpointKey, value: i64) {
+        let entry = self.count.entry(endpoint).or_insert(0);
         *entry = entry.saturating_add(value);
     }
 
+    pub fn get(&self, endpoint: &EndpointKey) -> Option<i64> {
+        self.count.get(endpoint).copied()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.count.is_empty()
     }
+
+    /// Returns the counts grouped by endpoint type, then by name.
+    pub fn counts_by_type(&self) -> BTreeMap<&str, BTreeMap<&str, i64>> {
+        let mut by_type: BTreeMap<&str, BTreeMap<&str, i64>> = BTreeMap::new();
+        for (endpoint, count) in self.count.iter() {
+            by_type
+                .entry(endpoint.r#type.as_str())
+                .or_default()
+                .insert(endpoint.name.as_str(), *count);
+        }
+        by_type
+    }
+}
+
+impl Serialize for ProfiledEndpointsStats {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.count
+                .iter()
+                .filter(|(endpoint, _)| endpoint.r#type == Self::DEFAULT_TYPE)
+                .map(|(endpoint, count)| (endpoint.name.as_str(), count)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn counts_are_keyed_by_type_and_name() {
+        let mut stats = ProfiledEndpointsStats::default();
+        stats.add_endpoint_count("GET /users".to_string(), 2);
+        stats.add_typed_endpoint_count(EndpointKey::new("queue", "send_email"), 1);
+        stats.add_typed_endpoint_count(EndpointKey::new("queue", "send_email"), 3);
+        stats.add_typed_endpoint_count(EndpointKey::new("queue", "GET /users"), 1);
+
+        assert_eq!(stats.get(&EndpointKey::new("web", "GET /users")), Some(2));
+        assert_eq!(stats.get(&EndpointKey::new("queue", "send_email")), Some(4));
+        assert_eq!(
+            serde_json::to_value(stats.counts_by_type()).unwrap(),
+            json!({
+                "queue": {"GET /users": 1, "send_email": 4},
+                "web": {"GET /users": 2},
+            })
+        );
+    }
+
+    #[test]
+    fn serializes_web_endpoints_only() {
+        let mut stats = ProfiledEndpointsStats::default();
+        stats.add_endpoint_count("GET /users".to_string(), 2);
+        stats.add_typed_endpoint_count(EndpointKey::new("queue", "send_email"), 1);
+        assert_eq!(
+            serde_json::to_value(&stats).unwrap(),
+            json!({"GET /users": 2})
+        );
+    }
 }
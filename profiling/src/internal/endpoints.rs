@@ -7,6 +7,9 @@ pub struct Endpoints {
     pub endpoint_label: StringId,
     pub local_root_span_id_label: StringId,
     pub mappings: FxIndexMap<u64, StringId>,
+    /// Extra labels, as (key, value) pairs, to add to the samples of each
+    /// local root span, e.g. its resource or span type.
+    pub attributes: FxIndexMap<u64, Vec<(StringId, StringId)>>,
     pub stats: ProfiledEndpointsStats,
 }
 
@@ -14,6 +17,7 @@ impl Endpoints {
     pub fn new() -> Self {
         Self {
             mappings: Default::default(),
+            attributes: Default::default(),
             local_root_span_id_label: Default::default(),
             endpoint_label: Default::default(),
             stats: Default::default(),
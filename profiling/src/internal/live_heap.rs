@@ -0,0 +1,269 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::api;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// Keeps track of sampled allocations which have not been freed yet, so that
+/// a "live heap" profile can be produced.
+///
+/// Unlike [Profile], which only ever aggregates what it's given, samples
+/// added here can be removed again with [LiveHeapTracker::untrack] when the
+/// corresponding object is freed. Whatever is still tracked at serialization
+/// time gets folded into the profile, and stays tracked for the next one.
+///
+/// The tracker owns a copy of every tracked sample, so the number of entries
+/// is bounded by `max_tracked`. Samples are validated when they are tracked,
+/// so that folding them into a profile doesn't fail halfway.
+pub struct LiveHeapTracker {
+    max_tracked: usize,
+    num_sample_types: usize,
+    allocations: HashMap<u64, TrackedAllocation>,
+}
+
+struct TrackedAllocation {
+    sample: owned_types::Sample,
+    weight: i64,
+}
+
+impl LiveHeapTracker {
+    /// Creates a tracker which holds at most `max_tracked` allocations, to be
+    /// folded into profiles with `num_sample_types` sample types.
+    pub fn new(max_tracked: usize, num_sample_types: usize) -> Self {
+        Self {
+            max_tracked,
+            num_sample_types,
+            allocations: HashMap::new(),
+        }
+    }
+
+    pub fn max_tracked(&self) -> usize {
+        self.max_tracked
+    }
+
+    /// Returns the number of allocations currently being tracked.
+    pub fn len(&self) -> usize {
+        self.allocations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allocations.is_empty()
+    }
+
+    /// Starts tracking the allocation identified by `id`, usually its
+    /// address. Tracking an `id` which is already tracked replaces the
+    /// previous entry, as the old object must have been freed for the
+    /// address to be reused.
+    ///
+    /// # Arguments
+    /// * `id` - Identifier later passed to [LiveHeapTracker::untrack].
+    /// * `sample` - The sample to emit while the allocation is alive.
+    /// * `weight` - How many allocations this sample stands for; each value of the sample is
+    ///   multiplied by it when folded into a profile. Pass 1 if the values are already scaled.
+    ///
+    /// Fails, without tracking anything, if the sample couldn't be added to a profile.
+    pub fn track(&mut self, id: u64, sample: &api::Sample, weight: i64) -> anyhow::Result<()> {
+        anyhow::ensure!(weight > 0, "weight must be positive, got {weight}");
+        Profile::validate_sample(sample, self.num_sample_types)?;
+        anyhow::ensure!(
+            self.allocations.len() < self.max_tracked || self.allocations.contains_key(&id),
+            "cannot track allocation {id}: already tracking the maximum of {} allocations",
+            self.max_tracked
+        );
+
+        let sample = owned_types::Sample::from(sample);
+        self.allocations
+            .insert(id, TrackedAllocation { sample, weight });
+        Ok(())
+    }
+
+    /// Stops tracking the allocation identified by `id`. Returns false if it
+    /// was not tracked, which is expected for allocations that were not
+    /// sampled.
+    pub fn untrack(&mut self, id: u64) -> bool {
+        self.allocations.remove(&id).is_some()
+    }
+
+    /// Stops tracking all allocations.
+    pub fn clear(&mut self) {
+        self.allocations.clear()
+    }
+
+    /// Adds a sample for each tracked allocation to `profile`. The
+    /// allocations remain tracked. Fails without changing `profile` if its
+    /// sample types don't match the tracker's.
+    pub fn add_to_profile(&self, profile: &mut Profile) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            profile.num_sample_types() == self.num_sample_types,
+            "expected a profile with {} sample types, but it has {}",
+            self.num_sample_types,
+            profile.num_sample_types()
+        );
+        for allocation in self.allocations.values() {
+            let mut sample = api::Sample::from(&allocation.sample);
+            for value in sample.values.iter_mut() {
+                *value = value.saturating_mul(allocation.weight);
+            }
+            profile.add_sample(sample, None)?;
+        }
+        Ok(())
+    }
+
+    /// Folds the allocations that are still alive into `profile` and
+    /// serializes it. See [Profile::serialize_into_compressed_pprof].
+    pub fn serialize(
+        &self,
+        mut profile: Profile,
+        end_time: Option<SystemTime>,
+        duration: Option<Duration>,
+    ) -> anyhow::Result<EncodedProfile> {
+        self.add_to_profile(&mut profile)?;
+        profile.serialize_into_compressed_pprof(end_time, duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pprof::roundtrip_to_pprof;
+
+    fn location(name: &str) -> api::Location<'_> {
+        api::Location {
+            function: api::Function {
+                name,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn sample<'a>(locations: Vec<api::Location<'a>>, size: i64) -> api::Sample<'a> {
+        api::Sample {
+            locations,
+            values: vec![1, size],
+            labels: vec![api::Label {
+                key: "allocation class",
+                str: Some("Object"),
+                ..Default::default()
+            }],
+        }
+    }
+
+    fn profile() -> Profile {
+        let sample_types = [
+            api::ValueType::new("heap-live-samples", "count"),
+            api::ValueType::new("heap-live-size", "bytes"),
+        ];
+        Profile::new(SystemTime::now(), &sample_types, None)
+    }
+
+    #[test]
+    fn only_live_allocations_are_emitted() {
+        let mut tracker = LiveHeapTracker::new(16, 2);
+        tracker
+            .track(0x1000, &sample(vec![location("alloc_a")], 64), 1)
+            .unwrap();
+        tracker
+            .track(0x2000, &sample(vec![location("alloc_b")], 128), 1)
+            .unwrap();
+        tracker
+            .track(0x3000, &sample(vec![location("alloc_a")], 64), 1)
+            .unwrap();
+
+        assert!(tracker.untrack(0x2000));
+        assert!(!tracker.untrack(0x2000));
+        assert_eq!(tracker.len(), 2);
+
+        let mut profile = profile();
+        tracker.add_to_profile(&mut profile).unwrap();
+        let pprof = roundtrip_to_pprof(profile).unwrap();
+
+        // Both remaining allocations share a stack and labels, so they are
+        // aggregated into one sample.
+        assert_eq!(pprof.samples.len(), 1);
+        assert_eq!(pprof.samples[0].values, vec![2, 128]);
+        let function = &pprof.functions[0];
+        assert_eq!(pprof.string_table[function.name as usize], "alloc_a");
+    }
+
+    #[test]
+    fn weight_scales_values() {
+        let mut tracker = LiveHeapTracker::new(16, 2);
+        tracker
+            .track(1, &sample(vec![location("alloc")], 100), 3)
+            .unwrap();
+
+        let mut profile = profile();
+        tracker.add_to_profile(&mut profile).unwrap();
+        let pprof = roundtrip_to_pprof(profile).unwrap();
+        assert_eq!(pprof.samples[0].values, vec![3, 300]);
+
+        tracker
+            .track(2, &sample(vec![location("alloc")], 100), 0)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn tracking_is_bounded() {
+        let mut tracker = LiveHeapTracker::new(2, 2);
+        tracker.track(1, &sample(vec![], 8), 1).unwrap();
+        tracker.track(2, &sample(vec![], 8), 1).unwrap();
+        tracker.track(3, &sample(vec![], 8), 1).unwrap_err();
+
+        // Replacing an existing id doesn't need extra room.
+        tracker.track(2, &sample(vec![], 16), 1).unwrap();
+        assert_eq!(tracker.len(), 2);
+
+        tracker.untrack(1);
+        tracker.track(3, &sample(vec![], 8), 1).unwrap();
+        assert_eq!(tracker.len(), 2);
+    }
+
+    #[test]
+    fn allocations_survive_serialization() {
+        let mut tracker = LiveHeapTracker::new(16, 2);
+        tracker
+            .track(1, &sample(vec![location("alloc")], 32), 1)
+            .unwrap();
+
+        tracker.serialize(profile(), None, None).unwrap();
+        tracker.serialize(profile(), None, None).unwrap();
+        assert_eq!(tracker.len(), 1);
+
+        let mut profile = profile();
+        tracker.add_to_profile(&mut profile).unwrap();
+        assert_eq!(profile.only_for_testing_num_aggregated_samples(), 1);
+    }
+
+    #[test]
+    fn invalid_samples_are_rejected_on_track() {
+        let mut tracker = LiveHeapTracker::new(16, 2);
+        tracker
+            .track(1, &sample(vec![location("alloc")], 8), 1)
+            .unwrap();
+
+        let mut bad = sample(vec![], 8);
+        bad.values.push(1);
+        tracker.track(2, &bad, 1).unwrap_err();
+
+        let mut bad = sample(vec![], 8);
+        bad.labels.push(bad.labels[0]);
+        tracker.track(3, &bad, 1).unwrap_err();
+        assert_eq!(tracker.len(), 1);
+
+        // A mismatching profile is rejected before anything is folded into it.
+        let mut mismatch = Profile::new(
+            SystemTime::now(),
+            &[api::ValueType::new("heap-live-size", "bytes")],
+            None,
+        );
+        tracker.add_to_profile(&mut mismatch).unwrap_err();
+        assert_eq!(mismatch.only_for_testing_num_aggregated_samples(), 0);
+
+        let mut profile = profile();
+        tracker.add_to_profile(&mut profile).unwrap();
+        assert_eq!(profile.only_for_testing_num_aggregated_samples(), 1);
+    }
+}
@@ -5,6 +5,7 @@ mod endpoint_stats;
 mod endpoints;
 mod function;
 mod label;
+mod live_heap;
 mod location;
 mod mapping;
 mod observation;
@@ -20,6 +21,7 @@ pub use endpoint_stats::*;
 pub use endpoints::*;
 pub use function::*;
 pub use label::*;
+pub use live_heap::*;
 pub use location::*;
 pub use mapping::*;
 pub use observation::*;
@@ -14,7 +14,7 @@ use byteorder::{NativeEndian, ReadBytesExt};
 use lz4_flex::frame::FrameDecoder;
 use lz4_flex::frame::FrameEncoder;
 use std::io::Cursor;
-use std::io::Write;
+use std::io::{Read, Write};
 
 #[derive(Debug)]
 pub struct TimestampedObservations {
@@ -92,13 +92,16 @@ impl Iterator for TimestampedObservationsIter {
     fn next(&mut self) -> Option<Self::Item> {
         // In here we read the bytes in the same order as in add above
 
-        let stacktrace = self.decoder.read_u32::<NativeEndian>().ok()?;
-        let labels = self.decoder.read_u32::<NativeEndian>().ok()?;
-        let ts = self.decoder.read_i64::<NativeEndian>().ok()?;
-        let mut values = Vec::with_capacity(self.sample_types_len);
-        for _ in 0..self.sample_types_len {
-            values.push(self.decoder.read_i64::<NativeEndian>().ok()?);
-        }
+        let mut header = [0u8; 16];
+        self.decoder.read_exact(&mut header).ok()?;
+        let mut header = header.as_slice();
+        let stacktrace = header.read_u32::<NativeEndian>().ok()?;
+        let labels = header.read_u32::<NativeEndian>().ok()?;
+        let ts = header.read_i64::<NativeEndian>().ok()?;
+        let mut values = vec![0i64; self.sample_types_len];
+        self.decoder
+            .read_i64_into::<NativeEndian>(&mut values)
+            .ok()?;
         Some((
             Sample {
                 stacktrace: StackTraceId::from_offset(stacktrace as usize),
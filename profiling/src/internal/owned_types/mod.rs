@@ -42,3 +42,153 @@ impl<'a> From<&'a api::Period<'a>> for Period {
         }
     }
 }
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Mapping {
+    pub memory_start: u64,
+    pub memory_limit: u64,
+    pub file_offset: u64,
+    pub filename: Box<str>,
+    pub build_id: Box<str>,
+}
+
+impl<'a> From<&'a api::Mapping<'a>> for Mapping {
+    fn from(mapping: &'a api::Mapping<'a>) -> Self {
+        Self {
+            memory_start: mapping.memory_start,
+            memory_limit: mapping.memory_limit,
+            file_offset: mapping.file_offset,
+            filename: Box::from(mapping.filename),
+            build_id: Box::from(mapping.build_id),
+        }
+    }
+}
+
+impl<'a> From<&'a Mapping> for api::Mapping<'a> {
+    fn from(mapping: &'a Mapping) -> Self {
+        Self {
+            memory_start: mapping.memory_start,
+            memory_limit: mapping.memory_limit,
+            file_offset: mapping.file_offset,
+            filename: &mapping.filename,
+            build_id: &mapping.build_id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Function {
+    pub name: Box<str>,
+    pub system_name: Box<str>,
+    pub filename: Box<str>,
+    pub start_line: i64,
+}
+
+impl<'a> From<&'a api::Function<'a>> for Function {
+    fn from(function: &'a api::Function<'a>) -> Self {
+        Self {
+            name: Box::from(function.name),
+            system_name: Box::from(function.system_name),
+            filename: Box::from(function.filename),
+            start_line: function.start_line,
+        }
+    }
+}
+
+impl<'a> From<&'a Function> for api::Function<'a> {
+    fn from(function: &'a Function) -> Self {
+        Self {
+            name: &function.name,
+            system_name: &function.system_name,
+            filename: &function.filename,
+            start_line: function.start_line,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Location {
+    pub mapping: Mapping,
+    pub function: Function,
+    pub address: u64,
+    pub line: i64,
+}
+
+impl<'a> From<&'a api::Location<'a>> for Location {
+    fn from(location: &'a api::Location<'a>) -> Self {
+        Self {
+            mapping: Mapping::from(&location.mapping),
+            function: Function::from(&location.function),
+            address: location.address,
+            line: location.line,
+        }
+    }
+}
+
+impl<'a> From<&'a Location> for api::Location<'a> {
+    fn from(location: &'a Location) -> Self {
+        Self {
+            mapping: api::Mapping::from(&location.mapping),
+            function: api::Function::from(&location.function),
+            address: location.address,
+            line: location.line,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Label {
+    pub key: Box<str>,
+    pub str: Option<Box<str>>,
+    pub num: i64,
+    pub num_unit: Option<Box<str>>,
+}
+
+impl<'a> From<&'a api::Label<'a>> for Label {
+    fn from(label: &'a api::Label<'a>) -> Self {
+        Self {
+            key: Box::from(label.key),
+            str: label.str.map(Box::from),
+            num: label.num,
+            num_unit: label.num_unit.map(Box::from),
+        }
+    }
+}
+
+impl<'a> From<&'a Label> for api::Label<'a> {
+    fn from(label: &'a Label) -> Self {
+        Self {
+            key: &label.key,
+            str: label.str.as_deref(),
+            num: label.num,
+            num_unit: label.num_unit.as_deref(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Sample {
+    pub locations: Vec<Location>,
+    pub values: Vec<i64>,
+    pub labels: Vec<Label>,
+}
+
+impl<'a> From<&'a api::Sample<'a>> for Sample {
+    fn from(sample: &'a api::Sample<'a>) -> Self {
+        Self {
+            locations: sample.locations.iter().map(Location::from).collect(),
+            values: sample.values.clone(),
+            labels: sample.labels.iter().map(Label::from).collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a Sample> for api::Sample<'a> {
+    fn from(sample: &'a Sample) -> Self {
+        Self {
+            locations: sample.locations.iter().map(api::Location::from).collect(),
+            values: sample.values.clone(),
+            labels: sample.labels.iter().map(api::Label::from).collect(),
+        }
+    }
+}
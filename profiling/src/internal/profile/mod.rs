@@ -11,7 +11,9 @@ use crate::collections::identifiable::*;
 use crate::collections::string_table::StringTable;
 use crate::iter::{IntoLendingIterator, LendingIterator};
 use crate::pprof::sliced_proto::*;
-use crate::serializer::CompressedProtobufSerializer;
+use crate::serializer::{
+    chunk_channel, ChunkReceiver, ChunkedBuffer, CompressedProtobufSerializer,
+};
 use anyhow::Context;
 use std::borrow::Cow;
 use std::collections::HashMap;
@@ -42,10 +44,13 @@ pub struct Profile {
     upscaling_rules: UpscalingRules,
 }
 
-pub struct EncodedProfile {
+/// A serialized, lz4-compressed pprof. The `buffer` is a `Vec<u8>` unless it
+/// was produced by [Profile::serialize_into_compressed_pprof_writer] or
+/// [Profile::serialize_into_compressed_pprof_stream].
+pub struct EncodedProfile<B = Vec<u8>> {
     pub start: SystemTime,
     pub end: SystemTime,
-    pub buffer: Vec<u8>,
+    pub buffer: B,
     pub endpoints_stats: ProfiledEndpointsStats,
 }
 
@@ -73,19 +78,65 @@ impl Profile {
         Ok(())
     }
 
-    pub fn add_sample(
+    /// Counts an endpoint of the given type, e.g. "queue" for a background
+    /// job. [Profile::add_endpoint_count] counts endpoints of type
+    /// [ProfiledEndpointsStats::DEFAULT_TYPE].
+    pub fn add_typed_endpoint_count(
         &mut self,
-        sample: api::Sample,
-        timestamp: Option<Timestamp>,
+        endpoint_type: Cow<str>,
+        endpoint: Cow<str>,
+        value: i64,
     ) -> anyhow::Result<()> {
+        anyhow::ensure!(!endpoint_type.is_empty(), "endpoint type must not be empty");
+        self.endpoints.stats.add_typed_endpoint_count(
+            EndpointKey::new(endpoint_type.into_owned(), endpoint.into_owned()),
+            value,
+        );
+        Ok(())
+    }
+
+    /// Attaches a label with the given key and value to every sample of the
+    /// local root span at serialization time, like [Profile::add_endpoint]
+    /// does for the "trace endpoint" label. This allows linking samples to
+    /// arbitrary root-span attributes, such as the resource or span type.
+    ///
+    /// Setting the same key again for a span replaces the value. If a sample
+    /// already has a label with that key, the sample's own label is kept.
+    pub fn add_root_span_attribute(
+        &mut self,
+        local_root_span_id: u64,
+        key: Cow<str>,
+        value: Cow<str>,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(!key.is_empty(), "root span attribute key must not be empty");
+        let key_str = key.as_ref();
+        let key = self.intern(key_str);
         anyhow::ensure!(
-            sample.values.len() == self.sample_types.len(),
-            "expected {} sample types, but sample had {} sample types",
-            self.sample_types.len(),
-            sample.values.len(),
+            key != self.endpoints.local_root_span_id_label
+                && key != self.endpoints.endpoint_label
+                && key != self.timestamp_key,
+            "root span attribute key \"{key_str}\" is reserved"
         );
+        let value = self.intern(value.as_ref());
+
+        let attributes = self
+            .endpoints
+            .attributes
+            .entry(local_root_span_id)
+            .or_default();
+        match attributes.iter_mut().find(|(k, _)| *k == key) {
+            Some(attribute) => attribute.1 = value,
+            None => attributes.push((key, value)),
+        }
+        Ok(())
+    }
 
-        self.validate_sample_labels(&sample)?;
+    pub fn add_sample(
+        &mut self,
+        sample: api::Sample,
+        timestamp: Option<Timestamp>,
+    ) -> anyhow::Result<()> {
+        Self::validate_sample(&sample, self.sample_types.len())?;
         let labels: Vec<_> = sample
             .labels
             .iter()
@@ -182,14 +233,110 @@ impl Profile {
     ///   may fail as system clocks can be adjusted. The programmer may also accidentally pass an
     ///   earlier time. The duration will be set to zero these cases.
     pub fn serialize_into_compressed_pprof(
-        mut self,
+        self,
         end_time: Option<SystemTime>,
         duration: Option<Duration>,
     ) -> anyhow::Result<EncodedProfile> {
+        // On 2023-08-23, we analyzed the uploaded tarball size per language.
+        // These tarballs include 1 or more profiles, but for most languages
+        // using libdatadog (all?) there is only 1 profile, so this is a good
+        // proxy for the compressed, final size of the profiles.
+        // We found that for all languages using libdatadog, the average
+        // tarball was at least 18 KiB. Since these archives are compressed,
+        // and because profiles compress well, especially ones with timeline
+        // enabled (over 9x for some analyzed timeline profiles), this initial
+        // size of 32KiB should definitely out-perform starting at zero for
+        // time consumed, allocator pressure, and allocator fragmentation.
+        const INITIAL_PPROF_BUFFER_SIZE: usize = 32 * 1024;
+        let encoder = CompressedProtobufSerializer::with_capacity(INITIAL_PPROF_BUFFER_SIZE);
+        self.serialize_into(encoder, end_time, duration)
+    }
+
+    /// Like [Profile::serialize_into_compressed_pprof], but the compressed
+    /// pprof is written to `writer` as it is produced, instead of being
+    /// accumulated in a `Vec`. Each message is encoded and compressed on its
+    /// own, so apart from what `writer` keeps, serialization only needs the
+    /// largest message and the compressor's block buffers.
+    ///
+    /// Writing into a [crate::serializer::ChunkedBuffer] avoids the copies
+    /// made when a `Vec` grows, and the result can be passed to
+    /// [crate::exporter::ProfileExporter::build_with_chunked_files] without
+    /// being copied again. The whole output is still held in memory; see
+    /// [Profile::serialize_into_compressed_pprof_stream] to avoid that.
+    pub fn serialize_into_compressed_pprof_writer<W: std::io::Write>(
+        self,
+        writer: W,
+        end_time: Option<SystemTime>,
+        duration: Option<Duration>,
+    ) -> anyhow::Result<EncodedProfile<W>> {
+        self.serialize_into(
+            CompressedProtobufSerializer::new(writer),
+            end_time,
+            duration,
+        )
+    }
+
+    /// Like [Profile::serialize_into_compressed_pprof], but the profile is
+    /// serialized on a background thread, and the returned `buffer` yields
+    /// the compressed pprof as it is produced. Passing it to
+    /// [crate::exporter::ProfileExporter::build_with_chunked_files] writes
+    /// the profile straight into the request body: serialization only runs
+    /// a few chunks ahead of the upload, and waits for it otherwise, so the
+    /// output is never held in memory as a whole.
+    ///
+    /// If serialization fails, reading the `buffer` fails too, so that a
+    /// truncated profile is not uploaded.
+    pub fn serialize_into_compressed_pprof_stream(
+        mut self,
+        end_time: Option<SystemTime>,
+        duration: Option<Duration>,
+    ) -> anyhow::Result<EncodedProfile<ChunkReceiver>> {
+        // Lets the compressor get a little ahead of the upload, without
+        // waiting for it on every block.
+        const MAX_QUEUED_CHUNKS: usize = 4;
+        let start = self.start_time;
         let end = end_time.unwrap_or_else(SystemTime::now);
+        let duration_nanos = Self::duration_nanos(start, end, duration);
+        let endpoints_stats = std::mem::take(&mut self.endpoints.stats);
+        let (sender, receiver) =
+            chunk_channel(MAX_QUEUED_CHUNKS, ChunkedBuffer::DEFAULT_CHUNK_SIZE);
+        std::thread::Builder::new()
+            .name("dd-prof-serialize".to_string())
+            .spawn(move || -> anyhow::Result<()> {
+                let encoder = CompressedProtobufSerializer::new(sender);
+                Ok(self.encode_into(encoder, duration_nanos)?.finish()?)
+            })?;
+        Ok(EncodedProfile {
+            start,
+            end,
+            buffer: receiver,
+            endpoints_stats,
+        })
+    }
+}
+
+/// Private helper functions
+impl Profile {
+    fn serialize_into<W: std::io::Write>(
+        mut self,
+        encoder: CompressedProtobufSerializer<W>,
+        end_time: Option<SystemTime>,
+        duration: Option<Duration>,
+    ) -> anyhow::Result<EncodedProfile<W>> {
         let start = self.start_time;
+        let end = end_time.unwrap_or_else(SystemTime::now);
+        let duration_nanos = Self::duration_nanos(start, end, duration);
         let endpoints_stats = std::mem::take(&mut self.endpoints.stats);
-        let duration_nanos = duration
+        Ok(EncodedProfile {
+            start,
+            end,
+            buffer: self.encode_into(encoder, duration_nanos)?,
+            endpoints_stats,
+        })
+    }
+
+    fn duration_nanos(start: SystemTime, end: SystemTime, duration: Option<Duration>) -> i64 {
+        duration
             .unwrap_or_else(|| {
                 end.duration_since(start).unwrap_or({
                     // Let's not throw away the whole profile just because the clocks were wrong.
@@ -198,25 +345,21 @@ impl Profile {
                 })
             })
             .as_nanos()
-            .min(i64::MAX as u128) as i64;
+            .min(i64::MAX as u128) as i64
+    }
+
+    /// Encodes everything but the endpoint stats, which are not part of the
+    /// pprof, and returns the writer of the encoder.
+    fn encode_into<W: std::io::Write>(
+        mut self,
+        mut encoder: CompressedProtobufSerializer<W>,
+        duration_nanos: i64,
+    ) -> anyhow::Result<W> {
         let (period, period_type) = match self.period {
             Some(tuple) => (tuple.0, Some(tuple.1.into())),
             None => (0, None),
         };
 
-        // On 2023-08-23, we analyzed the uploaded tarball size per language.
-        // These tarballs include 1 or more profiles, but for most languages
-        // using libdatadog (all?) there is only 1 profile, so this is a good
-        // proxy for the compressed, final size of the profiles.
-        // We found that for all languages using libdatadog, the average
-        // tarball was at least 18 KiB. Since these archives are compressed,
-        // and because profiles compress well, especially ones with timeline
-        // enabled (over 9x for some analyzed timeline profiles), this initial
-        // size of 32KiB should definitely out-perform starting at zero for
-        // time consumed, allocator pressure, and allocator fragmentation.
-        const INITIAL_PPROF_BUFFER_SIZE: usize = 32 * 1024;
-        let mut encoder = CompressedProtobufSerializer::with_capacity(INITIAL_PPROF_BUFFER_SIZE);
-
         for (sample, timestamp, mut values) in std::mem::take(&mut self.observations).into_iter() {
             let labels = self.enrich_sample_labels(sample, timestamp)?;
             let location_ids: Vec<_> = self
@@ -237,6 +380,15 @@ impl Profile {
             encoder.encode(ProfileSamplesEntry::from(item))?;
         }
 
+        // Everything below only needs mappings, locations, functions, and
+        // strings, so release what was only used by samples before going on.
+        drop(std::mem::take(&mut self.stack_traces));
+        drop(std::mem::take(&mut self.label_sets));
+        drop(std::mem::take(&mut self.labels));
+        drop(std::mem::take(&mut self.endpoints.mappings));
+        drop(std::mem::take(&mut self.endpoints.attributes));
+        drop(std::mem::take(&mut self.upscaling_rules));
+
         // `Sample`s must be emitted before `SampleTypes` since we consume
         // fields as we convert (using `into_iter`).  This allows Rust to
         // release memory faster, reducing our peak RSS, but means that we
@@ -281,17 +433,9 @@ impl Profile {
             period,
         })?;
 
-        Ok(EncodedProfile {
-            start,
-            end,
-            buffer: encoder.finish()?,
-            endpoints_stats,
-        })
+        encoder.finish()
     }
-}
 
-/// Private helper functions
-impl Profile {
     fn add_function(&mut self, function: &api::Function) -> FunctionId {
         let name = self.intern(function.name);
         let system_name = self.intern(function.system_name);
@@ -344,13 +488,11 @@ impl Profile {
         Some(src.iter().map(owned_types::ValueType::from).collect())
     }
 
-    /// Fetches the endpoint information for the label. There may be errors,
-    /// but there may also be no endpoint information for a given endpoint.
-    /// Hence, the return type of Result<Option<_>, _>.
-    fn get_endpoint_for_label(&self, label: &Label) -> anyhow::Result<Option<Label>> {
+    /// Extracts the span id from a "local root span id" label.
+    fn get_local_root_span_id(&self, label: &Label) -> anyhow::Result<u64> {
         anyhow::ensure!(
             label.get_key() == self.endpoints.local_root_span_id_label,
-            "bug: get_endpoint_for_label should only be called on labels with the key \"local root span id\""
+            "bug: get_local_root_span_id should only be called on labels with the key \"local root span id\""
         );
 
         anyhow::ensure!(
@@ -359,24 +501,22 @@ impl Profile {
             label
         );
 
-        let local_root_span_id = if let LabelValue::Num { num, .. } = label.get_value() {
+        if let LabelValue::Num { num, .. } = label.get_value() {
             // Safety: the value is a u64, but pprof only has signed values, so we
             // transmute it; the backend does the same.
-            unsafe { std::intrinsics::transmute::<i64, u64>(*num) }
+            Ok(unsafe { std::intrinsics::transmute::<i64, u64>(*num) })
         } else {
-            return Err(anyhow::format_err!("the local root span id label value must be sent as a number, not a string, given {:?}",
-            label));
-        };
-
-        Ok(self
-            .endpoints
-            .mappings
-            .get(&local_root_span_id)
-            .map(|v| Label::str(self.endpoints.endpoint_label, *v)))
+            Err(anyhow::format_err!("the local root span id label value must be sent as a number, not a string, given {:?}",
+            label))
+        }
     }
 
-    fn get_endpoint_for_labels(&self, label_set_id: LabelSetId) -> anyhow::Result<Option<Label>> {
-        let label = self.get_label_set(label_set_id)?.iter().find_map(|id| {
+    /// Returns the labels linked to the local root span of the label set:
+    /// its endpoint and any root span attributes which the label set doesn't
+    /// already have a label for.
+    fn get_root_span_labels(&self, label_set_id: LabelSetId) -> anyhow::Result<Vec<Label>> {
+        let label_set = self.get_label_set(label_set_id)?;
+        let label = label_set.iter().find_map(|id| {
             if let Ok(label) = self.get_label(*id) {
                 if label.get_key() == self.endpoints.local_root_span_id_label {
                     return Some(label);
@@ -384,11 +524,30 @@ impl Profile {
             }
             None
         });
-        if let Some(label) = label {
-            self.get_endpoint_for_label(label)
-        } else {
-            Ok(None)
+        let Some(label) = label else {
+            return Ok(vec![]);
+        };
+
+        let local_root_span_id = self.get_local_root_span_id(label)?;
+        let mut labels: Vec<Label> = self
+            .endpoints
+            .mappings
+            .get(&local_root_span_id)
+            .map(|v| Label::str(self.endpoints.endpoint_label, *v))
+            .into_iter()
+            .collect();
+        if let Some(attributes) = self.endpoints.attributes.get(&local_root_span_id) {
+            for (key, value) in attributes.iter() {
+                let already_set = label_set
+                    .iter()
+                    .filter_map(|id| self.get_label(*id).ok())
+                    .any(|label| label.get_key() == *key);
+                if !already_set {
+                    labels.push(Label::str(*key, *value));
+                }
+            }
         }
+        Ok(labels)
     }
 
     fn get_label(&self, id: LabelId) -> anyhow::Result<&Label> {
@@ -491,13 +650,36 @@ impl Profile {
         self.get_label_set(sample.labels)?
             .iter()
             .map(|l| self.get_label(*l).copied())
-            .chain(self.get_endpoint_for_labels(sample.labels).transpose())
+            .chain(
+                self.get_root_span_labels(sample.labels)?
+                    .into_iter()
+                    .map(Ok),
+            )
             .chain(timestamp.map(|ts| Ok(Label::num(self.timestamp_key, ts.get(), None))))
             .collect()
     }
 
+    /// Validates what [Profile::add_sample] validates, for a profile with `num_sample_types`
+    /// sample types. A sample passing this can be added without failing.
+    pub(crate) fn validate_sample(
+        sample: &api::Sample,
+        num_sample_types: usize,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            sample.values.len() == num_sample_types,
+            "expected {} sample types, but sample had {} sample types",
+            num_sample_types,
+            sample.values.len(),
+        );
+        Self::validate_sample_labels(sample)
+    }
+
+    pub(crate) fn num_sample_types(&self) -> usize {
+        self.sample_types.len()
+    }
+
     /// Validates labels
-    fn validate_sample_labels(&mut self, sample: &api::Sample) -> anyhow::Result<()> {
+    fn validate_sample_labels(sample: &api::Sample) -> anyhow::Result<()> {
         let mut seen: HashMap<&str, &api::Label> = HashMap::new();
 
         for label in sample.labels.iter() {
@@ -1053,6 +1235,126 @@ mod api_tests {
         Ok(())
     }
 
+    #[test]
+    fn streamed_serialization_test() -> anyhow::Result<()> {
+        let mut profile = provide_distinct_locations();
+        profile.add_endpoint_count(Cow::from("my endpoint"), 1)?;
+        let end = profile.start_time + Duration::from_secs(60);
+
+        let mut streamed = profile.serialize_into_compressed_pprof_stream(Some(end), None)?;
+        assert_eq!(streamed.end, end);
+        assert!(!streamed.endpoints_stats.is_empty());
+        let mut buffer = vec![];
+        std::io::Read::read_to_end(&mut streamed.buffer, &mut buffer)?;
+        let pprof = pprof::deserialize_compressed_pprof(&buffer)?;
+
+        assert_eq!(pprof.samples.len(), 3);
+        assert_eq!(pprof.duration_nanos, 60_000_000_000);
+        Ok(())
+    }
+
+    #[test]
+    fn typed_endpoint_counts_test() -> anyhow::Result<()> {
+        let sample_types = [api::ValueType::new("samples", "count")];
+        let mut profile: Profile = Profile::new(SystemTime::now(), &sample_types, None);
+
+        profile.add_endpoint_count(Cow::from("GET /"), 1)?;
+        profile.add_typed_endpoint_count(Cow::from("queue"), Cow::from("send_email"), 2)?;
+        profile.add_typed_endpoint_count(Cow::from("queue"), Cow::from("send_email"), 1)?;
+        profile
+            .add_typed_endpoint_count(Cow::from(""), Cow::from("send_email"), 1)
+            .unwrap_err();
+
+        let endpoints_stats = profile
+            .serialize_into_compressed_pprof(None, None)?
+            .endpoints_stats;
+        assert_eq!(
+            endpoints_stats.get(&EndpointKey::new("web", "GET /")),
+            Some(1)
+        );
+        assert_eq!(
+            endpoints_stats.get(&EndpointKey::new("queue", "send_email")),
+            Some(3)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn root_span_attributes() -> anyhow::Result<()> {
+        let sample_types = [api::ValueType::new("samples", "count")];
+        let mut profile: Profile = Profile::new(SystemTime::now(), &sample_types, None);
+
+        let span_label = |num| api::Label {
+            key: "local root span id",
+            num,
+            ..Default::default()
+        };
+        let resource_label = api::Label {
+            key: "resource",
+            str: Some("from sample"),
+            ..Default::default()
+        };
+        for labels in [
+            vec![span_label(10)],
+            vec![span_label(11), resource_label],
+            vec![span_label(12)],
+        ] {
+            let sample = api::Sample {
+                locations: vec![],
+                values: vec![1],
+                labels,
+            };
+            profile.add_sample(sample, None)?;
+        }
+
+        profile.add_endpoint(10, Cow::from("send_email"))?;
+        profile.add_root_span_attribute(10, Cow::from("span type"), Cow::from("queue"))?;
+        profile.add_root_span_attribute(10, Cow::from("resource"), Cow::from("old"))?;
+        profile.add_root_span_attribute(10, Cow::from("resource"), Cow::from("mailer"))?;
+        profile.add_root_span_attribute(11, Cow::from("resource"), Cow::from("ignored"))?;
+        for reserved in [
+            "",
+            "trace endpoint",
+            "local root span id",
+            "end_timestamp_ns",
+        ] {
+            profile
+                .add_root_span_attribute(12, Cow::from(reserved), Cow::from("x"))
+                .unwrap_err();
+        }
+
+        let pprof = pprof::roundtrip_to_pprof(profile)?;
+        let labels_of = |span_id: i64| -> Vec<(&str, &str)> {
+            let sample = pprof
+                .samples
+                .iter()
+                .find(|sample| sample.labels[0].num == span_id)
+                .expect("sample to exist");
+            sample.labels[1..]
+                .iter()
+                .map(|label| {
+                    (
+                        pprof.string_table[label.key as usize].as_str(),
+                        pprof.string_table[label.str as usize].as_str(),
+                    )
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            labels_of(10),
+            vec![
+                ("trace endpoint", "send_email"),
+                ("span type", "queue"),
+                ("resource", "mailer")
+            ]
+        );
+        // The sample's own label wins over the attribute.
+        assert_eq!(labels_of(11), vec![("resource", "from sample")]);
+        assert_eq!(labels_of(12), vec![]);
+        Ok(())
+    }
+
     #[test]
     fn local_root_span_id_label_cannot_occur_more_than_once() {
         let sample_types = [api::ValueType::new("wall-time", "nanoseconds")];
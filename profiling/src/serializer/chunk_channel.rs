@@ -0,0 +1,177 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io::{Read, Write};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Mutex, PoisonError};
+
+/// Creates a bounded pipe of chunks: what is written to the [ChunkSender] can
+/// be read from the [ChunkReceiver], typically on another thread.
+///
+/// At most `max_queued_chunks` full chunks wait to be read; once that many
+/// are queued, writing blocks until the reader catches up. Together with the
+/// chunk each side is working on, this bounds the memory held by the pipe to
+/// `(max_queued_chunks + 2) * chunk_size`, whatever the amount of data
+/// going through it.
+pub fn chunk_channel(max_queued_chunks: usize, chunk_size: usize) -> (ChunkSender, ChunkReceiver) {
+    assert!(chunk_size > 0, "chunk size must be non-zero");
+    let (sender, receiver) = sync_channel(max_queued_chunks);
+    (
+        ChunkSender {
+            chunk: Vec::new(),
+            chunk_size,
+            sender,
+        },
+        ChunkReceiver {
+            receiver: Mutex::new(receiver),
+            chunk: Vec::new(),
+            read_offset: 0,
+            finished: false,
+        },
+    )
+}
+
+/// A chunk of data, or `None` to mark the end of the data.
+type Message = Option<Vec<u8>>;
+
+/// The writing end of a [chunk_channel].
+///
+/// [ChunkSender::finish] must be called once everything has been written.
+/// If the sender is dropped without it, e.g. because producing the data
+/// failed, the reader gets an error instead of truncated data.
+pub struct ChunkSender {
+    chunk: Vec<u8>,
+    chunk_size: usize,
+    sender: SyncSender<Message>,
+}
+
+impl ChunkSender {
+    /// Sends what is left of the data and marks its end.
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.send_chunk()?;
+        self.send(None)
+    }
+
+    fn send_chunk(&mut self) -> std::io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::take(&mut self.chunk);
+        self.send(Some(chunk))
+    }
+
+    fn send(&self, message: Message) -> std::io::Result<()> {
+        self.sender.send(message).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "the reading end of the chunk channel was dropped",
+            )
+        })
+    }
+}
+
+impl Write for ChunkSender {
+    fn write(&mut self, mut buf: &[u8]) -> std::io::Result<usize> {
+        let written = buf.len();
+        while !buf.is_empty() {
+            if self.chunk.capacity() == 0 {
+                self.chunk
+                    .try_reserve_exact(self.chunk_size)
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::OutOfMemory, err))?;
+            }
+            let n = buf.len().min(self.chunk_size - self.chunk.len());
+            self.chunk.extend_from_slice(&buf[..n]);
+            buf = &buf[n..];
+            if self.chunk.len() == self.chunk_size {
+                self.send_chunk()?;
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The reading end of a [chunk_channel]. Reading blocks until the writer has
+/// filled the next chunk, or finished.
+pub struct ChunkReceiver {
+    // Only to make the receiver Sync, as required for multipart form parts.
+    receiver: Mutex<Receiver<Message>>,
+    chunk: Vec<u8>,
+    read_offset: usize,
+    finished: bool,
+}
+
+impl Read for ChunkReceiver {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.read_offset == self.chunk.len() && !self.finished {
+            let message = self
+                .receiver
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .recv();
+            match message {
+                Ok(Some(chunk)) => {
+                    self.chunk = chunk;
+                    self.read_offset = 0;
+                }
+                Ok(None) => self.finished = true,
+                Err(_) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "the writing end of the chunk channel was dropped before finishing",
+                    ))
+                }
+            }
+        }
+        let available = &self.chunk[self.read_offset..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.read_offset += n;
+        if self.read_offset == self.chunk.len() {
+            // Release the chunk as soon as it has been read.
+            self.chunk = Vec::new();
+            self.read_offset = 0;
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_then_read_across_threads() {
+        let (mut sender, mut receiver) = chunk_channel(1, 4);
+        let writer = std::thread::spawn(move || {
+            sender.write_all(b"hello, ").unwrap();
+            sender.write_all(b"world").unwrap();
+            sender.finish().unwrap();
+        });
+        let mut out = String::new();
+        receiver.read_to_string(&mut out).unwrap();
+        writer.join().unwrap();
+        assert_eq!(out, "hello, world");
+    }
+
+    #[test]
+    fn unfinished_writer_is_an_error() {
+        let (mut sender, mut receiver) = chunk_channel(4, 4);
+        sender.write_all(b"truncated").unwrap();
+        drop(sender);
+        let mut out = vec![];
+        let err = receiver.read_to_end(&mut out).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn dropped_reader_fails_the_writer() {
+        let (mut sender, receiver) = chunk_channel(0, 4);
+        drop(receiver);
+        let err = sender.write_all(b"nobody listens").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+
+/// An in-memory byte buffer made of fixed-size chunks.
+///
+/// Growing a `Vec<u8>` doubles its capacity and copies the old contents,
+/// so writing N bytes into one can briefly need 3N bytes, and the result
+/// may be up to 2N in size. Appending to a [ChunkedBuffer] never copies
+/// what was already written, and wastes at most one partially filled chunk.
+///
+/// Reading from it consumes the data, and each chunk is released as soon as
+/// it has been read. This makes it suitable for handing a serialized profile
+/// over to the exporter without copying it into a contiguous buffer.
+#[derive(Debug)]
+pub struct ChunkedBuffer {
+    chunks: VecDeque<Vec<u8>>,
+    chunk_size: usize,
+    /// How much of the front chunk has been read already.
+    read_offset: usize,
+    len: usize,
+}
+
+impl ChunkedBuffer {
+    /// Matches the LZ4 frame encoder's default block size, so that every
+    /// compressed block written to the buffer fits in about one chunk.
+    pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+    pub fn new() -> Self {
+        Self::with_chunk_size(Self::DEFAULT_CHUNK_SIZE)
+    }
+
+    /// # Panics
+    /// Panics if `chunk_size` is zero.
+    pub fn with_chunk_size(chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunk size must be non-zero");
+        Self {
+            chunks: VecDeque::new(),
+            chunk_size,
+            read_offset: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of bytes which have been written but not read yet.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bytes allocated for the chunks.
+    pub fn capacity(&self) -> usize {
+        self.chunks.iter().map(Vec::capacity).sum()
+    }
+
+    /// Copies the unread contents into one contiguous `Vec`, consuming the
+    /// buffer.
+    pub fn into_vec(mut self) -> Vec<u8> {
+        let mut vec = Vec::with_capacity(self.len);
+        if let Some(front) = self.chunks.pop_front() {
+            vec.extend_from_slice(&front[self.read_offset..]);
+        }
+        for chunk in self.chunks {
+            vec.extend_from_slice(&chunk);
+        }
+        vec
+    }
+}
+
+impl Default for ChunkedBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for ChunkedBuffer {
+    fn write(&mut self, mut buf: &[u8]) -> std::io::Result<usize> {
+        let written = buf.len();
+        while !buf.is_empty() {
+            let chunk = match self.chunks.back_mut() {
+                Some(chunk) if chunk.len() < chunk.capacity() => chunk,
+                _ => {
+                    let mut chunk = Vec::new();
+                    chunk
+                        .try_reserve_exact(self.chunk_size)
+                        .map_err(|err| std::io::Error::new(std::io::ErrorKind::OutOfMemory, err))?;
+                    self.chunks.push_back(chunk);
+                    // Safety: a chunk was pushed on the line above.
+                    self.chunks.back_mut().unwrap()
+                }
+            };
+            let n = buf.len().min(chunk.capacity() - chunk.len());
+            chunk.extend_from_slice(&buf[..n]);
+            buf = &buf[n..];
+        }
+        self.len += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for ChunkedBuffer {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut read = 0;
+        while read < buf.len() {
+            let Some(front) = self.chunks.front() else {
+                break;
+            };
+            let available = &front[self.read_offset..];
+            let n = available.len().min(buf.len() - read);
+            buf[read..read + n].copy_from_slice(&available[..n]);
+            read += n;
+            self.read_offset += n;
+            if self.read_offset == front.len() {
+                self.chunks.pop_front();
+                self.read_offset = 0;
+            }
+        }
+        self.len -= read;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_then_read() {
+        let mut buffer = ChunkedBuffer::with_chunk_size(4);
+        buffer.write_all(b"hello, ").unwrap();
+        buffer.write_all(b"world").unwrap();
+        assert_eq!(buffer.len(), 12);
+        assert_eq!(buffer.capacity(), 12);
+
+        let mut out = [0u8; 5];
+        buffer.read_exact(&mut out).unwrap();
+        assert_eq!(&out, b"hello");
+        assert_eq!(buffer.len(), 7);
+        // The first chunk has been fully read and released.
+        assert_eq!(buffer.capacity(), 8);
+
+        let mut rest = String::new();
+        buffer.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, ", world");
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.capacity(), 0);
+    }
+
+    #[test]
+    fn into_vec_skips_read_bytes() {
+        let mut buffer = ChunkedBuffer::with_chunk_size(3);
+        buffer.write_all(b"abcdefgh").unwrap();
+        let mut out = [0u8; 2];
+        buffer.read_exact(&mut out).unwrap();
+        assert_eq!(buffer.into_vec(), b"cdefgh");
+    }
+}
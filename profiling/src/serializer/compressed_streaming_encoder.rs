@@ -6,11 +6,21 @@ use lz4_flex::frame::FrameEncoder;
 use prost::encoding::{encode_key, encode_varint, encoded_len_varint, key_len, WireType};
 use std::io::Write;
 
-pub struct CompressedProtobufSerializer {
+/// Encodes protobuf messages one at a time and feeds them into an LZ4 frame
+/// compressor, which writes compressed blocks to `W` as they fill up. Only
+/// one message is ever held uncompressed, so memory use is bounded by the
+/// largest message plus the compressor's block buffers, and not by the size
+/// of the profile.
+pub struct CompressedProtobufSerializer<W: Write = Vec<u8>> {
     buffer: Vec<u8>,
-    zipper: FrameEncoder<Vec<u8>>,
+    zipper: FrameEncoder<W>,
 }
 
+/// The scratch buffer keeps its capacity between messages so that encoding
+/// doesn't allocate, but an unusually large message shouldn't pin that much
+/// memory for the rest of the serialization.
+const MAX_RETAINED_SCRATCH_CAPACITY: usize = 64 * 1024;
+
 // I've opened a PR for a generic version of this upstream:
 // https://github.com/tokio-rs/prost/pull/978
 fn encode_str(tag: u32, value: &str, buf: &mut Vec<u8>) {
@@ -19,12 +29,18 @@ fn encode_str(tag: u32, value: &str, buf: &mut Vec<u8>) {
     buf.put_slice(value.as_bytes());
 }
 
-impl CompressedProtobufSerializer {
+impl<W: Write> CompressedProtobufSerializer<W> {
+    /// Creates a serializer which writes the compressed output to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            buffer: Vec::new(),
+            zipper: FrameEncoder::new(writer),
+        }
+    }
+
     pub fn encode(&mut self, item: impl prost::Message) -> anyhow::Result<()> {
         item.encode(&mut self.buffer)?;
-        self.zipper.write_all(&self.buffer)?;
-        self.buffer.clear();
-        Ok(())
+        self.flush_buffer()
     }
 
     /// Only meant for string table strings. This is essentially an
@@ -46,15 +62,25 @@ impl CompressedProtobufSerializer {
         }
 
         encode_str(tag, str, &mut self.buffer);
-        self.zipper.write_all(&self.buffer)?;
-        self.buffer.clear();
-        Ok(())
+        self.flush_buffer()
     }
 
-    pub fn finish(self) -> anyhow::Result<Vec<u8>> {
+    /// Finishes the LZ4 frame and returns the writer.
+    pub fn finish(self) -> anyhow::Result<W> {
         Ok(self.zipper.finish()?)
     }
 
+    fn flush_buffer(&mut self) -> anyhow::Result<()> {
+        self.zipper.write_all(&self.buffer)?;
+        self.buffer.clear();
+        if self.buffer.capacity() > MAX_RETAINED_SCRATCH_CAPACITY {
+            self.buffer.shrink_to(MAX_RETAINED_SCRATCH_CAPACITY);
+        }
+        Ok(())
+    }
+}
+
+impl CompressedProtobufSerializer {
     pub fn with_capacity(capacity: usize) -> Self {
         let buffer = Vec::with_capacity(capacity);
         let zipper = FrameEncoder::new(Vec::with_capacity(capacity));
@@ -1,6 +1,10 @@
 // Copyright 2023-Present Datadog, Inc. https://www.datadoghq.com/
 // SPDX-License-Identifier: Apache-2.0
 
+mod chunk_channel;
+mod chunked_buffer;
 mod compressed_streaming_encoder;
 
+pub use chunk_channel::*;
+pub use chunked_buffer::*;
 pub use compressed_streaming_encoder::*;
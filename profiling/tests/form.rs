@@ -246,4 +246,43 @@ mod tests {
             profiling_library_version
         );
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn failed_send_is_spooled() {
+        // Nothing listens on port 1, so the connection is refused right away.
+        let base_url = "http://127.0.0.1:1".parse().expect("url to parse");
+        let endpoint = config::agent(base_url).expect("endpoint to construct");
+        let mut exporter = ProfileExporter::new(
+            "dd-trace-foo",
+            "1.2.3",
+            "php",
+            Some(default_tags()),
+            endpoint,
+        )
+        .expect("exporter to construct");
+
+        let dir = tempfile::tempdir().expect("tempdir to be created");
+        exporter
+            .set_spool(spool::SpoolConfig::new(dir.path()))
+            .expect("spool to be configured");
+
+        let request = multipart(&mut exporter, None, None);
+        exporter
+            .send(request, None)
+            .expect_err("send to fail without an agent");
+
+        let spooled = exporter.spooled_requests().expect("spool to be listed");
+        assert_eq!(spooled.len(), 1);
+        assert_eq!(spooled[0].uri, "http://127.0.0.1:1/profiling/v1/input");
+        assert_eq!(spooled[0].attempts, 1);
+
+        // Draining against an endpoint that is still down keeps the entry around.
+        let result = exporter.drain_spool(None).expect("drain to run");
+        assert_eq!(result.sent, 0);
+        assert_eq!(result.failed, 1);
+        let spooled = exporter.spooled_requests().expect("spool to be listed");
+        assert_eq!(spooled.len(), 1);
+        assert_eq!(spooled[0].attempts, 2);
+    }
 }
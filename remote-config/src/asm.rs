@@ -0,0 +1,366 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+//! Typed contents of the ASM remote config products.
+//!
+//! A product may be delivered as several files. The `merge` functions combine the files of a
+//! product into the configuration to apply, in the order they are given: where files conflict,
+//! later files take precedence.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// The contents of an ASM_FEATURES file.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AsmFeaturesFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asm: Option<AsmActivation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_security: Option<ApiSecurity>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AsmActivation {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ApiSecurity {
+    pub request_sample_rate: f64,
+}
+
+impl AsmFeaturesFile {
+    /// Each feature is taken from the last file setting it.
+    pub fn merge<'a>(files: impl IntoIterator<Item = &'a AsmFeaturesFile>) -> AsmFeaturesFile {
+        let mut merged = AsmFeaturesFile::default();
+        for file in files {
+            if file.asm.is_some() {
+                merged.asm.clone_from(&file.asm);
+            }
+            if file.api_security.is_some() {
+                merged.api_security.clone_from(&file.api_security);
+            }
+        }
+        merged
+    }
+
+    pub fn is_enabled(&self) -> Option<bool> {
+        self.asm.as_ref().map(|asm| asm.enabled)
+    }
+}
+
+/// The contents of an ASM_DATA file: the data of the WAF rules and exclusions, like the lists
+/// of blocked IPs or users.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AsmDataFile {
+    #[serde(default)]
+    pub rules_data: Vec<AsmData>,
+    #[serde(default)]
+    pub exclusion_data: Vec<AsmData>,
+}
+
+/// A list of values referenced by its id from the WAF rules.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AsmData {
+    pub id: String,
+    /// E.g. `ip_with_expiration` or `data_with_expiration`.
+    #[serde(rename = "type")]
+    pub data_type: String,
+    pub data: Vec<AsmDataValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AsmDataValue {
+    pub value: String,
+    /// The unix timestamp in seconds after which the value no longer applies, never if absent
+    /// or 0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration: Option<u64>,
+}
+
+impl AsmDataValue {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at()
+            .is_some_and(|expiration| expiration <= now)
+    }
+
+    /// The expiration, None if the value never expires.
+    fn expires_at(&self) -> Option<u64> {
+        self.expiration.filter(|&expiration| expiration != 0)
+    }
+}
+
+impl AsmDataFile {
+    /// The lists with the same id are merged. A value present in several of them is kept until
+    /// its latest expiration.
+    pub fn merge<'a>(files: impl IntoIterator<Item = &'a AsmDataFile>) -> AsmDataFile {
+        let mut merged = AsmDataFile::default();
+        for file in files {
+            merge_data(&mut merged.rules_data, &file.rules_data);
+            merge_data(&mut merged.exclusion_data, &file.exclusion_data);
+        }
+        merged
+    }
+
+    /// Removes the values which expired at `now`, in unix seconds.
+    pub fn remove_expired(&mut self, now: u64) {
+        for data in self
+            .rules_data
+            .iter_mut()
+            .chain(self.exclusion_data.iter_mut())
+        {
+            data.data.retain(|value| !value.is_expired(now));
+        }
+    }
+}
+
+fn merge_data(merged: &mut Vec<AsmData>, data: &[AsmData]) {
+    for data in data {
+        let Some(existing) = merged
+            .iter_mut()
+            .find(|existing| existing.id == data.id && existing.data_type == data.data_type)
+        else {
+            merged.push(data.clone());
+            continue;
+        };
+        for value in data.data.iter() {
+            match existing.data.iter_mut().find(|v| v.value == value.value) {
+                Some(existing) => {
+                    existing.expiration = match (existing.expires_at(), value.expires_at()) {
+                        (Some(a), Some(b)) => Some(a.max(b)),
+                        _ => None,
+                    }
+                }
+                None => existing.data.push(value.clone()),
+            }
+        }
+    }
+}
+
+/// The contents of an ASM_DD file: the WAF rules defined by Datadog.
+///
+/// The rules are not interpreted, but passed as is to the WAF.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmDDFile {
+    pub version: Option<String>,
+    pub rules_version: Option<String>,
+    /// The raw JSON contents of the file.
+    pub rules: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct AsmDDHeader {
+    version: Option<String>,
+    #[serde(default)]
+    metadata: Option<AsmDDMetadata>,
+}
+
+#[derive(Deserialize)]
+struct AsmDDMetadata {
+    rules_version: Option<String>,
+}
+
+impl AsmDDFile {
+    /// Only a single ruleset may be applied: the one of the last file.
+    pub fn merge<'a>(files: impl IntoIterator<Item = &'a AsmDDFile>) -> Option<&'a AsmDDFile> {
+        files.into_iter().last()
+    }
+}
+
+/// The contents of an ASM file: the user defined configuration of the WAF.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AsmFile {
+    #[serde(default)]
+    pub exclusions: Vec<Value>,
+    #[serde(default)]
+    pub custom_rules: Vec<Value>,
+    #[serde(default)]
+    pub rules_override: Vec<Value>,
+    #[serde(default)]
+    pub processor_overrides: Vec<Value>,
+    #[serde(default)]
+    pub scanners: Vec<Value>,
+    #[serde(default)]
+    pub actions: Vec<AsmAction>,
+}
+
+/// An action to take when a rule matches, e.g. blocking the request.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AsmAction {
+    pub id: String,
+    /// E.g. `block_request` or `redirect_request`.
+    #[serde(rename = "type")]
+    pub action_type: String,
+    #[serde(default)]
+    pub parameters: HashMap<String, Value>,
+}
+
+impl AsmFile {
+    /// The WAF items of all files are concatenated. An action defined in several files is
+    /// taken from the last one.
+    pub fn merge<'a>(files: impl IntoIterator<Item = &'a AsmFile>) -> AsmFile {
+        let mut merged = AsmFile::default();
+        for file in files {
+            merged.exclusions.extend_from_slice(&file.exclusions);
+            merged.custom_rules.extend_from_slice(&file.custom_rules);
+            merged
+                .rules_override
+                .extend_from_slice(&file.rules_override);
+            merged
+                .processor_overrides
+                .extend_from_slice(&file.processor_overrides);
+            merged.scanners.extend_from_slice(&file.scanners);
+            for action in file.actions.iter() {
+                match merged.actions.iter_mut().find(|a| a.id == action.id) {
+                    Some(existing) => *existing = action.clone(),
+                    None => merged.actions.push(action.clone()),
+                }
+            }
+        }
+        merged
+    }
+}
+
+pub fn parse_asm_features(data: &[u8]) -> serde_json::error::Result<AsmFeaturesFile> {
+    serde_json::from_slice(data)
+}
+
+pub fn parse_asm_data(data: &[u8]) -> serde_json::error::Result<AsmDataFile> {
+    serde_json::from_slice(data)
+}
+
+pub fn parse_asm_dd(data: &[u8]) -> serde_json::error::Result<AsmDDFile> {
+    let header: AsmDDHeader = serde_json::from_slice(data)?;
+    Ok(AsmDDFile {
+        version: header.version,
+        rules_version: header.metadata.and_then(|metadata| metadata.rules_version),
+        rules: data.to_vec(),
+    })
+}
+
+pub fn parse_asm(data: &[u8]) -> serde_json::error::Result<AsmFile> {
+    serde_json::from_slice(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asm_features() {
+        let files = [
+            parse_asm_features(br#"{"asm": {"enabled": true}}"#).unwrap(),
+            parse_asm_features(br#"{"api_security": {"request_sample_rate": 0.1}}"#).unwrap(),
+            parse_asm_features(br#"{"asm": {"enabled": false}, "auto_user_instrum": {}}"#).unwrap(),
+        ];
+        assert_eq!(files[0].is_enabled(), Some(true));
+        assert_eq!(files[1].is_enabled(), None);
+
+        let merged = AsmFeaturesFile::merge(&files);
+        assert_eq!(merged.is_enabled(), Some(false));
+        assert_eq!(merged.api_security.unwrap().request_sample_rate, 0.1);
+    }
+
+    #[test]
+    fn test_asm_data() {
+        let first = parse_asm_data(
+            br#"{"rules_data": [{"id": "blocked_ips", "type": "ip_with_expiration", "data": [
+                {"value": "1.2.3.4", "expiration": 100},
+                {"value": "5.6.7.8", "expiration": 100}
+            ]}]}"#,
+        )
+        .unwrap();
+        let second = parse_asm_data(
+            br#"{"rules_data": [
+                {"id": "blocked_ips", "type": "ip_with_expiration", "data": [
+                    {"value": "1.2.3.4", "expiration": 200},
+                    {"value": "5.6.7.8"},
+                    {"value": "9.9.9.9", "expiration": 50}
+                ]},
+                {"id": "blocked_users", "type": "data_with_expiration", "data": [{"value": "admin"}]}
+            ]}"#,
+        )
+        .unwrap();
+
+        let mut merged = AsmDataFile::merge([&first, &second]);
+        assert_eq!(merged.rules_data.len(), 2);
+        assert!(merged.exclusion_data.is_empty());
+        let ips = &merged.rules_data[0].data;
+        assert_eq!(ips.len(), 3);
+        assert_eq!(ips[0].expiration, Some(200));
+        assert_eq!(ips[1].expiration, None);
+
+        merged.remove_expired(100);
+        let ips: Vec<_> = merged.rules_data[0]
+            .data
+            .iter()
+            .map(|v| v.value.as_str())
+            .collect();
+        assert_eq!(ips, ["1.2.3.4", "5.6.7.8"]);
+        assert_eq!(merged.rules_data[1].data[0].value, "admin");
+    }
+
+    #[test]
+    fn test_asm_data_zero_expiration() {
+        let first = parse_asm_data(
+            br#"{"rules_data": [{"id": "blocked_ips", "type": "ip_with_expiration", "data": [
+                {"value": "1.2.3.4", "expiration": 0},
+                {"value": "5.6.7.8", "expiration": 0}
+            ]}]}"#,
+        )
+        .unwrap();
+        let second = parse_asm_data(
+            br#"{"rules_data": [{"id": "blocked_ips", "type": "ip_with_expiration", "data": [
+                {"value": "1.2.3.4", "expiration": 100}
+            ]}]}"#,
+        )
+        .unwrap();
+        assert!(!first.rules_data[0].data[0].is_expired(100));
+
+        // A value which never expires in one file still never expires once merged.
+        let mut merged = AsmDataFile::merge([&first, &second]);
+        assert_eq!(merged.rules_data[0].data[0].expiration, None);
+        merged.remove_expired(100);
+        assert_eq!(merged.rules_data[0].data.len(), 2);
+    }
+
+    #[test]
+    fn test_asm_dd() {
+        let data = br#"{"version": "2.2", "metadata": {"rules_version": "1.10.0"}, "rules": []}"#;
+        let first = parse_asm_dd(data).unwrap();
+        assert_eq!(first.version.as_deref(), Some("2.2"));
+        assert_eq!(first.rules_version.as_deref(), Some("1.10.0"));
+        assert_eq!(first.rules, data);
+
+        let second = parse_asm_dd(br#"{"rules": []}"#).unwrap();
+        assert_eq!(second.rules_version, None);
+        assert_eq!(AsmDDFile::merge([&first, &second]), Some(&second));
+        assert!(parse_asm_dd(b"[]").is_err());
+    }
+
+    #[test]
+    fn test_asm() {
+        let first = parse_asm(
+            br#"{"exclusions": [{"id": "e1"}], "actions": [
+                {"id": "block", "type": "block_request", "parameters": {"status_code": 403}}
+            ]}"#,
+        )
+        .unwrap();
+        let second = parse_asm(
+            br#"{"exclusions": [{"id": "e2"}], "custom_rules": [{"id": "r1"}], "actions": [
+                {"id": "block", "type": "redirect_request", "parameters": {"location": "/"}},
+                {"id": "other", "type": "block_request"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let merged = AsmFile::merge([&first, &second]);
+        assert_eq!(merged.exclusions.len(), 2);
+        assert_eq!(merged.custom_rules.len(), 1);
+        assert_eq!(merged.actions.len(), 2);
+        assert_eq!(merged.actions[0].action_type, "redirect_request");
+        assert_eq!(merged.actions[0].parameters["location"], "/");
+        assert!(merged.actions[1].parameters.is_empty());
+    }
+}
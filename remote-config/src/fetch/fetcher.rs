@@ -1,7 +1,9 @@
 // Copyright 2021-Present Datadog, Inc. https://www.datadoghq.com/
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::fetch::snapshot;
 use crate::targets::TargetsList;
+use crate::tuf::TrustedRoot;
 use crate::{
     RemoteConfigCapabilities, RemoteConfigPath, RemoteConfigPathRef, RemoteConfigPathType,
     RemoteConfigProduct, Target,
@@ -81,6 +83,8 @@ pub struct ConfigFetcherState<S> {
     endpoint: Endpoint,
     encoded_capabilities: Vec<u8>,
     pub expire_unused_files: bool,
+    /// When set, the signatures of the targets metadata are verified against it.
+    trusted_root: Mutex<Option<TrustedRoot>>,
 }
 
 #[derive(Default, Serialize, Deserialize)]
@@ -142,9 +146,39 @@ impl<S> ConfigFetcherState<S> {
             invariants,
             encoded_capabilities,
             expire_unused_files: true,
+            trusted_root: Mutex::new(None),
         }
     }
 
+    /// Enables the verification of the TUF signatures of the remote config metadata, trusting
+    /// the given root.json. Configs which don't verify are refused.
+    pub fn set_trusted_root(&self, root: &[u8]) -> anyhow::Result<()> {
+        *self.trusted_root.lock().unwrap() = Some(TrustedRoot::new(root)?);
+        Ok(())
+    }
+
+    /// Rotates the trusted root with the new roots of a response, then verifies the targets
+    /// metadata.
+    fn verify_targets(&self, roots: &[Vec<u8>], targets: &[u8]) -> anyhow::Result<()> {
+        let mut trusted_root = self.trusted_root.lock().unwrap();
+        let Some(trusted_root) = trusted_root.as_mut() else {
+            return Ok(());
+        };
+        for root in roots {
+            let decoded = base64::engine::general_purpose::STANDARD.decode(root)?;
+            trusted_root.update(&decoded)?;
+        }
+        trusted_root.verify_targets(targets, time::OffsetDateTime::now_utc())
+    }
+
+    fn root_version(&self) -> u64 {
+        self.trusted_root
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(1, TrustedRoot::version)
+    }
+
     /// To remove unused remote files manually. Must not be called when auto expiration is active.
     /// Note: careful attention must be paid when using this API in order to not deadlock:
     /// - This files_lock() must always be called prior to locking any data structure locked within
@@ -212,8 +246,14 @@ impl<S: FileStorage> ConfigFetcher<S> {
         self.state.set_config_state(file, state)
     }
 
+    /// Enables the verification of the TUF signatures of the remote config metadata.
+    pub fn set_trusted_root(&self, root: &[u8]) -> anyhow::Result<()> {
+        self.state.set_trusted_root(root)
+    }
+
     /// Quite generic fetching implementation:
-    ///  - runs a request against the Remote Config Server,
+    ///  - runs a request against the Remote Config Server, or reads the remote config snapshot
+    ///    referenced by a file endpoint,
     ///  - validates the data,
     ///  - removes unused files
     ///  - checks if the files are already known,
@@ -265,7 +305,7 @@ impl<S: FileStorage> ConfigFetcher<S> {
         let config_req = ClientGetConfigsRequest {
             client: Some(datadog_trace_protobuf::remoteconfig::Client {
                 state: Some(ClientState {
-                    root_version: 1,
+                    root_version: self.state.root_version(),
                     targets_version: opaque_state.targets_version,
                     config_states,
                     has_error: opaque_state.last_error.is_some(),
@@ -301,49 +341,77 @@ impl<S: FileStorage> ConfigFetcher<S> {
 
         trace!("Submitting remote config request: {config_req:?}");
 
-        let req = self
-            .state
-            .endpoint
-            .into_request_builder(concat!("Sidecar/", env!("CARGO_PKG_VERSION")))?
-            .method(http::Method::POST)
-            .header(
-                http::header::CONTENT_TYPE,
-                ddcommon::header::APPLICATION_JSON,
-            )
-            .body(serde_json::to_string(&config_req)?)?;
-        let response = tokio::time::timeout(
-            Duration::from_millis(self.state.endpoint.timeout_ms),
-            Client::builder()
-                .build(connector::Connector::default())
-                .request(req),
-        )
-        .await
-        .map_err(|e| anyhow::Error::msg(e).context(format!("Url: {:?}", self.state.endpoint)))?
-        .map_err(|e| anyhow::Error::msg(e).context(format!("Url: {:?}", self.state.endpoint)))?;
-        let status = response.status();
-        let body_bytes = response.into_body().collect().await?.to_bytes();
-        if status != StatusCode::OK {
-            // Not active
-            if status == StatusCode::NOT_FOUND {
-                trace!("Requested remote config and but remote config not active");
-                return Ok(Some(vec![]));
+        // Only snapshots are fetched from file endpoints. They are read off the runtime.
+        let from_snapshot = self.state.endpoint.url.scheme_str() == Some("file");
+        let response: ClientGetConfigsResponse = if from_snapshot {
+            let endpoint = self.state.endpoint.clone();
+            let products = self.state.invariants.products.clone();
+            let client = config_req.client.clone().unwrap_or_default();
+            let targets_version = opaque_state.targets_version;
+            match tokio::task::spawn_blocking(move || {
+                snapshot::read_snapshot(&endpoint, &products, &client, targets_version)
+            })
+            .await??
+            {
+                Some(response) => response,
+                None => {
+                    trace!("The remote config snapshot did not change");
+                    return Ok(None);
+                }
             }
+        } else {
+            let req = self
+                .state
+                .endpoint
+                .into_request_builder(concat!("Sidecar/", env!("CARGO_PKG_VERSION")))?
+                .method(http::Method::POST)
+                .header(
+                    http::header::CONTENT_TYPE,
+                    ddcommon::header::APPLICATION_JSON,
+                )
+                .body(serde_json::to_string(&config_req)?)?;
+            let response = tokio::time::timeout(
+                Duration::from_millis(self.state.endpoint.timeout_ms),
+                Client::builder()
+                    .build(connector::Connector::default())
+                    .request(req),
+            )
+            .await
+            .map_err(|e| anyhow::Error::msg(e).context(format!("Url: {:?}", self.state.endpoint)))?
+            .map_err(|e| {
+                anyhow::Error::msg(e).context(format!("Url: {:?}", self.state.endpoint))
+            })?;
+            let status = response.status();
+            let body_bytes = response.into_body().collect().await?.to_bytes();
+            if status != StatusCode::OK {
+                // Not active
+                if status == StatusCode::NOT_FOUND {
+                    trace!("Requested remote config and but remote config not active");
+                    return Ok(Some(vec![]));
+                }
 
-            let response_body = String::from_utf8(body_bytes.to_vec()).unwrap_or_default();
-            anyhow::bail!("Server did not accept remote config request: {response_body}");
-        }
+                let response_body = String::from_utf8(body_bytes.to_vec()).unwrap_or_default();
+                anyhow::bail!("Server did not accept remote config request: {response_body}");
+            }
 
-        // Nothing changed
-        if body_bytes.len() <= 3 {
-            trace!("Requested remote config and got an empty reply");
-            return Ok(None);
-        }
+            // Nothing changed
+            if body_bytes.len() <= 3 {
+                trace!("Requested remote config and got an empty reply");
+                return Ok(None);
+            }
 
-        let response: ClientGetConfigsResponse =
-            serde_json::from_str(&String::from_utf8_lossy(body_bytes.as_ref()))?;
+            serde_json::from_str(&String::from_utf8_lossy(body_bytes.as_ref()))?
+        };
 
         let decoded_targets =
             base64::engine::general_purpose::STANDARD.decode(response.targets.as_slice())?;
+        if let Err(e) = self
+            .state
+            .verify_targets(&response.roots, decoded_targets.as_slice())
+        {
+            opaque_state.last_error = Some(format!("Invalid remote config metadata: {e}"));
+            return Err(e.context("Refusing to apply remote config with invalid metadata"));
+        }
         let targets_list = TargetsList::try_parse(decoded_targets.as_slice()).map_err(|e| {
             anyhow::Error::msg(e).context(format!(
                 "Decoded targets reply: {}",
@@ -359,8 +427,7 @@ impl<S: FileStorage> ConfigFetcher<S> {
             .to_vec();
 
         debug!(
-            "Received remote config of length {}, containing {:?} paths for target {:?}",
-            body_bytes.len(),
+            "Received remote config containing {:?} paths for target {:?}",
             targets_list.signed.targets.keys().collect::<Vec<_>>(),
             target
         );
@@ -526,10 +593,12 @@ fn get_product_endpoint(subdomain: &str, endpoint: &Endpoint) -> Endpoint {
 pub mod tests {
     use super::*;
     use crate::fetch::test_server::RemoteConfigServer;
+    use crate::tuf::test_keys::{root, TestKey};
     use crate::RemoteConfigSource;
     use http::Response;
     use hyper::Body;
     use lazy_static::lazy_static;
+    use time::OffsetDateTime;
 
     lazy_static! {
         pub static ref PATH_FIRST: RemoteConfigPath = RemoteConfigPath {
@@ -836,6 +905,97 @@ pub mod tests {
         }
     }
 
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_verify_signatures() {
+        const FUTURE: &str = "2100-01-01T00:00:00Z";
+        let root_key = TestKey::generate("root");
+        let targets_key = TestKey::generate("targets");
+        let trusted_root = root(1, FUTURE, &[&root_key], &[&targets_key], &[&root_key]);
+
+        let server = RemoteConfigServer::spawn();
+        server.files.lock().unwrap().insert(
+            PATH_FIRST.clone(),
+            (vec![DUMMY_TARGET.clone()], 1, "v1".to_string()),
+        );
+        *server.signing_key.lock().unwrap() = Some(targets_key);
+
+        let storage = Arc::new(Storage::default());
+        let state = Arc::new(ConfigFetcherState::new(server.dummy_invariants()));
+        state.set_trusted_root(&trusted_root).unwrap();
+        let mut fetcher = ConfigFetcher::new(storage.clone(), state);
+        let mut opaque_state = ConfigClientState::default();
+        macro_rules! fetch {
+            () => {
+                fetcher.fetch_once(
+                    DUMMY_RUNTIME_ID,
+                    DUMMY_TARGET.clone(),
+                    "foo",
+                    &mut opaque_state,
+                )
+            };
+        }
+
+        let fetched = fetch!().await.unwrap().unwrap();
+        assert_eq!(fetched.len(), 1);
+
+        // Rotate the root to trust another targets key.
+        let rotated_key = TestKey::generate("rotated");
+        server.roots.lock().unwrap().push(root(
+            2,
+            FUTURE,
+            &[&root_key],
+            &[&rotated_key],
+            &[&root_key],
+        ));
+        *server.signing_key.lock().unwrap() = Some(rotated_key);
+        server.files.lock().unwrap().insert(
+            PATH_FIRST.clone(),
+            (vec![DUMMY_TARGET.clone()], 2, "v2".to_string()),
+        );
+        let fetched = fetch!().await.unwrap().unwrap();
+        assert_eq!(fetched[0].data.lock().unwrap().contents, "v2");
+
+        // Targets signed by an untrusted key are refused.
+        let rotated_key = server
+            .signing_key
+            .lock()
+            .unwrap()
+            .replace(TestKey::generate("rotated"))
+            .unwrap();
+        server.files.lock().unwrap().insert(
+            PATH_FIRST.clone(),
+            (vec![DUMMY_TARGET.clone()], 3, "v3".to_string()),
+        );
+        let err = fetch!().await.err().unwrap();
+        assert!(format!("{err:?}").contains("valid signatures"), "{err:?}");
+        assert_eq!(fetched[0].data.lock().unwrap().contents, "v2");
+
+        // As are unsigned targets, and the error is reported.
+        *server.signing_key.lock().unwrap() = None;
+        assert!(fetch!().await.is_err());
+        {
+            let req = server.last_request.lock().unwrap();
+            let state = req
+                .as_ref()
+                .unwrap()
+                .client
+                .as_ref()
+                .unwrap()
+                .state
+                .as_ref();
+            assert_eq!(state.unwrap().root_version, 2);
+            assert!(state.unwrap().has_error);
+        }
+
+        // And expired targets.
+        *server.signing_key.lock().unwrap() = Some(rotated_key);
+        *server.targets_expires.lock().unwrap() = OffsetDateTime::from_unix_timestamp(0).unwrap();
+        let err = fetch!().await.err().unwrap();
+        assert!(format!("{err:?}").contains("expired"), "{err:?}");
+        assert_eq!(storage.files.lock().unwrap().len(), 1);
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)]
     fn test_capability_encoding() {
@@ -844,13 +1004,11 @@ pub mod tests {
             tracer_version: "".to_string(),
             endpoint: Default::default(),
             products: vec![],
-            capabilities: unsafe {
-                vec![
-                    transmute::<u32, RemoteConfigCapabilities>(1u32),
-                    transmute::<u32, RemoteConfigCapabilities>(24u32),
-                    transmute::<u32, RemoteConfigCapabilities>(31u32),
-                ]
-            },
+            capabilities: vec![
+                RemoteConfigCapabilities::AsmActivation,
+                RemoteConfigCapabilities::AsmRaspShi,
+                RemoteConfigCapabilities::AsmAutoUserInstrumMode,
+            ],
         });
         assert_eq!(state.encoded_capabilities.len(), 4);
         assert_eq!(
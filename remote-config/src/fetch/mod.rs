@@ -5,6 +5,7 @@ mod fetcher;
 mod multitarget;
 mod shared;
 mod single;
+mod snapshot;
 #[cfg(any(test, feature = "test"))]
 pub mod test_server;
 
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::fetch::{
-    ConfigApplyState, ConfigFetcherState, ConfigInvariants, FileStorage, RefcountedFile,
+    snapshot, ConfigApplyState, ConfigFetcherState, ConfigInvariants, FileStorage, RefcountedFile,
     RefcountingStorage, RefcountingStorageStats, SharedFetcher,
 };
 use crate::Target;
@@ -325,14 +325,9 @@ where
                 }
             }
             Entry::Vacant(e) => {
-                if self
-                    .storage
-                    .invariants()
-                    .endpoint
-                    .url
-                    .scheme()
-                    .map(|s| s.as_str() != "file")
-                    == Some(true)
+                let endpoint = &self.storage.invariants().endpoint;
+                if endpoint.url.scheme().map(|s| s.as_str() != "file") == Some(true)
+                    || snapshot::is_snapshot(endpoint)
                 {
                     let info = RuntimeInfo {
                         notify_target,
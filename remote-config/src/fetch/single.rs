@@ -34,6 +34,12 @@ impl<S: FileStorage> SingleFetcher<S> {
         self
     }
 
+    /// Verifies the TUF signatures of the remote config metadata against the given root.json.
+    pub fn with_trusted_root(self, root: &[u8]) -> anyhow::Result<Self> {
+        self.fetcher.set_trusted_root(root)?;
+        Ok(self)
+    }
+
     /// Polls the current runtime config files.
     pub async fn fetch_once(&mut self) -> anyhow::Result<Option<Vec<Arc<S::StoredFile>>>> {
         self.fetcher
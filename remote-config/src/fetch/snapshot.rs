@@ -0,0 +1,369 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+//! Offline remote config, for hosts without an agent serving it: a snapshot of the remote config
+//! on disk replaces the agent's replies. It is referenced by a `file://` endpoint to either:
+//!  - a JSON bundle in the format of the agent's `/v0.7/config` replies, i.e. with the `roots`,
+//!    `targets` and `target_files` base64 encoded, and optionally the `client_configs` (all the
+//!    targets of the subscribed products otherwise). Its name must end with `.json`.
+//!  - a directory containing the TUF targets metadata as `targets.json`, and the target files at
+//!    their paths relative to the directory, e.g. `datadog/2/APM_TRACING/<config_id>/config`.
+//!
+//! The snapshot is read again on every poll and considered changed whenever the version of its
+//! targets changes. Without `client_configs`, the targets are filtered like the agent does, by
+//! their `tracer-predicates` on the service, env, etc. of the client.
+//!
+//! Target paths are relative to the snapshot: absolute paths and `..` are rejected.
+
+use crate::targets::{TargetData, TargetsList};
+use crate::{RemoteConfigPath, RemoteConfigProduct};
+use anyhow::Context;
+use base64::Engine;
+use datadog_trace_protobuf::remoteconfig::{Client, ClientGetConfigsResponse, File};
+use ddcommon::Endpoint;
+use serde::Deserialize;
+use std::path::{Component, Path, PathBuf};
+
+const TARGETS_FILE: &str = "targets.json";
+
+fn snapshot_path(endpoint: &Endpoint) -> Option<PathBuf> {
+    if endpoint.url.scheme_str() != Some("file") {
+        return None;
+    }
+    let path = ddcommon::decode_uri_path_in_authority(&endpoint.url).ok()?;
+    (path.is_dir() || path.extension().is_some_and(|ext| ext == "json")).then_some(path)
+}
+
+/// Whether the endpoint references a snapshot. Other file endpoints don't serve remote config.
+pub(crate) fn is_snapshot(endpoint: &Endpoint) -> bool {
+    snapshot_path(endpoint).is_some()
+}
+
+/// Reads the snapshot referenced by `endpoint` as a reply of the agent to `client`, or None if
+/// the version of its targets is `targets_version`. This reads files, so it blocks.
+pub(crate) fn read_snapshot(
+    endpoint: &Endpoint,
+    products: &[RemoteConfigProduct],
+    client: &Client,
+    targets_version: u64,
+) -> anyhow::Result<Option<ClientGetConfigsResponse>> {
+    let Some(path) = snapshot_path(endpoint) else {
+        anyhow::bail!("{:?} is not a remote config snapshot", endpoint.url);
+    };
+    let mut response = if path.is_dir() {
+        let targets = read(&path.join(TARGETS_FILE))?;
+        if parse_targets(&targets)?.signed.version as u64 == targets_version {
+            return Ok(None);
+        }
+        let target_files = parse_targets(&targets)?
+            .signed
+            .targets
+            .keys()
+            .map(|target| {
+                Ok(File {
+                    path: target.to_string(),
+                    raw: encode(&read(&path.join(relative_path(target)?))?),
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        ClientGetConfigsResponse {
+            roots: vec![],
+            targets: encode(&targets),
+            target_files,
+            client_configs: vec![],
+        }
+    } else {
+        let response: ClientGetConfigsResponse = serde_json::from_slice(&read(&path)?)
+            .with_context(|| format!("Invalid remote config snapshot {}", path.display()))?;
+        let targets =
+            base64::engine::general_purpose::STANDARD.decode(response.targets.as_slice())?;
+        if parse_targets(&targets)?.signed.version as u64 == targets_version {
+            return Ok(None);
+        }
+        response
+    };
+
+    if response.client_configs.is_empty() {
+        let targets =
+            base64::engine::general_purpose::STANDARD.decode(response.targets.as_slice())?;
+        response.client_configs = parse_targets(&targets)?
+            .signed
+            .targets
+            .iter()
+            .filter(|(target, data)| {
+                RemoteConfigPath::try_parse(target)
+                    .is_ok_and(|target| products.contains(&target.product))
+                    && matches_tracer_predicates(data, client)
+            })
+            .map(|(target, _)| target.to_string())
+            .collect();
+    }
+    Ok(Some(response))
+}
+
+/// The `tracer-predicates` of a target: the target is for the clients matching any of them, or
+/// for all clients without any.
+#[derive(Deserialize)]
+struct TracerPredicates {
+    #[serde(default)]
+    tracer_predicates_v1: Vec<TracerPredicate>,
+}
+
+/// Matches the clients whose attributes equal the non-empty ones, and whose tracer version
+/// satisfies `tracer_version`, a semver requirement.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct TracerPredicate {
+    #[serde(rename = "clientID")]
+    client_id: String,
+    service: String,
+    environment: String,
+    app_version: String,
+    tracer_version: String,
+    language: String,
+    #[serde(rename = "runtimeID")]
+    runtime_id: String,
+}
+
+impl TracerPredicate {
+    fn matches(&self, client: &Client) -> bool {
+        let Some(tracer) = &client.client_tracer else {
+            return false;
+        };
+        let matches = |expected: &str, actual: &str| expected.is_empty() || expected == actual;
+        matches(&self.client_id, &client.id)
+            && matches(&self.service, &tracer.service)
+            && matches(&self.environment, &tracer.env)
+            && matches(&self.app_version, &tracer.app_version)
+            && matches(&self.language, &tracer.language)
+            && matches(&self.runtime_id, &tracer.runtime_id)
+            && (self.tracer_version.is_empty()
+                || semver::VersionReq::parse(&self.tracer_version)
+                    .ok()
+                    .zip(semver::Version::parse(&tracer.tracer_version).ok())
+                    .is_some_and(|(req, version)| req.matches(&version)))
+    }
+}
+
+fn matches_tracer_predicates(target: &TargetData, client: &Client) -> bool {
+    let Some(predicates) = target.custom.get("tracer-predicates") else {
+        return true;
+    };
+    // Like the agent, invalid predicates match no client.
+    match serde_json::from_str::<TracerPredicates>(predicates.get()) {
+        Ok(predicates) => {
+            predicates.tracer_predicates_v1.is_empty()
+                || predicates
+                    .tracer_predicates_v1
+                    .iter()
+                    .any(|predicate| predicate.matches(client))
+        }
+        Err(_) => false,
+    }
+}
+
+/// The path of a target file relative to the snapshot directory, which it must not leave.
+fn relative_path(target: &str) -> anyhow::Result<&Path> {
+    let path = Path::new(target);
+    anyhow::ensure!(
+        path.components().all(|c| matches!(c, Component::Normal(_))),
+        "Invalid target path {target} in remote config snapshot"
+    );
+    Ok(path)
+}
+
+fn read(path: &Path) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("Failed reading {}", path.display()))
+}
+
+fn parse_targets(targets: &[u8]) -> anyhow::Result<TargetsList<'_>> {
+    TargetsList::try_parse(targets).context("Invalid targets metadata in remote config snapshot")
+}
+
+fn encode(data: &[u8]) -> Vec<u8> {
+    base64::engine::general_purpose::STANDARD
+        .encode(data)
+        .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::{ConfigInvariants, SingleFetcher};
+    use crate::file_change_tracker::FilePath;
+    use crate::file_storage::SimpleFileStorage;
+    use crate::Target;
+    use sha2::{Digest, Sha256};
+
+    fn targets(version: u64, files: &[(&str, &[u8])]) -> Vec<u8> {
+        let files: Vec<_> = files
+            .iter()
+            .map(|&(path, contents)| (path, contents, serde_json::json!({"v": 1})))
+            .collect();
+        targets_with_custom(version, &files)
+    }
+
+    fn targets_with_custom(version: u64, files: &[(&str, &[u8], serde_json::Value)]) -> Vec<u8> {
+        let targets: serde_json::Map<_, _> = files
+            .iter()
+            .map(|(path, contents, custom)| {
+                (
+                    path.to_string(),
+                    serde_json::json!({
+                        "custom": custom,
+                        "hashes": {"sha256": format!("{:x}", Sha256::digest(contents))},
+                        "length": contents.len(),
+                    }),
+                )
+            })
+            .collect();
+        serde_json::to_vec(&serde_json::json!({
+            "signatures": [],
+            "signed": {
+                "_type": "targets",
+                "custom": {"opaque_backend_state": ""},
+                "expires": "9999-12-31T23:59:59Z",
+                "spec_version": "1.0.0",
+                "targets": targets,
+                "version": version,
+            },
+        }))
+        .unwrap()
+    }
+
+    fn fetcher(path: &Path) -> SingleFetcher<SimpleFileStorage> {
+        SingleFetcher::new(
+            SimpleFileStorage::default(),
+            Target {
+                service: "service".to_string(),
+                env: "env".to_string(),
+                app_version: "1.0".to_string(),
+                tags: vec![],
+            },
+            "runtime".to_string(),
+            ConfigInvariants {
+                language: "php".to_string(),
+                tracer_version: "1.0".to_string(),
+                endpoint: Endpoint::from_url(
+                    ddcommon::parse_uri(&format!("file://{}", path.display())).unwrap(),
+                ),
+                products: vec![RemoteConfigProduct::ApmTracing],
+                capabilities: vec![],
+            },
+        )
+    }
+
+    const APM_TRACING: &str = "datadog/2/APM_TRACING/config/config";
+    const ASM: &str = "datadog/2/ASM/config/config";
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_directory_snapshot() {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        for path in [APM_TRACING, ASM] {
+            std::fs::create_dir_all(dir.join(path).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(path), b"{}").unwrap();
+        }
+        let files: [(&str, &[u8]); 2] = [(APM_TRACING, b"{}"), (ASM, b"{}")];
+        std::fs::write(dir.join(TARGETS_FILE), targets(1, &files)).unwrap();
+
+        let mut fetcher = fetcher(&dir);
+        let fetched = fetcher.fetch_once().await.unwrap().unwrap();
+        assert_eq!(fetched.len(), 1);
+        assert_eq!(fetched[0].path().to_string(), APM_TRACING);
+        assert!(fetcher.fetch_once().await.unwrap().is_none());
+
+        // Changes are only picked up with a new version of the targets.
+        std::fs::write(dir.join(APM_TRACING), b"[]").unwrap();
+        assert!(fetcher.fetch_once().await.unwrap().is_none());
+        let files: [(&str, &[u8]); 1] = [(APM_TRACING, b"[]")];
+        std::fs::write(dir.join(TARGETS_FILE), targets(2, &files)).unwrap();
+        let fetched = fetcher.fetch_once().await.unwrap().unwrap();
+        assert_eq!(*fetched[0].contents(), b"[]");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_tracer_predicates() {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let predicates = |service: &str, env: &str| {
+            serde_json::json!({
+                "v": 1,
+                "tracer-predicates": {"tracer_predicates_v1": [
+                    {"service": service, "environment": env},
+                    {"runtimeID": "other-runtime"},
+                ]},
+            })
+        };
+        let ours = "datadog/2/APM_TRACING/ours/config";
+        let other_service = "datadog/2/APM_TRACING/other_service/config";
+        let other_env = "datadog/2/APM_TRACING/other_env/config";
+        let files = [
+            (ours, b"{}".as_slice(), predicates("service", "env")),
+            (other_service, b"{}", predicates("other", "env")),
+            (other_env, b"{}", predicates("service", "other")),
+            (APM_TRACING, b"{}", serde_json::json!({"v": 1})),
+        ];
+        for (path, contents, _) in &files {
+            std::fs::create_dir_all(dir.join(path).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(path), contents).unwrap();
+        }
+        std::fs::write(dir.join(TARGETS_FILE), targets_with_custom(1, &files)).unwrap();
+
+        let mut fetcher = fetcher(&dir);
+        let fetched = fetcher.fetch_once().await.unwrap().unwrap();
+        let mut paths: Vec<_> = fetched.iter().map(|f| f.path().to_string()).collect();
+        paths.sort();
+        assert_eq!(paths, [APM_TRACING, ours]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_target_paths_stay_in_the_snapshot() {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&dir).unwrap();
+        let endpoint =
+            Endpoint::from_url(ddcommon::parse_uri(&format!("file://{}", dir.display())).unwrap());
+        for target in ["datadog/2/APM_TRACING/../../../../secret", "/etc/passwd"] {
+            let files: [(&str, &[u8]); 1] = [(target, b"{}")];
+            std::fs::write(dir.join(TARGETS_FILE), targets(1, &files)).unwrap();
+            let read = read_snapshot(
+                &endpoint,
+                &[RemoteConfigProduct::ApmTracing],
+                &Client::default(),
+                0,
+            );
+            assert!(read.is_err(), "{target} was read");
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg_attr(miri, ignore)]
+    async fn test_bundle_snapshot() {
+        let path = std::env::temp_dir().join(format!("{}.json", uuid::Uuid::new_v4()));
+        let bundle = serde_json::json!({
+            "targets": String::from_utf8(encode(&targets(1, &[(APM_TRACING, b"{}")]))).unwrap(),
+            "target_files": [{
+                "path": APM_TRACING,
+                "raw": String::from_utf8(encode(b"{}")).unwrap(),
+            }],
+        });
+        std::fs::write(&path, serde_json::to_vec(&bundle).unwrap()).unwrap();
+
+        let mut fetcher = fetcher(&path);
+        let fetched = fetcher.fetch_once().await.unwrap().unwrap();
+        assert_eq!(fetched.len(), 1);
+        assert!(fetcher.fetch_once().await.unwrap().is_none());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(!is_snapshot(&Endpoint::from_url(
+            ddcommon::parse_uri("file:///tmp/telemetry.log").unwrap()
+        )));
+    }
+}
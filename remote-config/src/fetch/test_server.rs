@@ -3,6 +3,7 @@
 
 use crate::fetch::ConfigInvariants;
 use crate::targets::{TargetData, TargetsCustom, TargetsData, TargetsList};
+use crate::tuf::test_keys::{sign, TestKey};
 use crate::{RemoteConfigCapabilities, RemoteConfigPath, RemoteConfigProduct, Target};
 use base64::Engine;
 use datadog_trace_protobuf::remoteconfig::{
@@ -28,6 +29,11 @@ pub struct RemoteConfigServer {
     #[allow(clippy::type_complexity)]
     pub files: Mutex<HashMap<RemoteConfigPath, (Vec<Arc<Target>>, u64, String)>>,
     pub next_response: Mutex<Option<Response<Body>>>,
+    /// Signs the targets metadata, if set.
+    pub signing_key: Mutex<Option<TestKey>>,
+    /// The new root.json files sent along the targets metadata.
+    pub roots: Mutex<Vec<Vec<u8>>>,
+    pub targets_expires: Mutex<OffsetDateTime>,
     pub endpoint: Endpoint,
     #[allow(dead_code)] // stops receiver on drop
     shutdown_complete_tx: Sender<()>,
@@ -42,6 +48,9 @@ impl RemoteConfigServer {
             last_request: Mutex::new(None),
             files: Default::default(),
             next_response: Mutex::new(None),
+            signing_key: Mutex::new(None),
+            roots: Mutex::new(vec![]),
+            targets_expires: Mutex::new(OffsetDateTime::from_unix_timestamp(253402300799).unwrap()),
             endpoint: Endpoint::from_slice(&format!("http://127.0.0.1:{port}/")),
             shutdown_complete_tx,
         });
@@ -128,15 +137,12 @@ impl RemoteConfigServer {
                                         let targets = TargetsList {
                                             signatures: vec![],
                                             signed: TargetsData {
-                                                _type: "",
+                                                _type: "targets",
                                                 custom: TargetsCustom {
                                                     agent_refresh_interval: Some(1000),
                                                     opaque_backend_state: "some state",
                                                 },
-                                                expires: OffsetDateTime::from_unix_timestamp(
-                                                    253402300799,
-                                                )
-                                                .unwrap(),
+                                                expires: *this.targets_expires.lock().unwrap(),
                                                 spec_version: "1.0.0",
                                                 targets: target_info
                                                     .iter()
@@ -159,10 +165,27 @@ impl RemoteConfigServer {
                                                 version: 1,
                                             },
                                         };
+                                        let targets = match &*this.signing_key.lock().unwrap() {
+                                            Some(key) => sign(
+                                                serde_json::to_value(&targets.signed).unwrap(),
+                                                &[key],
+                                            ),
+                                            None => serde_json::to_vec(&targets).unwrap(),
+                                        };
                                         let response = ClientGetConfigsResponse {
-                                            roots: vec![], /* not checked */
+                                            roots: this
+                                                .roots
+                                                .lock()
+                                                .unwrap()
+                                                .iter()
+                                                .map(|root| {
+                                                    base64::engine::general_purpose::STANDARD
+                                                        .encode(root)
+                                                        .into_bytes()
+                                                })
+                                                .collect(),
                                             targets: base64::engine::general_purpose::STANDARD
-                                                .encode(serde_json::to_vec(&targets).unwrap())
+                                                .encode(targets)
                                                 .into_bytes(),
                                             target_files: target_info
                                                 .iter()
@@ -119,6 +119,6 @@ pub type ParsedFileStorage = RawFileStorage<anyhow::Result<RemoteConfigData>>;
 
 impl ParseFile for anyhow::Result<RemoteConfigData> {
     fn parse(path: &RemoteConfigPath, contents: Vec<u8>) -> Self {
-        RemoteConfigData::try_parse(path.product, contents.as_slice())
+        RemoteConfigData::try_parse(&path.product, contents.as_slice())
     }
 }
@@ -1,12 +1,14 @@
 // Copyright 2021-Present Datadog, Inc. https://www.datadoghq.com/
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod asm;
 pub mod fetch;
 pub mod file_change_tracker;
 pub mod file_storage;
 mod parse;
 mod path;
 mod targets;
+pub mod tuf;
 
 use ddcommon::tag::Tag;
 pub use parse::*;
@@ -55,4 +57,15 @@ pub enum RemoteConfigCapabilities {
     AsmRaspXss = 28,
     ApmTracingSampleRules = 29,
     CsmActivation = 30,
+    AsmAutoUserInstrumMode = 31,
+    AsmEndpointFingerprint = 32,
+    AsmSessionFingerprint = 33,
+    AsmNetworkFingerprint = 34,
+    AsmHeaderFingerprint = 35,
+    AsmTruncationRules = 36,
+    AsmRaspCmdi = 37,
+    ApmTracingEnableDynamicInstrumentation = 38,
+    ApmTracingEnableExceptionReplay = 39,
+    ApmTracingEnableCodeOrigin = 40,
+    ApmTracingEnableLiveDebugging = 41,
 }
@@ -1,6 +1,7 @@
 // Copyright 2021-Present Datadog, Inc. https://www.datadoghq.com/
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::asm::{AsmDDFile, AsmDataFile, AsmFeaturesFile, AsmFile};
 use crate::{RemoteConfigPath, RemoteConfigProduct, RemoteConfigSource};
 use datadog_dynamic_configuration::data::DynamicConfigFile;
 use datadog_live_debugger::LiveDebuggingData;
@@ -9,12 +10,16 @@ use datadog_live_debugger::LiveDebuggingData;
 pub enum RemoteConfigData {
     DynamicConfig(DynamicConfigFile),
     LiveDebugger(LiveDebuggingData),
+    AsmFeatures(AsmFeaturesFile),
+    AsmData(AsmDataFile),
+    AsmDD(AsmDDFile),
+    Asm(AsmFile),
     Ignored(RemoteConfigProduct),
 }
 
 impl RemoteConfigData {
     pub fn try_parse(
-        product: RemoteConfigProduct,
+        product: &RemoteConfigProduct,
         data: &[u8],
     ) -> anyhow::Result<RemoteConfigData> {
         Ok(match product {
@@ -25,7 +30,15 @@ impl RemoteConfigData {
                 let parsed = datadog_live_debugger::parse_json(&String::from_utf8_lossy(data))?;
                 RemoteConfigData::LiveDebugger(parsed)
             }
-            _ => RemoteConfigData::Ignored(product),
+            RemoteConfigProduct::AsmFeatures => {
+                RemoteConfigData::AsmFeatures(crate::asm::parse_asm_features(data)?)
+            }
+            RemoteConfigProduct::AsmData => {
+                RemoteConfigData::AsmData(crate::asm::parse_asm_data(data)?)
+            }
+            RemoteConfigProduct::AsmDD => RemoteConfigData::AsmDD(crate::asm::parse_asm_dd(data)?),
+            RemoteConfigProduct::Asm => RemoteConfigData::Asm(crate::asm::parse_asm(data)?),
+            _ => RemoteConfigData::Ignored(product.clone()),
         })
     }
 }
@@ -35,7 +48,11 @@ impl From<&RemoteConfigData> for RemoteConfigProduct {
         match value {
             RemoteConfigData::DynamicConfig(_) => RemoteConfigProduct::ApmTracing,
             RemoteConfigData::LiveDebugger(_) => RemoteConfigProduct::LiveDebugger,
-            RemoteConfigData::Ignored(product) => *product,
+            RemoteConfigData::AsmFeatures(_) => RemoteConfigProduct::AsmFeatures,
+            RemoteConfigData::AsmData(_) => RemoteConfigProduct::AsmData,
+            RemoteConfigData::AsmDD(_) => RemoteConfigProduct::AsmDD,
+            RemoteConfigData::Asm(_) => RemoteConfigProduct::Asm,
+            RemoteConfigData::Ignored(product) => product.clone(),
         }
     }
 }
@@ -51,7 +68,7 @@ pub struct RemoteConfigValue {
 impl RemoteConfigValue {
     pub fn try_parse(path: &str, data: &[u8]) -> anyhow::Result<Self> {
         let path = RemoteConfigPath::try_parse(path)?;
-        let data = RemoteConfigData::try_parse(path.product, data)?;
+        let data = RemoteConfigData::try_parse(&path.product, data)?;
         Ok(RemoteConfigValue {
             source: path.source,
             data,
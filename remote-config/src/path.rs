@@ -13,31 +13,62 @@ pub enum RemoteConfigSource {
     Employee,
 }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub enum RemoteConfigProduct {
+    AgentConfig,
+    AgentTask,
+    ApmSampling,
     ApmTracing,
+    AsmApiSecuritySampleRate,
     AsmData,
     Asm,
     AsmDD,
     AsmFeatures,
     LiveDebugger,
+    LiveDebuggerSymbolDb,
+    /// A product unknown to this version of libdatadog, by its name.
+    Unknown(String),
 }
 
 impl Display for RemoteConfigProduct {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let str = match self {
+            RemoteConfigProduct::AgentConfig => "AGENT_CONFIG",
+            RemoteConfigProduct::AgentTask => "AGENT_TASK",
+            RemoteConfigProduct::ApmSampling => "APM_SAMPLING",
             RemoteConfigProduct::ApmTracing => "APM_TRACING",
+            RemoteConfigProduct::AsmApiSecuritySampleRate => "ASM_API_SECURITY_SAMPLE_RATE",
             RemoteConfigProduct::LiveDebugger => "LIVE_DEBUGGING",
+            RemoteConfigProduct::LiveDebuggerSymbolDb => "LIVE_DEBUGGING_SYMBOL_DB",
             RemoteConfigProduct::Asm => "ASM",
             RemoteConfigProduct::AsmDD => "ASM_DD",
             RemoteConfigProduct::AsmData => "ASM_DATA",
             RemoteConfigProduct::AsmFeatures => "ASM_FEATURES",
+            RemoteConfigProduct::Unknown(product) => product.as_str(),
         };
         write!(f, "{}", str)
     }
 }
 
+impl From<&str> for RemoteConfigProduct {
+    fn from(product: &str) -> Self {
+        match product {
+            "AGENT_CONFIG" => RemoteConfigProduct::AgentConfig,
+            "AGENT_TASK" => RemoteConfigProduct::AgentTask,
+            "APM_SAMPLING" => RemoteConfigProduct::ApmSampling,
+            "APM_TRACING" => RemoteConfigProduct::ApmTracing,
+            "ASM_API_SECURITY_SAMPLE_RATE" => RemoteConfigProduct::AsmApiSecuritySampleRate,
+            "LIVE_DEBUGGING" => RemoteConfigProduct::LiveDebugger,
+            "LIVE_DEBUGGING_SYMBOL_DB" => RemoteConfigProduct::LiveDebuggerSymbolDb,
+            "ASM" => RemoteConfigProduct::Asm,
+            "ASM_DD" => RemoteConfigProduct::AsmDD,
+            "ASM_DATA" => RemoteConfigProduct::AsmData,
+            "ASM_FEATURES" => RemoteConfigProduct::AsmFeatures,
+            product => RemoteConfigProduct::Unknown(product.to_string()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub struct RemoteConfigPath {
     pub source: RemoteConfigSource,
@@ -73,15 +104,7 @@ impl RemoteConfigPath {
                 }
                 source => anyhow::bail!("Unknown source {}", source),
             },
-            product: match parts[parts.len() - 3] {
-                "APM_TRACING" => RemoteConfigProduct::ApmTracing,
-                "LIVE_DEBUGGING" => RemoteConfigProduct::LiveDebugger,
-                "ASM" => RemoteConfigProduct::Asm,
-                "ASM_DD" => RemoteConfigProduct::AsmDD,
-                "ASM_DATA" => RemoteConfigProduct::AsmData,
-                "ASM_FEATURES" => RemoteConfigProduct::AsmFeatures,
-                product => anyhow::bail!("Unknown product {}", product),
-            },
+            product: parts[parts.len() - 3].into(),
             config_id: parts[parts.len() - 2],
             name: parts[parts.len() - 1],
         })
@@ -117,7 +140,7 @@ impl<'a> From<&RemoteConfigPathRef<'a>> for RemoteConfigPath {
     fn from(from: &RemoteConfigPathRef<'a>) -> RemoteConfigPath {
         RemoteConfigPath {
             source: from.source,
-            product: from.product,
+            product: from.product.clone(),
             config_id: from.config_id.to_owned(),
             name: from.name.to_owned(),
         }
@@ -133,7 +156,7 @@ impl<'a> From<&'a RemoteConfigPath> for RemoteConfigPathRef<'a> {
     fn from(from: &'a RemoteConfigPath) -> RemoteConfigPathRef<'a> {
         RemoteConfigPathRef {
             source: from.source,
-            product: from.product,
+            product: from.product.clone(),
             config_id: from.config_id.as_str(),
             name: from.name.as_str(),
         }
@@ -145,8 +168,8 @@ impl RemoteConfigPathType for RemoteConfigPath {
         self.source
     }
 
-    fn product(&self) -> RemoteConfigProduct {
-        self.product
+    fn product(&self) -> &RemoteConfigProduct {
+        &self.product
     }
 
     fn config_id(&self) -> &str {
@@ -167,8 +190,8 @@ impl<'a> RemoteConfigPathType for &RemoteConfigPathRef<'a> {
         self.source
     }
 
-    fn product(&self) -> RemoteConfigProduct {
-        self.product
+    fn product(&self) -> &RemoteConfigProduct {
+        &self.product
     }
 
     fn config_id(&self) -> &'a str {
@@ -189,8 +212,8 @@ impl<'a> RemoteConfigPathType for RemoteConfigPathRef<'a> {
         self.source
     }
 
-    fn product(&self) -> RemoteConfigProduct {
-        self.product
+    fn product(&self) -> &RemoteConfigProduct {
+        &self.product
     }
 
     fn config_id(&self) -> &'a str {
@@ -208,7 +231,7 @@ impl<'a> RemoteConfigPathType for RemoteConfigPathRef<'a> {
 
 pub trait RemoteConfigPathType {
     fn source(&self) -> RemoteConfigSource;
-    fn product(&self) -> RemoteConfigProduct;
+    fn product(&self) -> &RemoteConfigProduct;
     fn config_id(&self) -> &str;
     fn name(&self) -> &str;
     fn to_owned(&self) -> RemoteConfigPath;
@@ -253,3 +276,27 @@ impl PartialEq for dyn RemoteConfigPathType + '_ {
 }
 
 impl Eq for dyn RemoteConfigPathType + '_ {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_path() {
+        let path = RemoteConfigPath::try_parse("datadog/2/APM_SAMPLING/config/name").unwrap();
+        assert_eq!(path.source, RemoteConfigSource::Datadog(2));
+        assert_eq!(path.product, RemoteConfigProduct::ApmSampling);
+        assert_eq!(path.config_id, "config");
+        assert_eq!(path.name, "name");
+
+        let path = RemoteConfigPath::try_parse("employee/NEW_PRODUCT/config/name").unwrap();
+        assert_eq!(
+            path.product,
+            RemoteConfigProduct::Unknown("NEW_PRODUCT".to_string())
+        );
+        assert_eq!(path.to_string(), "employee/NEW_PRODUCT/config/name");
+
+        assert!(RemoteConfigPath::try_parse("datadog/2/ASM/config").is_err());
+        assert!(RemoteConfigPath::try_parse("other/ASM/config/name").is_err());
+    }
+}
@@ -0,0 +1,460 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+//! Verification of remote config metadata following TUF (The Update Framework): the targets
+//! metadata must be signed by enough keys of the targets role of a trusted root, and neither may
+//! be expired. Roots are rotated by chaining each new root version to the previous one.
+
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use time::OffsetDateTime;
+
+#[derive(Deserialize)]
+struct SignedEnvelope {
+    signatures: Vec<Signature>,
+    signed: Value,
+}
+
+#[derive(Deserialize)]
+struct Signature {
+    keyid: String,
+    sig: String,
+}
+
+#[derive(Deserialize)]
+struct RootData {
+    _type: String,
+    #[serde(with = "time::serde::iso8601")]
+    expires: OffsetDateTime,
+    keys: HashMap<String, Key>,
+    roles: HashMap<String, Role>,
+    version: u64,
+}
+
+#[derive(Deserialize)]
+struct Key {
+    keytype: String,
+    keyval: KeyValue,
+}
+
+#[derive(Deserialize)]
+struct KeyValue {
+    public: String,
+}
+
+#[derive(Deserialize)]
+struct Role {
+    keyids: Vec<String>,
+    threshold: usize,
+}
+
+#[derive(Deserialize)]
+struct ExpiringData {
+    _type: String,
+    #[serde(with = "time::serde::iso8601")]
+    expires: OffsetDateTime,
+}
+
+/// The root of trust of the remote config metadata: the keys of each role.
+pub struct TrustedRoot {
+    version: u64,
+    expires: OffsetDateTime,
+    /// Public ed25519 keys by key id.
+    keys: HashMap<String, Vec<u8>>,
+    roles: HashMap<String, Role>,
+}
+
+impl TrustedRoot {
+    /// Trusts a root.json, as configured or embedded. It must still be signed by its own root
+    /// keys.
+    pub fn new(root: &[u8]) -> anyhow::Result<Self> {
+        let envelope: SignedEnvelope = serde_json::from_slice(root)?;
+        let root = Self::parse(&envelope.signed)?;
+        root.verify_role("root", &envelope)?;
+        Ok(root)
+    }
+
+    fn parse(signed: &Value) -> anyhow::Result<Self> {
+        let data = RootData::deserialize(signed)?;
+        anyhow::ensure!(
+            data._type == "root",
+            "Expected root metadata, got {}",
+            data._type
+        );
+        let keys = data
+            .keys
+            .into_iter()
+            .filter(|(_, key)| key.keytype == "ed25519")
+            .map(|(keyid, key)| Ok((keyid, decode_hex(&key.keyval.public)?)))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(TrustedRoot {
+            version: data.version,
+            expires: data.expires,
+            keys,
+            roles: data.roles,
+        })
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Rotates to the next version of the root, which must be signed both by the keys of the
+    /// current root and by its own keys. Older or current versions are ignored.
+    pub fn update(&mut self, root: &[u8]) -> anyhow::Result<()> {
+        let envelope: SignedEnvelope = serde_json::from_slice(root)?;
+        let new_root = Self::parse(&envelope.signed)?;
+        if new_root.version <= self.version {
+            return Ok(());
+        }
+        anyhow::ensure!(
+            new_root.version == self.version + 1,
+            "Cannot rotate the root from version {} to version {}",
+            self.version,
+            new_root.version
+        );
+        self.verify_role("root", &envelope)?;
+        new_root.verify_role("root", &envelope)?;
+        *self = new_root;
+        Ok(())
+    }
+
+    /// Verifies the signatures and the expiration of targets.json.
+    pub fn verify_targets(&self, targets: &[u8], now: OffsetDateTime) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.expires > now,
+            "The root metadata (version {}) expired at {}",
+            self.version,
+            self.expires
+        );
+        let envelope: SignedEnvelope = serde_json::from_slice(targets)?;
+        let data = ExpiringData::deserialize(&envelope.signed)?;
+        anyhow::ensure!(
+            data._type == "targets",
+            "Expected targets metadata, got {}",
+            data._type
+        );
+        self.verify_role("targets", &envelope)?;
+        anyhow::ensure!(
+            data.expires > now,
+            "The targets metadata expired at {}",
+            data.expires
+        );
+        Ok(())
+    }
+
+    fn verify_role(&self, role_name: &str, envelope: &SignedEnvelope) -> anyhow::Result<()> {
+        let Some(role) = self.roles.get(role_name) else {
+            anyhow::bail!("The root metadata has no {role_name} role");
+        };
+        anyhow::ensure!(role.threshold > 0, "Invalid threshold for {role_name}");
+        let message = canonical_json(&envelope.signed)?;
+        let mut valid_keys = HashSet::new();
+        for signature in envelope.signatures.iter() {
+            if !role.keyids.contains(&signature.keyid) || valid_keys.contains(&signature.keyid) {
+                continue;
+            }
+            let (Some(key), Ok(sig)) =
+                (self.keys.get(&signature.keyid), decode_hex(&signature.sig))
+            else {
+                continue;
+            };
+            if UnparsedPublicKey::new(&ED25519, key)
+                .verify(&message, &sig)
+                .is_ok()
+            {
+                valid_keys.insert(&signature.keyid);
+            }
+        }
+        anyhow::ensure!(
+            valid_keys.len() >= role.threshold,
+            "Found {} valid signatures for {role_name}, {} required",
+            valid_keys.len(),
+            role.threshold
+        );
+        Ok(())
+    }
+}
+
+fn decode_hex(hex: &str) -> anyhow::Result<Vec<u8>> {
+    anyhow::ensure!(
+        hex.len() % 2 == 0 && hex.is_ascii(),
+        "Invalid hex string {hex}"
+    );
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(anyhow::Error::from))
+        .collect()
+}
+
+/// Serializes `value` as the canonical JSON which TUF signs: sorted keys, no whitespace, only
+/// quotes and backslashes escaped in strings, and integer numbers only.
+pub(crate) fn canonical_json(value: &Value) -> anyhow::Result<Vec<u8>> {
+    fn write_string(out: &mut Vec<u8>, s: &str) {
+        out.push(b'"');
+        for c in s.chars() {
+            if c == '"' || c == '\\' {
+                out.push(b'\\');
+            }
+            let mut buf = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+        out.push(b'"');
+    }
+
+    fn write(out: &mut Vec<u8>, value: &Value) -> anyhow::Result<()> {
+        match value {
+            Value::Null => out.extend_from_slice(b"null"),
+            Value::Bool(b) => write!(out, "{b}")?,
+            Value::Number(n) => {
+                anyhow::ensure!(
+                    n.is_i64() || n.is_u64(),
+                    "Canonical JSON does not support the number {n}"
+                );
+                write!(out, "{n}")?;
+            }
+            Value::String(s) => write_string(out, s),
+            Value::Array(array) => {
+                out.push(b'[');
+                for (i, item) in array.iter().enumerate() {
+                    if i > 0 {
+                        out.push(b',');
+                    }
+                    write(out, item)?;
+                }
+                out.push(b']');
+            }
+            Value::Object(object) => {
+                let mut entries: Vec<_> = object.iter().collect();
+                entries.sort_by_key(|(key, _)| *key);
+                out.push(b'{');
+                for (i, (key, item)) in entries.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push(b',');
+                    }
+                    write_string(out, key);
+                    out.push(b':');
+                    write(out, item)?;
+                }
+                out.push(b'}');
+            }
+        }
+        Ok(())
+    }
+
+    let mut out = vec![];
+    write(&mut out, value)?;
+    Ok(out)
+}
+
+#[cfg(any(test, feature = "test"))]
+pub mod test_keys {
+    use super::canonical_json;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use serde_json::{json, Value};
+
+    /// A locally generated ed25519 key to sign test metadata with.
+    pub struct TestKey {
+        pub keyid: String,
+        pair: Ed25519KeyPair,
+    }
+
+    fn encode_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    impl TestKey {
+        pub fn generate(keyid: &str) -> Self {
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+            TestKey {
+                keyid: keyid.to_string(),
+                pair: Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap(),
+            }
+        }
+
+        pub fn public_hex(&self) -> String {
+            encode_hex(self.pair.public_key().as_ref())
+        }
+    }
+
+    /// Wraps `signed` in a metadata envelope signed by `signers`.
+    pub fn sign(signed: Value, signers: &[&TestKey]) -> Vec<u8> {
+        let message = canonical_json(&signed).unwrap();
+        let signatures: Vec<_> = signers
+            .iter()
+            .map(|key| {
+                json!({
+                    "keyid": key.keyid,
+                    "sig": encode_hex(key.pair.sign(&message).as_ref()),
+                })
+            })
+            .collect();
+        serde_json::to_vec(&json!({"signatures": signatures, "signed": signed})).unwrap()
+    }
+
+    /// A root.json of `version`, with the given keys for the root and targets roles, signed by
+    /// `signers`.
+    pub fn root(
+        version: u64,
+        expires: &str,
+        root_keys: &[&TestKey],
+        targets_keys: &[&TestKey],
+        signers: &[&TestKey],
+    ) -> Vec<u8> {
+        let keys: serde_json::Map<_, _> = root_keys
+            .iter()
+            .chain(targets_keys)
+            .map(|key| {
+                (
+                    key.keyid.clone(),
+                    json!({
+                        "keytype": "ed25519",
+                        "scheme": "ed25519",
+                        "keyval": {"public": key.public_hex()},
+                    }),
+                )
+            })
+            .collect();
+        let keyids = |keys: &[&TestKey]| keys.iter().map(|k| k.keyid.clone()).collect::<Vec<_>>();
+        sign(
+            json!({
+                "_type": "root",
+                "consistent_snapshot": true,
+                "expires": expires,
+                "keys": keys,
+                "roles": {
+                    "root": {"keyids": keyids(root_keys), "threshold": 1},
+                    "targets": {"keyids": keyids(targets_keys), "threshold": 1},
+                },
+                "spec_version": "1.0",
+                "version": version,
+            }),
+            signers,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_keys::*;
+    use super::*;
+    use serde_json::json;
+
+    const FUTURE: &str = "2100-01-01T00:00:00Z";
+
+    fn targets(expires: &str) -> Value {
+        json!({
+            "_type": "targets",
+            "custom": {"opaque_backend_state": "state"},
+            "expires": expires,
+            "spec_version": "1.0",
+            "targets": {},
+            "version": 3,
+        })
+    }
+
+    #[test]
+    fn test_canonical_json() {
+        let value = json!({"b": [1, -2, null, true], "a": {"d": "q\"\\é\n", "c": {}}});
+        assert_eq!(
+            String::from_utf8(canonical_json(&value).unwrap()).unwrap(),
+            "{\"a\":{\"c\":{},\"d\":\"q\\\"\\\\é\n\"},\"b\":[1,-2,null,true]}"
+        );
+        assert!(canonical_json(&json!({"a": 1.5})).is_err());
+    }
+
+    #[test]
+    fn test_verify_targets() {
+        let root_key = TestKey::generate("root");
+        let targets_key = TestKey::generate("targets");
+        let other_key = TestKey::generate("other");
+        let trusted = TrustedRoot::new(&root(
+            1,
+            FUTURE,
+            &[&root_key],
+            &[&targets_key],
+            &[&root_key],
+        ))
+        .unwrap();
+        let now = OffsetDateTime::now_utc();
+
+        trusted
+            .verify_targets(&sign(targets(FUTURE), &[&targets_key]), now)
+            .unwrap();
+        // Signed by keys which aren't trusted for targets.
+        assert!(trusted
+            .verify_targets(&sign(targets(FUTURE), &[&root_key, &other_key]), now)
+            .is_err());
+        assert!(trusted
+            .verify_targets(&sign(targets(FUTURE), &[]), now)
+            .is_err());
+        // Tampered with after signing.
+        let mut tampered: Value =
+            serde_json::from_slice(&sign(targets(FUTURE), &[&targets_key])).unwrap();
+        tampered["signed"]["version"] = json!(4);
+        assert!(trusted
+            .verify_targets(&serde_json::to_vec(&tampered).unwrap(), now)
+            .is_err());
+        // Expired.
+        let expired = sign(targets("2020-01-01T00:00:00Z"), &[&targets_key]);
+        let err = trusted.verify_targets(&expired, now).unwrap_err();
+        assert!(err.to_string().contains("expired"), "{err}");
+
+        // The trusted root itself must be signed by its root keys.
+        assert!(TrustedRoot::new(&root(
+            1,
+            FUTURE,
+            &[&root_key],
+            &[&targets_key],
+            &[&other_key]
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_root_rotation() {
+        let old_key = TestKey::generate("old");
+        let new_key = TestKey::generate("new");
+        let targets_key = TestKey::generate("targets");
+        let mut trusted =
+            TrustedRoot::new(&root(1, FUTURE, &[&old_key], &[&targets_key], &[&old_key])).unwrap();
+
+        // Version 2 must be signed by both the old and the new root keys.
+        let only_new = root(2, FUTURE, &[&new_key], &[&new_key], &[&new_key]);
+        assert!(trusted.update(&only_new).is_err());
+        let skipping = root(3, FUTURE, &[&new_key], &[&new_key], &[&old_key, &new_key]);
+        assert!(trusted.update(&skipping).is_err());
+        assert_eq!(trusted.version(), 1);
+
+        let rotated = root(2, FUTURE, &[&new_key], &[&new_key], &[&old_key, &new_key]);
+        trusted.update(&rotated).unwrap();
+        assert_eq!(trusted.version(), 2);
+        // Replaying an older root is a no-op.
+        trusted.update(&rotated).unwrap();
+
+        let now = OffsetDateTime::now_utc();
+        assert!(trusted
+            .verify_targets(&sign(targets(FUTURE), &[&targets_key]), now)
+            .is_err());
+        trusted
+            .verify_targets(&sign(targets(FUTURE), &[&new_key]), now)
+            .unwrap();
+
+        // An expired root doesn't verify anything.
+        let expired = root(
+            3,
+            "2020-01-01T00:00:00Z",
+            &[&new_key],
+            &[&new_key],
+            &[&new_key],
+        );
+        trusted.update(&expired).unwrap();
+        assert!(trusted
+            .verify_targets(&sign(targets(FUTURE), &[&new_key]), now)
+            .is_err());
+    }
+}
@@ -42,6 +42,55 @@ use std::slice;
 use std::sync::Arc;
 use std::time::Duration;
 
+/// The remote config products a tracer may subscribe to.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub enum FfiRemoteConfigProduct {
+    ApmTracing,
+    AsmData,
+    Asm,
+    AsmDD,
+    AsmFeatures,
+    LiveDebugger,
+    AgentConfig,
+    AgentTask,
+    ApmSampling,
+    AsmApiSecuritySampleRate,
+    LiveDebuggerSymbolDb,
+}
+
+impl From<FfiRemoteConfigProduct> for RemoteConfigProduct {
+    fn from(product: FfiRemoteConfigProduct) -> Self {
+        match product {
+            FfiRemoteConfigProduct::ApmTracing => RemoteConfigProduct::ApmTracing,
+            FfiRemoteConfigProduct::AsmData => RemoteConfigProduct::AsmData,
+            FfiRemoteConfigProduct::Asm => RemoteConfigProduct::Asm,
+            FfiRemoteConfigProduct::AsmDD => RemoteConfigProduct::AsmDD,
+            FfiRemoteConfigProduct::AsmFeatures => RemoteConfigProduct::AsmFeatures,
+            FfiRemoteConfigProduct::LiveDebugger => RemoteConfigProduct::LiveDebugger,
+            FfiRemoteConfigProduct::AgentConfig => RemoteConfigProduct::AgentConfig,
+            FfiRemoteConfigProduct::AgentTask => RemoteConfigProduct::AgentTask,
+            FfiRemoteConfigProduct::ApmSampling => RemoteConfigProduct::ApmSampling,
+            FfiRemoteConfigProduct::AsmApiSecuritySampleRate => {
+                RemoteConfigProduct::AsmApiSecuritySampleRate
+            }
+            FfiRemoteConfigProduct::LiveDebuggerSymbolDb => {
+                RemoteConfigProduct::LiveDebuggerSymbolDb
+            }
+        }
+    }
+}
+
+unsafe fn to_remote_config_products(
+    products: *const FfiRemoteConfigProduct,
+    count: usize,
+) -> Vec<RemoteConfigProduct> {
+    slice::from_raw_parts(products, count)
+        .iter()
+        .map(|&product| product.into())
+        .collect()
+}
+
 #[repr(C)]
 pub struct NativeFile {
     pub handle: Box<PlatformHandle<File>>,
@@ -218,7 +267,7 @@ pub unsafe extern "C" fn ddog_remote_config_reader_for_endpoint<'a>(
     env_name: ffi::CharSlice,
     app_version: ffi::CharSlice,
     tags: &ddcommon_ffi::Vec<Tag>,
-    remote_config_products: *const RemoteConfigProduct,
+    remote_config_products: *const FfiRemoteConfigProduct,
     remote_config_products_count: usize,
     remote_config_capabilities: *const RemoteConfigCapabilities,
     remote_config_capabilities_count: usize,
@@ -228,8 +277,10 @@ pub unsafe extern "C" fn ddog_remote_config_reader_for_endpoint<'a>(
             language: language.to_utf8_lossy().into(),
             tracer_version: tracer_version.to_utf8_lossy().into(),
             endpoint: endpoint.clone(),
-            products: slice::from_raw_parts(remote_config_products, remote_config_products_count)
-                .to_vec(),
+            products: to_remote_config_products(
+                remote_config_products,
+                remote_config_products_count,
+            ),
             capabilities: slice::from_raw_parts(
                 remote_config_capabilities,
                 remote_config_capabilities_count,
@@ -534,7 +585,7 @@ pub unsafe extern "C" fn ddog_sidecar_session_set_config(
     log_path: ffi::CharSlice,
     #[allow(unused)] // On FFI layer we cannot conditionally compile, so we need the arg
     remote_config_notify_function: *mut c_void,
-    remote_config_products: *const RemoteConfigProduct,
+    remote_config_products: *const FfiRemoteConfigProduct,
     remote_config_products_count: usize,
     remote_config_capabilities: *const RemoteConfigCapabilities,
     remote_config_capabilities_count: usize,
@@ -567,11 +618,10 @@ pub unsafe extern "C" fn ddog_sidecar_session_set_config(
             } else {
                 LogMethod::File(String::from(log_path.to_utf8_lossy()).into())
             },
-            remote_config_products: slice::from_raw_parts(
+            remote_config_products: to_remote_config_products(
                 remote_config_products,
                 remote_config_products_count
-            )
-            .to_vec(),
+            ),
             remote_config_capabilities: slice::from_raw_parts(
                 remote_config_capabilities,
                 remote_config_capabilities_count
@@ -26,6 +26,8 @@ const DEFAULT_IDLE_LINGER_TIME: Duration = Duration::from_secs(60);
 
 const ENV_SIDECAR_SELF_TELEMETRY: &str = "_DD_SIDECAR_SELF_TELEMETRY";
 
+const ENV_SIDECAR_CRASHTRACKER_SPOOL_DIR: &str = "_DD_SIDECAR_CRASHTRACKER_SPOOL_DIR";
+
 const ENV_SIDECAR_APPSEC_SHARED_LIB_PATH: &str = "_DD_SIDECAR_APPSEC_SHARED_LIB_PATH";
 const ENV_SIDECAR_APPSEC_SOCKET_FILE_PATH: &str = "_DD_SIDECAR_APPSEC_SOCKET_FILE_PATH";
 const ENV_SIDECAR_APPSEC_LOCK_FILE_PATH: &str = "_DD_SIDECAR_APPSEC_LOCK_FILE_PATH";
@@ -88,6 +90,9 @@ pub struct Config {
     pub library_dependencies: Vec<LibDependency>,
     pub child_env: HashMap<std::ffi::OsString, std::ffi::OsString>,
     pub appsec_config: Option<AppSecConfig>,
+    /// Where crashtracker reports are kept until uploaded.  Reports left there by
+    /// earlier failed uploads are retried when the sidecar starts.
+    pub crashtracker_spool_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone)]
@@ -120,6 +125,9 @@ impl Config {
         if self.appsec_config.is_some() {
             res.extend(self.appsec_config.as_ref().unwrap().to_env());
         }
+        if let Some(spool_dir) = &self.crashtracker_spool_dir {
+            res.insert(ENV_SIDECAR_CRASHTRACKER_SPOOL_DIR, spool_dir.into());
+        }
         res
     }
 }
@@ -218,6 +226,8 @@ impl FromEnv {
             library_dependencies: vec![],
             child_env: std::env::vars_os().collect(),
             appsec_config: Self::appsec_config(),
+            crashtracker_spool_dir: std::env::var_os(ENV_SIDECAR_CRASHTRACKER_SPOOL_DIR)
+                .map(PathBuf::from),
         }
     }
 
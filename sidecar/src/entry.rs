@@ -70,6 +70,24 @@ where
         cancel();
     });
 
+    #[cfg(unix)]
+    if let Some(spool_dir) = Config::get().crashtracker_spool_dir {
+        tokio::spawn(async move {
+            // API keys aren't spooled, the sidecar's own is used for agentless endpoints.
+            let api_key = std::env::var("DD_API_KEY").ok();
+            match datadog_crashtracker::async_upload_pending_reports(
+                &spool_dir,
+                datadog_crashtracker::DEFAULT_MAX_PENDING_REPORT_AGE,
+                api_key.as_deref(),
+            )
+            .await
+            {
+                Ok(summary) => tracing::info!("Uploaded pending crash reports: {summary:?}"),
+                Err(err) => tracing::warn!("Unable to upload pending crash reports: {err}"),
+            }
+        });
+    }
+
     #[cfg(unix)]
     tokio::spawn(async move {
         let socket_path = crashtracker_unix_socket_path();
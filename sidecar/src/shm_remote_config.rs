@@ -699,7 +699,7 @@ mod tests {
         // just one update
         assert!(matches!(manager.fetch_update(), RemoteConfigUpdate::None));
 
-        manager.unload_configs(&[PATH_FIRST.product]);
+        manager.unload_configs(&[PATH_FIRST.product.clone()]);
 
         if let RemoteConfigUpdate::Add { value, .. } = manager.fetch_update() {
             assert_eq!(value.config_id, PATH_FIRST.config_id);
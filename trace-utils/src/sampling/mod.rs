@@ -0,0 +1,370 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+//! Trace sampling: decides whether a trace is kept from its root span, with
+//! sampling rules configured locally or through remote config, the sample
+//! rates sent by the agent, and a rate limiter.  Spans of dropped traces can
+//! still be kept individually, with single span sampling rules.
+
+mod rate_limiter;
+mod rules;
+mod span_sampling;
+
+pub use rate_limiter::RateLimiter;
+pub use rules::{Glob, RuleProvenance, SamplingRule};
+pub use span_sampling::{
+    is_span_sampled, SpanSampler, SpanSamplingRule, SPAN_SAMPLING_MAX_PER_SECOND_KEY,
+    SPAN_SAMPLING_MECHANISM_KEY, SPAN_SAMPLING_RULE_RATE_KEY,
+};
+
+use datadog_dynamic_configuration::Configs;
+use datadog_trace_protobuf::pb;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+
+pub const SAMPLING_PRIORITY_KEY: &str = "_sampling_priority_v1";
+pub const DECISION_MAKER_KEY: &str = "_dd.p.dm";
+pub const RULE_RATE_KEY: &str = "_dd.rule_psr";
+pub const LIMIT_RATE_KEY: &str = "_dd.limit_psr";
+pub const AGENT_RATE_KEY: &str = "_dd.agent_psr";
+
+/// The default of `DD_TRACE_RATE_LIMIT`, in traces per second.
+pub const DEFAULT_RATE_LIMIT: f64 = 100.0;
+
+pub const PRIORITY_USER_REJECT: i8 = -1;
+pub const PRIORITY_AUTO_REJECT: i8 = 0;
+pub const PRIORITY_AUTO_KEEP: i8 = 1;
+pub const PRIORITY_USER_KEEP: i8 = 2;
+
+/// The agent's default rate, for services it hasn't sent a rate for.
+const DEFAULT_AGENT_RATE_KEY: &str = "service:,env:";
+
+const KNUTH_FACTOR: u64 = 1111111111111111111;
+
+/// What made a sampling decision, reported in `_dd.p.dm`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SamplingMechanism {
+    Default = 0,
+    AgentRate = 1,
+    Rule = 3,
+    SpanSamplingRule = 8,
+    RemoteUserRule = 11,
+    RemoteDynamicRule = 12,
+}
+
+/// Whether an id is sampled at `rate`, in a way which is consistent across
+/// tracers and the agent.
+pub(crate) fn is_sampled_by_rate(id: u64, rate: f64) -> bool {
+    if rate >= 1.0 {
+        return true;
+    }
+    if rate <= 0.0 {
+        return false;
+    }
+    id.wrapping_mul(KNUTH_FACTOR) <= (rate * u64::MAX as f64) as u64
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SamplingDecision {
+    pub priority: i8,
+    pub mechanism: SamplingMechanism,
+    /// The sample rate of the matching rule, if any.
+    pub rule_rate: Option<f64>,
+    /// The effective rate of the rate limiter, if it was used.
+    pub limit_rate: Option<f64>,
+    /// The sample rate sent by the agent, if it was used.
+    pub agent_rate: Option<f64>,
+}
+
+impl SamplingDecision {
+    pub fn is_keep(&self) -> bool {
+        self.priority > 0
+    }
+
+    /// Sets the tags of the decision on the root span of the trace.
+    pub fn apply(&self, root: &mut pb::Span) {
+        root.metrics
+            .insert(SAMPLING_PRIORITY_KEY.to_string(), self.priority as f64);
+        for (key, rate) in [
+            (RULE_RATE_KEY, self.rule_rate),
+            (LIMIT_RATE_KEY, self.limit_rate),
+            (AGENT_RATE_KEY, self.agent_rate),
+        ] {
+            if let Some(rate) = rate {
+                root.metrics.insert(key.to_string(), rate);
+            }
+        }
+        if self.is_keep() {
+            root.meta.insert(
+                DECISION_MAKER_KEY.to_string(),
+                format!("-{}", self.mechanism as u8),
+            );
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AgentResponse {
+    rate_by_service: HashMap<String, f64>,
+}
+
+#[derive(Debug, Default)]
+struct RemoteSamplingConfig {
+    rules: Option<Vec<SamplingRule>>,
+    sample_rate: Option<f64>,
+}
+
+/// Makes the sampling decisions of the traces of a tracer.  Remote config
+/// overrides the local rules and sample rate, until it is removed.
+#[derive(Debug)]
+pub struct TraceSampler {
+    local_rules: Vec<SamplingRule>,
+    local_sample_rate: Option<f64>,
+    remote: RwLock<RemoteSamplingConfig>,
+    agent_rates: RwLock<HashMap<String, f64>>,
+    limiter: Mutex<RateLimiter>,
+}
+
+impl TraceSampler {
+    /// `rate_limit` is in traces per second, unlimited if negative.
+    pub fn new(
+        local_rules: Vec<SamplingRule>,
+        local_sample_rate: Option<f64>,
+        rate_limit: f64,
+    ) -> Self {
+        TraceSampler {
+            local_rules,
+            local_sample_rate: local_sample_rate.map(|rate| rate.clamp(0.0, 1.0)),
+            remote: RwLock::default(),
+            agent_rates: RwLock::default(),
+            limiter: Mutex::new(RateLimiter::new(rate_limit)),
+        }
+    }
+
+    /// Applies the current dynamic configuration of the tracer: the sampling
+    /// rules and sample rate it contains override the local ones, and those
+    /// it doesn't contain revert to the local ones.
+    pub fn apply_dynamic_config(&self, configs: impl IntoIterator<Item = Configs>) {
+        let mut remote = RemoteSamplingConfig::default();
+        for config in configs {
+            match config {
+                Configs::TracingSamplingRules(rules) => {
+                    remote.rules = Some(rules.into_iter().map(SamplingRule::from).collect())
+                }
+                Configs::TracingSampleRate(rate) => remote.sample_rate = Some(rate.clamp(0.0, 1.0)),
+                _ => (),
+            }
+        }
+        let mut current = self.remote.write().unwrap();
+        *current = remote;
+    }
+
+    /// Updates the sample rates by service from a response of the agent to a
+    /// trace payload, e.g. `{"rate_by_service": {"service:web,env:prod": 0.5}}`.
+    pub fn update_agent_rates(&self, response: &str) -> anyhow::Result<()> {
+        let response: AgentResponse = serde_json::from_str(response)?;
+        let mut rates = self.agent_rates.write().unwrap();
+        *rates = response.rate_by_service;
+        Ok(())
+    }
+
+    /// Decides whether the trace of `root` is kept.
+    pub fn sample(&self, root: &pb::Span) -> SamplingDecision {
+        if let Some(decision) = self.sample_by_rules(root) {
+            return decision;
+        }
+        self.sample_by_agent_rates(root)
+    }
+
+    fn sample_by_rules(&self, root: &pb::Span) -> Option<SamplingDecision> {
+        let remote = self.remote.read().unwrap();
+        let rules = remote.rules.as_ref().unwrap_or(&self.local_rules);
+        let (rate, mechanism) = match rules.iter().find(|rule| rule.is_match(root)) {
+            Some(rule) => (rule.sample_rate, rule.provenance.mechanism()),
+            None => match (remote.sample_rate, self.local_sample_rate) {
+                (Some(rate), _) => (rate, RuleProvenance::Customer.mechanism()),
+                (None, Some(rate)) => (rate, RuleProvenance::Local.mechanism()),
+                (None, None) => return None,
+            },
+        };
+        drop(remote);
+
+        let mut decision = SamplingDecision {
+            priority: PRIORITY_USER_REJECT,
+            mechanism,
+            rule_rate: Some(rate),
+            limit_rate: None,
+            agent_rate: None,
+        };
+        if is_sampled_by_rate(root.trace_id, rate) {
+            let mut limiter = self.limiter.lock().unwrap();
+            if limiter.is_allowed() {
+                decision.priority = PRIORITY_USER_KEEP;
+            }
+            decision.limit_rate = Some(limiter.effective_rate());
+        }
+        Some(decision)
+    }
+
+    fn sample_by_agent_rates(&self, root: &pb::Span) -> SamplingDecision {
+        let env = root.meta.get("env").map(String::as_str).unwrap_or_default();
+        let key = format!("service:{},env:{env}", root.service);
+        let rates = self.agent_rates.read().unwrap();
+        match rates
+            .get(&key)
+            .or_else(|| rates.get(DEFAULT_AGENT_RATE_KEY))
+        {
+            Some(&rate) => SamplingDecision {
+                priority: if is_sampled_by_rate(root.trace_id, rate) {
+                    PRIORITY_AUTO_KEEP
+                } else {
+                    PRIORITY_AUTO_REJECT
+                },
+                mechanism: SamplingMechanism::AgentRate,
+                rule_rate: None,
+                limit_rate: None,
+                agent_rate: Some(rate),
+            },
+            None => SamplingDecision {
+                priority: PRIORITY_AUTO_KEEP,
+                mechanism: SamplingMechanism::Default,
+                rule_rate: None,
+                limit_rate: None,
+                agent_rate: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datadog_dynamic_configuration::data::TracingSamplingRule;
+
+    fn root_span(trace_id: u64, service: &str) -> pb::Span {
+        let mut span = pb::Span {
+            trace_id,
+            service: service.to_string(),
+            name: "http.request".to_string(),
+            resource: "GET /".to_string(),
+            ..Default::default()
+        };
+        span.meta.insert("env".to_string(), "prod".to_string());
+        span
+    }
+
+    #[test]
+    fn test_knuth_sampling() {
+        assert!(is_sampled_by_rate(u64::MAX, 1.0));
+        assert!(!is_sampled_by_rate(0, 0.0));
+        assert!(is_sampled_by_rate(0, 0.01));
+        assert!(!is_sampled_by_rate(1, 0.01));
+        let kept = (0..10_000u64)
+            .filter(|id| is_sampled_by_rate(id.wrapping_mul(7919), 0.3))
+            .count();
+        assert!((2700..3300).contains(&kept), "{kept}");
+    }
+
+    #[test]
+    fn test_agent_rates() {
+        let sampler = TraceSampler::new(vec![], None, DEFAULT_RATE_LIMIT);
+        let decision = sampler.sample(&root_span(1, "web"));
+        assert_eq!(decision.priority, PRIORITY_AUTO_KEEP);
+        assert_eq!(decision.mechanism, SamplingMechanism::Default);
+
+        sampler
+            .update_agent_rates(
+                r#"{"rate_by_service": {"service:web,env:prod": 0, "service:,env:": 1}}"#,
+            )
+            .unwrap();
+        let decision = sampler.sample(&root_span(1, "web"));
+        assert_eq!(decision.priority, PRIORITY_AUTO_REJECT);
+        assert_eq!(decision.mechanism, SamplingMechanism::AgentRate);
+        assert_eq!(decision.agent_rate, Some(0.0));
+        let decision = sampler.sample(&root_span(1, "db"));
+        assert_eq!(decision.priority, PRIORITY_AUTO_KEEP);
+        assert_eq!(decision.agent_rate, Some(1.0));
+
+        assert!(sampler.update_agent_rates("{}").is_err());
+    }
+
+    #[test]
+    fn test_rules_and_limiter() {
+        let rules = SamplingRule::parse_local_rules(
+            r#"[{"service": "db", "sample_rate": 0}, {"service": "web", "sample_rate": 1}]"#,
+        )
+        .unwrap();
+        let sampler = TraceSampler::new(rules, None, 1.0);
+
+        let decision = sampler.sample(&root_span(1, "db"));
+        assert_eq!(decision.priority, PRIORITY_USER_REJECT);
+        assert_eq!(decision.mechanism, SamplingMechanism::Rule);
+        assert_eq!(decision.rule_rate, Some(0.0));
+        assert_eq!(decision.limit_rate, None);
+
+        let mut root = root_span(1, "web");
+        let decision = sampler.sample(&root);
+        assert_eq!(decision.priority, PRIORITY_USER_KEEP);
+        assert_eq!(decision.limit_rate, Some(1.0));
+        decision.apply(&mut root);
+        assert_eq!(root.metrics[SAMPLING_PRIORITY_KEY], 2.0);
+        assert_eq!(root.metrics[RULE_RATE_KEY], 1.0);
+        assert_eq!(root.metrics[LIMIT_RATE_KEY], 1.0);
+        assert_eq!(root.meta[DECISION_MAKER_KEY], "-3");
+
+        // The limiter only allows one trace per second.
+        let mut root = root_span(2, "web");
+        let decision = sampler.sample(&root);
+        assert_eq!(decision.priority, PRIORITY_USER_REJECT);
+        assert_eq!(decision.limit_rate, Some(0.5));
+        decision.apply(&mut root);
+        assert!(!root.meta.contains_key(DECISION_MAKER_KEY));
+
+        // Traces which match no rule fall back to the agent rates.
+        let decision = sampler.sample(&root_span(1, "cache"));
+        assert_eq!(decision.mechanism, SamplingMechanism::Default);
+    }
+
+    #[test]
+    fn test_dynamic_config() {
+        let sampler = TraceSampler::new(
+            SamplingRule::parse_local_rules(r#"[{"service": "web", "sample_rate": 0}]"#).unwrap(),
+            Some(0.0),
+            -1.0,
+        );
+        let decision = sampler.sample(&root_span(1, "db"));
+        assert_eq!(
+            (decision.priority, decision.mechanism),
+            (PRIORITY_USER_REJECT, SamplingMechanism::Rule)
+        );
+
+        let remote_rules: Vec<TracingSamplingRule> = serde_json::from_str(
+            r#"[{"service": "web", "resource": "*", "provenance": "dynamic", "sample_rate": 1}]"#,
+        )
+        .unwrap();
+        sampler.apply_dynamic_config(vec![
+            Configs::TracingSamplingRules(remote_rules),
+            Configs::TracingSampleRate(1.0),
+        ]);
+        let decision = sampler.sample(&root_span(1, "web"));
+        assert_eq!(
+            (decision.priority, decision.mechanism),
+            (PRIORITY_USER_KEEP, SamplingMechanism::RemoteDynamicRule)
+        );
+        let decision = sampler.sample(&root_span(1, "db"));
+        assert_eq!(
+            (decision.priority, decision.mechanism),
+            (PRIORITY_USER_KEEP, SamplingMechanism::RemoteUserRule)
+        );
+
+        // Removing the remote config restores the local rules.
+        sampler.apply_dynamic_config(vec![]);
+        let decision = sampler.sample(&root_span(1, "web"));
+        assert_eq!(
+            (decision.priority, decision.mechanism),
+            (PRIORITY_USER_REJECT, SamplingMechanism::Rule)
+        );
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::{Duration, Instant};
+
+/// A token bucket allowing up to `rate_limit` traces per second, which also
+/// tracks the ratio of allowed traces, reported as `_dd.limit_psr`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Traces allowed per second, unlimited if negative.
+    rate_limit: f64,
+    tokens: f64,
+    last_refill: Instant,
+    /// The start of the current one second window, and the number of traces
+    /// allowed and seen during it.
+    window_start: Instant,
+    window_allowed: u64,
+    window_total: u64,
+    previous_window_rate: Option<f64>,
+}
+
+impl RateLimiter {
+    pub fn new(rate_limit: f64) -> Self {
+        Self::new_at(rate_limit, Instant::now())
+    }
+
+    fn new_at(rate_limit: f64, now: Instant) -> Self {
+        RateLimiter {
+            rate_limit,
+            tokens: rate_limit.max(0.0),
+            last_refill: now,
+            window_start: now,
+            window_allowed: 0,
+            window_total: 0,
+            previous_window_rate: None,
+        }
+    }
+
+    pub fn rate_limit(&self) -> f64 {
+        self.rate_limit
+    }
+
+    /// Takes a token if there is one.
+    pub fn is_allowed(&mut self) -> bool {
+        self.is_allowed_at(Instant::now())
+    }
+
+    fn is_allowed_at(&mut self, now: Instant) -> bool {
+        self.roll_window(now);
+        let allowed = if self.rate_limit < 0.0 {
+            true
+        } else {
+            let elapsed = now.saturating_duration_since(self.last_refill);
+            // The bucket holds a second worth of tokens, but at least one, so that rates below
+            // one per second still allow traces.
+            self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate_limit)
+                .min(self.rate_limit.max(1.0));
+            self.last_refill = now;
+            if self.tokens >= 1.0 {
+                self.tokens -= 1.0;
+                true
+            } else {
+                false
+            }
+        };
+        self.window_total += 1;
+        if allowed {
+            self.window_allowed += 1;
+        }
+        allowed
+    }
+
+    /// The ratio of allowed traces over the current and previous windows.
+    pub fn effective_rate(&self) -> f64 {
+        match self.previous_window_rate {
+            Some(previous) => (self.window_rate() + previous) / 2.0,
+            None => self.window_rate(),
+        }
+    }
+
+    fn roll_window(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if elapsed < Duration::from_secs(1) {
+            return;
+        }
+        // The previous window only counts if it immediately precedes this one.
+        self.previous_window_rate = if elapsed < Duration::from_secs(2) {
+            Some(self.window_rate())
+        } else {
+            None
+        };
+        self.window_start = now;
+        self.window_allowed = 0;
+        self.window_total = 0;
+    }
+
+    fn window_rate(&self) -> f64 {
+        if self.window_total == 0 {
+            1.0
+        } else {
+            self.window_allowed as f64 / self.window_total as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new_at(2.0, start);
+        assert!(limiter.is_allowed_at(start));
+        assert!(limiter.is_allowed_at(start));
+        assert!(!limiter.is_allowed_at(start));
+        assert!(!limiter.is_allowed_at(start));
+        assert_eq!(limiter.effective_rate(), 0.5);
+
+        // Half a second refills one token.
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.is_allowed_at(later));
+        assert!(!limiter.is_allowed_at(later));
+
+        // The bucket never holds more than a second worth of tokens.
+        let next_window = start + Duration::from_millis(1500);
+        assert!(limiter.is_allowed_at(next_window));
+        assert!(limiter.is_allowed_at(next_window));
+        assert!(!limiter.is_allowed_at(next_window));
+        // (2/3 + 3/6) / 2
+        assert!((limiter.effective_rate() - 7.0 / 12.0).abs() < 1e-9);
+
+        // After an idle window, only the current one counts.
+        let idle = start + Duration::from_secs(5);
+        assert!(limiter.is_allowed_at(idle));
+        assert_eq!(limiter.effective_rate(), 1.0);
+    }
+
+    #[test]
+    fn test_fractional_rate() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new_at(0.5, start);
+        assert!(!limiter.is_allowed_at(start));
+        // One trace every two seconds.
+        let second = start + Duration::from_secs(1);
+        assert!(limiter.is_allowed_at(second));
+        assert!(!limiter.is_allowed_at(second + Duration::from_secs(1)));
+        assert!(limiter.is_allowed_at(second + Duration::from_secs(2)));
+
+        // Idling still only fills the bucket with one token.
+        let idle = start + Duration::from_secs(20);
+        assert!(limiter.is_allowed_at(idle));
+        assert!(!limiter.is_allowed_at(idle));
+    }
+
+    #[test]
+    fn test_limits() {
+        let now = Instant::now();
+        let mut unlimited = RateLimiter::new_at(-1.0, now);
+        assert!((0..1000).all(|_| unlimited.is_allowed_at(now)));
+        assert_eq!(unlimited.effective_rate(), 1.0);
+
+        let mut none = RateLimiter::new_at(0.0, now);
+        assert!(!none.is_allowed_at(now + Duration::from_secs(10)));
+        assert_eq!(none.effective_rate(), 0.0);
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use super::SamplingMechanism;
+use datadog_dynamic_configuration::data::{TracingSamplingRule, TracingSamplingRuleProvenance};
+use datadog_trace_protobuf::pb;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// A case-insensitive glob pattern, where `*` matches any sequence of
+/// characters and `?` matches exactly one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glob {
+    pattern: Vec<char>,
+    matches_all: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        Glob {
+            pattern: pattern.chars().flat_map(char::to_lowercase).collect(),
+            matches_all: !pattern.is_empty() && pattern.chars().all(|c| c == '*'),
+        }
+    }
+
+    /// Whether the pattern only consists of `*`, and so matches anything,
+    /// including missing values.
+    pub fn matches_all(&self) -> bool {
+        self.matches_all
+    }
+
+    pub fn is_match(&self, subject: &str) -> bool {
+        if self.matches_all {
+            return true;
+        }
+        let subject: Vec<char> = subject.chars().flat_map(char::to_lowercase).collect();
+        let (mut p, mut s) = (0, 0);
+        // The position of the last `*` in the pattern, and of the subject
+        // character it was matched up to, to backtrack to.
+        let mut backtrack = None;
+        while s < subject.len() {
+            match self.pattern.get(p) {
+                Some('*') => {
+                    backtrack = Some((p, s));
+                    p += 1;
+                }
+                Some(&c) if c == '?' || c == subject[s] => {
+                    p += 1;
+                    s += 1;
+                }
+                _ => match backtrack {
+                    Some((star, matched)) => {
+                        p = star + 1;
+                        s = matched + 1;
+                        backtrack = Some((star, matched + 1));
+                    }
+                    None => return false,
+                },
+            }
+        }
+        self.pattern[p..].iter().all(|&c| c == '*')
+    }
+}
+
+/// Where a sampling rule was configured, which determines the sampling
+/// mechanism reported for the decisions it makes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RuleProvenance {
+    /// Configured in the tracer, e.g. with `DD_TRACE_SAMPLING_RULES`.
+    Local,
+    /// Configured by a user in the Datadog UI, through remote config.
+    Customer,
+    /// Computed by Datadog, through remote config.
+    Dynamic,
+}
+
+impl From<TracingSamplingRuleProvenance> for RuleProvenance {
+    fn from(provenance: TracingSamplingRuleProvenance) -> Self {
+        match provenance {
+            TracingSamplingRuleProvenance::Customer => RuleProvenance::Customer,
+            TracingSamplingRuleProvenance::Dynamic => RuleProvenance::Dynamic,
+        }
+    }
+}
+
+impl RuleProvenance {
+    pub fn mechanism(self) -> SamplingMechanism {
+        match self {
+            RuleProvenance::Local => SamplingMechanism::Rule,
+            RuleProvenance::Customer => SamplingMechanism::RemoteUserRule,
+            RuleProvenance::Dynamic => SamplingMechanism::RemoteDynamicRule,
+        }
+    }
+}
+
+/// A trace sampling rule: traces whose root span matches all of its globs
+/// are kept with its sample rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingRule {
+    pub service: Glob,
+    pub name: Glob,
+    pub resource: Glob,
+    pub tags: Vec<(String, Glob)>,
+    pub sample_rate: f64,
+    pub provenance: RuleProvenance,
+}
+
+/// A rule in the format of `DD_TRACE_SAMPLING_RULES`.
+#[derive(Deserialize)]
+struct LocalSamplingRule {
+    service: Option<String>,
+    name: Option<String>,
+    resource: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    sample_rate: f64,
+}
+
+impl From<TracingSamplingRule> for SamplingRule {
+    fn from(rule: TracingSamplingRule) -> Self {
+        SamplingRule {
+            service: Glob::new(&rule.service),
+            name: Glob::new(rule.name.as_deref().unwrap_or("*")),
+            resource: Glob::new(&rule.resource),
+            tags: rule
+                .tags
+                .into_iter()
+                .map(|tag| (tag.key, Glob::new(&tag.value_glob)))
+                .collect(),
+            sample_rate: rule.sample_rate.clamp(0.0, 1.0),
+            provenance: rule.provenance.into(),
+        }
+    }
+}
+
+impl SamplingRule {
+    /// Parses rules in the JSON format of `DD_TRACE_SAMPLING_RULES`, e.g.
+    /// `[{"service": "web*", "tags": {"http.status_code": "5??"}, "sample_rate": 1}]`.
+    pub fn parse_local_rules(json: &str) -> anyhow::Result<Vec<SamplingRule>> {
+        let rules: Vec<LocalSamplingRule> = serde_json::from_str(json)?;
+        rules
+            .into_iter()
+            .map(|rule| {
+                anyhow::ensure!(
+                    (0.0..=1.0).contains(&rule.sample_rate),
+                    "Invalid sample rate {} in sampling rule",
+                    rule.sample_rate
+                );
+                Ok(SamplingRule {
+                    service: Glob::new(rule.service.as_deref().unwrap_or("*")),
+                    name: Glob::new(rule.name.as_deref().unwrap_or("*")),
+                    resource: Glob::new(rule.resource.as_deref().unwrap_or("*")),
+                    tags: rule
+                        .tags
+                        .into_iter()
+                        .map(|(key, value)| (key, Glob::new(&value)))
+                        .collect(),
+                    sample_rate: rule.sample_rate,
+                    provenance: RuleProvenance::Local,
+                })
+            })
+            .collect()
+    }
+
+    pub fn is_match(&self, span: &pb::Span) -> bool {
+        self.service.is_match(&span.service)
+            && self.name.is_match(&span.name)
+            && self.resource.is_match(&span.resource)
+            && self.tags.iter().all(|(key, glob)| {
+                if let Some(value) = span.meta.get(key) {
+                    glob.is_match(value)
+                } else if let Some(&value) = span.metrics.get(key) {
+                    // Numeric tags only match as integers, anything else
+                    // only matches a glob which accepts everything.
+                    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+                        glob.is_match(&(value as i64).to_string())
+                    } else {
+                        glob.matches_all()
+                    }
+                } else {
+                    false
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob() {
+        let cases = [
+            ("*", "", true),
+            ("*", "anything", true),
+            ("web", "web", true),
+            ("web", "WEB", true),
+            ("WEB-?", "web-1", true),
+            ("web", "web-1", false),
+            ("web*", "web-server", true),
+            ("*server", "web-server", true),
+            ("w*b*r", "web-server", true),
+            ("*a*a*", "banana", true),
+            ("*a*x", "banana", false),
+            ("?", "", false),
+            ("??", "é1", true),
+            ("web*?", "web", false),
+            ("", "", true),
+            ("", "web", false),
+        ];
+        for (pattern, subject, expected) in cases {
+            assert_eq!(
+                Glob::new(pattern).is_match(subject),
+                expected,
+                "{pattern} {subject}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rule_matching() {
+        let rules = SamplingRule::parse_local_rules(
+            r#"[{"service": "web*", "name": "http.request", "tags": {"http.status_code": "5??", "region": "us-*"}, "sample_rate": 0.5}]"#,
+        )
+        .unwrap();
+        let rule = &rules[0];
+        assert_eq!(rule.provenance, RuleProvenance::Local);
+
+        let mut span = pb::Span {
+            service: "web-server".to_string(),
+            name: "http.request".to_string(),
+            resource: "GET /".to_string(),
+            ..Default::default()
+        };
+        span.meta
+            .insert("region".to_string(), "us-east-1".to_string());
+        assert!(!rule.is_match(&span));
+        span.metrics.insert("http.status_code".to_string(), 503.0);
+        assert!(rule.is_match(&span));
+        span.metrics.insert("http.status_code".to_string(), 503.5);
+        assert!(!rule.is_match(&span));
+        span.metrics.insert("http.status_code".to_string(), 200.0);
+        assert!(!rule.is_match(&span));
+        span.meta
+            .insert("http.status_code".to_string(), "504".to_string());
+        assert!(rule.is_match(&span));
+        span.service = "db".to_string();
+        assert!(!rule.is_match(&span));
+
+        assert!(SamplingRule::parse_local_rules(r#"[{"sample_rate": 1.5}]"#).is_err());
+        assert!(SamplingRule::parse_local_rules(r#"{"sample_rate": 1}"#).is_err());
+    }
+
+    #[test]
+    fn test_remote_rule() {
+        let rule: TracingSamplingRule = serde_json::from_str(
+            r#"{"service": "web", "provenance": "dynamic", "resource": "GET /*", "tags": [{"key": "k", "value_glob": "v"}], "sample_rate": 0.1}"#,
+        )
+        .unwrap();
+        let rule = SamplingRule::from(rule);
+        assert_eq!(rule.provenance, RuleProvenance::Dynamic);
+        assert!(rule.name.matches_all());
+        assert_eq!(rule.tags, vec![("k".to_string(), Glob::new("v"))]);
+        assert_eq!(rule.sample_rate, 0.1);
+    }
+}
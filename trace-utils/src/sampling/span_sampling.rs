@@ -0,0 +1,237 @@
+// Copyright 2024-Present Datadog, Inc. https://www.datadoghq.com/
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{is_sampled_by_rate, Glob, RateLimiter, SamplingMechanism, SAMPLING_PRIORITY_KEY};
+use anyhow::Context;
+use datadog_trace_protobuf::pb;
+use serde::Deserialize;
+use std::sync::Mutex;
+
+pub const SPAN_SAMPLING_MECHANISM_KEY: &str = "_dd.span_sampling.mechanism";
+pub const SPAN_SAMPLING_RULE_RATE_KEY: &str = "_dd.span_sampling.rule_rate";
+pub const SPAN_SAMPLING_MAX_PER_SECOND_KEY: &str = "_dd.span_sampling.max_per_second";
+
+const SPAN_SAMPLING_RULES_ENV: &str = "DD_SPAN_SAMPLING_RULES";
+const SPAN_SAMPLING_RULES_FILE_ENV: &str = "DD_SPAN_SAMPLING_RULES_FILE";
+
+/// A rule in the format of `DD_SPAN_SAMPLING_RULES`.
+#[derive(Deserialize)]
+struct SpanSamplingRuleConfig {
+    service: Option<String>,
+    name: Option<String>,
+    #[serde(default = "default_sample_rate")]
+    sample_rate: f64,
+    max_per_second: Option<f64>,
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+/// A single span sampling rule: spans of dropped traces matching its globs
+/// are kept with its sample rate, up to `max_per_second` spans per second.
+#[derive(Debug)]
+pub struct SpanSamplingRule {
+    pub service: Glob,
+    pub name: Glob,
+    pub sample_rate: f64,
+    pub max_per_second: Option<f64>,
+    limiter: Mutex<RateLimiter>,
+}
+
+impl SpanSamplingRule {
+    pub fn new(service: &str, name: &str, sample_rate: f64, max_per_second: Option<f64>) -> Self {
+        SpanSamplingRule {
+            service: Glob::new(service),
+            name: Glob::new(name),
+            sample_rate: sample_rate.clamp(0.0, 1.0),
+            max_per_second,
+            limiter: Mutex::new(RateLimiter::new(max_per_second.unwrap_or(-1.0))),
+        }
+    }
+
+    pub fn is_match(&self, span: &pb::Span) -> bool {
+        self.service.is_match(&span.service) && self.name.is_match(&span.name)
+    }
+}
+
+/// Keeps individual spans of the traces dropped by trace sampling.
+#[derive(Debug, Default)]
+pub struct SpanSampler {
+    rules: Vec<SpanSamplingRule>,
+}
+
+impl SpanSampler {
+    pub fn new(rules: Vec<SpanSamplingRule>) -> Self {
+        SpanSampler { rules }
+    }
+
+    /// Parses rules in the JSON format of `DD_SPAN_SAMPLING_RULES`, e.g.
+    /// `[{"service": "web", "name": "http.*", "sample_rate": 0.5, "max_per_second": 10}]`.
+    pub fn parse_rules(json: &str) -> anyhow::Result<Self> {
+        let rules: Vec<SpanSamplingRuleConfig> = serde_json::from_str(json)?;
+        let rules = rules
+            .into_iter()
+            .map(|rule| {
+                anyhow::ensure!(
+                    (0.0..=1.0).contains(&rule.sample_rate),
+                    "Invalid sample rate {} in span sampling rule",
+                    rule.sample_rate
+                );
+                Ok(SpanSamplingRule::new(
+                    rule.service.as_deref().unwrap_or("*"),
+                    rule.name.as_deref().unwrap_or("*"),
+                    rule.sample_rate,
+                    rule.max_per_second,
+                ))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(SpanSampler { rules })
+    }
+
+    /// Reads the rules from `DD_SPAN_SAMPLING_RULES`, or else from the file
+    /// at `DD_SPAN_SAMPLING_RULES_FILE`.  Without either, no span is kept.
+    pub fn from_env() -> anyhow::Result<Self> {
+        if let Ok(rules) = std::env::var(SPAN_SAMPLING_RULES_ENV) {
+            return Self::parse_rules(&rules)
+                .with_context(|| format!("Invalid {SPAN_SAMPLING_RULES_ENV}"));
+        }
+        if let Ok(path) = std::env::var(SPAN_SAMPLING_RULES_FILE_ENV) {
+            let rules =
+                std::fs::read_to_string(&path).with_context(|| format!("Failed to read {path}"))?;
+            return Self::parse_rules(&rules).with_context(|| format!("Invalid rules in {path}"));
+        }
+        Ok(Self::default())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Evaluates the first rule matching `span`, and tags the span if it is
+    /// kept.  Returns whether it is.
+    pub fn sample(&self, span: &mut pb::Span) -> bool {
+        let Some(rule) = self.rules.iter().find(|rule| rule.is_match(span)) else {
+            return false;
+        };
+        if !is_sampled_by_rate(span.span_id, rule.sample_rate) {
+            return false;
+        }
+        if !rule.limiter.lock().unwrap().is_allowed() {
+            return false;
+        }
+        span.metrics.insert(
+            SPAN_SAMPLING_MECHANISM_KEY.to_string(),
+            SamplingMechanism::SpanSamplingRule as u8 as f64,
+        );
+        span.metrics
+            .insert(SPAN_SAMPLING_RULE_RATE_KEY.to_string(), rule.sample_rate);
+        if let Some(max_per_second) = rule.max_per_second {
+            span.metrics
+                .insert(SPAN_SAMPLING_MAX_PER_SECOND_KEY.to_string(), max_per_second);
+        }
+        true
+    }
+
+    /// Tags the spans of `chunk` to keep if its trace is dropped, so they
+    /// survive the dropping of P0 chunks.  Returns the number of spans kept.
+    pub fn sample_chunk(&self, chunk: &mut [pb::Span]) -> usize {
+        let is_trace_kept = chunk.iter().any(|span| {
+            span.metrics
+                .get(SAMPLING_PRIORITY_KEY)
+                .is_some_and(|&priority| priority > 0.0)
+        });
+        if is_trace_kept || self.is_empty() {
+            return 0;
+        }
+        chunk
+            .iter_mut()
+            .map(|span| self.sample(span))
+            .filter(|&kept| kept)
+            .count()
+    }
+}
+
+/// Whether span sampling tagged `span` to be kept.
+pub fn is_span_sampled(span: &pb::Span) -> bool {
+    span.metrics
+        .get(SPAN_SAMPLING_MECHANISM_KEY)
+        .is_some_and(|&mechanism| mechanism == SamplingMechanism::SpanSamplingRule as u8 as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(span_id: u64, service: &str, name: &str) -> pb::Span {
+        pb::Span {
+            span_id,
+            service: service.to_string(),
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let sampler = SpanSampler::parse_rules(
+            r#"[{"service": "web", "name": "http.*", "max_per_second": 10}, {"sample_rate": 0}]"#,
+        )
+        .unwrap();
+        assert_eq!(sampler.rules.len(), 2);
+        assert_eq!(sampler.rules[0].sample_rate, 1.0);
+        assert_eq!(sampler.rules[0].max_per_second, Some(10.0));
+        assert_eq!(sampler.rules[1].max_per_second, None);
+        assert!(sampler.rules[1].service.matches_all());
+
+        assert!(SpanSampler::parse_rules(r#"[{"sample_rate": 2}]"#).is_err());
+        assert!(SpanSampler::parse_rules("nope").is_err());
+    }
+
+    #[test]
+    fn test_sample() {
+        let sampler = SpanSampler::parse_rules(
+            r#"[{"service": "web", "name": "http.*", "max_per_second": 1}, {"service": "db", "sample_rate": 0}]"#,
+        )
+        .unwrap();
+
+        let mut kept = span(1, "web", "http.request");
+        assert!(sampler.sample(&mut kept));
+        assert!(is_span_sampled(&kept));
+        assert_eq!(kept.metrics[SPAN_SAMPLING_RULE_RATE_KEY], 1.0);
+        assert_eq!(kept.metrics[SPAN_SAMPLING_MAX_PER_SECOND_KEY], 1.0);
+
+        // The rule only keeps one span per second.
+        let mut limited = span(2, "web", "http.request");
+        assert!(!sampler.sample(&mut limited));
+        assert!(limited.metrics.is_empty());
+
+        let mut rejected = span(3, "db", "query");
+        assert!(!sampler.sample(&mut rejected));
+        let mut unmatched = span(4, "web", "render");
+        assert!(!sampler.sample(&mut unmatched));
+        assert!(!is_span_sampled(&unmatched));
+    }
+
+    #[test]
+    fn test_sample_chunk() {
+        let sampler = SpanSampler::parse_rules(r#"[{"name": "http.*"}]"#).unwrap();
+        let mut chunk = vec![
+            span(1, "web", "http.request"),
+            span(2, "web", "render"),
+            span(3, "web", "http.client"),
+        ];
+        chunk[0]
+            .metrics
+            .insert(SAMPLING_PRIORITY_KEY.to_string(), 1.0);
+        assert_eq!(sampler.sample_chunk(&mut chunk), 0);
+
+        chunk[0]
+            .metrics
+            .insert(SAMPLING_PRIORITY_KEY.to_string(), -1.0);
+        assert_eq!(sampler.sample_chunk(&mut chunk), 2);
+        assert!(is_span_sampled(&chunk[0]));
+        assert!(!is_span_sampled(&chunk[1]));
+        assert!(is_span_sampled(&chunk[2]));
+    }
+}